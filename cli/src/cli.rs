@@ -16,6 +16,7 @@
 
 //! Polkadot CLI library.
 
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[allow(missing_docs)]
@@ -42,6 +43,12 @@ pub enum Subcommand {
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
+	/// Inspect what the local parachain databases hold for a candidate.
+	InspectCandidate(InspectCandidateCmd),
+
+	/// Remove the parachain databases, leaving the relay chain database untouched.
+	PurgeParachainDb(PurgeParachainDbCmd),
+
 	#[allow(missing_docs)]
 	#[structopt(name = "validation-worker", setting = structopt::clap::AppSettings::Hidden)]
 	ValidationWorker(ValidationWorkerCommand),
@@ -61,6 +68,35 @@ pub struct ValidationWorkerCommand {
 	pub mem_id: String,
 }
 
+#[allow(missing_docs)]
+#[derive(Debug, StructOpt)]
+pub struct InspectCandidateCmd {
+	/// The hash of the candidate to inspect.
+	pub candidate_hash: sp_core::H256,
+
+	/// Path to the availability store database.
+	///
+	/// The node must not be running, as the database is opened directly.
+	#[structopt(long = "av-store-path", parse(from_os_str))]
+	pub av_store_path: PathBuf,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, StructOpt)]
+pub struct PurgeParachainDbCmd {
+	/// Path to the availability store database.
+	#[structopt(long = "av-store-path", parse(from_os_str))]
+	pub av_store_path: PathBuf,
+
+	/// Only report the space that would be reclaimed, without removing anything.
+	#[structopt(long = "dry-run")]
+	pub dry_run: bool,
+
+	/// Skip interactive prompt by answering yes automatically.
+	#[structopt(short = "y")]
+	pub yes: bool,
+}
+
 #[allow(missing_docs)]
 #[derive(Debug, StructOpt)]
 pub struct RunCmd {
@@ -98,6 +134,31 @@ pub struct RunCmd {
 	/// elapsed (i.e. until a block at height `pause_block + delay` is imported).
 	#[structopt(long = "grandpa-pause", number_of_values(2))]
 	pub grandpa_pause: Vec<u32>,
+
+	/// Replace a non-essential subsystem with a dummy that ignores all messages.
+	///
+	/// Meant for isolating a misbehaving subsystem. Can be passed multiple times. The names are
+	/// checked, but the flag has no effect yet: the overseer of the node only runs dummy
+	/// subsystems until the real ones are wired into the service.
+	#[cfg(feature = "service-rewr")]
+	#[structopt(long = "disable-subsystem", value_name = "NAME")]
+	pub disabled_subsystems: Vec<String>,
+
+	/// Log every message routed between subsystems at the `trace` level, under the
+	/// `overseer::messages` target.
+	///
+	/// Large payloads such as PoVs and validation code are logged by their size.
+	#[cfg(feature = "service-rewr")]
+	#[structopt(long = "log-subsystem-messages")]
+	pub log_subsystem_messages: bool,
+
+	/// Run as a validator even if the checks of the environment fail.
+	///
+	/// At startup, validators check that the system clock is sane, that the keystore is only
+	/// writable by its owner and that the keys in it are only readable by their owner. Only meant
+	/// for test setups.
+	#[structopt(long = "insecure-validator-i-know-what-i-do")]
+	pub insecure_validator: bool,
 }
 
 #[allow(missing_docs)]
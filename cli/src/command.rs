@@ -14,13 +14,13 @@
 // You should have received a copy of the GNU General Public License
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
-use log::info;
+use log::{info, warn};
 #[cfg(not(feature = "service-rewr"))]
 use service::{IdentifyVariant, self};
 #[cfg(feature = "service-rewr")]
 use service_new::{IdentifyVariant, self as service};
 use sc_cli::{SubstrateCli, Result, RuntimeVersion, Role};
-use crate::cli::{Cli, Subcommand};
+use crate::cli::{Cli, Subcommand, InspectCandidateCmd, PurgeParachainDbCmd};
 
 fn get_exec_name() -> Option<String> {
 	std::env::current_exe()
@@ -110,6 +110,74 @@ fn set_default_ss58_version(spec: &Box<dyn service::ChainSpec>) {
 	sp_core::crypto::set_default_ss58_version(ss58_version);
 }
 
+fn inspect_candidate(cmd: &InspectCandidateCmd) -> Result<()> {
+	let config = av_store::Config {
+		cache_size: None,
+		path: cmd.av_store_path.clone(),
+	};
+	let av_store = av_store::AvailabilityStoreSubsystem::new_on_disk(config, Default::default())?;
+	let availability = av_store.inspect_candidate(&cmd.candidate_hash);
+
+	println!("Candidate {:?}", cmd.candidate_hash);
+	match (availability.n_validators, availability.pov_size) {
+		(Some(n_validators), Some(pov_size)) => {
+			println!("  available data: stored ({} bytes of PoV, {} validators)", pov_size, n_validators);
+		}
+		_ => println!("  available data: not stored"),
+	}
+	println!("  erasure chunks: {} stored {:?}", availability.chunks.len(), availability.chunks);
+
+	Ok(())
+}
+
+fn dir_size(path: &std::path::Path) -> std::io::Result<u64> {
+	let mut size = 0;
+	for entry in std::fs::read_dir(path)? {
+		let entry = entry?;
+		let metadata = entry.metadata()?;
+		size += if metadata.is_dir() { dir_size(&entry.path())? } else { metadata.len() };
+	}
+	Ok(size)
+}
+
+fn purge_parachain_db(cmd: &PurgeParachainDbCmd) -> Result<()> {
+	use std::io::{self, Write};
+
+	let path = &cmd.av_store_path;
+	if !path.exists() {
+		println!("{:?} did not exist.", path);
+		return Ok(());
+	}
+
+	let size = dir_size(path)?;
+	if cmd.dry_run {
+		println!("Would remove {:?}, reclaiming {} bytes.", path, size);
+		return Ok(());
+	}
+
+	if !cmd.yes {
+		print!("Are you sure to remove {:?} ({} bytes)? [y/N]: ", path, size);
+		io::stdout().flush()?;
+
+		let mut input = String::new();
+		io::stdin().read_line(&mut input)?;
+		let input = input.trim();
+
+		match input.chars().nth(0) {
+			Some('y') | Some('Y') => {},
+			_ => {
+				println!("Aborted");
+				return Ok(());
+			},
+		}
+	}
+
+	std::fs::remove_dir_all(path)?;
+	println!("{:?} removed, {} bytes reclaimed.", path, size);
+
+	Ok(())
+}
+
 /// Parses polkadot specific CLI arguments and run the service.
 pub fn run() -> Result<()> {
 	let cli = Cli::from_args();
@@ -128,6 +196,28 @@ pub fn run() -> Result<()> {
 				Some((cli.run.grandpa_pause[0], cli.run.grandpa_pause[1]))
 			};
 
+			#[cfg(feature = "service-rewr")]
+			let disabled_subsystems = cli.run.disabled_subsystems.clone();
+			#[cfg(feature = "service-rewr")]
+			{
+				if !disabled_subsystems.is_empty() {
+					warn!("--disable-subsystem has no effect yet, all subsystems of the overseer are dummies.");
+				}
+			}
+			#[cfg(feature = "service-rewr")]
+			let log_subsystem_messages = cli.run.log_subsystem_messages;
+
+			if runner.config().role.is_authority() {
+				if cli.run.insecure_validator {
+					warn!("Running as a validator without checking the environment.");
+				} else {
+					crate::secure_validator::check(runner.config()).map_err(|e| sc_cli::Error::Input(format!(
+						"Refusing to run as a validator: {}. Pass --insecure-validator-i-know-what-i-do to run anyway.",
+						e,
+					)))?;
+				}
+			}
+
 			if chain_spec.is_kusama() {
 				info!("----------------------------");
 				info!("This chain is not in any way");
@@ -140,13 +230,26 @@ pub fn run() -> Result<()> {
 				let role = config.role.clone();
 
 				match role {
+					#[cfg(not(feature = "service-rewr"))]
 					Role::Light => service::build_light(config).map(|(task_manager, _)| task_manager),
+					#[cfg(feature = "service-rewr")]
+					Role::Light => service::build_light(config),
+					#[cfg(not(feature = "service-rewr"))]
 					_ => service::build_full(
 						config,
 						None,
 						authority_discovery_enabled,
 						grandpa_pause,
 					).map(|r| r.0),
+					#[cfg(feature = "service-rewr")]
+					_ => service::build_full(
+						config,
+						None,
+						authority_discovery_enabled,
+						grandpa_pause,
+						disabled_subsystems,
+						log_subsystem_messages,
+					).map(|r| r.0),
 				}
 			})
 		},
@@ -213,6 +316,11 @@ pub fn run() -> Result<()> {
 				Ok((cmd.run(client, backend), task_manager))
 			})
 		},
+		Some(Subcommand::InspectCandidate(cmd)) => {
+			sc_cli::init_logger("");
+			inspect_candidate(cmd)
+		},
+		Some(Subcommand::PurgeParachainDb(cmd)) => purge_parachain_db(cmd),
 		Some(Subcommand::ValidationWorker(cmd)) => {
 			sc_cli::init_logger("");
 
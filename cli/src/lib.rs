@@ -25,6 +25,8 @@ mod browser;
 mod cli;
 #[cfg(feature = "cli")]
 mod command;
+#[cfg(feature = "cli")]
+mod secure_validator;
 
 #[cfg(not(feature = "service-rewr"))]
 pub use service::{
@@ -0,0 +1,157 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks a validator has to pass at startup, unless started with
+//! `--insecure-validator-i-know-what-i-do`.
+
+use sc_service::config::{Configuration, KeystoreConfig};
+use std::{path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+/// A point in time the system clock of a validator must be past: 2020-06-01 00:00:00 UTC.
+///
+/// A clock this far behind would have the node author and accept blocks in the wrong slots.
+const MIN_SANE_TIMESTAMP: Duration = Duration::from_secs(1_590_969_600);
+
+/// Run all the checks, returning an error describing the first failing one.
+pub fn check(config: &Configuration) -> Result<(), String> {
+	check_clock()?;
+
+	if let KeystoreConfig::Path { path, .. } = &config.keystore {
+		check_keystore_permissions(path)?;
+	}
+
+	Ok(())
+}
+
+fn check_clock() -> Result<(), String> {
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+	if now < MIN_SANE_TIMESTAMP {
+		return Err(format!("the system clock is set to {}s since the UNIX epoch, which is in the past", now.as_secs()));
+	}
+
+	Ok(())
+}
+
+// The keystore must only be writable by its owner, and the keys in it must also only be readable
+// by their owner.
+#[cfg(unix)]
+fn check_keystore_permissions(path: &Path) -> Result<(), String> {
+	use std::os::unix::fs::PermissionsExt;
+
+	const GROUP_OR_OTHERS_WRITABLE: u32 = 0o022;
+	const GROUP_OR_OTHERS_READABLE: u32 = 0o044;
+
+	if !path.exists() {
+		return Ok(());
+	}
+
+	let mode = |path: &Path| -> Result<u32, String> {
+		Ok(std::fs::metadata(path)
+			.map_err(|e| format!("can't read the permissions of {:?}: {}", path, e))?
+			.permissions()
+			.mode())
+	};
+
+	let dir_mode = mode(path)?;
+	if dir_mode & GROUP_OR_OTHERS_WRITABLE != 0 {
+		return Err(format!("{:?} is writable by users other than its owner (mode {:o})", path, dir_mode & 0o777));
+	}
+
+	for entry in std::fs::read_dir(path).map_err(|e| format!("can't read the keystore {:?}: {}", path, e))? {
+		let path = entry.map_err(|e| format!("can't read the keystore {:?}: {}", path, e))?.path();
+		let file_mode = mode(&path)?;
+		if file_mode & GROUP_OR_OTHERS_WRITABLE != 0 {
+			return Err(format!("{:?} is writable by users other than its owner (mode {:o})", path, file_mode & 0o777));
+		}
+		if file_mode & GROUP_OR_OTHERS_READABLE != 0 {
+			return Err(format!(
+				"{:?} is readable by users other than its owner (mode {:o}), restrict it with `chmod 600`",
+				path,
+				file_mode & 0o777,
+			));
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_keystore_permissions(_path: &Path) -> Result<(), String> {
+	Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use super::*;
+	use std::{fs, os::unix::fs::PermissionsExt};
+
+	fn set_mode(path: &Path, mode: u32) {
+		fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+	}
+
+	fn keystore_with_key(dir_mode: u32, key_mode: u32) -> tempfile::TempDir {
+		let keystore = tempfile::tempdir().unwrap();
+		let key = keystore.path().join("key");
+		fs::write(&key, b"secret").unwrap();
+		set_mode(&key, key_mode);
+		set_mode(keystore.path(), dir_mode);
+		keystore
+	}
+
+	#[test]
+	fn keystore_only_accessible_by_owner_passes() {
+		let keystore = keystore_with_key(0o700, 0o600);
+		assert!(check_keystore_permissions(keystore.path()).is_ok());
+
+		// others may list the keystore, as long as they can't read the keys.
+		let keystore = keystore_with_key(0o755, 0o400);
+		assert!(check_keystore_permissions(keystore.path()).is_ok());
+	}
+
+	#[test]
+	fn missing_keystore_passes() {
+		let dir = tempfile::tempdir().unwrap();
+		assert!(check_keystore_permissions(&dir.path().join("keystore")).is_ok());
+	}
+
+	#[test]
+	fn writable_keystore_fails() {
+		let keystore = keystore_with_key(0o770, 0o600);
+		assert!(check_keystore_permissions(keystore.path()).is_err());
+
+		let keystore = keystore_with_key(0o702, 0o600);
+		assert!(check_keystore_permissions(keystore.path()).is_err());
+	}
+
+	#[test]
+	fn writable_key_fails() {
+		let keystore = keystore_with_key(0o700, 0o620);
+		assert!(check_keystore_permissions(keystore.path()).is_err());
+
+		let keystore = keystore_with_key(0o700, 0o602);
+		assert!(check_keystore_permissions(keystore.path()).is_err());
+	}
+
+	#[test]
+	fn readable_key_fails() {
+		let keystore = keystore_with_key(0o700, 0o640);
+		let err = check_keystore_permissions(keystore.path()).unwrap_err();
+		assert!(err.contains("readable by users other than its owner"), "{}", err);
+
+		let keystore = keystore_with_key(0o700, 0o604);
+		assert!(check_keystore_permissions(keystore.path()).is_err());
+	}
+}
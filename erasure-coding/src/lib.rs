@@ -23,12 +23,18 @@
 //! Each of n validators stores their piece of data. We assume n=3f+k, 0 < k ≤ 3.
 //! f is the maximum number of faulty validators in the system.
 //! The data is coded so any f+1 chunks can be used to reconstruct the full data.
+//!
+//! Validators do not hold the chunk with their own index. Instead, the chunk indices are
+//! shuffled per core, see [`validator_chunk_indices`], so that the systematic chunks of
+//! every candidate are not always held by the same validators. Runtimes which don't expose a
+//! shuffle seed keep the identity mapping.
 
 use codec::{Encode, Decode};
 use reed_solomon::galois_16::{self, ReedSolomon};
 use primitives::v0::{self, Hash as H256, BlakeTwo256, HashT};
 use primitives::v1;
-use sp_core::Blake2Hasher;
+use sp_core::{Blake2Hasher, hashing::blake2_256};
+use rand_chacha::{ChaCha20Rng, rand_core::{RngCore, SeedableRng}};
 use trie::{EMPTY_PREFIX, MemoryDB, Trie, TrieMut, trie_types::{TrieDBMut, TrieDB}};
 
 use self::wrapped_shard::WrappedShard;
@@ -263,19 +269,8 @@ impl<'a, I: AsRef<[u8]>> Iterator for Branches<'a, I> {
 	type Item = (Vec<Vec<u8>>, &'a [u8]);
 
 	fn next(&mut self) -> Option<Self::Item> {
-		use trie::Recorder;
-
-		let trie = TrieDB::new(&self.trie_storage, &self.root)
-			.expect("`Branches` is only created with a valid memorydb that contains all nodes for the trie with given root; qed");
-
-		let mut recorder = Recorder::new();
-		let res = (self.current_pos as u32).using_encoded(|s|
-			trie.get_with(s, &mut recorder)
-		);
-
-		match res.expect("all nodes in trie present; qed") {
-			Some(_) => {
-				let nodes = recorder.drain().into_iter().map(|r| r.data).collect();
+		match branch_proof(&self.trie_storage, &self.root, self.current_pos) {
+			Some(nodes) => {
 				let chunk = self.chunks.get(self.current_pos)
 					.expect("there is a one-to-one mapping of chunks to valid merkle branches; qed");
 
@@ -287,26 +282,48 @@ impl<'a, I: AsRef<[u8]>> Iterator for Branches<'a, I> {
 	}
 }
 
-/// Construct a trie from chunks of an erasure-coded value. This returns the root hash and an
-/// iterator of merkle proofs, one for each validator.
-pub fn branches<'a, I: 'a>(chunks: &'a [I]) -> Branches<'a, I>
-	where I: AsRef<[u8]>,
-{
+// construct a trie mapping each chunk's index to its hash.
+fn make_trie<'a>(chunks: impl Iterator<Item = &'a [u8]>) -> (MemoryDB<Blake2Hasher>, H256) {
 	let mut trie_storage: MemoryDB<Blake2Hasher> = MemoryDB::default();
 	let mut root = H256::default();
 
-	// construct trie mapping each chunk's index to its hash.
 	{
 		let mut trie = TrieDBMut::new(&mut trie_storage, &mut root);
-		for (i, chunk) in chunks.as_ref().iter().enumerate() {
+		for (i, chunk) in chunks.enumerate() {
 			(i as u32).using_encoded(|encoded_index| {
-				let chunk_hash = BlakeTwo256::hash(chunk.as_ref());
+				let chunk_hash = BlakeTwo256::hash(chunk);
 				trie.insert(encoded_index, chunk_hash.as_ref())
 					.expect("a fresh trie stored in memory cannot have errors loading nodes; qed");
 			})
 		}
 	}
 
+	(trie_storage, root)
+}
+
+// the merkle proof of the chunk with the given index, if it is in the trie.
+fn branch_proof(trie_storage: &MemoryDB<Blake2Hasher>, root: &H256, index: usize) -> Option<Vec<Vec<u8>>> {
+	use trie::Recorder;
+
+	let trie = TrieDB::new(trie_storage, root)
+		.expect("only called with a valid memorydb that contains all nodes for the trie with given root; qed");
+
+	let mut recorder = Recorder::new();
+	let res = (index as u32).using_encoded(|s|
+		trie.get_with(s, &mut recorder)
+	);
+
+	res.expect("all nodes in trie present; qed")
+		.map(|_| recorder.drain().into_iter().map(|r| r.data).collect())
+}
+
+/// Construct a trie from chunks of an erasure-coded value. This returns the root hash and an
+/// iterator of merkle proofs, one for each validator.
+pub fn branches<'a, I: 'a>(chunks: &'a [I]) -> Branches<'a, I>
+	where I: AsRef<[u8]>,
+{
+	let (trie_storage, root) = make_trie(chunks.iter().map(|chunk| chunk.as_ref()));
+
 	Branches {
 		trie_storage,
 		root,
@@ -315,6 +332,85 @@ pub fn branches<'a, I: 'a>(chunks: &'a [I]) -> Branches<'a, I>
 	}
 }
 
+/// Erasure-coded chunks of v1 `AvailableData`, one for each validator, along with the merkle
+/// trie committing to them.
+///
+/// All chunks are computed up front, as Reed-Solomon derives every parity chunk from all the
+/// data. Unlike with [`obtain_chunks_v1`] and [`branches`], only the merkle proof of a chunk is
+/// generated on demand, and a chunk is only copied into an [`v1::ErasureChunk`] along with its
+/// proof when requested, so that callers never hold every chunk with its proof at once.
+pub struct Chunks {
+	shards: Vec<WrappedShard>,
+	trie_storage: MemoryDB<Blake2Hasher>,
+	root: H256,
+}
+
+impl Chunks {
+	/// Get the trie root, i.e. the erasure root.
+	pub fn root(&self) -> H256 { self.root.clone() }
+
+	/// The number of chunks, which is the number of validators.
+	pub fn len(&self) -> usize { self.shards.len() }
+
+	/// Get the chunk with the given index.
+	pub fn chunk(&self, index: usize) -> Option<&[u8]> {
+		self.shards.get(index).map(AsRef::<[u8]>::as_ref)
+	}
+
+	/// Generate the merkle proof of the chunk with the given index.
+	pub fn branch(&self, index: usize) -> Option<Vec<Vec<u8>>> {
+		branch_proof(&self.trie_storage, &self.root, index)
+	}
+
+	/// Assemble the chunk with the given index along with its merkle proof.
+	pub fn erasure_chunk(&self, index: usize) -> Option<v1::ErasureChunk> {
+		let chunk = self.chunk(index)?;
+		let proof = self.branch(index)?;
+
+		Some(v1::ErasureChunk {
+			chunk: chunk.to_vec(),
+			index: index as u32,
+			proof,
+		})
+	}
+
+	/// Iterate over all the chunks along with their merkle proofs, assembling each one as it is
+	/// yielded.
+	pub fn iter(&self) -> impl Iterator<Item = v1::ErasureChunk> + '_ {
+		(0..self.len()).map(move |index| self.erasure_chunk(index)
+			.expect("every index below `len` has a chunk and a merkle branch; qed"))
+	}
+}
+
+/// Erasure-code v1 `AvailableData` into [`Chunks`], one for each validator.
+///
+/// The chunks are the same as those of [`obtain_chunks_v1`] and the root is the same as that of
+/// [`branches`] of these.
+///
+/// Works only up to 65536 validators, and `n_validators` must be non-zero.
+pub fn chunks_v1(n_validators: usize, data: &v1::AvailableData) -> Result<Chunks, Error> {
+	let params = code_params(n_validators)?;
+	let encoded = data.encode();
+
+	if encoded.is_empty() {
+		return Err(Error::BadPayload);
+	}
+
+	let mut shards = params.make_shards_for(&encoded[..]);
+	drop(encoded);
+
+	params.make_encoder().encode(&mut shards[..])
+		.expect("Payload non-empty, shard sizes are uniform, and validator numbers checked; qed");
+
+	let (trie_storage, root) = make_trie(shards.iter().map(AsRef::<[u8]>::as_ref));
+
+	Ok(Chunks {
+		shards,
+		trie_storage,
+		root,
+	})
+}
+
 /// Verify a merkle branch, yielding the chunk hash meant to be present at that
 /// index.
 pub fn branch_hash(root: &H256, branch_nodes: &[Vec<u8>], index: usize) -> Result<H256, Error> {
@@ -336,6 +432,68 @@ pub fn branch_hash(root: &H256, branch_nodes: &[Vec<u8>], index: usize) -> Resul
 	}
 }
 
+/// Obtain the index of the chunk each validator is responsible for, indexed by validator index,
+/// for a candidate occupying the core `core_index`.
+///
+/// The result is a permutation of `0..n_validators`, shuffled deterministically with the
+/// session's `shuffle_seed`, as exposed by the runtime, and the core index. Without a shuffle
+/// seed, because the runtime doesn't support chunk shuffling, each validator is responsible for
+/// the chunk with its own index.
+///
+/// Every node must compute the same permutation, so the shuffle is a Fisher-Yates shuffle spelled
+/// out here over the output of ChaCha20, rather than one of a library which may change.
+pub fn validator_chunk_indices(
+	shuffle_seed: Option<&[u8; 32]>,
+	core_index: v1::CoreIndex,
+	n_validators: usize,
+) -> Vec<u32> {
+	let mut chunk_indices: Vec<u32> = (0..n_validators as u32).collect();
+
+	let shuffle_seed = match shuffle_seed {
+		Some(shuffle_seed) => shuffle_seed,
+		None => return chunk_indices,
+	};
+
+	let seed = (shuffle_seed, core_index).using_encoded(blake2_256);
+	let mut rng = ChaCha20Rng::from_seed(seed);
+
+	for i in (1..chunk_indices.len()).rev() {
+		let j = uniform_below(&mut rng, i as u32 + 1);
+		chunk_indices.swap(i, j as usize);
+	}
+
+	chunk_indices
+}
+
+/// Draw a number uniformly from `0..bound`, which must not be zero.
+///
+/// Draws below `2^32 % bound` are rejected, so that every result is backed by the same number of
+/// draws.
+fn uniform_below(rng: &mut ChaCha20Rng, bound: u32) -> u32 {
+	let threshold = bound.wrapping_neg() % bound;
+	loop {
+		let draw = rng.next_u32();
+		if draw >= threshold {
+			return draw % bound;
+		}
+	}
+}
+
+/// Obtain the index of the chunk the validator with the given index is responsible for, for a
+/// candidate occupying the core `core_index`.
+///
+/// Returns `None` if the validator index is out of bounds.
+pub fn chunk_index(
+	shuffle_seed: Option<&[u8; 32]>,
+	core_index: v1::CoreIndex,
+	n_validators: usize,
+	validator_index: v1::ValidatorIndex,
+) -> Option<u32> {
+	validator_chunk_indices(shuffle_seed, core_index, n_validators)
+		.get(validator_index as usize)
+		.copied()
+}
+
 // input for `codec` which draws data from the data shards
 struct ShardInput<'a, I> {
 	remaining_len: usize,
@@ -507,4 +665,100 @@ mod tests {
 			assert_eq!(branch_hash(&root, &proof, i).unwrap(), BlakeTwo256::hash(&chunks[i]));
 		}
 	}
+
+	#[test]
+	fn chunks_match_obtained_chunks_and_branches() {
+		for &n_validators in &[1, 2, 3, 4, 10, 100] {
+			let available_data = v1::AvailableData {
+				pov: v1::PoV { block_data: BlockData((0..=255).cycle().take(1001).collect()) },
+				validation_data: Default::default(),
+			};
+
+			let obtained = obtain_chunks_v1(n_validators, &available_data).unwrap();
+			let root = branches(obtained.as_ref()).root();
+
+			let chunks = chunks_v1(n_validators, &available_data).unwrap();
+			assert_eq!(chunks.len(), n_validators);
+			assert_eq!(chunks.root(), root);
+			assert!(chunks.chunk(n_validators).is_none());
+			assert!(chunks.branch(n_validators).is_none());
+
+			for (i, erasure_chunk) in chunks.iter().enumerate() {
+				assert_eq!(erasure_chunk.index, i as u32);
+				assert_eq!(erasure_chunk.chunk, obtained[i]);
+				assert_eq!(
+					branch_hash(&root, &erasure_chunk.proof, i).unwrap(),
+					BlakeTwo256::hash(&obtained[i]),
+				);
+			}
+
+			let reconstructed: v1::AvailableData = reconstruct_v1(
+				n_validators,
+				(0..n_validators).rev().map(|i| (chunks.chunk(i).unwrap(), i)),
+			).unwrap();
+			assert_eq!(reconstructed, available_data);
+		}
+	}
+
+	#[test]
+	fn validator_chunk_indices_are_a_permutation() {
+		let chunk_indices = validator_chunk_indices(Some(&[1; 32]), v1::CoreIndex(0), 10);
+
+		let mut sorted = chunk_indices.clone();
+		sorted.sort();
+		assert_eq!(sorted, (0..10).collect::<Vec<u32>>());
+	}
+
+	#[test]
+	fn validator_chunk_indices_are_deterministic_per_core() {
+		let seed = [1; 32];
+		let n_validators = 100;
+
+		assert_eq!(
+			validator_chunk_indices(Some(&seed), v1::CoreIndex(0), n_validators),
+			validator_chunk_indices(Some(&seed), v1::CoreIndex(0), n_validators),
+		);
+		assert_ne!(
+			validator_chunk_indices(Some(&seed), v1::CoreIndex(0), n_validators),
+			validator_chunk_indices(Some(&seed), v1::CoreIndex(1), n_validators),
+		);
+		assert_ne!(
+			validator_chunk_indices(Some(&seed), v1::CoreIndex(0), n_validators),
+			validator_chunk_indices(Some(&[2; 32]), v1::CoreIndex(0), n_validators),
+		);
+	}
+
+	#[test]
+	fn chunk_index_matches_validator_chunk_indices() {
+		let seed = [1; 32];
+		let chunk_indices = validator_chunk_indices(Some(&seed), v1::CoreIndex(3), 10);
+
+		for validator_index in 0..10 {
+			assert_eq!(
+				chunk_index(Some(&seed), v1::CoreIndex(3), 10, validator_index),
+				Some(chunk_indices[validator_index as usize]),
+			);
+		}
+		assert_eq!(chunk_index(Some(&seed), v1::CoreIndex(3), 10, 10), None);
+	}
+
+	#[test]
+	fn validator_chunk_indices_without_seed_are_the_identity() {
+		assert_eq!(
+			validator_chunk_indices(None, v1::CoreIndex(3), 10),
+			(0..10).collect::<Vec<u32>>(),
+		);
+		assert_eq!(chunk_index(None, v1::CoreIndex(3), 10, 7), Some(7));
+		assert_eq!(chunk_index(None, v1::CoreIndex(3), 10, 10), None);
+	}
+
+	#[test]
+	fn uniform_below_stays_below_bound() {
+		let mut rng = ChaCha20Rng::from_seed([1; 32]);
+
+		for bound in 1..100 {
+			assert!(uniform_below(&mut rng, bound) < bound);
+		}
+		assert!(uniform_below(&mut rng, u32::max_value()) < u32::max_value());
+	}
 }
@@ -29,7 +29,9 @@ use futures::{
 use polkadot_node_primitives::CollationGenerationConfig;
 use polkadot_node_subsystem::{
 	errors::RuntimeApiError,
-	messages::{AllMessages, CollationGenerationMessage, CollatorProtocolMessage},
+	messages::{
+		CollationGenerationMessage, CollationGenerationOutgoingMessages, CollatorProtocolMessage,
+	},
 	FromOverseer, SpawnedSubsystem, Subsystem, SubsystemContext, SubsystemError, SubsystemResult,
 	metrics::{self, prometheus},
 };
@@ -109,7 +111,7 @@ impl CollationGenerationSubsystem {
 		&mut self,
 		incoming: SubsystemResult<FromOverseer<Context::Message>>,
 		ctx: &mut Context,
-		sender: &mpsc::Sender<AllMessages>,
+		sender: &mpsc::Sender<CollationGenerationOutgoingMessages>,
 	) -> bool
 	where
 		Context: SubsystemContext<Message = CollationGenerationMessage>,
@@ -144,7 +146,7 @@ impl CollationGenerationSubsystem {
 					false
 				}
 			}
-			Ok(Signal(BlockFinalized(_))) => false,
+			Ok(Signal(BlockFinalized(..))) => false,
 			Err(err) => {
 				log::error!(target: "collation_generation", "error receiving message from subsystem context: {:?}", err);
 				true
@@ -185,12 +187,12 @@ enum Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
-async fn handle_new_activations<Context: SubsystemContext>(
+async fn handle_new_activations<Context: SubsystemContext<Message = CollationGenerationMessage>>(
 	config: Arc<CollationGenerationConfig>,
 	activated: &[Hash],
 	ctx: &mut Context,
 	metrics: Metrics,
-	sender: &mpsc::Sender<AllMessages>,
+	sender: &mpsc::Sender<CollationGenerationOutgoingMessages>,
 ) -> Result<()> {
 	// follow the procedure from the guide:
 	// https://w3f.github.io/parachain-implementers-guide/node/collators/collation-generation.html
@@ -205,16 +207,21 @@ async fn handle_new_activations<Context: SubsystemContext>(
 
 		let availability_cores = availability_cores??;
 		let n_validators = validators??.len();
+		let mut collating = false;
 
 		for core in availability_cores {
 			let (scheduled_core, assumption) = match core {
 				CoreState::Scheduled(scheduled_core) => {
 					(scheduled_core, OccupiedCoreAssumption::Free)
 				}
-				CoreState::Occupied(_occupied_core) => {
-					// TODO: https://github.com/paritytech/polkadot/issues/1573
-					continue;
-				}
+				CoreState::Occupied(occupied_core) => match occupied_core.next_up_on_available {
+					// build on the candidate occupying the core, which is usually made available
+					// before our collation could be backed.
+					Some(next_up) if !config.scheduled_only => {
+						(next_up, OccupiedCoreAssumption::Included)
+					}
+					_ => continue,
+				},
 				_ => continue,
 			};
 
@@ -238,10 +245,12 @@ async fn handle_new_activations<Context: SubsystemContext>(
 				None => continue,
 			};
 
+			collating = true;
+
 			let task_config = config.clone();
 			let mut task_sender = sender.clone();
 			let metrics = metrics.clone();
-			ctx.spawn("collation generation collation builder", Box::pin(async move {
+			ctx.spawn("collation-generation-builder", Box::pin(async move {
 				let persisted_validation_data_hash = validation_data.persisted.hash();
 
 				let collation = (task_config.collator)(&validation_data).await;
@@ -270,9 +279,11 @@ async fn handle_new_activations<Context: SubsystemContext>(
 				let commitments = CandidateCommitments {
 					fees: collation.fees,
 					upward_messages: collation.upward_messages,
+					horizontal_messages: collation.horizontal_messages,
 					new_validation_code: collation.new_validation_code,
 					head_data: collation.head_data,
 					erasure_root,
+					hrmp_watermark: collation.hrmp_watermark,
 				};
 
 				let ccr = CandidateReceipt {
@@ -289,13 +300,23 @@ async fn handle_new_activations<Context: SubsystemContext>(
 
 				metrics.on_collation_generated();
 
-				if let Err(err) = task_sender.send(AllMessages::CollatorProtocol(
+				if let Err(err) = task_sender.send(CollationGenerationOutgoingMessages::CollatorProtocol(
 					CollatorProtocolMessage::DistributeCollation(ccr, collation.proof_of_validity)
 				)).await {
 					log::warn!(target: "collation_generation", "failed to send collation result for para_id {}: {:?}", scheduled_core.para_id, err);
 				}
 			})).await?;
 		}
+
+		if !collating {
+			log::trace!(
+				target: "collation_generation",
+				"para {} has no core to collate on at {}",
+				config.para_id,
+				relay_parent,
+			);
+			metrics.on_collation_skipped();
+		}
 	}
 
 	Ok(())
@@ -311,13 +332,13 @@ fn erasure_root(
 		pov,
 	};
 
-	let chunks = polkadot_erasure_coding::obtain_chunks_v1(n_validators, &available_data)?;
-	Ok(polkadot_erasure_coding::branches(&chunks).root())
+	Ok(polkadot_erasure_coding::chunks_v1(n_validators, &available_data)?.root())
 }
 
 #[derive(Clone)]
 struct MetricsInner {
 	collations_generated_total: prometheus::Counter<prometheus::U64>,
+	collations_skipped_total: prometheus::Counter<prometheus::U64>,
 }
 
 /// CollationGenerationSubsystem metrics.
@@ -330,6 +351,12 @@ impl Metrics {
 			metrics.collations_generated_total.inc();
 		}
 	}
+
+	fn on_collation_skipped(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.collations_skipped_total.inc();
+		}
+	}
 }
 
 impl metrics::Metrics for Metrics {
@@ -342,6 +369,13 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			collations_skipped_total: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_collations_skipped_total",
+					"Number of activated relay-chain blocks at which the para had no core to collate on."
+				)?,
+				registry,
+			)?,
 		};
 		Ok(Metrics(Some(metrics)))
 	}
@@ -364,7 +398,7 @@ mod tests {
 			subsystem_test_harness, TestSubsystemContextHandle,
 		};
 		use polkadot_primitives::v1::{
-			BlockData, BlockNumber, CollatorPair, Id as ParaId,
+			BlockData, BlockNumber, CollatorPair, Id as ParaId, OccupiedCore,
 			PersistedValidationData, PoV, ScheduledCore, ValidationData,
 		};
 		use std::pin::Pin;
@@ -373,6 +407,8 @@ mod tests {
 			Collation {
 				fees: Default::default(),
 				upward_messages: Default::default(),
+				horizontal_messages: Default::default(),
+				hrmp_watermark: Default::default(),
 				new_validation_code: Default::default(),
 				head_data: Default::default(),
 				proof_of_validity: PoV {
@@ -401,6 +437,7 @@ mod tests {
 					Box::new(TestCollator)
 				}),
 				para_id: para_id.into(),
+				scheduled_only: true,
 			})
 		}
 
@@ -540,6 +577,87 @@ mod tests {
 			assert_eq!(requested_full_validation_data, vec![[4; 32].into()]);
 		}
 
+		fn requested_assumptions_for_occupied_core(scheduled_only: bool) -> Vec<OccupiedCoreAssumption> {
+			let requested_assumptions = Arc::new(Mutex::new(Vec::new()));
+
+			let overseer_requested_assumptions = requested_assumptions.clone();
+			let overseer = |mut handle: TestSubsystemContextHandle<CollationGenerationMessage>| async move {
+				loop {
+					match handle.try_recv().await {
+						None => break,
+						Some(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+							_hash,
+							RuntimeApiRequest::AvailabilityCores(tx),
+						))) => {
+							tx.send(Ok(vec![CoreState::Occupied(OccupiedCore {
+								para_id: 16.into(),
+								next_up_on_available: Some(scheduled_core_for(16)),
+								occupied_since: 1,
+								time_out_at: 10,
+								next_up_on_time_out: None,
+								availability: Default::default(),
+								group_responsible: Default::default(),
+							})]))
+							.unwrap();
+						}
+						Some(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+							_hash,
+							RuntimeApiRequest::FullValidationData(
+								_para_id,
+								occupied_core_assumption,
+								tx,
+							),
+						))) => {
+							overseer_requested_assumptions
+								.lock()
+								.await
+								.push(occupied_core_assumption);
+							tx.send(Ok(None)).unwrap();
+						}
+						Some(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+							_hash,
+							RuntimeApiRequest::Validators(tx),
+						))) => {
+							tx.send(Ok(vec![Default::default(); 3])).unwrap();
+						}
+						Some(msg) => {
+							panic!("didn't expect any other overseer requests; got {:?}", msg)
+						}
+					}
+				}
+			};
+
+			let config = Arc::new(CollationGenerationConfig {
+				key: CollatorPair::generate().0,
+				collator: Box::new(|_vd: &ValidationData| {
+					Box::new(TestCollator)
+				}),
+				para_id: 16.into(),
+				scheduled_only,
+			});
+
+			let (tx, _rx) = mpsc::channel(0);
+
+			subsystem_test_harness(overseer, |mut ctx| async move {
+				handle_new_activations(config, &[Hash::repeat_byte(1)], &mut ctx, Metrics(None), &tx)
+					.await
+					.unwrap();
+			});
+
+			Arc::try_unwrap(requested_assumptions)
+				.expect("overseer should have shut down by now")
+				.into_inner()
+		}
+
+		#[test]
+		fn builds_on_occupied_core_unless_scheduled_only() {
+			assert_eq!(
+				requested_assumptions_for_occupied_core(false),
+				vec![OccupiedCoreAssumption::Included],
+			);
+			assert!(requested_assumptions_for_occupied_core(true).is_empty());
+		}
+
 		#[test]
 		fn sends_distribute_collation_message() {
 			let activated_hashes: Vec<Hash> = vec![
@@ -639,7 +757,7 @@ mod tests {
 
 			assert_eq!(sent_messages.len(), 1);
 			match &sent_messages[0] {
-				AllMessages::CollatorProtocol(CollatorProtocolMessage::DistributeCollation(
+				CollationGenerationOutgoingMessages::CollatorProtocol(CollatorProtocolMessage::DistributeCollation(
 					CandidateReceipt { descriptor, .. },
 					_pov,
 				)) => {
@@ -36,6 +36,7 @@ use polkadot_subsystem::{
 	FromOverseer, SubsystemError, Subsystem, SubsystemContext, SpawnedSubsystem,
 	metrics::{self, prometheus},
 };
+use polkadot_subsystem::errors::StoreAvailableDataError;
 use polkadot_subsystem::messages::AvailabilityStoreMessage;
 
 const LOG_TARGET: &str = "availability";
@@ -55,6 +56,7 @@ enum Error {
 	Oneshot(oneshot::Canceled),
 	#[from]
 	Subsystem(SubsystemError),
+	InvalidErasureRoot,
 }
 
 /// An implementation of the Availability Store subsystem.
@@ -77,6 +79,17 @@ struct StoredAvailableData {
 	n_validators: u32,
 }
 
+/// What the availability store holds for a single candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateAvailability {
+	/// The number of validators the data was erasure-coded for, if the full data is stored.
+	pub n_validators: Option<u32>,
+	/// The encoded size of the stored PoV, if the full data is stored.
+	pub pov_size: Option<usize>,
+	/// The indices of the erasure chunks present in the store, in ascending order.
+	pub chunks: Vec<u32>,
+}
+
 /// Configuration for the availability store.
 pub struct Config {
 	/// Total cache size in megabytes. If `None` the default (128 MiB per column) is used.
@@ -112,6 +125,13 @@ impl AvailabilityStoreSubsystem {
 		})
 	}
 
+	/// Inspect what the store holds for the given candidate.
+	///
+	/// This reads the database directly and is meant for offline debugging tools.
+	pub fn inspect_candidate(&self, candidate_hash: &Hash) -> CandidateAvailability {
+		inspect_candidate(&self.inner, candidate_hash)
+	}
+
 	#[cfg(test)]
 	fn new_in_memory(inner: Arc<dyn KeyValueDB>) -> Self {
 		Self {
@@ -158,8 +178,11 @@ fn process_message(db: &Arc<dyn KeyValueDB>, metrics: &Metrics, msg: Availabilit
 		QueryChunk(hash, id, tx) => {
 			tx.send(get_chunk(db, &hash, id, metrics)?).map_err(|_| oneshot::Canceled)?;
 		}
-		QueryChunkAvailability(hash, id, tx) => {
-			tx.send(get_chunk(db, &hash, id, metrics)?.is_some()).map_err(|_| oneshot::Canceled)?;
+		QueryChunkAvailability(hash, tx) => {
+			tx.send(stored_chunk_indices(db, &hash)).map_err(|_| oneshot::Canceled)?;
+		}
+		QueryAllChunks(hash, tx) => {
+			tx.send(stored_chunks(db, &hash)).map_err(|_| oneshot::Canceled)?;
 		}
 		StoreChunk(hash, id, chunk, tx) => {
 			match store_chunk(db, &hash, id, chunk) {
@@ -172,10 +195,34 @@ fn process_message(db: &Arc<dyn KeyValueDB>, metrics: &Metrics, msg: Availabilit
 				}
 			}
 		}
-		StoreAvailableData(hash, id, n_validators, av_data, tx) => {
-			match store_available_data(db, &hash, id, n_validators, av_data, metrics) {
+		StoreAvailableData {
+			candidate_hash,
+			chunk_index,
+			n_validators,
+			available_data,
+			expected_erasure_root,
+			tx,
+		} => {
+			match store_available_data(
+				db,
+				&candidate_hash,
+				chunk_index,
+				n_validators,
+				available_data,
+				expected_erasure_root,
+				metrics,
+			) {
+				Err(Error::InvalidErasureRoot) => {
+					log::debug!(
+						target: LOG_TARGET,
+						"Refusing to store available data for {} with an unexpected erasure root",
+						candidate_hash,
+					);
+					tx.send(Err(StoreAvailableDataError::InvalidErasureRoot))
+						.map_err(|_| oneshot::Canceled)?;
+				}
 				Err(e) => {
-					tx.send(Err(())).map_err(|_| oneshot::Canceled)?;
+					tx.send(Err(StoreAvailableDataError::Failed)).map_err(|_| oneshot::Canceled)?;
 					return Err(e);
 				}
 				Ok(()) => {
@@ -192,19 +239,66 @@ fn available_data(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash) -> Option<Sto
 	query_inner(db, columns::DATA, &available_data_key(candidate_hash))
 }
 
+fn inspect_candidate(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash) -> CandidateAvailability {
+	let stored = available_data(db, candidate_hash);
+
+	CandidateAvailability {
+		n_validators: stored.as_ref().map(|s| s.n_validators),
+		pov_size: stored.as_ref().map(|s| s.data.pov.encoded_size()),
+		chunks: stored_chunk_indices(db, candidate_hash),
+	}
+}
+
+// The erasure chunks stored for the candidate along with their indices, in ascending order.
+fn stored_chunk_entries(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash)
+	-> impl Iterator<Item = (u32, Box<[u8]>)>
+{
+	let prefix = candidate_hash.encode();
+
+	// Chunk keys are the candidate hash followed by the chunk index and a trailing zero byte.
+	let chunk_key_len = erasure_chunk_key(candidate_hash, 0).len();
+	let mut entries: Vec<_> = db.iter_with_prefix(columns::DATA, &prefix)
+		.filter(|(key, _)| key.len() == chunk_key_len)
+		.filter_map(|(key, value)| u32::decode(&mut &key[prefix.len()..]).ok().map(|i| (i, value)))
+		.collect();
+	entries.sort_by_key(|(index, _)| *index);
+
+	entries.into_iter()
+}
+
+fn stored_chunk_indices(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash) -> Vec<u32> {
+	stored_chunk_entries(db, candidate_hash).map(|(index, _)| index).collect()
+}
+
+fn stored_chunks(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash) -> Vec<ErasureChunk> {
+	stored_chunk_entries(db, candidate_hash)
+		.map(|(_, value)| {
+			ErasureChunk::decode(&mut &value[..]).expect("all stored data serialized correctly; qed")
+		})
+		.collect()
+}
+
 fn store_available_data(
 	db: &Arc<dyn KeyValueDB>,
 	candidate_hash: &Hash,
 	id: Option<ValidatorIndex>,
 	n_validators: u32,
 	available_data: AvailableData,
+	expected_erasure_root: Hash,
 	metrics: &Metrics,
 ) -> Result<(), Error> {
 	let mut tx = DBTransaction::new();
 
+	let chunks = erasure::chunks_v1(n_validators as usize, &available_data)?;
+	if chunks.root() != expected_erasure_root {
+		return Err(Error::InvalidErasureRoot);
+	}
+
 	if let Some(index) = id {
-		let chunks = get_chunks(&available_data, n_validators as usize, metrics)?;
-		store_chunk(db, candidate_hash, n_validators, chunks[index as usize].clone())?;
+		metrics.on_chunks_received(chunks.len());
+		let chunk = chunks.erasure_chunk(index as usize)
+			.ok_or(erasure::Error::ChunkIndexOutOfBounds(index as usize, chunks.len()))?;
+		store_chunk(db, candidate_hash, n_validators, chunk)?;
 	}
 
 	let stored_data = StoredAvailableData {
@@ -247,12 +341,12 @@ fn get_chunk(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash, index: u32, metric
 	}
 
 	if let Some(data) = available_data(db, candidate_hash) {
-		let mut chunks = get_chunks(&data.data, data.n_validators as usize, metrics)?;
-		let desired_chunk = chunks.get(index as usize).cloned();
-		for chunk in chunks.drain(..) {
+		let chunks = erasure::chunks_v1(data.n_validators as usize, &data.data)?;
+		metrics.on_chunks_received(chunks.len());
+		for chunk in chunks.iter() {
 			store_chunk(db, candidate_hash, data.n_validators, chunk)?;
 		}
-		return Ok(desired_chunk);
+		return Ok(chunks.erasure_chunk(index as usize));
 	}
 
 	Ok(None)
@@ -292,24 +386,6 @@ impl<Context> Subsystem<Context> for AvailabilityStoreSubsystem
 	}
 }
 
-fn get_chunks(data: &AvailableData, n_validators: usize, metrics: &Metrics) -> Result<Vec<ErasureChunk>, Error> {
-	let chunks = erasure::obtain_chunks_v1(n_validators, data)?;
-	metrics.on_chunks_received(chunks.len());
-	let branches = erasure::branches(chunks.as_ref());
-
-	Ok(chunks
-		.iter()
-		.zip(branches.map(|(proof, _)| proof))
-		.enumerate()
-		.map(|(index, (chunk, proof))| ErasureChunk {
-			chunk: chunk.clone(),
-			proof,
-			index: index as u32,
-		})
-		.collect()
-	)
-}
-
 #[derive(Clone)]
 struct MetricsInner {
 	received_availability_chunks_total: prometheus::Counter<prometheus::U64>,
@@ -466,13 +542,14 @@ mod tests {
 
 
 			let (tx, rx) = oneshot::channel();
-			let block_msg = AvailabilityStoreMessage::StoreAvailableData(
+			let block_msg = AvailabilityStoreMessage::StoreAvailableData {
 				candidate_hash,
-				Some(validator_index),
+				chunk_index: Some(validator_index),
 				n_validators,
-				available_data.clone(),
+				available_data: available_data.clone(),
+				expected_erasure_root: erasure_root(&available_data, n_validators),
 				tx,
-			);
+			};
 
 			virtual_overseer.send(FromOverseer::Communication{ msg: block_msg }).await;
 			assert_eq!(rx.await.unwrap(), Ok(()));
@@ -517,17 +594,20 @@ mod tests {
 				validation_data: test_state.persisted_validation_data,
 			};
 
-			let no_metrics = Metrics(None);
-			let chunks_expected = get_chunks(&available_data, n_validators as usize, &no_metrics).unwrap();
+			let chunks_expected: Vec<_> = erasure::chunks_v1(n_validators as usize, &available_data)
+				.unwrap()
+				.iter()
+				.collect();
 
 			let (tx, rx) = oneshot::channel();
-			let block_msg = AvailabilityStoreMessage::StoreAvailableData(
+			let block_msg = AvailabilityStoreMessage::StoreAvailableData {
 				candidate_hash,
-				None,
+				chunk_index: None,
 				n_validators,
+				expected_erasure_root: erasure_root(&available_data, n_validators),
 				available_data,
 				tx,
-			);
+			};
 
 			virtual_overseer.send(FromOverseer::Communication{ msg: block_msg }).await;
 
@@ -541,6 +621,107 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn store_available_data_with_wrong_erasure_root_fails() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let test_state = TestState::default();
+
+		test_harness(store.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+			let candidate_hash = Hash::from([1; 32]);
+			let n_validators = 10;
+
+			let available_data = AvailableData {
+				pov: PoV { block_data: BlockData(vec![4, 5, 6]) },
+				validation_data: test_state.persisted_validation_data,
+			};
+
+			// The root of the same data erasure-coded for a different number of validators.
+			let wrong_root = erasure_root(&available_data, n_validators + 1);
+			assert_ne!(wrong_root, erasure_root(&available_data, n_validators));
+
+			let (tx, rx) = oneshot::channel();
+			let block_msg = AvailabilityStoreMessage::StoreAvailableData {
+				candidate_hash,
+				chunk_index: Some(5),
+				n_validators,
+				available_data,
+				expected_erasure_root: wrong_root,
+				tx,
+			};
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: block_msg }).await;
+			assert_eq!(rx.await.unwrap(), Err(StoreAvailableDataError::InvalidErasureRoot));
+
+			assert!(query_available_data(&mut virtual_overseer, candidate_hash).await.is_none());
+			assert!(query_chunk(&mut virtual_overseer, candidate_hash, 5).await.is_none());
+		});
+	}
+
+	#[test]
+	fn inspect_candidate_reports_stored_chunks() {
+		let store: Arc<dyn KeyValueDB> = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let candidate_hash = Hash::from([1; 32]);
+		let other_hash = Hash::from([2; 32]);
+
+		assert_eq!(
+			inspect_candidate(&store, &candidate_hash),
+			CandidateAvailability { n_validators: None, pov_size: None, chunks: Vec::new() },
+		);
+
+		for &(hash, index) in &[(candidate_hash, 7), (candidate_hash, 2), (other_hash, 3)] {
+			let chunk = ErasureChunk { chunk: vec![1, 2, 3], index, proof: Vec::new() };
+			store_chunk(&store, &hash, 10, chunk).unwrap();
+		}
+
+		let available_data = AvailableData {
+			pov: PoV { block_data: BlockData(vec![4, 5, 6]) },
+			validation_data: TestState::default().persisted_validation_data,
+		};
+		let pov_size = available_data.pov.encoded_size();
+		let root = erasure_root(&available_data, 10);
+		store_available_data(&store, &candidate_hash, None, 10, available_data, root, &Metrics(None)).unwrap();
+
+		assert_eq!(
+			inspect_candidate(&store, &candidate_hash),
+			CandidateAvailability { n_validators: Some(10), pov_size: Some(pov_size), chunks: vec![2, 7] },
+		);
+	}
+
+	#[test]
+	fn query_chunk_availability_and_all_chunks_work() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		test_harness(store.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+			let candidate_hash = Hash::from([1; 32]);
+			let other_hash = Hash::from([2; 32]);
+
+			let chunk = |index| ErasureChunk { chunk: vec![index as u8], index, proof: Vec::new() };
+
+			for &(hash, index) in &[(candidate_hash, 7), (candidate_hash, 2), (other_hash, 3)] {
+				let (tx, rx) = oneshot::channel();
+				let store_msg = AvailabilityStoreMessage::StoreChunk(hash, index, chunk(index), tx);
+				virtual_overseer.send(FromOverseer::Communication{ msg: store_msg }).await;
+				assert_eq!(rx.await.unwrap(), Ok(()));
+			}
+
+			let (tx, rx) = oneshot::channel();
+			let query = AvailabilityStoreMessage::QueryChunkAvailability(candidate_hash, tx);
+			virtual_overseer.send(FromOverseer::Communication{ msg: query }).await;
+			assert_eq!(rx.await.unwrap(), vec![2, 7]);
+
+			let (tx, rx) = oneshot::channel();
+			let query = AvailabilityStoreMessage::QueryAllChunks(candidate_hash, tx);
+			virtual_overseer.send(FromOverseer::Communication{ msg: query }).await;
+			assert_eq!(rx.await.unwrap(), vec![chunk(2), chunk(7)]);
+
+			let (tx, rx) = oneshot::channel();
+			let query = AvailabilityStoreMessage::QueryAllChunks(Hash::from([3; 32]), tx);
+			virtual_overseer.send(FromOverseer::Communication{ msg: query }).await;
+			assert!(rx.await.unwrap().is_empty());
+		});
+	}
+
 	async fn query_available_data(
 		virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<AvailabilityStoreMessage>,
 		candidate_hash: Hash,
@@ -553,6 +734,11 @@ mod tests {
 		rx.await.unwrap()
 	}
 
+	fn erasure_root(available_data: &AvailableData, n_validators: u32) -> Hash {
+		let chunks = erasure::obtain_chunks_v1(n_validators as usize, available_data).unwrap();
+		erasure::branches(chunks.as_ref()).root()
+	}
+
 	async fn query_chunk(
 		virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<AvailabilityStoreMessage>,
 		candidate_hash: Hash,
@@ -32,28 +32,33 @@ use polkadot_primitives::v1::{
 	CommittedCandidateReceipt, BackedCandidate, Id as ParaId, ValidatorId,
 	ValidatorIndex, SigningContext, PoV,
 	CandidateDescriptor, AvailableData, ValidatorSignature, Hash, CandidateReceipt,
-	CandidateCommitments, CoreState, CoreIndex, CollatorId,
+	CandidateCommitments, CoreState, CoreIndex, CollatorId, HeadData,
 };
 use polkadot_node_primitives::{
 	FromTableMisbehavior, Statement, SignedFullStatement, MisbehaviorReport,
-	ValidationOutputs, ValidationResult,
+	ValidationOutputs, ValidationResult, InvalidCandidate, check_pov,
 };
 use polkadot_subsystem::{
 	messages::{
-		AllMessages, AvailabilityStoreMessage, CandidateBackingMessage, CandidateSelectionMessage,
-		CandidateValidationMessage, NewBackedCandidate, PoVDistributionMessage, ProvisionableData,
-		ProvisionerMessage, RuntimeApiMessage, StatementDistributionMessage, ValidationFailed,
-		RuntimeApiRequest,
+		AllMessages, AvailabilityStoreMessage, CandidateBackingMessage, CandidateBackingOutgoingMessages,
+		CandidateSelectionMessage, CandidateValidationMessage, NewBackedCandidate, PoVDistributionMessage,
+		ProvisionableData, ProvisionerMessage, RuntimeApiMessage, StatementDistributionMessage,
+		ValidationFailed, RuntimeApiRequest,
 	},
+	errors::{RuntimeApiError, StoreAvailableDataError},
 	metrics::{self, prometheus},
 };
 use polkadot_node_subsystem_util::{
 	self as util,
+	backing_group_for_core,
+	request_allowed_relay_parents,
+	request_availability_chunk_shuffle_seed,
 	request_session_index_for_child,
 	request_validator_groups,
 	request_validators,
 	request_from_runtime,
 	Validator,
+	ValidatorKeyCache,
 	delegated_subsystem,
 };
 use statement_table::{
@@ -66,6 +71,8 @@ use statement_table::{
 	},
 };
 
+const LOG_TARGET: &str = "candidate_backing";
+
 #[derive(Debug, derive_more::From)]
 enum Error {
 	CandidateNotFound,
@@ -95,6 +102,13 @@ struct CandidateBackingJob {
 	assignment: ParaId,
 	/// The collator required to author the candidate, if any.
 	required_collator: Option<CollatorId>,
+	/// Whether the core of our assignment is occupied by a candidate pending availability with
+	/// nothing next up on it, so that no candidate can be backed on it.
+	core_occupied: bool,
+	/// The relay parents candidates backed in a child of the parent may be built on.
+	allowed_relay_parents: HashSet<Hash>,
+	/// The index of the erasure chunk this validator is responsible for on the assigned core.
+	chunk_index: Option<u32>,
 	/// We issued `Valid` or `Invalid` statements on about these candidates.
 	issued_statements: HashSet<Hash>,
 	/// `Some(h)` if this job has already issues `Seconded` statemt for some candidate with `h` hash.
@@ -190,15 +204,21 @@ enum FromJob {
 }
 
 impl From<FromJob> for AllMessages {
+	fn from(f: FromJob) -> AllMessages {
+		CandidateBackingOutgoingMessages::from(f).into()
+	}
+}
+
+impl From<FromJob> for CandidateBackingOutgoingMessages {
 	fn from(f: FromJob) -> Self {
 		match f {
-			FromJob::AvailabilityStore(msg) => AllMessages::AvailabilityStore(msg),
-			FromJob::RuntimeApiMessage(msg) => AllMessages::RuntimeApi(msg),
-			FromJob::CandidateValidation(msg) => AllMessages::CandidateValidation(msg),
-			FromJob::CandidateSelection(msg) => AllMessages::CandidateSelection(msg),
-			FromJob::StatementDistribution(msg) => AllMessages::StatementDistribution(msg),
-			FromJob::PoVDistribution(msg) => AllMessages::PoVDistribution(msg),
-			FromJob::Provisioner(msg) => AllMessages::Provisioner(msg),
+			FromJob::AvailabilityStore(msg) => CandidateBackingOutgoingMessages::AvailabilityStore(msg),
+			FromJob::RuntimeApiMessage(msg) => CandidateBackingOutgoingMessages::RuntimeApi(msg),
+			FromJob::CandidateValidation(msg) => CandidateBackingOutgoingMessages::CandidateValidation(msg),
+			FromJob::CandidateSelection(msg) => CandidateBackingOutgoingMessages::CandidateSelection(msg),
+			FromJob::StatementDistribution(msg) => CandidateBackingOutgoingMessages::StatementDistribution(msg),
+			FromJob::PoVDistribution(msg) => CandidateBackingOutgoingMessages::PoVDistribution(msg),
+			FromJob::Provisioner(msg) => CandidateBackingOutgoingMessages::Provisioner(msg),
 		}
 	}
 }
@@ -220,6 +240,17 @@ impl TryFrom<AllMessages> for FromJob {
 	}
 }
 
+// The descriptor doesn't commit to the head-data the candidate produces, so candidate validation
+// can't check it against the receipt. A valid outcome with other head-data is turned into an
+// invalid one here, so the reason is reported like any other.
+fn check_head_data(result: ValidationResult, expected: &HeadData) -> ValidationResult {
+	match result {
+		ValidationResult::Valid(ref outputs) if &outputs.head_data != expected =>
+			ValidationResult::Invalid(InvalidCandidate::HeadDataMismatch),
+		result => result,
+	}
+}
+
 // It looks like it's not possible to do an `impl From` given the current state of
 // the code. So this does the necessary conversion.
 fn primitive_statement_to_table(s: &SignedFullStatement) -> TableSignedStatement {
@@ -278,6 +309,19 @@ impl CandidateBackingJob {
 			return Ok(false);
 		}
 
+		// Check that the PoV supplied by the collator is within limits and is the one the
+		// candidate commits to before spending any effort on validating it.
+		if let Err(e) = check_pov(&pov, &candidate.descriptor().pov_hash) {
+			log::debug!(
+				target: LOG_TARGET,
+				"Refusing to second candidate {}: bad PoV ({:?})",
+				candidate.hash(),
+				e,
+			);
+			self.issue_candidate_invalid_message(candidate.clone()).await?;
+			return Ok(false);
+		}
+
 		let valid = self.request_candidate_validation(
 			candidate.descriptor().clone(),
 			Arc::new(pov.clone()),
@@ -294,8 +338,10 @@ impl CandidateBackingJob {
 				// If the commitments hash produced by validation is not the same as given by
 				// the collator, do not make available and report the collator.
 				let commitments_check = self.make_pov_available(
+					candidate_hash,
 					pov,
 					outputs,
+					None,
 					|commitments| if commitments.hash() == candidate.commitments_hash {
 						Ok(CommittedCandidateReceipt {
 							descriptor: candidate.descriptor().clone(),
@@ -312,12 +358,23 @@ impl CandidateBackingJob {
 						Some(Statement::Seconded(candidate))
 					}
 					Err(()) => {
+						log::debug!(
+							target: LOG_TARGET,
+							"Candidate {} to second produced commitments or available data not matching its receipt",
+							candidate_hash,
+						);
 						self.issue_candidate_invalid_message(candidate.clone()).await?;
 						None
 					}
 				}
 			}
-			ValidationResult::Invalid(_reason) => {
+			ValidationResult::Invalid(reason) => {
+				log::debug!(
+					target: LOG_TARGET,
+					"Candidate {} to second is invalid: {:?}",
+					candidate_hash,
+					reason,
+				);
 				// no need to issue a statement about this if we aren't seconding it.
 				//
 				// there's an infinite amount of garbage out there. no need to acknowledge
@@ -431,6 +488,32 @@ impl CandidateBackingJob {
 					return Ok(());
 				}
 
+				// Nothing is next up on our core once the candidate occupying it is available,
+				// so no candidate built on this relay parent can be included on it. Don't waste
+				// effort on fetching its PoV and validating it.
+				if self.core_occupied {
+					log::debug!(
+						target: LOG_TARGET,
+						"Refusing to second candidate {} of para {:?}: its core is occupied at {}",
+						candidate.hash(),
+						self.assignment,
+						self.parent,
+					);
+					self.metrics.on_candidate_refused_occupied_core();
+					return Ok(());
+				}
+
+				if !self.is_allowed_relay_parent(&candidate.descriptor().relay_parent) {
+					log::debug!(
+						target: LOG_TARGET,
+						"Refusing to second candidate {}: relay parent {} is not allowed at {}",
+						candidate.hash(),
+						candidate.descriptor().relay_parent,
+						self.parent,
+					);
+					return Ok(());
+				}
+
 				// If the message is a `CandidateBackingMessage::Second`, sign and dispatch a
 				// Seconded statement only if we have not seconded any other candidate and
 				// have not signed a Valid statement for the requested candidate.
@@ -490,9 +573,11 @@ impl CandidateBackingJob {
 
 		let descriptor = candidate.descriptor().clone();
 
-		// Check that candidate is collated by the right collator.
+		// Check that candidate is collated by the right collator and is built on a relay parent
+		// it can be backed on.
 		if self.required_collator.as_ref()
 			.map_or(false, |c| c != &descriptor.collator)
+			|| !self.is_allowed_relay_parent(&descriptor.relay_parent)
 		{
 			// If not, we've got the statement in the table but we will
 			// not issue validation work for it.
@@ -504,13 +589,17 @@ impl CandidateBackingJob {
 
 		let pov = self.request_pov_from_distribution(descriptor.clone()).await?;
 		let v = self.request_candidate_validation(descriptor, pov.clone()).await?;
+		let v = check_head_data(v, &expected_commitments.head_data);
 
 		let statement = match v {
 			ValidationResult::Valid(outputs) => {
 				// If validation produces a new set of commitments, we vote the candidate as invalid.
+				let expected_erasure_root = expected_commitments.erasure_root;
 				let commitments_check = self.make_pov_available(
+					candidate_hash,
 					(&*pov).clone(),
 					outputs,
+					Some(expected_erasure_root),
 					|commitments| if commitments == expected_commitments {
 						Ok(())
 					} else {
@@ -520,10 +609,23 @@ impl CandidateBackingJob {
 
 				match commitments_check {
 					Ok(()) => Statement::Valid(candidate_hash),
-					Err(()) => Statement::Invalid(candidate_hash),
+					Err(()) => {
+						log::debug!(
+							target: LOG_TARGET,
+							"Candidate {} produced commitments or available data not matching its receipt",
+							candidate_hash,
+						);
+						Statement::Invalid(candidate_hash)
+					}
 				}
 			}
-			ValidationResult::Invalid(_reason) => {
+			ValidationResult::Invalid(reason) => {
+				log::debug!(
+					target: LOG_TARGET,
+					"Candidate {} is invalid: {:?}",
+					candidate_hash,
+					reason,
+				);
 				Statement::Invalid(candidate_hash)
 			}
 		};
@@ -553,6 +655,10 @@ impl CandidateBackingJob {
 		Ok(())
 	}
 
+	fn is_allowed_relay_parent(&self, relay_parent: &Hash) -> bool {
+		relay_parent == &self.parent || self.allowed_relay_parents.contains(relay_parent)
+	}
+
 	fn sign_statement(&self, statement: Statement) -> Option<SignedFullStatement> {
 		let signed = self.table_context.validator.as_ref()?.sign(statement);
 		self.metrics.on_statement_signed();
@@ -600,85 +706,109 @@ impl CandidateBackingJob {
 	) -> Result<ValidationResult, Error> {
 		let (tx, rx) = oneshot::channel();
 
-		self.tx_from.send(FromJob::CandidateValidation(
-				CandidateValidationMessage::ValidateFromChainState(
-					candidate,
-					pov,
-					tx,
-				)
-			)
-		).await?;
+		// Candidates built on an ancestor are validated in the state of our parent, which
+		// knows the relay parents allowed at it.
+		let msg = if candidate.relay_parent == self.parent {
+			CandidateValidationMessage::ValidateFromChainState(candidate, pov, tx)
+		} else {
+			CandidateValidationMessage::ValidateFromChainStateAt(self.parent, candidate, pov, tx)
+		};
+
+		self.tx_from.send(FromJob::CandidateValidation(msg)).await?;
 
 		Ok(rx.await??)
 	}
 
+	// Store the available data of a candidate along with our chunk of it, if the data has the
+	// erasure root the candidate commits to. Returns `Ok(false)` if it hasn't.
 	async fn store_available_data(
 		&mut self,
-		id: Option<ValidatorIndex>,
+		candidate_hash: Hash,
+		chunk_index: Option<u32>,
 		n_validators: u32,
 		available_data: AvailableData,
-	) -> Result<(), Error> {
+		expected_erasure_root: Hash,
+	) -> Result<bool, Error> {
 		let (tx, rx) = oneshot::channel();
 		self.tx_from.send(FromJob::AvailabilityStore(
-				AvailabilityStoreMessage::StoreAvailableData(
-					self.parent,
-					id,
+				AvailabilityStoreMessage::StoreAvailableData {
+					candidate_hash,
+					chunk_index,
 					n_validators,
 					available_data,
+					expected_erasure_root,
 					tx,
-				)
+				}
 			)
 		).await?;
 
-		rx.await?.map_err(|_| Error::StoreFailed)?;
-
-		Ok(())
+		match rx.await? {
+			Ok(()) => Ok(true),
+			Err(StoreAvailableDataError::InvalidErasureRoot) => Ok(false),
+			Err(StoreAvailableDataError::Failed) => Err(Error::StoreFailed),
+		}
 	}
 
 	// Make a `PoV` available.
 	//
-	// This calls an inspection function before making the PoV available for any last checks
-	// that need to be done. If the inspection function returns an error, this function returns
-	// early without making the PoV available.
-	async fn make_pov_available<T, E>(
+	// `erasure_root` is the erasure root the candidate commits to. A candidate to second only
+	// commits to it through the hash of its commitments, so it is computed here if `None`.
+	// Otherwise, the availability store checks the erasure root of the data against it, and
+	// no erasure coding is done here.
+	//
+	// This calls an inspection function with the commitments of the candidate before making
+	// the PoV available for any last checks that need to be done. If the inspection function
+	// returns an error, or the data doesn't have the erasure root of the commitments, this
+	// function returns `Ok(Err(()))` without making the PoV available.
+	async fn make_pov_available<T>(
 		&mut self,
+		candidate_hash: Hash,
 		pov: PoV,
 		outputs: ValidationOutputs,
-		with_commitments: impl FnOnce(CandidateCommitments) -> Result<T, E>,
-	) -> Result<Result<T, E>, Error> {
+		erasure_root: Option<Hash>,
+		with_commitments: impl FnOnce(CandidateCommitments) -> Result<T, ()>,
+	) -> Result<Result<T, ()>, Error> {
 		let available_data = AvailableData {
 			pov,
 			validation_data: outputs.validation_data,
 		};
 
-		let chunks = erasure_coding::obtain_chunks_v1(
-			self.table_context.validators.len(),
-			&available_data,
-		)?;
-
-		let branches = erasure_coding::branches(chunks.as_ref());
-		let erasure_root = branches.root();
+		let erasure_root = match erasure_root {
+			Some(erasure_root) => erasure_root,
+			None => erasure_coding::chunks_v1(
+				self.table_context.validators.len(),
+				&available_data,
+			)?.root(),
+		};
 
 		let commitments = CandidateCommitments {
 			fees: outputs.fees,
 			upward_messages: outputs.upward_messages,
+			horizontal_messages: outputs.horizontal_messages,
 			erasure_root,
 			new_validation_code: outputs.new_validation_code,
 			head_data: outputs.head_data,
+			hrmp_watermark: outputs.hrmp_watermark,
 		};
 
 		let res = match with_commitments(commitments) {
 			Ok(x) => x,
-			Err(e) => return Ok(Err(e)),
+			Err(()) => return Ok(Err(())),
 		};
 
-		self.store_available_data(
-			self.table_context.validator.as_ref().map(|v| v.index()),
+		let stored = self.store_available_data(
+			candidate_hash,
+			self.chunk_index,
 			self.table_context.validators.len() as u32,
 			available_data,
+			erasure_root,
 		).await?;
 
-		Ok(Ok(res))
+		if stored {
+			Ok(Ok(res))
+		} else {
+			Ok(Err(()))
+		}
 	}
 
 	async fn distribute_signed_statement(&mut self, s: SignedFullStatement) -> Result<(), Error> {
@@ -694,14 +824,14 @@ impl util::JobTrait for CandidateBackingJob {
 	type ToJob = ToJob;
 	type FromJob = FromJob;
 	type Error = Error;
-	type RunArgs = KeyStorePtr;
+	type RunArgs = (KeyStorePtr, ValidatorKeyCache);
 	type Metrics = Metrics;
 
-	const NAME: &'static str = "CandidateBackingJob";
+	const NAME: &'static str = "candidate-backing-job";
 
 	fn run(
 		parent: Hash,
-		keystore: KeyStorePtr,
+		(keystore, key_cache): (KeyStorePtr, ValidatorKeyCache),
 		metrics: Metrics,
 		rx_to: mpsc::Receiver<Self::ToJob>,
 		mut tx_from: mpsc::Sender<Self::FromJob>,
@@ -713,7 +843,7 @@ impl util::JobTrait for CandidateBackingJob {
 						Ok(x) => x,
 						Err(e) => {
 							log::warn!(
-								target: "candidate_backing",
+								target: LOG_TARGET,
 								"Failed to fetch runtime API data for job: {:?}",
 								e,
 							);
@@ -727,7 +857,14 @@ impl util::JobTrait for CandidateBackingJob {
 				}
 			}
 
-			let (validators, groups, session_index, cores) = futures::try_join!(
+			let (
+				validators,
+				groups,
+				session_index,
+				cores,
+				chunk_shuffle_seed,
+				allowed_relay_parents,
+			) = futures::try_join!(
 				request_validators(parent, &mut tx_from).await?,
 				request_validator_groups(parent, &mut tx_from).await?,
 				request_session_index_for_child(parent, &mut tx_from).await?,
@@ -736,24 +873,39 @@ impl util::JobTrait for CandidateBackingJob {
 					&mut tx_from,
 					|tx| RuntimeApiRequest::AvailabilityCores(tx),
 				).await?,
+				request_availability_chunk_shuffle_seed(parent, &mut tx_from).await?,
+				request_allowed_relay_parents(parent, &mut tx_from).await?,
 			)?;
 
 			let validators = try_runtime_api!(validators);
 			let (validator_groups, group_rotation_info) = try_runtime_api!(groups);
 			let session_index = try_runtime_api!(session_index);
 			let cores = try_runtime_api!(cores);
+			// runtimes without chunk shuffling keep the identity mapping of chunks to validators.
+			let chunk_shuffle_seed = match chunk_shuffle_seed {
+				Err(RuntimeApiError::NotSupported { .. }) => None,
+				res => Some(try_runtime_api!(res)),
+			};
+			// runtimes without allowed ancestry only accept candidates built on the parent.
+			let allowed_relay_parents = match allowed_relay_parents {
+				Err(RuntimeApiError::NotSupported { .. }) => HashSet::new(),
+				res => try_runtime_api!(res).into_iter().map(|(hash, _)| hash).collect(),
+			};
 
 			let signing_context = SigningContext { parent_hash: parent, session_index };
-			let validator = match Validator::construct(
+			// The local key is resolved from the keystore once per session, so rotated
+			// session keys are picked up at the first relay-parent of the new session.
+			let validator = match Validator::construct_cached(
 				&validators,
 				signing_context,
 				keystore.clone(),
+				&key_cache,
 			) {
 				Ok(v) => v,
 				Err(util::Error::NotAValidator) => { return Ok(()) },
 				Err(e) => {
 					log::warn!(
-						target: "candidate_backing",
+						target: LOG_TARGET,
 						"Cannot participate in candidate backing: {:?}",
 						e
 					);
@@ -768,19 +920,57 @@ impl util::JobTrait for CandidateBackingJob {
 
 			let mut assignment = None;
 			for (idx, core) in cores.into_iter().enumerate() {
-				// Ignore prospective assignments on occupied cores for the time being.
-				if let CoreState::Scheduled(scheduled) = core {
-					let core_index = CoreIndex(idx as _);
-					let group_index = group_rotation_info.group_for_core(core_index, n_cores);
-					if let Some(g) = validator_groups.get(group_index.0 as usize) {
+				let core_index = CoreIndex(idx as _);
+				let group = backing_group_for_core(
+					&validator_groups,
+					&group_rotation_info,
+					core_index,
+					n_cores,
+				);
+				let g = match group {
+					Some((_, g)) => g,
+					None => continue,
+				};
+
+				match core {
+					CoreState::Scheduled(scheduled) => {
 						if g.contains(&validator.index()) {
-							assignment = Some((scheduled.para_id, scheduled.collator));
+							assignment = Some((scheduled.para_id, scheduled.collator, core_index, false));
 						}
-						groups.insert(scheduled.para_id, g.clone());
+						groups.insert(scheduled.para_id, g.to_vec());
 					}
+					// Candidates for the para next up on an occupied core can be backed already and
+					// are included once the core is freed. Without one, remember our group's para in
+					// order to refuse seconding candidates for it.
+					CoreState::Occupied(occupied) => match occupied.next_up_on_available {
+						Some(next_up) => {
+							if g.contains(&validator.index()) {
+								assignment = Some((next_up.para_id, next_up.collator, core_index, false));
+							}
+							groups.insert(next_up.para_id, g.to_vec());
+						}
+						None => {
+							if g.contains(&validator.index()) {
+								assignment = Some((occupied.para_id, None, core_index, true));
+							}
+						}
+					},
+					CoreState::Free => {}
 				}
 			}
 
+			let (assignment, required_collator, core_index, core_occupied) = match assignment {
+				None => return Ok(()), // no need to work.
+				Some((a, r, c, o)) => (a, r, c, o),
+			};
+
+			let chunk_index = erasure_coding::chunk_index(
+				chunk_shuffle_seed.as_ref(),
+				core_index,
+				validators.len(),
+				validator.index(),
+			);
+
 			let table_context = TableContext {
 				groups,
 				validators,
@@ -788,17 +978,15 @@ impl util::JobTrait for CandidateBackingJob {
 				validator: Some(validator),
 			};
 
-			let (assignment, required_collator) = match assignment {
-				None => return Ok(()), // no need to work.
-				Some((a, r)) => (a, r),
-			};
-
 			let job = CandidateBackingJob {
 				parent,
 				rx_to,
 				tx_from,
 				assignment,
 				required_collator,
+				core_occupied,
+				allowed_relay_parents,
+				chunk_index,
 				issued_statements: HashSet::new(),
 				seconded: None,
 				reported_misbehavior_for: HashSet::new(),
@@ -816,7 +1004,8 @@ impl util::JobTrait for CandidateBackingJob {
 #[derive(Clone)]
 struct MetricsInner {
 	signed_statements_total: prometheus::Counter<prometheus::U64>,
-	candidates_seconded_total: prometheus::Counter<prometheus::U64>
+	candidates_seconded_total: prometheus::Counter<prometheus::U64>,
+	candidates_refused_occupied_core_total: prometheus::Counter<prometheus::U64>,
 }
 
 /// Candidate backing metrics.
@@ -835,6 +1024,12 @@ impl Metrics {
 			metrics.candidates_seconded_total.inc();
 		}
 	}
+
+	fn on_candidate_refused_occupied_core(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.candidates_refused_occupied_core_total.inc();
+		}
+	}
 }
 
 impl metrics::Metrics for Metrics {
@@ -854,12 +1049,19 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			candidates_refused_occupied_core_total: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_candidates_refused_occupied_core_total",
+					"Number of candidates not seconded because the core of their para was occupied.",
+				)?,
+				registry,
+			)?,
 		};
 		Ok(Metrics(Some(metrics)))
 	}
 }
 
-delegated_subsystem!(CandidateBackingJob(KeyStorePtr, Metrics) <- ToJob as CandidateBackingSubsystem);
+delegated_subsystem!(CandidateBackingJob((KeyStorePtr, ValidatorKeyCache), Metrics) <- ToJob as CandidateBackingSubsystem);
 
 #[cfg(test)]
 mod tests {
@@ -867,15 +1069,14 @@ mod tests {
 	use assert_matches::assert_matches;
 	use futures::{executor, future, Future};
 	use polkadot_primitives::v1::{
-		ScheduledCore, BlockData, CandidateCommitments,
-		PersistedValidationData, ValidationData, TransientValidationData, HeadData,
-		ValidatorPair, ValidityAttestation, GroupRotationInfo,
+		ScheduledCore, OccupiedCore, GroupIndex, BlockData, CandidateCommitments,
+		PersistedValidationData, ValidationData, TransientValidationData,
+		ValidatorPair, ValidityAttestation, GroupRotationInfo, BlockNumber,
 	};
 	use polkadot_subsystem::{
 		messages::RuntimeApiRequest,
 		ActiveLeavesUpdate, FromOverseer, OverseerSignal,
 	};
-	use polkadot_node_primitives::InvalidCandidate;
 	use sp_keyring::Sr25519Keyring;
 	use std::collections::HashMap;
 
@@ -894,6 +1095,8 @@ mod tests {
 		head_data: HashMap<ParaId, HeadData>,
 		signing_context: SigningContext,
 		relay_parent: Hash,
+		chunk_shuffle_seed: [u8; 32],
+		allowed_relay_parents: Vec<(Hash, BlockNumber)>,
 	}
 
 	impl Default for TestState {
@@ -961,6 +1164,8 @@ mod tests {
 				transient: TransientValidationData {
 					max_code_size: 1000,
 					max_head_data_size: 1000,
+					max_upward_message_num: 0,
+					max_upward_message_size: 0,
 					balance: Default::default(),
 					code_upgrade_allowed: None,
 				},
@@ -977,6 +1182,8 @@ mod tests {
 				validation_data,
 				signing_context,
 				relay_parent,
+				chunk_shuffle_seed: [5; 32],
+				allowed_relay_parents: vec![(relay_parent, 1), (Hash::from([4; 32]), 0)],
 			}
 		}
 	}
@@ -990,7 +1197,12 @@ mod tests {
 
 		let (context, virtual_overseer) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool.clone());
 
-		let subsystem = CandidateBackingSubsystem::run(context, keystore, Metrics(None), pool.clone());
+		let subsystem = CandidateBackingSubsystem::run(
+			context,
+			(keystore, ValidatorKeyCache::default()),
+			Metrics(None),
+			pool.clone(),
+		);
 
 		let test_fut = test(TestHarness {
 			virtual_overseer,
@@ -1088,6 +1300,26 @@ mod tests {
 				tx.send(Ok(test_state.availability_cores.clone())).unwrap();
 			}
 		);
+
+		// Check that subsystem job issues a request for the availability chunk shuffle seed.
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(
+				RuntimeApiMessage::Request(parent, RuntimeApiRequest::AvailabilityChunkShuffleSeed(tx))
+			) if parent == test_state.relay_parent => {
+				tx.send(Ok(test_state.chunk_shuffle_seed)).unwrap();
+			}
+		);
+
+		// Check that subsystem job issues a request for the allowed relay parents.
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(
+				RuntimeApiMessage::Request(parent, RuntimeApiRequest::AllowedRelayParents(tx))
+			) if parent == test_state.relay_parent => {
+				tx.send(Ok(test_state.allowed_relay_parents.clone())).unwrap();
+			}
+		);
 	}
 
 	// Test that a `CandidateBackingMessage::Second` issues validation work
@@ -1139,6 +1371,8 @@ mod tests {
 							validation_data: test_state.validation_data.persisted,
 							head_data: expected_head_data.clone(),
 							upward_messages: Vec::new(),
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 							fees: Default::default(),
 							new_validation_code: None,
 						}),
@@ -1149,8 +1383,8 @@ mod tests {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::AvailabilityStore(
-					AvailabilityStoreMessage::StoreAvailableData(parent_hash, _, _, _, tx)
-				) if parent_hash == test_state.relay_parent => {
+					AvailabilityStoreMessage::StoreAvailableData { candidate_hash, tx, .. }
+				) if candidate_hash == candidate.hash() => {
 					tx.send(Ok(())).unwrap();
 				}
 			);
@@ -1250,6 +1484,8 @@ mod tests {
 							validation_data: test_state.validation_data.persisted,
 							head_data: expected_head_data.clone(),
 							upward_messages: Vec::new(),
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 							fees: Default::default(),
 							new_validation_code: None,
 						}),
@@ -1260,8 +1496,8 @@ mod tests {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::AvailabilityStore(
-					AvailabilityStoreMessage::StoreAvailableData(parent_hash, _, _, _, tx)
-				) if parent_hash == test_state.relay_parent => {
+					AvailabilityStoreMessage::StoreAvailableData { candidate_hash, tx, .. }
+				) if candidate_hash == candidate_a.hash() => {
 					tx.send(Ok(())).unwrap();
 				}
 			);
@@ -1379,6 +1615,8 @@ mod tests {
 							validation_data: test_state.validation_data.persisted,
 							head_data: expected_head_data.clone(),
 							upward_messages: Vec::new(),
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 							fees: Default::default(),
 							new_validation_code: None,
 						}),
@@ -1389,8 +1627,8 @@ mod tests {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::AvailabilityStore(
-					AvailabilityStoreMessage::StoreAvailableData(parent_hash, _, _, _, tx)
-					) if parent_hash == test_state.relay_parent => {
+					AvailabilityStoreMessage::StoreAvailableData { candidate_hash, tx, .. }
+					) if candidate_hash == candidate_a.hash() => {
 						tx.send(Ok(())).unwrap();
 					}
 			);
@@ -1535,6 +1773,8 @@ mod tests {
 							validation_data: test_state.validation_data.persisted,
 							head_data: expected_head_data.clone(),
 							upward_messages: Vec::new(),
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 							fees: Default::default(),
 							new_validation_code: None,
 						}),
@@ -1545,8 +1785,8 @@ mod tests {
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::AvailabilityStore(
-					AvailabilityStoreMessage::StoreAvailableData(parent_hash, _, _, _, tx)
-				) if parent_hash == test_state.relay_parent => {
+					AvailabilityStoreMessage::StoreAvailableData { candidate_hash, tx, .. }
+				) if candidate_hash == candidate_b.hash() => {
 					tx.send(Ok(())).unwrap();
 				}
 			);
@@ -1709,10 +1949,10 @@ mod tests {
 		});
 	}
 
-	// That that if the validation of the candidate has failed this does not stop
-	// the work of this subsystem and so it is not fatal to the node.
+	// Test that a candidate whose available data doesn't have the erasure root it commits to is
+	// voted invalid.
 	#[test]
-	fn backing_works_after_failed_validation() {
+	fn backing_votes_invalid_on_wrong_erasure_root() {
 		let test_state = TestState::default();
 		test_harness(test_state.keystore.clone(), |test_harness| async move {
 			let TestHarness { mut virtual_overseer } = test_harness;
@@ -1723,13 +1963,15 @@ mod tests {
 				block_data: BlockData(vec![42, 43, 44]),
 			};
 
-			let pov_hash = pov.hash();
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+			let wrong_erasure_root = Hash::repeat_byte(0x42);
 
 			let candidate = TestCandidateBuilder {
 				para_id: test_state.chain_ids[0],
 				relay_parent: test_state.relay_parent,
-				pov_hash,
-				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				pov_hash: pov.hash(),
+				head_data: expected_head_data.clone(),
+				erasure_root: wrong_erasure_root,
 				..Default::default()
 			}.build();
 
@@ -1740,26 +1982,19 @@ mod tests {
 				&test_state.validators[2].pair().into(),
 			);
 
-			// Send in a `Statement` with a candidate.
-			let statement = CandidateBackingMessage::Statement(
-				test_state.relay_parent,
-				signed_a.clone(),
-			);
+			let statement = CandidateBackingMessage::Statement(test_state.relay_parent, signed_a);
 
 			virtual_overseer.send(FromOverseer::Communication{ msg: statement }).await;
 
-			// Subsystem requests PoV and requests validation.
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::PoVDistribution(
 					PoVDistributionMessage::FetchPoV(relay_parent, _, tx)
-				) => {
-					assert_eq!(relay_parent, test_state.relay_parent);
+				) if relay_parent == test_state.relay_parent => {
 					tx.send(Arc::new(pov.clone())).unwrap();
 				}
 			);
 
-			// Tell subsystem that this candidate is invalid.
 			assert_matches!(
 				virtual_overseer.recv().await,
 				AllMessages::CandidateValidation(
@@ -1769,33 +2004,122 @@ mod tests {
 						tx,
 					)
 				) if pov == pov && &c == candidate.descriptor() => {
-					tx.send(Err(ValidationFailed("Internal test error".into()))).unwrap();
+					tx.send(Ok(
+						ValidationResult::Valid(ValidationOutputs {
+							validation_data: test_state.validation_data.persisted,
+							head_data: expected_head_data.clone(),
+							upward_messages: Vec::new(),
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
+							fees: Default::default(),
+							new_validation_code: None,
+						}),
+					)).unwrap();
 				}
 			);
 
-			// Try to get a set of backable candidates to trigger _some_ action in the subsystem
-			// and check that it is still alive.
-			let (tx, rx) = oneshot::channel();
-			let msg = CandidateBackingMessage::GetBackedCandidates(
-				test_state.relay_parent,
-				tx,
+			// The store is asked to check the erasure root the candidate commits to.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(
+					AvailabilityStoreMessage::StoreAvailableData {
+						candidate_hash,
+						expected_erasure_root,
+						tx,
+						..
+					}
+				) if candidate_hash == candidate.hash() => {
+					assert_eq!(expected_erasure_root, wrong_erasure_root);
+					tx.send(Err(StoreAvailableDataError::InvalidErasureRoot)).unwrap();
+				}
 			);
 
-			virtual_overseer.send(FromOverseer::Communication{ msg }).await;
-			assert_eq!(rx.await.unwrap().len(), 0);
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::Share(
+						relay_parent,
+						signed_statement,
+					)
+				) => {
+					assert_eq!(relay_parent, test_state.relay_parent);
+					assert_eq!(*signed_statement.payload(), Statement::Invalid(candidate.hash()));
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
 		});
 	}
 
-	// Test that a `CandidateBackingMessage::Second` issues validation work
-	// and in case validation is successful issues a `StatementDistributionMessage`.
+	// Test that candidates failing execution are not seconded, whatever the reason.
 	#[test]
-	fn backing_doesnt_second_wrong_collator() {
-		let mut test_state = TestState::default();
-		test_state.availability_cores[0] = CoreState::Scheduled(ScheduledCore {
-			para_id: ParaId::from(1),
-			collator: Some(Sr25519Keyring::Bob.public().into()),
+	fn backing_doesnt_second_on_execution_failure() {
+		let test_state = TestState::default();
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let reasons = vec![
+				InvalidCandidate::ExecutionTrap("unreachable".into()),
+				InvalidCandidate::ParamsDecodeFailure,
+			];
+
+			for (i, reason) in reasons.into_iter().enumerate() {
+				let pov = PoV {
+					block_data: BlockData(vec![42, 43, i as u8]),
+				};
+
+				let candidate = TestCandidateBuilder {
+					para_id: test_state.chain_ids[0],
+					relay_parent: test_state.relay_parent,
+					pov_hash: pov.hash(),
+					erasure_root: make_erasure_root(&test_state, pov.clone()),
+					..Default::default()
+				}.build();
+
+				let second = CandidateBackingMessage::Second(
+					test_state.relay_parent,
+					candidate.to_plain(),
+					pov.clone(),
+				);
+
+				virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+				assert_matches!(
+					virtual_overseer.recv().await,
+					AllMessages::CandidateValidation(
+						CandidateValidationMessage::ValidateFromChainState(
+							c,
+							_,
+							tx,
+						)
+					) if &c == candidate.descriptor() => {
+						tx.send(Ok(ValidationResult::Invalid(reason))).unwrap();
+					}
+				);
+
+				assert_matches!(
+					virtual_overseer.recv().await,
+					AllMessages::CandidateSelection(
+						CandidateSelectionMessage::Invalid(parent_hash, c)
+					) if parent_hash == test_state.relay_parent && c == candidate.to_plain()
+				);
+			}
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
 		});
+	}
 
+	// Test that a candidate producing other head-data than the one it commits to is voted
+	// invalid without making its data available.
+	#[test]
+	fn backing_votes_invalid_on_head_data_mismatch() {
+		let test_state = TestState::default();
 		test_harness(test_state.keystore.clone(), |test_harness| async move {
 			let TestHarness { mut virtual_overseer } = test_harness;
 
@@ -1807,29 +2131,69 @@ mod tests {
 
 			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
 
-			let pov_hash = pov.hash();
 			let candidate = TestCandidateBuilder {
 				para_id: test_state.chain_ids[0],
 				relay_parent: test_state.relay_parent,
-				pov_hash,
+				pov_hash: pov.hash(),
 				head_data: expected_head_data.clone(),
 				erasure_root: make_erasure_root(&test_state, pov.clone()),
 				..Default::default()
 			}.build();
 
-			let second = CandidateBackingMessage::Second(
-				test_state.relay_parent,
-				candidate.to_plain(),
-				pov.clone(),
+			let signed_a = SignedFullStatement::sign(
+				Statement::Seconded(candidate.clone()),
+				&test_state.signing_context,
+				2,
+				&test_state.validators[2].pair().into(),
 			);
 
-			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+			let statement = CandidateBackingMessage::Statement(test_state.relay_parent, signed_a);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: statement }).await;
 
 			assert_matches!(
 				virtual_overseer.recv().await,
-				AllMessages::CandidateSelection(
-					CandidateSelectionMessage::Invalid(parent, c)
-				) if parent == test_state.relay_parent && c == candidate.to_plain() => {
+				AllMessages::PoVDistribution(
+					PoVDistributionMessage::FetchPoV(relay_parent, _, tx)
+				) if relay_parent == test_state.relay_parent => {
+					tx.send(Arc::new(pov.clone())).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(
+					CandidateValidationMessage::ValidateFromChainState(
+						c,
+						pov,
+						tx,
+					)
+				) if pov == pov && &c == candidate.descriptor() => {
+					tx.send(Ok(
+						ValidationResult::Valid(ValidationOutputs {
+							validation_data: test_state.validation_data.persisted,
+							head_data: HeadData(vec![1, 2, 3]),
+							upward_messages: Vec::new(),
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
+							fees: Default::default(),
+							new_validation_code: None,
+						}),
+					)).unwrap();
+				}
+			);
+
+			// Nothing is stored, the invalid vote is shared right away.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::Share(
+						relay_parent,
+						signed_statement,
+					)
+				) => {
+					assert_eq!(relay_parent, test_state.relay_parent);
+					assert_eq!(*signed_statement.payload(), Statement::Invalid(candidate.hash()));
 				}
 			);
 
@@ -1839,6 +2203,551 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn head_data_mismatch_makes_valid_outcome_invalid() {
+		let outputs = |head_data| ValidationOutputs {
+			validation_data: Default::default(),
+			head_data,
+			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
+			hrmp_watermark: 0,
+			fees: Default::default(),
+			new_validation_code: None,
+		};
+
+		let expected = HeadData(vec![1, 2, 3]);
+
+		assert_matches!(
+			check_head_data(ValidationResult::Valid(outputs(expected.clone())), &expected),
+			ValidationResult::Valid(_)
+		);
+		assert_matches!(
+			check_head_data(ValidationResult::Valid(outputs(HeadData(vec![3, 2, 1]))), &expected),
+			ValidationResult::Invalid(InvalidCandidate::HeadDataMismatch)
+		);
+		assert_matches!(
+			check_head_data(ValidationResult::Invalid(InvalidCandidate::BadReturn), &expected),
+			ValidationResult::Invalid(InvalidCandidate::BadReturn)
+		);
+	}
+
+	// That that if the validation of the candidate has failed this does not stop
+	// the work of this subsystem and so it is not fatal to the node.
+	#[test]
+	fn backing_works_after_failed_validation() {
+		let test_state = TestState::default();
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let pov_hash = pov.hash();
+
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: test_state.relay_parent,
+				pov_hash,
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let signed_a = SignedFullStatement::sign(
+				Statement::Seconded(candidate.clone()),
+				&test_state.signing_context,
+				2,
+				&test_state.validators[2].pair().into(),
+			);
+
+			// Send in a `Statement` with a candidate.
+			let statement = CandidateBackingMessage::Statement(
+				test_state.relay_parent,
+				signed_a.clone(),
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: statement }).await;
+
+			// Subsystem requests PoV and requests validation.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::PoVDistribution(
+					PoVDistributionMessage::FetchPoV(relay_parent, _, tx)
+				) => {
+					assert_eq!(relay_parent, test_state.relay_parent);
+					tx.send(Arc::new(pov.clone())).unwrap();
+				}
+			);
+
+			// Tell subsystem that this candidate is invalid.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(
+					CandidateValidationMessage::ValidateFromChainState(
+						c,
+						pov,
+						tx,
+					)
+				) if pov == pov && &c == candidate.descriptor() => {
+					tx.send(Err(ValidationFailed("Internal test error".into()))).unwrap();
+				}
+			);
+
+			// Try to get a set of backable candidates to trigger _some_ action in the subsystem
+			// and check that it is still alive.
+			let (tx, rx) = oneshot::channel();
+			let msg = CandidateBackingMessage::GetBackedCandidates(
+				test_state.relay_parent,
+				tx,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg }).await;
+			assert_eq!(rx.await.unwrap().len(), 0);
+		});
+	}
+
+	// Test that a `CandidateBackingMessage::Second` issues validation work
+	// and in case validation is successful issues a `StatementDistributionMessage`.
+	#[test]
+	fn backing_doesnt_second_wrong_collator() {
+		let mut test_state = TestState::default();
+		test_state.availability_cores[0] = CoreState::Scheduled(ScheduledCore {
+			para_id: ParaId::from(1),
+			collator: Some(Sr25519Keyring::Bob.public().into()),
+		});
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+
+			let pov_hash = pov.hash();
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: test_state.relay_parent,
+				pov_hash,
+				head_data: expected_head_data.clone(),
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let second = CandidateBackingMessage::Second(
+				test_state.relay_parent,
+				candidate.to_plain(),
+				pov.clone(),
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(parent, c)
+				) if parent == test_state.relay_parent && c == candidate.to_plain() => {
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
+		});
+	}
+
+	// Test that a candidate is not validated if its PoV doesn't match the PoV hash in its
+	// descriptor.
+	#[test]
+	fn backing_doesnt_second_mismatched_pov() {
+		let test_state = TestState::default();
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let other_pov = PoV {
+				block_data: BlockData(vec![45, 46, 47]),
+			};
+
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: test_state.relay_parent,
+				pov_hash: pov.hash(),
+				head_data: expected_head_data.clone(),
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let second = CandidateBackingMessage::Second(
+				test_state.relay_parent,
+				candidate.to_plain(),
+				other_pov,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(parent, c)
+				) if parent == test_state.relay_parent && c == candidate.to_plain() => {
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
+		});
+	}
+
+	// Test that no candidate is seconded while the core of its para is occupied.
+	#[test]
+	fn backing_doesnt_second_on_occupied_core() {
+		let mut test_state = TestState::default();
+		test_state.availability_cores[0] = CoreState::Occupied(OccupiedCore {
+			para_id: test_state.chain_ids[0],
+			next_up_on_available: None,
+			occupied_since: 0,
+			time_out_at: 10,
+			next_up_on_time_out: None,
+			availability: Default::default(),
+			group_responsible: GroupIndex(0),
+		});
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: test_state.relay_parent,
+				pov_hash: pov.hash(),
+				head_data: expected_head_data.clone(),
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let second = CandidateBackingMessage::Second(
+				test_state.relay_parent,
+				candidate.to_plain(),
+				pov,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+			// No validation work is issued, so the job answers right away and has nothing
+			// backed.
+			let (tx, rx) = oneshot::channel();
+			let msg = CandidateBackingMessage::GetBackedCandidates(
+				test_state.relay_parent,
+				tx,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg }).await;
+			assert_eq!(rx.await.unwrap().len(), 0);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
+		});
+	}
+
+	// Test that candidates for the para next up on an occupied core are seconded, under the
+	// collator restriction of the next up assignment.
+	#[test]
+	fn backing_seconds_next_up_on_occupied_core() {
+		let mut test_state = TestState::default();
+		test_state.availability_cores[0] = CoreState::Occupied(OccupiedCore {
+			para_id: test_state.chain_ids[1],
+			next_up_on_available: Some(ScheduledCore {
+				para_id: test_state.chain_ids[0],
+				collator: Some(Sr25519Keyring::Bob.public().into()),
+			}),
+			occupied_since: 0,
+			time_out_at: 10,
+			next_up_on_time_out: None,
+			availability: Default::default(),
+			group_responsible: GroupIndex(0),
+		});
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: test_state.relay_parent,
+				pov_hash: pov.hash(),
+				head_data: expected_head_data.clone(),
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let second = CandidateBackingMessage::Second(
+				test_state.relay_parent,
+				candidate.to_plain(),
+				pov,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+			// The candidate isn't refused for the occupied core, but its collator isn't the one
+			// required by the assignment next up.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(parent, c)
+				) if parent == test_state.relay_parent && c == candidate.to_plain() => {
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
+		});
+	}
+
+	// Test that a candidate upgrading its code is not seconded when validation finds that
+	// the runtime restricts upgrades of the para.
+	#[test]
+	fn backing_doesnt_second_restricted_code_upgrade() {
+		let test_state = TestState::default();
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+
+			let pov_hash = pov.hash();
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: test_state.relay_parent,
+				pov_hash,
+				head_data: expected_head_data.clone(),
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let second = CandidateBackingMessage::Second(
+				test_state.relay_parent,
+				candidate.to_plain(),
+				pov.clone(),
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(
+					CandidateValidationMessage::ValidateFromChainState(
+						c,
+						pov,
+						tx,
+					)
+				) if pov == pov && &c == candidate.descriptor() => {
+					tx.send(Ok(
+						ValidationResult::Invalid(InvalidCandidate::CodeUpgradeNotAllowed),
+					)).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(parent, c)
+				) if parent == test_state.relay_parent && c == candidate.to_plain() => {
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
+		});
+	}
+
+	// Test that a candidate built on an allowed ancestor of the relay parent is validated in
+	// the state of the relay parent and seconded in its context.
+	#[test]
+	fn backing_seconds_candidate_built_on_allowed_ancestor() {
+		let test_state = TestState::default();
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+			let ancestor = test_state.allowed_relay_parents[1].0;
+
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: ancestor,
+				pov_hash: pov.hash(),
+				head_data: expected_head_data.clone(),
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let second = CandidateBackingMessage::Second(
+				test_state.relay_parent,
+				candidate.to_plain(),
+				pov.clone(),
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(
+					CandidateValidationMessage::ValidateFromChainStateAt(
+						leaf,
+						c,
+						pov,
+						tx,
+					)
+				) if leaf == test_state.relay_parent && pov == pov && &c == candidate.descriptor() => {
+					tx.send(Ok(
+						ValidationResult::Valid(ValidationOutputs {
+							validation_data: test_state.validation_data.persisted.clone(),
+							head_data: expected_head_data.clone(),
+							upward_messages: Vec::new(),
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
+							fees: Default::default(),
+							new_validation_code: None,
+						}),
+					)).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(
+					AvailabilityStoreMessage::StoreAvailableData { candidate_hash, tx, .. }
+				) if candidate_hash == candidate.hash() => {
+					tx.send(Ok(())).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::Share(
+						parent_hash,
+						signed_statement,
+					)
+				) if parent_hash == test_state.relay_parent => {
+					signed_statement.check_signature(
+						&test_state.signing_context,
+						&test_state.validator_public[0],
+					).unwrap();
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
+		});
+	}
+
+	// Test that candidates built on relay parents outside of the allowed ancestry are neither
+	// seconded nor validated.
+	#[test]
+	fn backing_doesnt_second_outside_allowed_ancestry() {
+		let test_state = TestState::default();
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			test_startup(&mut virtual_overseer, &test_state).await;
+
+			let pov = PoV {
+				block_data: BlockData(vec![42, 43, 44]),
+			};
+
+			let expected_head_data = test_state.head_data.get(&test_state.chain_ids[0]).unwrap();
+
+			let candidate = TestCandidateBuilder {
+				para_id: test_state.chain_ids[0],
+				relay_parent: Hash::from([3; 32]),
+				pov_hash: pov.hash(),
+				head_data: expected_head_data.clone(),
+				erasure_root: make_erasure_root(&test_state, pov.clone()),
+				..Default::default()
+			}.build();
+
+			let second = CandidateBackingMessage::Second(
+				test_state.relay_parent,
+				candidate.to_plain(),
+				pov,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: second }).await;
+
+			let seconding = SignedFullStatement::sign(
+				Statement::Seconded(candidate.clone()),
+				&test_state.signing_context,
+				2,
+				&test_state.validators[2].pair().into(),
+			);
+
+			let statement = CandidateBackingMessage::Statement(
+				test_state.relay_parent,
+				seconding,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg: statement }).await;
+
+			// No validation work is issued for either, so the job answers right away and has
+			// nothing backed.
+			let (tx, rx) = oneshot::channel();
+			let msg = CandidateBackingMessage::GetBackedCandidates(
+				test_state.relay_parent,
+				tx,
+			);
+
+			virtual_overseer.send(FromOverseer::Communication{ msg }).await;
+			assert_eq!(rx.await.unwrap().len(), 0);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(test_state.relay_parent)))
+			).await;
+		});
+	}
+
 	#[test]
 	fn validation_work_ignores_wrong_collator() {
 		let mut test_state = TestState::default();
@@ -26,15 +26,15 @@ use keystore::KeyStorePtr;
 use polkadot_node_subsystem::{
 	messages::{
 		self, AllMessages, AvailabilityStoreMessage, BitfieldDistributionMessage,
-		BitfieldSigningMessage, CandidateBackingMessage, RuntimeApiMessage,
+		BitfieldSigningMessage, BitfieldSigningOutgoingMessages, CandidateBackingMessage, RuntimeApiMessage,
 	},
 	errors::RuntimeApiError,
 	metrics::{self, prometheus},
 };
 use polkadot_node_subsystem_util::{
-	self as util, JobManager, JobTrait, ToJobTrait, Validator
+	self as util, JobManager, JobTrait, ToJobTrait, Validator, ValidatorKeyCache,
 };
-use polkadot_primitives::v1::{AvailabilityBitfield, CoreState, Hash, ValidatorIndex};
+use polkadot_primitives::v1::{AvailabilityBitfield, CoreIndex, CoreState, Hash, ValidatorIndex};
 use std::{convert::TryFrom, pin::Pin, time::Duration};
 use wasm_timer::{Delay, Instant};
 
@@ -88,11 +88,17 @@ pub enum FromJob {
 
 impl From<FromJob> for AllMessages {
 	fn from(from_job: FromJob) -> AllMessages {
+		BitfieldSigningOutgoingMessages::from(from_job).into()
+	}
+}
+
+impl From<FromJob> for BitfieldSigningOutgoingMessages {
+	fn from(from_job: FromJob) -> BitfieldSigningOutgoingMessages {
 		match from_job {
-			FromJob::AvailabilityStore(asm) => AllMessages::AvailabilityStore(asm),
-			FromJob::BitfieldDistribution(bdm) => AllMessages::BitfieldDistribution(bdm),
-			FromJob::CandidateBacking(cbm) => AllMessages::CandidateBacking(cbm),
-			FromJob::RuntimeApi(ram) => AllMessages::RuntimeApi(ram),
+			FromJob::AvailabilityStore(asm) => BitfieldSigningOutgoingMessages::AvailabilityStore(asm),
+			FromJob::BitfieldDistribution(bdm) => BitfieldSigningOutgoingMessages::BitfieldDistribution(bdm),
+			FromJob::CandidateBacking(cbm) => BitfieldSigningOutgoingMessages::CandidateBacking(cbm),
+			FromJob::RuntimeApi(ram) => BitfieldSigningOutgoingMessages::RuntimeApi(ram),
 		}
 	}
 }
@@ -135,11 +141,11 @@ pub enum Error {
 }
 
 // if there is a candidate pending availability, query the Availability Store
-// for whether we have the availability chunk for our validator index.
+// for whether we have the availability chunk we are responsible for on that core.
 async fn get_core_availability(
 	relay_parent: Hash,
 	core: CoreState,
-	validator_idx: ValidatorIndex,
+	chunk_idx: u32,
 	sender: &mpsc::Sender<FromJob>,
 ) -> Result<bool, Error> {
 	use messages::{
@@ -174,11 +180,10 @@ async fn get_core_availability(
 		sender
 			.send(AvailabilityStore(QueryChunkAvailability(
 				committed_candidate_receipt.descriptor.pov_hash,
-				validator_idx,
 				tx,
 			)))
 			.await?;
-		return rx.await.map_err(Into::into);
+		return rx.await.map(|chunks| chunks.contains(&chunk_idx)).map_err(Into::into);
 	}
 	Ok(false)
 }
@@ -200,7 +205,29 @@ async fn get_availability_cores(relay_parent: Hash, sender: &mut mpsc::Sender<Fr
 	}
 }
 
+// delegates to the v1 runtime API; runtimes without chunk shuffling have no seed and keep
+// the identity mapping of chunks to validators.
+async fn get_chunk_shuffle_params(
+	relay_parent: Hash,
+	sender: &mut mpsc::Sender<FromJob>,
+) -> Result<(usize, Option<[u8; 32]>), Error> {
+	let (validators, chunk_shuffle_seed) = futures::try_join!(
+		util::request_validators(relay_parent, sender).await?,
+		util::request_availability_chunk_shuffle_seed(relay_parent, sender).await?,
+	)?;
+
+	let chunk_shuffle_seed = match chunk_shuffle_seed {
+		Ok(chunk_shuffle_seed) => Some(chunk_shuffle_seed),
+		Err(RuntimeApiError::NotSupported { .. }) => None,
+		Err(e) => return Err(e.into()),
+	};
+
+	Ok((validators?.len(), chunk_shuffle_seed))
+}
+
 // - get the list of core states from the runtime
+// - get the number of validators and the chunk shuffle seed, which determine the chunk
+//   we are responsible for on each core
 // - for each core, concurrently determine chunk availability (see `get_core_availability`)
 // - return the bitfield if there were no errors at any point in this process
 //   (otherwise, it's prone to false negatives)
@@ -213,6 +240,8 @@ async fn construct_availability_bitfield(
 
 	// get the set of availability cores from the runtime
 	let availability_cores = get_availability_cores(relay_parent, sender).await?;
+	let (n_validators, chunk_shuffle_seed) = get_chunk_shuffle_params(relay_parent, sender).await?;
+	let chunk_shuffle_seed = chunk_shuffle_seed.as_ref();
 
 	// we now need sender to be immutable so we can copy the reference to multiple concurrent closures
 	let sender = &*sender;
@@ -234,7 +263,16 @@ async fn construct_availability_bitfield(
 	// we need the mutexes and explicit references above.
 	stream::iter(availability_cores.into_iter().enumerate())
 		.for_each_concurrent(None, |(idx, core)| async move {
-			let availability = match get_core_availability(relay_parent, core, validator_idx, sender).await {
+			let chunk_idx = match polkadot_erasure_coding::chunk_index(
+				chunk_shuffle_seed,
+				CoreIndex(idx as u32),
+				n_validators,
+				validator_idx,
+			) {
+				Some(chunk_idx) => chunk_idx,
+				None => return,
+			};
+			let availability = match get_core_availability(relay_parent, core, chunk_idx, sender).await {
 				Ok(availability) => availability,
 				Err(err) => {
 					errs_ref.lock().await.push(err);
@@ -289,15 +327,15 @@ impl JobTrait for BitfieldSigningJob {
 	type ToJob = ToJob;
 	type FromJob = FromJob;
 	type Error = Error;
-	type RunArgs = KeyStorePtr;
+	type RunArgs = (KeyStorePtr, ValidatorKeyCache);
 	type Metrics = Metrics;
 
-	const NAME: &'static str = "BitfieldSigningJob";
+	const NAME: &'static str = "bitfield-signing-job";
 
 	/// Run a job for the parent block indicated
 	fn run(
 		relay_parent: Hash,
-		keystore: Self::RunArgs,
+		(keystore, key_cache): Self::RunArgs,
 		metrics: Self::Metrics,
 		_receiver: mpsc::Receiver<ToJob>,
 		mut sender: mpsc::Sender<FromJob>,
@@ -308,7 +346,12 @@ impl JobTrait for BitfieldSigningJob {
 
 			// now do all the work we can before we need to wait for the availability store
 			// if we're not a validator, we can just succeed effortlessly
-			let validator = match Validator::new(relay_parent, keystore, sender.clone()).await {
+			let validator = match Validator::new_cached(
+				relay_parent,
+				keystore,
+				&key_cache,
+				sender.clone(),
+			).await {
 				Ok(validator) => validator,
 				Err(util::Error::NotAValidator) => return Ok(()),
 				Err(err) => return Err(Error::Util(err)),
@@ -329,7 +372,7 @@ impl JobTrait for BitfieldSigningJob {
 				Ok(bitfield) => bitfield,
 			};
 
-			let signed_bitfield = validator.sign(bitfield);
+			let signed_bitfield = validator.sign(bitfield).into_unchecked();
 			metrics.on_bitfield_signed();
 
 			// make an anonymous scope to contain some use statements to simplify creating the outbound message
@@ -24,7 +24,7 @@ use polkadot_subsystem::{
 	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
 	FromOverseer, OverseerSignal,
 	messages::{
-		AllMessages, CandidateValidationMessage, RuntimeApiMessage,
+		CandidateValidationMessage, RuntimeApiMessage,
 		ValidationFailed, RuntimeApiRequest,
 	},
 	metrics::{self, prometheus},
@@ -33,7 +33,8 @@ use polkadot_subsystem::errors::RuntimeApiError;
 use polkadot_node_primitives::{ValidationResult, ValidationOutputs, InvalidCandidate};
 use polkadot_primitives::v1::{
 	ValidationCode, PoV, CandidateDescriptor, ValidationData, PersistedValidationData,
-	TransientValidationData, OccupiedCoreAssumption, Hash,
+	TransientValidationData, OccupiedCoreAssumption, Hash, BlockNumber, ExecutorParams,
+	MAX_POV_SIZE,
 };
 use polkadot_parachain::wasm_executor::{self, ValidationPool, ExecutionMode, ValidationError,
 	InvalidCandidate as WasmInvalidCandidate};
@@ -45,10 +46,14 @@ use sp_core::traits::SpawnNamed;
 use futures::channel::oneshot;
 use futures::prelude::*;
 
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 
 const LOG_TARGET: &'static str = "candidate_validation";
 
+/// The maximum number of validation outcomes to keep in the cache.
+const VALIDATION_CACHE_SIZE: usize = 128;
+
 /// The candidate validation subsystem.
 pub struct CandidateValidationSubsystem<S> {
 	spawn: S,
@@ -100,6 +105,67 @@ impl metrics::Metrics for Metrics {
 	}
 }
 
+/// The parameters a validation outcome depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ValidationCacheKey {
+	descriptor_hash: Hash,
+	validation_code_hash: Hash,
+	executor_params_hash: Hash,
+	persisted_validation_data_hash: Hash,
+	transient_validation_data_hash: Hash,
+}
+
+impl ValidationCacheKey {
+	fn new(
+		descriptor: &CandidateDescriptor,
+		validation_code: &ValidationCode,
+		executor_params: &ExecutorParams,
+		persisted_validation_data: &PersistedValidationData,
+		transient_validation_data: &Option<TransientValidationData>,
+	) -> Self {
+		let hash_of = |data: &[u8]| Hash::from(sp_core::hashing::blake2_256(data));
+
+		ValidationCacheKey {
+			descriptor_hash: hash_of(&descriptor.encode()),
+			validation_code_hash: hash_of(&validation_code.0),
+			executor_params_hash: hash_of(&executor_params.encode()),
+			persisted_validation_data_hash: persisted_validation_data.hash(),
+			transient_validation_data_hash: hash_of(&transient_validation_data.encode()),
+		}
+	}
+}
+
+/// A bounded cache of recent validation outcomes, so candidates validated once, e.g. during
+/// backing, don't need to be executed again. The oldest outcome is evicted first.
+#[derive(Default)]
+struct ValidationCache {
+	outcomes: HashMap<ValidationCacheKey, ValidationResult>,
+	insertion_order: VecDeque<ValidationCacheKey>,
+}
+
+impl ValidationCache {
+	fn get(&self, key: &ValidationCacheKey) -> Option<ValidationResult> {
+		self.outcomes.get(key).cloned()
+	}
+
+	fn insert(&mut self, key: ValidationCacheKey, outcome: &ValidationResult) {
+		// Timeouts depend on the load of the machine rather than on the candidate.
+		if let ValidationResult::Invalid(InvalidCandidate::Timeout) = outcome {
+			return;
+		}
+
+		if self.outcomes.insert(key, outcome.clone()).is_none() {
+			self.insertion_order.push_back(key);
+		}
+
+		while self.insertion_order.len() > VALIDATION_CACHE_SIZE {
+			if let Some(oldest) = self.insertion_order.pop_front() {
+				self.outcomes.remove(&oldest);
+			}
+		}
+	}
+}
+
 impl<S> CandidateValidationSubsystem<S> {
 	/// Create a new `CandidateValidationSubsystem` with the given task spawner.
 	pub fn new(spawn: S, metrics: Metrics) -> Self {
@@ -129,11 +195,12 @@ async fn run(
 	-> SubsystemResult<()>
 {
 	let pool = ValidationPool::new();
+	let mut cache = ValidationCache::default();
 
 	loop {
 		match ctx.recv().await? {
 			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {}
-			FromOverseer::Signal(OverseerSignal::BlockFinalized(_)) => {}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
 			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
 			FromOverseer::Communication { msg } => match msg {
 				CandidateValidationMessage::ValidateFromChainState(
@@ -144,6 +211,8 @@ async fn run(
 					let res = spawn_validate_from_chain_state(
 						&mut ctx,
 						Some(pool.clone()),
+						&mut cache,
+						ValidationContext::RelayParent,
 						descriptor,
 						pov,
 						spawn.clone(),
@@ -157,10 +226,38 @@ async fn run(
 						Err(e) => return Err(e),
 					}
 				}
+				CandidateValidationMessage::ValidateFromChainStateAt(
+					leaf,
+					descriptor,
+					pov,
+					response_sender,
+				) => {
+					let res = match allowed_relay_parent_number(
+						&mut ctx,
+						leaf,
+						descriptor.relay_parent,
+					).await? {
+						Ok(Some(relay_parent_number)) => spawn_validate_from_chain_state(
+							&mut ctx,
+							Some(pool.clone()),
+							&mut cache,
+							ValidationContext::Leaf(leaf, relay_parent_number),
+							descriptor,
+							pov,
+							spawn.clone(),
+						).await?,
+						Ok(None) => Ok(ValidationResult::Invalid(InvalidCandidate::BadParent)),
+						Err(e) => Err(e),
+					};
+
+					metrics.on_validation_event(&res);
+					let _ = response_sender.send(res);
+				}
 				CandidateValidationMessage::ValidateFromExhaustive(
 					persisted_validation_data,
 					transient_validation_data,
 					validation_code,
+					executor_params,
 					descriptor,
 					pov,
 					response_sender,
@@ -168,9 +265,11 @@ async fn run(
 					let res = spawn_validate_exhaustive(
 						&mut ctx,
 						Some(pool.clone()),
+						&mut cache,
 						persisted_validation_data,
 						transient_validation_data,
 						validation_code,
+						executor_params,
 						descriptor,
 						pov,
 						spawn.clone(),
@@ -201,15 +300,51 @@ async fn runtime_api_request<T>(
 	receiver: oneshot::Receiver<Result<T, RuntimeApiError>>,
 ) -> SubsystemResult<Result<T, RuntimeApiError>> {
 	ctx.send_message(
-		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		RuntimeApiMessage::Request(
 			relay_parent,
 			request,
-		))
+		)
 	).await?;
 
 	receiver.await.map_err(Into::into)
 }
 
+/// The relay-chain state the validation data of a candidate is derived from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValidationContext {
+	/// The state of the relay parent of the candidate.
+	RelayParent,
+	/// The state of the given leaf, for a candidate built on the allowed relay parent of the
+	/// leaf with the given number.
+	Leaf(Hash, BlockNumber),
+}
+
+/// Find the number of the relay parent among the relay parents allowed at the leaf.
+///
+/// Returns `None` if the relay parent isn't allowed.
+async fn allowed_relay_parent_number(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	leaf: Hash,
+	relay_parent: Hash,
+) -> SubsystemResult<Result<Option<BlockNumber>, ValidationFailed>> {
+	let (tx, rx) = oneshot::channel();
+	let allowed_relay_parents = runtime_api_request(
+		ctx,
+		leaf,
+		RuntimeApiRequest::AllowedRelayParents(tx),
+		rx,
+	).await?;
+
+	Ok(match allowed_relay_parents {
+		Ok(allowed_relay_parents) => Ok(
+			allowed_relay_parents.into_iter()
+				.find(|(hash, _)| hash == &relay_parent)
+				.map(|(_, number)| number)
+		),
+		Err(_) => Err(ValidationFailed("Bad request".into())),
+	})
+}
+
 #[derive(Debug)]
 enum AssumptionCheckOutcome {
 	Matches(ValidationData, ValidationCode),
@@ -220,20 +355,34 @@ enum AssumptionCheckOutcome {
 async fn check_assumption_validation_data(
 	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
 	descriptor: &CandidateDescriptor,
+	context: ValidationContext,
 	assumption: OccupiedCoreAssumption,
 ) -> SubsystemResult<AssumptionCheckOutcome> {
 	let validation_data = {
 		let (tx, rx) = oneshot::channel();
-		let d = runtime_api_request(
-			ctx,
-			descriptor.relay_parent,
-			RuntimeApiRequest::FullValidationData(
-				descriptor.para_id,
-				assumption,
-				tx,
-			),
-			rx,
-		).await?;
+		let d = match context {
+			ValidationContext::RelayParent => runtime_api_request(
+				ctx,
+				descriptor.relay_parent,
+				RuntimeApiRequest::FullValidationData(
+					descriptor.para_id,
+					assumption,
+					tx,
+				),
+				rx,
+			).await?,
+			ValidationContext::Leaf(leaf, relay_parent_number) => runtime_api_request(
+				ctx,
+				leaf,
+				RuntimeApiRequest::FullValidationDataAt(
+					descriptor.para_id,
+					relay_parent_number,
+					assumption,
+					tx,
+				),
+				rx,
+			).await?,
+		};
 
 		match d {
 			Ok(None) | Err(_) => {
@@ -247,16 +396,29 @@ async fn check_assumption_validation_data(
 
 	SubsystemResult::Ok(if descriptor.persisted_validation_data_hash == persisted_validation_data_hash {
 		let (code_tx, code_rx) = oneshot::channel();
-		let validation_code = runtime_api_request(
-			ctx,
-			descriptor.relay_parent,
-			RuntimeApiRequest::ValidationCode(
-				descriptor.para_id,
-				OccupiedCoreAssumption::Included,
-				code_tx,
-			),
-			code_rx,
-		).await?;
+		let validation_code = match context {
+			ValidationContext::RelayParent => runtime_api_request(
+				ctx,
+				descriptor.relay_parent,
+				RuntimeApiRequest::ValidationCode(
+					descriptor.para_id,
+					OccupiedCoreAssumption::Included,
+					code_tx,
+				),
+				code_rx,
+			).await?,
+			// the code used for candidates in the context of the relay parent.
+			ValidationContext::Leaf(leaf, relay_parent_number) => runtime_api_request(
+				ctx,
+				leaf,
+				RuntimeApiRequest::HistoricalValidationCode(
+					descriptor.para_id,
+					relay_parent_number,
+					code_tx,
+				),
+				code_rx,
+			).await?,
+		};
 
 		match validation_code {
 			Ok(None) | Err(_) => AssumptionCheckOutcome::BadRequest,
@@ -267,13 +429,62 @@ async fn check_assumption_validation_data(
 	})
 }
 
+/// Fetch the parameters of the execution environment for candidates built on top of the relay
+/// parent, which are those of the session expected at its child.
+///
+/// Runtimes which don't provide executor parameters yet are assumed to use the defaults.
+/// Returns `None` if the parameters can't be determined.
+async fn executor_params_at_relay_parent(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	relay_parent: Hash,
+) -> SubsystemResult<Option<ExecutorParams>> {
+	let (tx, rx) = oneshot::channel();
+	let session_index = match runtime_api_request(
+		ctx,
+		relay_parent,
+		RuntimeApiRequest::SessionIndexForChild(tx),
+		rx,
+	).await? {
+		Ok(session_index) => session_index,
+		Err(_) => return Ok(None),
+	};
+
+	let (tx, rx) = oneshot::channel();
+	let executor_params = runtime_api_request(
+		ctx,
+		relay_parent,
+		RuntimeApiRequest::SessionExecutorParams(session_index, tx),
+		rx,
+	).await?;
+
+	Ok(match executor_params {
+		Ok(executor_params) => executor_params,
+		Err(RuntimeApiError::NotSupported { .. }) => Some(ExecutorParams::default()),
+		Err(_) => None,
+	})
+}
+
 async fn spawn_validate_from_chain_state(
 	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
 	validation_pool: Option<ValidationPool>,
+	cache: &mut ValidationCache,
+	context: ValidationContext,
 	descriptor: CandidateDescriptor,
 	pov: Arc<PoV>,
 	spawn: impl SpawnNamed + 'static,
 ) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	// a candidate built on an ancestor of the leaf is backed in a child of the leaf, so is
+	// executed in the environment of that block's session.
+	let executor_params_at = match context {
+		ValidationContext::RelayParent => descriptor.relay_parent,
+		ValidationContext::Leaf(leaf, _) => leaf,
+	};
+
+	let executor_params = match executor_params_at_relay_parent(ctx, executor_params_at).await? {
+		Some(executor_params) => executor_params,
+		None => return Ok(Err(ValidationFailed("Bad request".into()))),
+	};
+
 	// The candidate descriptor has a `persisted_validation_data_hash` which corresponds to
 	// one of up to two possible values that we can derive from the state of the
 	// relay-parent. We can fetch these values by getting the persisted validation data
@@ -281,15 +492,18 @@ async fn spawn_validate_from_chain_state(
 	match check_assumption_validation_data(
 		ctx,
 		&descriptor,
+		context,
 		OccupiedCoreAssumption::Included,
 	).await? {
 		AssumptionCheckOutcome::Matches(validation_data, validation_code) => {
 			return spawn_validate_exhaustive(
 				ctx,
 				validation_pool,
+				cache,
 				validation_data.persisted,
 				Some(validation_data.transient),
 				validation_code,
+				executor_params,
 				descriptor,
 				pov,
 				spawn,
@@ -302,15 +516,18 @@ async fn spawn_validate_from_chain_state(
 	match check_assumption_validation_data(
 		ctx,
 		&descriptor,
+		context,
 		OccupiedCoreAssumption::TimedOut,
 	).await? {
 		AssumptionCheckOutcome::Matches(validation_data, validation_code) => {
 			return spawn_validate_exhaustive(
 				ctx,
 				validation_pool,
+				cache,
 				validation_data.persisted,
 				Some(validation_data.transient),
 				validation_code,
+				executor_params,
 				descriptor,
 				pov,
 				spawn,
@@ -329,20 +546,34 @@ async fn spawn_validate_from_chain_state(
 async fn spawn_validate_exhaustive(
 	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
 	validation_pool: Option<ValidationPool>,
+	cache: &mut ValidationCache,
 	persisted_validation_data: PersistedValidationData,
 	transient_validation_data: Option<TransientValidationData>,
 	validation_code: ValidationCode,
+	executor_params: ExecutorParams,
 	descriptor: CandidateDescriptor,
 	pov: Arc<PoV>,
 	spawn: impl SpawnNamed + 'static,
 ) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	let cache_key = ValidationCacheKey::new(
+		&descriptor,
+		&validation_code,
+		&executor_params,
+		&persisted_validation_data,
+		&transient_validation_data,
+	);
+
+	let cached_outcome = cache.get(&cache_key);
+
 	let (tx, rx) = oneshot::channel();
 	let fut = async move {
-		let res = validate_candidate_exhaustive::<RealValidationBackend, _>(
+		let res = validate_candidate_or_use_cached::<RealValidationBackend, _>(
 			validation_pool,
+			cached_outcome,
 			persisted_validation_data,
 			transient_validation_data,
 			validation_code,
+			executor_params,
 			descriptor,
 			pov,
 			spawn,
@@ -351,8 +582,14 @@ async fn spawn_validate_exhaustive(
 		let _ = tx.send(res);
 	};
 
-	ctx.spawn_blocking("blocking-candidate-validation-task", fut.boxed()).await?;
-	rx.await.map_err(Into::into)
+	ctx.spawn_blocking("candidate-validation-task", fut.boxed()).await?;
+	let (res, cacheable) = rx.await?;
+
+	if let (Ok(outcome), true) = (&res, cacheable) {
+		cache.insert(cache_key, outcome);
+	}
+
+	Ok(res)
 }
 
 /// Does basic checks of a candidate. Provide the encoded PoV-block. Returns `Ok` if basic checks
@@ -403,6 +640,15 @@ fn check_wasm_result_against_constraints(
 		}
 	}
 
+	if result.upward_messages.len() > transient_params.max_upward_message_num as _ {
+		return Err(InvalidCandidate::TooManyUpwardMessages(result.upward_messages.len() as u64))
+	}
+
+	let upward_messages_size: usize = result.upward_messages.iter().map(|m| m.data.len()).sum();
+	if upward_messages_size > transient_params.max_upward_message_size as _ {
+		return Err(InvalidCandidate::UpwardMessagesTooLarge(upward_messages_size as u64))
+	}
+
 	Ok(())
 }
 
@@ -413,6 +659,7 @@ trait ValidationBackend {
 		arg: Self::Arg,
 		validation_code: &ValidationCode,
 		params: ValidationParams,
+		executor_params: &ExecutorParams,
 		spawn: S,
 	) -> Result<WasmValidationResult, ValidationError>;
 }
@@ -426,6 +673,7 @@ impl ValidationBackend for RealValidationBackend {
 		pool: Option<ValidationPool>,
 		validation_code: &ValidationCode,
 		params: ValidationParams,
+		executor_params: &ExecutorParams,
 		spawn: S,
 	) -> Result<WasmValidationResult, ValidationError> {
 		let execution_mode = pool.as_ref()
@@ -435,28 +683,63 @@ impl ValidationBackend for RealValidationBackend {
 		wasm_executor::validate_candidate(
 			&validation_code.0,
 			params,
+			executor_params,
 			execution_mode,
 			spawn,
 		)
 	}
 }
 
-/// Validates the candidate from exhaustive parameters.
+/// Performs the basic checks on the candidate and, if they pass, answers with the cached outcome
+/// or validates the candidate from exhaustive parameters.
 ///
-/// Sends the result of validation on the channel once complete.
-fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
+/// The cache key doesn't cover the PoV, so the basic checks, which tie the PoV to the descriptor,
+/// must pass before a cached outcome may be used. Their failures are specific to the supplied PoV.
+/// Also returns whether the outcome may be cached, which is only the case for a fresh validation.
+fn validate_candidate_or_use_cached<B: ValidationBackend, S: SpawnNamed + 'static>(
 	backend_arg: B::Arg,
+	cached_outcome: Option<ValidationResult>,
 	persisted_validation_data: PersistedValidationData,
 	transient_validation_data: Option<TransientValidationData>,
 	validation_code: ValidationCode,
+	executor_params: ExecutorParams,
 	descriptor: CandidateDescriptor,
 	pov: Arc<PoV>,
 	spawn: S,
-) -> Result<ValidationResult, ValidationFailed> {
-	if let Err(e) = perform_basic_checks(&descriptor, None, &*pov) {
-		return Ok(ValidationResult::Invalid(e))
+) -> (Result<ValidationResult, ValidationFailed>, bool) {
+	if let Err(e) = perform_basic_checks(&descriptor, Some(MAX_POV_SIZE as u64), &*pov) {
+		return (Ok(ValidationResult::Invalid(e)), false)
+	}
+
+	if let Some(outcome) = cached_outcome {
+		return (Ok(outcome), false)
 	}
 
+	let res = validate_candidate_exhaustive::<B, _>(
+		backend_arg,
+		persisted_validation_data,
+		transient_validation_data,
+		validation_code,
+		executor_params,
+		pov,
+		spawn,
+	);
+
+	(res, true)
+}
+
+/// Validates the candidate from exhaustive parameters.
+///
+/// The candidate must have passed the [`perform_basic_checks`] already.
+fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
+	backend_arg: B::Arg,
+	persisted_validation_data: PersistedValidationData,
+	transient_validation_data: Option<TransientValidationData>,
+	validation_code: ValidationCode,
+	executor_params: ExecutorParams,
+	pov: Arc<PoV>,
+	spawn: S,
+) -> Result<ValidationResult, ValidationFailed> {
 	let params = ValidationParams {
 		parent_head: persisted_validation_data.parent_head.clone(),
 		block_data: pov.block_data.clone(),
@@ -464,7 +747,7 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 		hrmp_mqc_heads: persisted_validation_data.hrmp_mqc_heads.clone(),
 	};
 
-	match B::validate(backend_arg, &validation_code, params, spawn) {
+	match B::validate(backend_arg, &validation_code, params, &executor_params, spawn) {
 		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::Timeout)) =>
 			Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)),
 		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::ParamsTooLarge(l))) =>
@@ -473,6 +756,10 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 			Ok(ValidationResult::Invalid(InvalidCandidate::CodeTooLarge(l as u64))),
 		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::BadReturn)) =>
 			Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn)),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::Trap(msg))) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionTrap(msg))),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::ParamsDecodeFailure)) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::ParamsDecodeFailure)),
 		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::WasmExecutor(e))) =>
 			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e.to_string()))),
 		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::ExternalWasmExecutor(e))) =>
@@ -493,6 +780,8 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 					head_data: res.head_data,
 					validation_data: persisted_validation_data,
 					upward_messages: res.upward_messages,
+					horizontal_messages: res.horizontal_messages,
+					hrmp_watermark: res.hrmp_watermark,
 					fees: 0,
 					new_validation_code: res.new_validation_code,
 				}),
@@ -505,8 +794,9 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use polkadot_subsystem::messages::AllMessages;
 	use polkadot_node_subsystem_test_helpers as test_helpers;
-	use polkadot_primitives::v1::{HeadData, BlockData};
+	use polkadot_primitives::v1::{HeadData, BlockData, UpwardMessage, ParachainDispatchOrigin};
 	use sp_core::testing::TaskExecutor;
 	use futures::executor;
 	use assert_matches::assert_matches;
@@ -525,6 +815,7 @@ mod tests {
 			arg: Self::Arg,
 			_validation_code: &ValidationCode,
 			_params: ValidationParams,
+			_executor_params: &ExecutorParams,
 			_spawn: S,
 		) -> Result<WasmValidationResult, ValidationError> {
 			arg.result
@@ -564,6 +855,7 @@ mod tests {
 		let (check_fut, check_result) = check_assumption_validation_data(
 			&mut ctx,
 			&candidate,
+			ValidationContext::RelayParent,
 			OccupiedCoreAssumption::Included,
 		).remote_handle();
 
@@ -604,6 +896,146 @@ mod tests {
 		executor::block_on(test_fut);
 	}
 
+	#[test]
+	fn checks_assumption_at_allowed_relay_parent_in_state_of_leaf() {
+		let validation_data: ValidationData = Default::default();
+		let validation_code: ValidationCode = vec![1, 2, 3].into();
+
+		let persisted_validation_data_hash = validation_data.persisted.hash();
+		let leaf = [3; 32].into();
+		let relay_parent = [2; 32].into();
+		let para_id = 5.into();
+
+		let mut candidate = CandidateDescriptor::default();
+		candidate.relay_parent = relay_parent;
+		candidate.persisted_validation_data_hash = persisted_validation_data_hash;
+		candidate.para_id = para_id;
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let check_fut = async move {
+			let relay_parent_number = allowed_relay_parent_number(&mut ctx, leaf, relay_parent)
+				.await
+				.unwrap()
+				.unwrap();
+
+			assert_eq!(relay_parent_number, Some(9));
+
+			let not_allowed = allowed_relay_parent_number(&mut ctx, leaf, [1; 32].into())
+				.await
+				.unwrap()
+				.unwrap();
+
+			assert_eq!(not_allowed, None);
+
+			check_assumption_validation_data(
+				&mut ctx,
+				&candidate,
+				ValidationContext::Leaf(leaf, 9),
+				OccupiedCoreAssumption::Included,
+			).await.unwrap()
+		};
+
+		let (check_fut, check_result) = check_fut.remote_handle();
+
+		let test_fut = async move {
+			for _ in 0..2 {
+				assert_matches!(
+					ctx_handle.recv().await,
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						rp,
+						RuntimeApiRequest::AllowedRelayParents(tx)
+					)) => {
+						assert_eq!(rp, leaf);
+
+						let _ = tx.send(Ok(vec![(leaf, 10), (relay_parent, 9)]));
+					}
+				);
+			}
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::FullValidationDataAt(p, 9, OccupiedCoreAssumption::Included, tx)
+				)) => {
+					assert_eq!(rp, leaf);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_data.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::HistoricalValidationCode(p, 9, tx)
+				)) => {
+					assert_eq!(rp, leaf);
+					assert_eq!(p, para_id);
+
+					let _ = tx.send(Ok(Some(validation_code.clone())));
+				}
+			);
+
+			assert_matches!(check_result.await, AssumptionCheckOutcome::Matches(o, v) => {
+				assert_eq!(o, validation_data);
+				assert_eq!(v, validation_code);
+			});
+		};
+
+		let test_fut = future::join(test_fut, check_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn executor_params_default_if_runtime_does_not_support_them() {
+		let relay_parent = [2; 32].into();
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (fetch_fut, fetch_result) = executor_params_at_relay_parent(
+			&mut ctx,
+			relay_parent,
+		).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::SessionIndexForChild(tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+
+					let _ = tx.send(Ok(1));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::SessionExecutorParams(1, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+
+					let _ = tx.send(Err(RuntimeApiError::NotSupported {
+						runtime_api_name: "session_executor_params",
+					}));
+				}
+			);
+
+			assert_eq!(fetch_result.await.unwrap(), Some(ExecutorParams::default()));
+		};
+
+		let test_fut = future::join(test_fut, fetch_fut);
+		executor::block_on(test_fut);
+	}
+
 	#[test]
 	fn correctly_checks_timed_out_assumption() {
 		let validation_data: ValidationData = Default::default();
@@ -624,6 +1056,7 @@ mod tests {
 		let (check_fut, check_result) = check_assumption_validation_data(
 			&mut ctx,
 			&candidate,
+			ValidationContext::RelayParent,
 			OccupiedCoreAssumption::TimedOut,
 		).remote_handle();
 
@@ -682,6 +1115,7 @@ mod tests {
 		let (check_fut, check_result) = check_assumption_validation_data(
 			&mut ctx,
 			&candidate,
+			ValidationContext::RelayParent,
 			OccupiedCoreAssumption::Included,
 		).remote_handle();
 
@@ -724,6 +1158,7 @@ mod tests {
 		let (check_fut, check_result) = check_assumption_validation_data(
 			&mut ctx,
 			&candidate,
+			ValidationContext::RelayParent,
 			OccupiedCoreAssumption::TimedOut,
 		).remote_handle();
 
@@ -778,6 +1213,7 @@ mod tests {
 		let (check_fut, check_result) = check_assumption_validation_data(
 			&mut ctx,
 			&candidate,
+			ValidationContext::RelayParent,
 			OccupiedCoreAssumption::Included,
 		).remote_handle();
 
@@ -821,7 +1257,9 @@ mod tests {
 			head_data: HeadData(vec![1, 1, 1]),
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
 			processed_downward_messages: 0,
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
@@ -834,7 +1272,7 @@ mod tests {
 			validation_data.persisted.clone(),
 			Some(validation_data.transient),
 			vec![1, 2, 3].into(),
-			descriptor,
+			Default::default(),
 			Arc::new(pov),
 			TaskExecutor::new(),
 		).unwrap();
@@ -868,7 +1306,9 @@ mod tests {
 			head_data: HeadData(vec![1, 1, 1]),
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
 			processed_downward_messages: 0,
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
@@ -885,7 +1325,7 @@ mod tests {
 			validation_data.persisted,
 			Some(validation_data.transient),
 			vec![1, 2, 3].into(),
-			descriptor,
+			Default::default(),
 			Arc::new(pov),
 			TaskExecutor::new(),
 		).unwrap();
@@ -893,6 +1333,33 @@ mod tests {
 		assert_matches!(v, ValidationResult::Invalid(InvalidCandidate::BadReturn));
 	}
 
+	#[test]
+	fn candidate_validation_trap_and_params_decode_failure_are_invalid() {
+		let validation_data: ValidationData = Default::default();
+		let pov = Arc::new(PoV { block_data: BlockData(vec![1; 32]) });
+
+		let validate = |error| validate_candidate_exhaustive::<MockValidationBackend, _>(
+			MockValidationArg {
+				result: Err(ValidationError::InvalidCandidate(error))
+			},
+			validation_data.persisted.clone(),
+			Some(validation_data.transient.clone()),
+			vec![1, 2, 3].into(),
+			Default::default(),
+			pov.clone(),
+			TaskExecutor::new(),
+		).unwrap();
+
+		assert_matches!(
+			validate(WasmInvalidCandidate::Trap("unreachable".into())),
+			ValidationResult::Invalid(InvalidCandidate::ExecutionTrap(msg)) if msg == "unreachable"
+		);
+		assert_matches!(
+			validate(WasmInvalidCandidate::ParamsDecodeFailure),
+			ValidationResult::Invalid(InvalidCandidate::ParamsDecodeFailure)
+		);
+	}
+
 
 	#[test]
 	fn candidate_validation_timeout_is_internal_error() {
@@ -914,7 +1381,9 @@ mod tests {
 			head_data: HeadData(vec![1, 1, 1]),
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
 			processed_downward_messages: 0,
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
@@ -931,7 +1400,7 @@ mod tests {
 			validation_data.persisted,
 			Some(validation_data.transient),
 			vec![1, 2, 3].into(),
-			descriptor,
+			Default::default(),
 			Arc::new(pov),
 			TaskExecutor::new(),
 		);
@@ -939,6 +1408,33 @@ mod tests {
 		assert_matches!(v, Ok(ValidationResult::Invalid(InvalidCandidate::Timeout)));
 	}
 
+	#[test]
+	fn candidate_validation_internal_error_is_not_invalid() {
+		let validation_data: ValidationData = Default::default();
+
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let v = validate_candidate_exhaustive::<MockValidationBackend, _>(
+			MockValidationArg {
+				result: Err(ValidationError::Internal(wasm_executor::InternalError::WasmWorker(
+					"Worker aborted".into(),
+				)))
+			},
+			validation_data.persisted,
+			Some(validation_data.transient),
+			vec![1, 2, 3].into(),
+			Default::default(),
+			Arc::new(pov),
+			TaskExecutor::new(),
+		);
+
+		assert_matches!(v, Err(ValidationFailed(_)));
+	}
+
 	#[test]
 	fn candidate_validation_ok_does_not_validate_outputs_if_no_transient() {
 		let mut validation_data: ValidationData = Default::default();
@@ -957,7 +1453,9 @@ mod tests {
 			head_data: HeadData(vec![1, 1, 1]),
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
+			horizontal_messages: Vec::new(),
 			processed_downward_messages: 0,
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
@@ -970,7 +1468,7 @@ mod tests {
 			validation_data.persisted.clone(),
 			None,
 			vec![1, 2, 3].into(),
-			descriptor,
+			Default::default(),
 			Arc::new(pov),
 			TaskExecutor::new(),
 		).unwrap();
@@ -983,4 +1481,151 @@ mod tests {
 			assert_eq!(outputs.new_validation_code, Some(vec![2, 2, 2].into()));
 		});
 	}
+
+	#[test]
+	fn upward_messages_over_limits_are_invalid() {
+		let mut validation_data: ValidationData = Default::default();
+		validation_data.transient.max_head_data_size = 1024;
+		validation_data.transient.max_upward_message_num = 2;
+		validation_data.transient.max_upward_message_size = 8;
+
+		let upward_message = |len| UpwardMessage {
+			origin: ParachainDispatchOrigin::Parachain,
+			data: vec![1; len],
+		};
+		let validation_result = |upward_messages| WasmValidationResult {
+			head_data: HeadData(vec![1, 1, 1]),
+			new_validation_code: None,
+			upward_messages,
+			horizontal_messages: Vec::new(),
+			processed_downward_messages: 0,
+			hrmp_watermark: 0,
+		};
+
+		assert!(check_wasm_result_against_constraints(
+			&validation_data.transient,
+			&validation_result(vec![upward_message(4), upward_message(4)]),
+		).is_ok());
+
+		assert_matches!(
+			check_wasm_result_against_constraints(
+				&validation_data.transient,
+				&validation_result(vec![upward_message(1), upward_message(1), upward_message(1)]),
+			),
+			Err(InvalidCandidate::TooManyUpwardMessages(3))
+		);
+
+		assert_matches!(
+			check_wasm_result_against_constraints(
+				&validation_data.transient,
+				&validation_result(vec![upward_message(4), upward_message(5)]),
+			),
+			Err(InvalidCandidate::UpwardMessagesTooLarge(9))
+		);
+	}
+
+	#[test]
+	fn validation_cache_keeps_recent_outcomes() {
+		let key = |n: u8| {
+			let mut descriptor = CandidateDescriptor::default();
+			descriptor.pov_hash = Hash::repeat_byte(n);
+			ValidationCacheKey::new(
+				&descriptor,
+				&vec![1, 2, 3].into(),
+				&Default::default(),
+				&Default::default(),
+				&None,
+			)
+		};
+
+		let mut cache = ValidationCache::default();
+		cache.insert(key(0), &ValidationResult::Invalid(InvalidCandidate::BadReturn));
+		cache.insert(key(1), &ValidationResult::Invalid(InvalidCandidate::Timeout));
+
+		assert_matches!(cache.get(&key(0)), Some(ValidationResult::Invalid(InvalidCandidate::BadReturn)));
+		// Timeouts aren't cached.
+		assert!(cache.get(&key(1)).is_none());
+
+		for n in 1..=VALIDATION_CACHE_SIZE as u8 {
+			cache.insert(key(n), &ValidationResult::Invalid(InvalidCandidate::BadParent));
+		}
+
+		// The oldest outcome has been evicted.
+		assert!(cache.get(&key(0)).is_none());
+		assert_matches!(cache.get(&key(1)), Some(ValidationResult::Invalid(InvalidCandidate::BadParent)));
+		assert_eq!(cache.outcomes.len(), VALIDATION_CACHE_SIZE);
+	}
+
+	#[test]
+	fn basic_checks_precede_cached_outcome() {
+		let validation_data: ValidationData = Default::default();
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validate = |cached_outcome, pov: PoV| validate_candidate_or_use_cached::<MockValidationBackend, _>(
+			MockValidationArg {
+				result: Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::BadReturn)),
+			},
+			cached_outcome,
+			validation_data.persisted.clone(),
+			None,
+			vec![1, 2, 3].into(),
+			Default::default(),
+			descriptor.clone(),
+			Arc::new(pov),
+			TaskExecutor::new(),
+		);
+
+		// A wrong PoV fails the basic checks, whatever is cached, and the failure isn't cacheable.
+		let wrong_pov = PoV { block_data: BlockData(vec![2; 32]) };
+		let cached = ValidationResult::Invalid(InvalidCandidate::BadParent);
+		assert_matches!(
+			validate(Some(cached.clone()), wrong_pov.clone()),
+			(Ok(ValidationResult::Invalid(InvalidCandidate::HashMismatch)), false)
+		);
+		assert_matches!(
+			validate(None, wrong_pov),
+			(Ok(ValidationResult::Invalid(InvalidCandidate::HashMismatch)), false)
+		);
+
+		// The right PoV is answered from the cache, without executing the validation function.
+		assert_matches!(
+			validate(Some(cached), pov.clone()),
+			(Ok(ValidationResult::Invalid(InvalidCandidate::BadParent)), false)
+		);
+
+		// Without a cached outcome, the validation function is executed and its outcome cacheable.
+		assert_matches!(
+			validate(None, pov),
+			(Ok(ValidationResult::Invalid(InvalidCandidate::BadReturn)), true)
+		);
+	}
+
+	#[test]
+	fn validation_cache_key_depends_on_all_parameters() {
+		let descriptor = CandidateDescriptor::default();
+		let code: ValidationCode = vec![1, 2, 3].into();
+		let params = ExecutorParams::default();
+		let persisted = PersistedValidationData::default();
+
+		let base = ValidationCacheKey::new(&descriptor, &code, &params, &persisted, &None);
+
+		assert_eq!(base, ValidationCacheKey::new(&descriptor, &code, &params, &persisted, &None));
+		assert_ne!(base, ValidationCacheKey::new(&descriptor, &vec![4].into(), &params, &persisted, &None));
+		assert_ne!(
+			base,
+			ValidationCacheKey::new(&descriptor, &code, &params, &persisted, &Some(Default::default())),
+		);
+
+		let mut other_params = params.clone();
+		other_params.heap_pages += 1;
+		assert_ne!(base, ValidationCacheKey::new(&descriptor, &code, &other_params, &persisted, &None));
+
+		let mut other_persisted = persisted.clone();
+		other_persisted.block_number = 1;
+		assert_ne!(base, ValidationCacheKey::new(&descriptor, &code, &params, &other_persisted, &None));
+	}
 }
@@ -25,17 +25,157 @@
 //! * Finalized block number to hash
 //! * Last finalized block number
 //! * Ancestors
+//! * Stream of finalized blocks
+//! * Relay VRF story of a block, cached per block
+//! * Batch of block headers
+//! * Hash of the block at a number on the chain of a given block
+//!
+//! Recently fetched headers are kept in an LRU cache shared by all requests needing them.
 
 use polkadot_subsystem::{
+	errors::ChainApiError,
 	FromOverseer, OverseerSignal,
 	SpawnedSubsystem, Subsystem, SubsystemResult, SubsystemContext,
 	messages::ChainApiMessage,
 	metrics::{self, prometheus},
 };
-use polkadot_primitives::v1::{Block, BlockId};
+use polkadot_node_primitives::RelayVrfStory;
+use polkadot_primitives::v1::{Block, BlockId, BlockNumber, Hash, Header};
 use sp_blockchain::HeaderBackend;
+use sp_consensus_babe::digests::{CompatibleDigestItem, PreDigest};
+use parity_scale_codec::Encode;
+
+use futures::{prelude::*, channel::mpsc};
+use lru::LruCache;
+
+const LOG_TARGET: &str = "chain_api";
+
+/// The context the VRF output of a BABE pre-digest is hashed with to obtain the relay VRF story.
+const RELAY_VRF_STORY_CONTEXT: &[u8] = b"A&V RC-VRF";
+
+/// The maximum number of relay VRF stories kept in the cache.
+const RELAY_VRF_STORY_CACHE_SIZE: usize = 1024;
+
+/// Relay VRF stories by block hash, evicting the least recently used ones beyond
+/// `RELAY_VRF_STORY_CACHE_SIZE`. The story of a block never changes, so entries are only evicted
+/// to bound the size of the cache.
+struct RelayVrfStoryCache {
+	stories: LruCache<Hash, Option<RelayVrfStory>>,
+}
+
+impl Default for RelayVrfStoryCache {
+	fn default() -> Self {
+		RelayVrfStoryCache { stories: LruCache::new(RELAY_VRF_STORY_CACHE_SIZE) }
+	}
+}
+
+impl RelayVrfStoryCache {
+	fn get(&mut self, hash: &Hash) -> Option<Option<RelayVrfStory>> {
+		self.stories.get(hash).copied()
+	}
+
+	fn insert(&mut self, hash: Hash, story: Option<RelayVrfStory>) {
+		self.stories.put(hash, story);
+	}
+}
+
+/// The maximum number of headers kept in the cache.
+const HEADER_CACHE_SIZE: usize = 1024;
+
+/// Recently fetched headers by block hash, evicting the least recently used ones beyond
+/// `HEADER_CACHE_SIZE`.
+struct HeaderCache {
+	headers: LruCache<Hash, Header>,
+}
+
+impl Default for HeaderCache {
+	fn default() -> Self {
+		HeaderCache { headers: LruCache::new(HEADER_CACHE_SIZE) }
+	}
+}
+
+impl HeaderCache {
+	fn get(&mut self, hash: &Hash) -> Option<Header> {
+		self.headers.get(hash).cloned()
+	}
+
+	fn insert(&mut self, hash: Hash, header: Header) {
+		self.headers.put(hash, header);
+	}
+}
+
+/// Fetch the header of a block, from the cache if possible.
+///
+/// Headers of blocks which are not in the db aren't cached, as they may still be imported.
+fn fetch_header<Client: HeaderBackend<Block>>(
+	client: &Client,
+	cache: &mut HeaderCache,
+	hash: Hash,
+) -> Result<Option<Header>, ChainApiError> {
+	if let Some(header) = cache.get(&hash) {
+		return Ok(Some(header));
+	}
+
+	let maybe_header = client.header(BlockId::Hash(hash)).map_err(|e| e.to_string())?;
+	if let Some(ref header) = maybe_header {
+		cache.insert(hash, header.clone());
+	}
+
+	Ok(maybe_header)
+}
+
+/// Find the hash of the block with the given number on the chain ending in `descendant_of`.
+///
+/// Only the unfinalized part of the chain is walked: once an ancestor turns out to be finalized,
+/// the hash is looked up by number.
+fn find_block_hash_at_number<Client: HeaderBackend<Block>>(
+	client: &Client,
+	cache: &mut HeaderCache,
+	number: BlockNumber,
+	descendant_of: Hash,
+) -> Result<Option<Hash>, ChainApiError> {
+	let finalized_number = client.info().finalized_number;
+	let mut hash = descendant_of;
+
+	loop {
+		let header = match fetch_header(client, cache, hash)? {
+			Some(header) => header,
+			None => return Ok(None),
+		};
+
+		if header.number == number {
+			return Ok(Some(hash));
+		}
+		if header.number < number {
+			return Ok(None);
+		}
+
+		if header.number <= finalized_number {
+			let finalized_hash = client.hash(header.number).map_err(|e| e.to_string())?;
+			if finalized_hash == Some(hash) {
+				return client.hash(number).map_err(|e| e.to_string().into());
+			}
+		}
+
+		hash = header.parent_hash;
+	}
+}
 
-use futures::prelude::*;
+/// Derive the relay VRF story from the VRF output of the BABE pre-digest of the header.
+///
+/// Returns `None` if the header has no BABE pre-digest or was authored in a secondary plain slot.
+fn relay_vrf_story(header: &Header) -> Option<RelayVrfStory> {
+	let pre_digest = header.digest.logs().iter().find_map(|log| log.as_babe_pre_digest())?;
+
+	let vrf_output = match pre_digest {
+		PreDigest::Primary(primary) => primary.vrf_output,
+		PreDigest::SecondaryVRF(secondary) => secondary.vrf_output,
+		PreDigest::SecondaryPlain(_) => return None,
+	};
+
+	let story = (RELAY_VRF_STORY_CONTEXT, vrf_output).using_encoded(sp_core::hashing::blake2_256);
+	Some(RelayVrfStory(story))
+}
 
 /// The Chain API Subsystem implementation.
 pub struct ChainApiSubsystem<Client> {
@@ -74,11 +214,18 @@ async fn run<Client>(
 where
 	Client: HeaderBackend<Block>,
 {
+	let mut finality_subscribers: Vec<mpsc::UnboundedSender<(Hash, BlockNumber)>> = Vec::new();
+	let mut story_cache = RelayVrfStoryCache::default();
+	let mut header_cache = HeaderCache::default();
+
 	loop {
 		match ctx.recv().await? {
 			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
 			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {},
-			FromOverseer::Signal(OverseerSignal::BlockFinalized(_)) => {},
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(hash, number)) => {
+				// drop the subscribers whose receiving end is gone
+				finality_subscribers.retain(|s| s.unbounded_send((hash, number)).is_ok());
+			},
 			FromOverseer::Communication { msg } => match msg {
 				ChainApiMessage::BlockNumber(hash, response_channel) => {
 					let result = subsystem.client.number(hash).map_err(|e| e.to_string().into());
@@ -101,10 +248,10 @@ where
 					let mut hash = hash;
 
 					let next_parent = core::iter::from_fn(|| {
-						let maybe_header = subsystem.client.header(BlockId::Hash(hash));
+						let maybe_header = fetch_header(&subsystem.client, &mut header_cache, hash);
 						match maybe_header {
 							// propagate the error
-							Err(e) => Some(Err(e.to_string().into())),
+							Err(e) => Some(Err(e)),
 							// fewer than `k` ancestors are available
 							Ok(None) => None,
 							Ok(Some(header)) => {
@@ -118,6 +265,43 @@ where
 					subsystem.metrics.on_request(result.is_ok());
 					let _ = response_channel.send(result);
 				},
+				ChainApiMessage::FinalizedBlockStream(sender) => {
+					finality_subscribers.push(sender);
+				},
+				ChainApiMessage::RelayVrfStory(hash, response_channel) => {
+					let result = match story_cache.get(&hash) {
+						Some(story) => Ok(story),
+						None => match fetch_header(&subsystem.client, &mut header_cache, hash) {
+							Ok(Some(header)) => {
+								let story = relay_vrf_story(&header);
+								story_cache.insert(hash, story);
+								Ok(story)
+							}
+							// the block may still be imported, so this isn't cached
+							Ok(None) => Ok(None),
+							Err(e) => Err(e),
+						},
+					};
+					subsystem.metrics.on_request(result.is_ok());
+					let _ = response_channel.send(result);
+				},
+				ChainApiMessage::BlockHeaders(hashes, response_channel) => {
+					let result = hashes.into_iter()
+						.map(|hash| fetch_header(&subsystem.client, &mut header_cache, hash))
+						.collect::<Result<Vec<_>, _>>();
+					subsystem.metrics.on_request(result.is_ok());
+					let _ = response_channel.send(result);
+				},
+				ChainApiMessage::FindBlockHashAtNumber { number, descendant_of, response_channel } => {
+					let result = find_block_hash_at_number(
+						&subsystem.client,
+						&mut header_cache,
+						number,
+						descendant_of,
+					);
+					subsystem.metrics.on_request(result.is_ok());
+					let _ = response_channel.send(result);
+				},
 			}
 		}
 	}
@@ -170,7 +354,7 @@ mod tests {
 	use std::collections::BTreeMap;
 	use futures::{future::BoxFuture, channel::oneshot};
 
-	use polkadot_primitives::v1::{Hash, BlockNumber, BlockId, Header};
+	use parity_scale_codec::Decode;
 	use polkadot_node_subsystem_test_helpers::{make_subsystem_context, TestSubsystemContextHandle};
 	use sp_blockchain::Info as BlockInfo;
 	use sp_core::testing::TaskExecutor;
@@ -187,6 +371,20 @@ mod tests {
 	const THREE: Hash = Hash::repeat_byte(0x03);
 	const FOUR: Hash = Hash::repeat_byte(0x04);
 	const ERROR_PATH: Hash = Hash::repeat_byte(0xFF);
+	const PRIMARY: Hash = Hash::repeat_byte(0x05);
+	const SECONDARY_PLAIN: Hash = Hash::repeat_byte(0x06);
+
+	fn header_with_pre_digest(pre_digest: PreDigest) -> Header {
+		let mut header = default_header();
+		header.digest.push(CompatibleDigestItem::babe_pre_digest(pre_digest));
+		header
+	}
+
+	fn primary_pre_digest() -> PreDigest {
+		// authority index, slot number, VRF output and an all-zero VRF proof.
+		let encoded = (0u32, 1u64, [7u8; 32], [0u8; 64]).encode();
+		PreDigest::Primary(Decode::decode(&mut &encoded[..]).unwrap())
+	}
 
 	fn default_header() -> Header {
 		Header {
@@ -229,7 +427,14 @@ mod tests {
 					},
 					ERROR_PATH => Header {
 						..default_header()
-					}
+					},
+					PRIMARY => header_with_pre_digest(primary_pre_digest()),
+					SECONDARY_PLAIN => header_with_pre_digest(PreDigest::SecondaryPlain(
+						sp_consensus_babe::digests::SecondaryPlainPreDigest {
+							authority_index: 0,
+							slot_number: 1,
+						},
+					)),
 				}
 			}
 		}
@@ -386,4 +591,173 @@ mod tests {
 			}.boxed()
 		})
 	}
+
+	#[test]
+	fn finalized_block_stream() {
+		test_harness(|_client, mut sender| {
+			async move {
+				let (tx, mut rx) = mpsc::unbounded();
+				sender.send(FromOverseer::Communication {
+					msg: ChainApiMessage::FinalizedBlockStream(tx),
+				}).await;
+
+				sender.send(FromOverseer::Signal(OverseerSignal::BlockFinalized(TWO, 2))).await;
+				assert_eq!(rx.next().await, Some((TWO, 2)));
+
+				sender.send(FromOverseer::Signal(OverseerSignal::BlockFinalized(FOUR, 4))).await;
+				assert_eq!(rx.next().await, Some((FOUR, 4)));
+
+				sender.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+				assert_eq!(rx.next().await, None);
+			}.boxed()
+		})
+	}
+
+	#[test]
+	fn request_relay_vrf_story() {
+		test_harness(|client, mut sender| {
+			async move {
+				let primary_header = client.header(BlockId::Hash(PRIMARY)).unwrap().unwrap();
+				let expected = relay_vrf_story(&primary_header);
+				assert!(expected.is_some());
+
+				let test_cases = [
+					(PRIMARY, expected),
+					// served from the cache
+					(PRIMARY, expected),
+					(SECONDARY_PLAIN, None),
+					(TWO, None), // no pre-digest
+					(ONE, None), // not here
+				];
+				for (hash, expected) in &test_cases {
+					let (tx, rx) = oneshot::channel();
+
+					sender.send(FromOverseer::Communication {
+						msg: ChainApiMessage::RelayVrfStory(*hash, tx),
+					}).await;
+
+					assert_eq!(rx.await.unwrap().unwrap(), *expected);
+				}
+
+				let (tx, rx) = oneshot::channel();
+				sender.send(FromOverseer::Communication {
+					msg: ChainApiMessage::RelayVrfStory(Hash::zero(), tx),
+				}).await;
+				assert!(rx.await.unwrap().is_err());
+
+				sender.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+			}.boxed()
+		})
+	}
+
+	#[test]
+	fn request_block_headers() {
+		test_harness(|client, mut sender| {
+			async move {
+				let expected = vec![
+					client.header(BlockId::Hash(THREE)).unwrap(),
+					None, // not here
+					client.header(BlockId::Hash(TWO)).unwrap(),
+				];
+				assert!(expected[0].is_some());
+
+				// the second request is served from the cache
+				for _ in 0..2 {
+					let (tx, rx) = oneshot::channel();
+					sender.send(FromOverseer::Communication {
+						msg: ChainApiMessage::BlockHeaders(vec![THREE, ONE, TWO], tx),
+					}).await;
+					assert_eq!(rx.await.unwrap().unwrap(), expected);
+				}
+
+				let (tx, rx) = oneshot::channel();
+				sender.send(FromOverseer::Communication {
+					msg: ChainApiMessage::BlockHeaders(vec![TWO, Hash::zero()], tx),
+				}).await;
+				assert!(rx.await.unwrap().is_err());
+
+				sender.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+			}.boxed()
+		})
+	}
+
+	#[test]
+	fn request_block_hash_at_number() {
+		test_harness(|_client, mut sender| {
+			async move {
+				let test_cases = [
+					(4, FOUR, Some(FOUR)),
+					(5, FOUR, None),
+					// found by number, as `THREE` is finalized
+					(1, FOUR, Some(ONE)),
+					// not here
+					(4, Hash::repeat_byte(0x10), None),
+				];
+				for (number, descendant_of, expected) in &test_cases {
+					let (tx, rx) = oneshot::channel();
+					sender.send(FromOverseer::Communication {
+						msg: ChainApiMessage::FindBlockHashAtNumber {
+							number: *number,
+							descendant_of: *descendant_of,
+							response_channel: tx,
+						},
+					}).await;
+					assert_eq!(rx.await.unwrap().unwrap(), *expected);
+				}
+
+				sender.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+			}.boxed()
+		})
+	}
+
+	#[test]
+	fn block_hash_at_number_walks_unfinalized_chain() {
+		let client = TestClient {
+			finalized_blocks: maplit::btreemap! {
+				1 => ONE,
+			},
+			..Default::default()
+		};
+		let mut cache = HeaderCache::default();
+
+		assert_eq!(find_block_hash_at_number(&client, &mut cache, 3, FOUR).unwrap(), Some(THREE));
+		assert_eq!(find_block_hash_at_number(&client, &mut cache, 2, FOUR).unwrap(), Some(TWO));
+		// the chain has a gap, as the header of `ONE` is unknown.
+		assert_eq!(find_block_hash_at_number(&client, &mut cache, 1, FOUR).unwrap(), None);
+		assert!(find_block_hash_at_number(&client, &mut cache, 1, Hash::zero()).is_err());
+	}
+
+	#[test]
+	fn header_cache_evicts_least_recently_used() {
+		let mut cache = HeaderCache::default();
+
+		for i in 0..HEADER_CACHE_SIZE {
+			cache.insert(Hash::from_low_u64_be(i as u64), default_header());
+		}
+
+		// using the oldest header makes the second oldest the least recently used one
+		assert!(cache.get(&Hash::from_low_u64_be(0)).is_some());
+		cache.insert(Hash::from_low_u64_be(HEADER_CACHE_SIZE as u64), default_header());
+
+		assert_eq!(cache.headers.len(), HEADER_CACHE_SIZE);
+		assert!(cache.get(&Hash::from_low_u64_be(0)).is_some());
+		assert!(cache.get(&Hash::from_low_u64_be(1)).is_none());
+	}
+
+	#[test]
+	fn relay_vrf_story_cache_evicts_least_recently_used() {
+		let mut cache = RelayVrfStoryCache::default();
+
+		for i in 0..RELAY_VRF_STORY_CACHE_SIZE {
+			cache.insert(Hash::from_low_u64_be(i as u64), None);
+		}
+
+		// using the oldest story makes the second oldest the least recently used one
+		assert_eq!(cache.get(&Hash::from_low_u64_be(0)), Some(None));
+		cache.insert(Hash::from_low_u64_be(RELAY_VRF_STORY_CACHE_SIZE as u64), None);
+
+		assert_eq!(cache.stories.len(), RELAY_VRF_STORY_CACHE_SIZE);
+		assert_eq!(cache.get(&Hash::from_low_u64_be(0)), Some(None));
+		assert!(cache.get(&Hash::from_low_u64_be(1)).is_none());
+	}
 }
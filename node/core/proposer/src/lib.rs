@@ -1,6 +1,5 @@
 use futures::prelude::*;
-use futures::select;
-use polkadot_node_subsystem::{messages::{AllMessages, ProvisionerInherentData, ProvisionerMessage}, SubsystemError};
+use polkadot_node_subsystem::SubsystemError;
 use polkadot_overseer::OverseerHandler;
 use polkadot_primitives::v1::{
 	Block, Hash, Header,
@@ -9,11 +8,15 @@ use sc_block_builder::{BlockBuilderApi, BlockBuilderProvider};
 use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_blockchain::HeaderBackend;
 use sp_consensus::{Proposal, RecordProof};
-use sp_inherents::InherentData;
+use sp_inherents::{InherentData, ProvideInherentData};
 use sp_runtime::traits::{DigestFor, HashFor};
 use sp_transaction_pool::TransactionPool;
 use std::{fmt, pin::Pin, sync::Arc, time};
 
+mod parachains_inherent;
+
+pub use parachains_inherent::ParachainsInherentDataProvider;
+
 /// How long proposal can take before we give up and err out
 const PROPOSE_TIMEOUT: core::time::Duration = core::time::Duration::from_secs(2);
 
@@ -91,62 +94,6 @@ pub struct Proposer<TxPool: TransactionPool<Block = Block>, Backend, Client> {
 	parent_header_hash: Hash,
 }
 
-// This impl has the same generic bounds as the Proposer impl.
-impl<TxPool, Backend, Client> Proposer<TxPool, Backend, Client>
-where
-	TxPool: 'static + TransactionPool<Block = Block>,
-	Client: 'static
-		+ BlockBuilderProvider<Backend, Block, Client>
-		+ ProvideRuntimeApi<Block>
-		+ HeaderBackend<Block>
-		+ Send
-		+ Sync,
-	Client::Api:
-		BlockBuilderApi<Block> + ApiExt<Block, Error = sp_blockchain::Error>,
-	Backend:
-		'static + sc_client_api::Backend<Block, State = sp_api::StateBackendFor<Client, Block>>,
-	// Rust bug: https://github.com/rust-lang/rust/issues/24159
-	sp_api::StateBackendFor<Client, Block>: sp_api::StateBackend<HashFor<Block>> + Send,
-{
-	/// Get provisioner inherent data
-	///
-	/// This function has a constant timeout: `PROPOSE_TIMEOUT`.
-	fn get_provisioner_data(&self) -> impl Future<Output = Result<ProvisionerInherentData, Error>> {
-		// clone this (lightweight) data because we're going to move it into the future
-		let mut overseer = self.overseer.clone();
-		let parent_header_hash = self.parent_header_hash.clone();
-
-		let mut provisioner_inherent_data = async move {
-			let (sender, receiver) = futures::channel::oneshot::channel();
-
-			overseer.wait_for_activation(parent_header_hash, sender).await?;
-			receiver.await.map_err(Error::ClosedChannelFromProvisioner)?;
-
-			let (sender, receiver) = futures::channel::oneshot::channel();
-			// strictly speaking, we don't _have_ to .await this send_msg before opening the
-			// receiver; it's possible that the response there would be ready slightly before
-			// this call completes. IMO it's not worth the hassle or overhead of spawning a
-			// distinct task for that kind of miniscule efficiency improvement.
-			overseer.send_msg(AllMessages::Provisioner(
-				ProvisionerMessage::RequestInherentData(parent_header_hash, sender),
-			)).await?;
-
-			receiver.await.map_err(Error::ClosedChannelFromProvisioner)
-		}
-		.boxed()
-		.fuse();
-
-		let mut timeout = wasm_timer::Delay::new(PROPOSE_TIMEOUT).fuse();
-
-		async move {
-			select! {
-				pid = provisioner_inherent_data => pid,
-				_ = timeout => Err(Error::Timeout),
-			}
-		}
-	}
-}
-
 impl<TxPool, Backend, Client> sp_consensus::Proposer<Block> for Proposer<TxPool, Backend, Client>
 where
 	TxPool: 'static + TransactionPool<Block = Block>,
@@ -176,21 +123,14 @@ where
 		max_duration: time::Duration,
 		record_proof: RecordProof,
 	) -> Self::Proposal {
-		let provisioner_data = self.get_provisioner_data();
+		let provisioner_data = ParachainsInherentDataProvider::create_or_empty(
+			self.overseer.clone(),
+			self.parent_header_hash,
+			PROPOSE_TIMEOUT,
+		);
 
 		async move {
-			let provisioner_data = match provisioner_data.await {
-				Ok(pd) => pd,
-				Err(err) => {
-					log::warn!("could not get provisioner inherent data; injecting default data: {}", err);
-					Default::default()
-				}
-			};
-
-			inherent_data.put_data(
-				polkadot_primitives::v1::INCLUSION_INHERENT_IDENTIFIER,
-				&provisioner_data,
-			)?;
+			provisioner_data.await.provide_inherent_data(&mut inherent_data)?;
 
 			self.inner
 				.propose(inherent_data, inherent_digests, max_duration, record_proof)
@@ -210,7 +150,7 @@ pub enum Error {
 	Consensus(sp_consensus::Error),
 	Blockchain(sp_blockchain::Error),
 	Inherent(sp_inherents::Error),
-	Timeout,
+	Timeout(time::Duration),
 	ClosedChannelFromProvisioner(futures::channel::oneshot::Canceled),
 	Subsystem(SubsystemError)
 }
@@ -245,7 +185,7 @@ impl fmt::Display for Error {
 			Self::Consensus(err) => write!(f, "consensus error: {}", err),
 			Self::Blockchain(err) => write!(f, "blockchain error: {}", err),
 			Self::Inherent(err) => write!(f, "inherent error: {:?}", err),
-			Self::Timeout => write!(f, "timeout: provisioner did not return inherent data after {:?}", PROPOSE_TIMEOUT),
+			Self::Timeout(timeout) => write!(f, "timeout: provisioner did not return inherent data after {:?}", timeout),
 			Self::ClosedChannelFromProvisioner(err) => write!(f, "provisioner closed inherent data channel before sending: {}", err),
 			Self::Subsystem(err) => write!(f, "subsystem error: {:?}", err),
 		}
@@ -0,0 +1,111 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Creation of the inherent data of the parachains inclusion inherent.
+//!
+//! The data is requested from the provisioner through the overseer. Besides the proposer, this is
+//! meant for code paths which author blocks themselves, such as tests and benchmarks.
+
+use futures::{prelude::*, select};
+use polkadot_node_subsystem::messages::{AllMessages, ProvisionerInherentData, ProvisionerMessage};
+use polkadot_overseer::OverseerHandler;
+use polkadot_primitives::v1::{Hash, INCLUSION_INHERENT_IDENTIFIER};
+use sp_inherents::{InherentData, InherentIdentifier, ProvideInherentData};
+use std::time::Duration;
+
+use crate::Error;
+
+/// Provides the inherent data of the parachains inclusion inherent for a block.
+#[derive(Default)]
+pub struct ParachainsInherentDataProvider {
+	inherent_data: ProvisionerInherentData,
+}
+
+impl ParachainsInherentDataProvider {
+	/// Request the inherent data for a block built on top of `parent` from the provisioner.
+	///
+	/// This waits for `parent` to be activated in the overseer. Fails if the provisioner doesn't
+	/// answer within `timeout`.
+	pub async fn create(
+		mut overseer: OverseerHandler,
+		parent: Hash,
+		timeout: Duration,
+	) -> Result<Self, Error> {
+		let mut inherent_data = async move {
+			let (sender, receiver) = futures::channel::oneshot::channel();
+
+			overseer.wait_for_activation(parent, sender).await?;
+			receiver.await.map_err(Error::ClosedChannelFromProvisioner)?;
+
+			let (sender, receiver) = futures::channel::oneshot::channel();
+			// strictly speaking, we don't _have_ to .await this send_msg before opening the
+			// receiver; it's possible that the response there would be ready slightly before
+			// this call completes. IMO it's not worth the hassle or overhead of spawning a
+			// distinct task for that kind of miniscule efficiency improvement.
+			overseer.send_msg(AllMessages::Provisioner(
+				ProvisionerMessage::RequestInherentData(parent, sender),
+			)).await?;
+
+			receiver.await.map_err(Error::ClosedChannelFromProvisioner)
+		}
+		.boxed()
+		.fuse();
+
+		let mut timeout_delay = wasm_timer::Delay::new(timeout).fuse();
+
+		select! {
+			inherent_data = inherent_data => Ok(ParachainsInherentDataProvider {
+				inherent_data: inherent_data?,
+			}),
+			_ = timeout_delay => Err(Error::Timeout(timeout)),
+		}
+	}
+
+	/// Like [`create`](Self::create), but falls back to empty inherent data if the provisioner
+	/// fails to answer in time.
+	pub async fn create_or_empty(
+		overseer: OverseerHandler,
+		parent: Hash,
+		timeout: Duration,
+	) -> Self {
+		match Self::create(overseer, parent, timeout).await {
+			Ok(provider) => provider,
+			Err(err) => {
+				log::warn!("could not get provisioner inherent data; injecting default data: {}", err);
+				Default::default()
+			}
+		}
+	}
+
+	/// The inherent data provided.
+	pub fn inherent_data(&self) -> &ProvisionerInherentData {
+		&self.inherent_data
+	}
+}
+
+impl ProvideInherentData for ParachainsInherentDataProvider {
+	fn inherent_identifier(&self) -> &'static InherentIdentifier {
+		&INCLUSION_INHERENT_IDENTIFIER
+	}
+
+	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), sp_inherents::Error> {
+		inherent_data.put_data(INCLUSION_INHERENT_IDENTIFIER, &self.inherent_data)
+	}
+
+	fn error_to_string(&self, _error: &[u8]) -> Option<String> {
+		None
+	}
+}
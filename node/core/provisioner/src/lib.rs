@@ -28,7 +28,7 @@ use polkadot_node_subsystem::{
 	errors::{ChainApiError, RuntimeApiError},
 	messages::{
 		AllMessages, ChainApiMessage, ProvisionableData, ProvisionerInherentData,
-		ProvisionerMessage, RuntimeApiMessage,
+		ProvisionerMessage, ProvisionerOutgoingMessages, RuntimeApiMessage,
 	},
 	metrics::{self, prometheus},
 };
@@ -41,7 +41,12 @@ use polkadot_primitives::v1::{
 	BackedCandidate, BlockNumber, CoreState, Hash, OccupiedCoreAssumption,
 	SignedAvailabilityBitfield,
 };
-use std::{collections::HashMap, convert::TryFrom, pin::Pin};
+use std::{
+	collections::HashMap,
+	convert::TryFrom,
+	pin::Pin,
+	time::{Duration, Instant},
+};
 
 struct ProvisioningJob {
 	relay_parent: Hash,
@@ -96,9 +101,15 @@ enum FromJob {
 
 impl From<FromJob> for AllMessages {
 	fn from(from_job: FromJob) -> AllMessages {
+		ProvisionerOutgoingMessages::from(from_job).into()
+	}
+}
+
+impl From<FromJob> for ProvisionerOutgoingMessages {
+	fn from(from_job: FromJob) -> ProvisionerOutgoingMessages {
 		match from_job {
-			FromJob::ChainApi(cam) => AllMessages::ChainApi(cam),
-			FromJob::Runtime(ram) => AllMessages::RuntimeApi(ram),
+			FromJob::ChainApi(cam) => ProvisionerOutgoingMessages::ChainApi(cam),
+			FromJob::Runtime(ram) => ProvisionerOutgoingMessages::RuntimeApi(ram),
 		}
 	}
 }
@@ -137,7 +148,7 @@ impl JobTrait for ProvisioningJob {
 	type RunArgs = ();
 	type Metrics = Metrics;
 
-	const NAME: &'static str = "ProvisioningJob";
+	const NAME: &'static str = "provisioner-job";
 
 	/// Run a job for the parent block indicated
 	//
@@ -186,7 +197,8 @@ impl ProvisioningJob {
 
 			match msg {
 				ToJob::Provisioner(RequestInherentData(_, return_sender)) => {
-					if let Err(err) = send_inherent_data(
+					let started = Instant::now();
+					match send_inherent_data(
 						self.relay_parent,
 						&self.signed_bitfields,
 						&self.backed_candidates,
@@ -195,10 +207,18 @@ impl ProvisioningJob {
 					)
 					.await
 					{
-						log::warn!(target: "provisioner", "failed to assemble or send inherent data: {:?}", err);
-						self.metrics.on_inherent_data_request(false);
-					} else {
-						self.metrics.on_inherent_data_request(true);
+						Err(err) => {
+							log::warn!(target: "provisioner", "failed to assemble or send inherent data: {:?}", err);
+							self.metrics.on_inherent_data_request(false);
+						}
+						Ok((n_bitfields, n_candidates)) => {
+							self.metrics.on_inherent_data_request(true);
+							self.metrics.on_inherent_data_created(
+								n_bitfields,
+								n_candidates,
+								started.elapsed(),
+							);
+						}
 					}
 				}
 				ToJob::Provisioner(RequestBlockAuthorshipData(_, sender)) => {
@@ -276,13 +296,15 @@ type CoreAvailability = BitVec<bitvec::order::Lsb0, u8>;
 // When we're choosing bitfields to include, the rule should be simple:
 // maximize availability. So basically, include all bitfields. And then
 // choose a coherent set of candidates along with that.
+//
+// Returns the number of bitfields and candidates which were sent.
 async fn send_inherent_data(
 	relay_parent: Hash,
 	bitfields: &[SignedAvailabilityBitfield],
 	candidates: &[BackedCandidate],
 	return_sender: oneshot::Sender<ProvisionerInherentData>,
 	mut from_job: mpsc::Sender<FromJob>,
-) -> Result<(), Error> {
+) -> Result<(usize, usize), Error> {
 	let availability_cores = request_availability_cores(relay_parent, &mut from_job)
 		.await?
 		.await??;
@@ -297,10 +319,12 @@ async fn send_inherent_data(
 	)
 	.await?;
 
+	let counts = (bitfields.len(), candidates.len());
+
 	return_sender
 		.send((bitfields, candidates))
 		.map_err(|_| Error::OneshotSend)?;
-	Ok(())
+	Ok(counts)
 }
 
 // in general, we want to pick all the bitfields. However, we have the following constraints:
@@ -465,6 +489,9 @@ fn bitfields_indicate_availability(
 #[derive(Clone)]
 struct MetricsInner {
 	inherent_data_requests: prometheus::CounterVec<prometheus::U64>,
+	inherent_data_bitfields: prometheus::Histogram,
+	inherent_data_candidates: prometheus::Histogram,
+	inherent_data_creation_time: prometheus::Histogram,
 }
 
 /// Candidate backing metrics.
@@ -481,6 +508,14 @@ impl Metrics {
 			}
 		}
 	}
+
+	fn on_inherent_data_created(&self, n_bitfields: usize, n_candidates: usize, took: Duration) {
+		if let Some(metrics) = &self.0 {
+			metrics.inherent_data_bitfields.observe(n_bitfields as f64);
+			metrics.inherent_data_candidates.observe(n_candidates as f64);
+			metrics.inherent_data_creation_time.observe(took.as_secs_f64());
+		}
+	}
 }
 
 impl metrics::Metrics for Metrics {
@@ -496,6 +531,33 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			inherent_data_bitfields: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_inherent_data_bitfields",
+						"Number of availability bitfields in the inherent data provided for a block.",
+					).buckets(vec![0.0, 1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 200.0, 500.0, 1000.0]),
+				)?,
+				registry,
+			)?,
+			inherent_data_candidates: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_inherent_data_candidates",
+						"Number of backed candidates in the inherent data provided for a block.",
+					).buckets(vec![0.0, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0]),
+				)?,
+				registry,
+			)?,
+			inherent_data_creation_time: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"parachain_inherent_data_creation_time",
+						"Time spent assembling the inherent data for a block, in seconds.",
+					),
+				)?,
+				registry,
+			)?,
 		};
 		Ok(Metrics(Some(metrics)))
 	}
@@ -873,4 +935,21 @@ mod tests {
 			})
 		}
 	}
+
+	#[test]
+	fn inherent_data_content_is_observed() {
+		use metrics::Metrics as _;
+
+		let registry = prometheus::Registry::new();
+		let metrics = Metrics::try_register(&registry).unwrap();
+
+		metrics.on_inherent_data_created(10, 2, Duration::from_millis(20));
+		metrics.on_inherent_data_created(12, 0, Duration::from_millis(30));
+
+		let inner = metrics.0.as_ref().unwrap();
+		assert_eq!(inner.inherent_data_bitfields.get_sample_count(), 2);
+		assert_eq!(inner.inherent_data_bitfields.get_sample_sum(), 22.0);
+		assert_eq!(inner.inherent_data_candidates.get_sample_sum(), 2.0);
+		assert_eq!(inner.inherent_data_creation_time.get_sample_count(), 2);
+	}
 }
@@ -18,38 +18,230 @@
 //!
 //! This provides a clean, ownerless wrapper around the parachain-related runtime APIs. This crate
 //! can also be used to cache responses from heavy runtime APIs.
+//!
+//! Requests which need a newer `ParachainHost` API version than the one of the runtime at the
+//! latest session seen at an active leaf are answered with `RuntimeApiError::NotSupported`,
+//! so that nodes may be upgraded ahead of the runtime.
+//!
+//! BABE epoch data is served through the `BabeApi` of the runtime, which isn't versioned along
+//! with `ParachainHost`.
+//!
+//! Responses to `HistoricalValidationCode` requests are cached by relay parent, as the code
+//! used by a para in the context of a past block never changes for a given relay parent. The
+//! cache only keeps the most recently used entries.
+//!
+//! A fallback client, such as one backed by an archive node, may be provided. Requests which fail
+//! on the primary client because it lacks the state of the block, most commonly because it has
+//! already pruned it, are retried on the fallback client. Other failures are not retried, as the
+//! runtime would fail the same way on the fallback client.
+//!
+//! Failed calls are answered with `RuntimeApiError::UnknownBlock` if the state of the block was
+//! unavailable and with `RuntimeApiError::Execution` otherwise, so that pruned state can be told
+//! apart from failures of the runtime itself.
+//!
+//! Optionally, a digest of every response delivered to a requester, including the ones served
+//! from the cache, can be kept in an [`AuditLog`], so that it can later be shown which runtime
+//! data the node acted on. The log can be exported, or appended to a file as entries are noted.
 
 use polkadot_subsystem::{
 	Subsystem, SpawnedSubsystem, SubsystemResult, SubsystemContext,
-	FromOverseer, OverseerSignal,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate,
 	metrics::{self, prometheus},
 };
 use polkadot_subsystem::messages::{
 	RuntimeApiMessage, RuntimeApiRequest as Request,
 };
 use polkadot_subsystem::errors::RuntimeApiError;
-use polkadot_primitives::v1::{Block, BlockId, Hash, ParachainHost};
+use polkadot_node_subsystem_util::backing_group_for_core;
+use polkadot_primitives::v1::{
+	Block, BlockId, BlockNumber, BlakeTwo256, Hash, HashT, Id as ParaId, ParachainHost,
+	SessionIndex, ValidationCode, validation_code_hash,
+};
+
+use codec::Encode;
 
-use sp_api::{ProvideRuntimeApi};
+use sp_api::{ProvideRuntimeApi, ApiErrorExt, ApiExt};
+use sp_consensus_babe::BabeApi;
 
 use futures::prelude::*;
+use futures::channel::oneshot;
+use futures::future::Either;
+use futures_timer::Delay;
+
+use lru::LruCache;
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The default amount of time a runtime API request may take before it is abandoned.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The number of `HistoricalValidationCode` responses to keep the code hash of.
+const CODE_HASH_CACHE_SIZE: usize = 1024;
+
+/// The number of distinct validation codes to keep. Each may be several megabytes large.
+const CODE_CACHE_SIZE: usize = 16;
+
+const LOG_TARGET: &str = "runtime_api";
+
+type RequestTask = Pin<Box<dyn Future<Output = ()> + Send>>;
 
 /// The `RuntimeApiSubsystem`. See module docs for more details.
 pub struct RuntimeApiSubsystem<Client> {
-	client: Client,
+	client: Arc<Client>,
+	fallback_client: Option<Arc<Client>>,
 	metrics: Metrics,
+	request_timeout: Duration,
+	audit_log: Option<AuditLog>,
 }
 
 impl<Client> RuntimeApiSubsystem<Client> {
 	/// Create a new Runtime API subsystem wrapping the given client and metrics.
 	pub fn new(client: Client, metrics: Metrics) -> Self {
-		RuntimeApiSubsystem { client, metrics }
+		RuntimeApiSubsystem {
+			client: Arc::new(client),
+			fallback_client: None,
+			metrics,
+			request_timeout: DEFAULT_REQUEST_TIMEOUT,
+			audit_log: None,
+		}
+	}
+
+	/// Set a client to retry requests on which fail on the primary client, e.g. because it lacks
+	/// the state of the requested block.
+	pub fn with_fallback_client(mut self, fallback_client: Client) -> Self {
+		self.fallback_client = Some(Arc::new(fallback_client));
+		self
+	}
+
+	/// Set the amount of time a runtime API request may take before `RuntimeApiError::Timeout`
+	/// is returned to the requester.
+	pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+		self.request_timeout = request_timeout;
+		self
+	}
+
+	/// Record a digest of every response delivered to a requester in the given audit log.
+	pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+		self.audit_log = Some(audit_log);
+		self
+	}
+}
+
+/// A runtime API response delivered to a requester.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+	/// The block the runtime API was called at.
+	pub relay_parent: Hash,
+	/// The name of the runtime API.
+	pub runtime_api_name: &'static str,
+	/// The hash of the relay parent, the runtime API name, the SCALE-encoded request parameters
+	/// and the SCALE-encoded response.
+	pub digest: Hash,
+}
+
+impl AuditEntry {
+	fn new(
+		relay_parent: Hash,
+		runtime_api_name: &'static str,
+		request_params: &[u8],
+		response: &impl Encode,
+	) -> Self {
+		AuditEntry {
+			relay_parent,
+			runtime_api_name,
+			digest: BlakeTwo256::hash_of(&(relay_parent, runtime_api_name, request_params, response)),
+		}
+	}
+}
+
+impl AuditEntry {
+	/// Write the entry as a single line of the relay parent, the runtime API name and the digest.
+	fn write_line(&self, writer: &mut impl Write) -> io::Result<()> {
+		writeln!(writer, "{:?} {} {:?}", self.relay_parent, self.runtime_api_name, self.digest)
+	}
+}
+
+/// A log of the most recent runtime API responses delivered to requesters.
+///
+/// Once the log is full, the oldest entry is dropped for every new one. Clones share the same
+/// log, so a clone can be kept to inspect the entries noted by the subsystem.
+#[derive(Clone)]
+pub struct AuditLog {
+	entries: Arc<Mutex<VecDeque<AuditEntry>>>,
+	capacity: usize,
+	file: Option<Arc<Mutex<File>>>,
+}
+
+impl AuditLog {
+	/// Create an empty audit log, keeping at most `capacity` entries.
+	pub fn new(capacity: usize) -> Self {
+		AuditLog {
+			entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+			capacity,
+			file: None,
+		}
+	}
+
+	/// Also append every entry noted from now on to the file at `path`, one line per entry.
+	/// The file is created if it doesn't exist.
+	pub fn persist_to(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+		self.file = Some(Arc::new(Mutex::new(file)));
+		Ok(self)
+	}
+
+	/// The entries in the log, from the oldest to the most recent.
+	pub fn entries(&self) -> Vec<AuditEntry> {
+		self.entries.lock().expect("poisoned lock").iter().cloned().collect()
+	}
+
+	/// Write the entries in the log to `writer`, from the oldest to the most recent, in the
+	/// format used by `persist_to`.
+	pub fn export(&self, mut writer: impl Write) -> io::Result<()> {
+		for entry in self.entries.lock().expect("poisoned lock").iter() {
+			entry.write_line(&mut writer)?;
+		}
+		writer.flush()
+	}
+
+	fn note(&self, entry: AuditEntry) {
+		log::trace!(
+			target: LOG_TARGET,
+			"Delivered {} response at {} with digest {}",
+			entry.runtime_api_name,
+			entry.relay_parent,
+			entry.digest,
+		);
+
+		if let Some(ref file) = self.file {
+			if let Err(e) = entry.write_line(&mut *file.lock().expect("poisoned lock")) {
+				log::warn!(target: LOG_TARGET, "Failed to persist audit log entry: {}", e);
+			}
+		}
+
+		if self.capacity == 0 {
+			return;
+		}
+
+		let mut entries = self.entries.lock().expect("poisoned lock");
+		if entries.len() == self.capacity {
+			entries.pop_front();
+		}
+		entries.push_back(entry);
 	}
 }
 
 impl<Client, Context> Subsystem<Context> for RuntimeApiSubsystem<Client> where
-	Client: ProvideRuntimeApi<Block> + Send + 'static,
-	Client::Api: ParachainHost<Block>,
+	Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	Client::Api: ParachainHost<Block> + BabeApi<Block>,
+	<Client::Api as ApiErrorExt>::Error: CallError,
 	Context: SubsystemContext<Message = RuntimeApiMessage>
 {
 	type Metrics = Metrics;
@@ -66,66 +258,388 @@ async fn run<Client>(
 	mut ctx: impl SubsystemContext<Message = RuntimeApiMessage>,
 	subsystem: RuntimeApiSubsystem<Client>,
 ) -> SubsystemResult<()> where
-	Client: ProvideRuntimeApi<Block>,
-	Client::Api: ParachainHost<Block>,
+	Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	Client::Api: ParachainHost<Block> + BabeApi<Block>,
+	<Client::Api as ApiErrorExt>::Error: CallError,
 {
+	// The session and `ParachainHost` API version of the runtime at the most recent new session
+	// seen at an active leaf.
+	let mut api_version: Option<(SessionIndex, u32)> = None;
+	let code_cache = Arc::new(Mutex::new(
+		ValidationCodeCache::new(CODE_HASH_CACHE_SIZE, CODE_CACHE_SIZE),
+	));
+
 	loop {
 		match ctx.recv().await? {
 			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
-			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {},
-			FromOverseer::Signal(OverseerSignal::BlockFinalized(_)) => {},
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, .. })) => {
+				for leaf in activated {
+					update_api_version(&*subsystem.client, leaf, &mut api_version);
+				}
+			},
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {},
 			FromOverseer::Communication { msg } => match msg {
-				RuntimeApiMessage::Request(relay_parent, request) => make_runtime_api_request(
-					&subsystem.client,
-					&subsystem.metrics,
-					relay_parent,
-					request,
-				),
+				RuntimeApiMessage::Request(relay_parent, request) => {
+					let request = match request {
+						Request::HistoricalValidationCode(para, context_height, sender) => {
+							let cached = code_cache.lock().expect("poisoned lock")
+								.get(relay_parent, para, context_height);
+							if let Some(code) = cached {
+								let response = Some(code);
+								if let Some(ref audit_log) = subsystem.audit_log {
+									audit_log.note(AuditEntry::new(
+										relay_parent,
+										"historical_validation_code",
+										&(para, context_height).encode(),
+										&response,
+									));
+								}
+								let _ = sender.send(Ok(response));
+								continue;
+							}
+
+							// Note the response in the cache on its way to the requester.
+							let (tx, rx) = oneshot::channel();
+							let code_cache = code_cache.clone();
+							let note_response = async move {
+								if let Ok(res) = rx.await {
+									if let Ok(Some(ref code)) = res {
+										code_cache.lock().expect("poisoned lock")
+											.insert(relay_parent, para, context_height, code.clone());
+									}
+									let _ = sender.send(res);
+								}
+							};
+							ctx.spawn("runtime-api-code-cache", note_response.boxed()).await?;
+
+							Request::HistoricalValidationCode(para, context_height, tx)
+						}
+						request => request,
+					};
+
+					let (call, respond) = make_runtime_api_request(
+						subsystem.client.clone(),
+						subsystem.fallback_client.clone(),
+						subsystem.metrics.clone(),
+						subsystem.request_timeout,
+						subsystem.audit_log.clone(),
+						api_version.map(|(_, version)| version),
+						relay_parent,
+						request,
+					);
+
+					// The call itself may block for an arbitrary amount of time. If it doesn't
+					// finish before the timeout, the requester is answered anyway and the result
+					// of the call is dropped once it is available.
+					ctx.spawn_blocking("runtime-api-request", call).await?;
+					ctx.spawn("runtime-api-response", respond).await?;
+				}
 			}
 		}
 	}
 }
 
-fn make_runtime_api_request<Client>(
+/// Validation code fetched by `HistoricalValidationCode` requests.
+///
+/// Responses are keyed by the relay parent they were requested at, since relay parents on
+/// different forks may see different code for the same para and block number. The code itself
+/// is stored by para and code hash, so that it is only kept once no matter for how many requests
+/// it was returned. Both are bounded, dropping the least recently used entries.
+struct ValidationCodeCache {
+	code_hashes: LruCache<(Hash, ParaId, BlockNumber), Hash>,
+	code: LruCache<(ParaId, Hash), ValidationCode>,
+}
+
+impl ValidationCodeCache {
+	fn new(code_hashes_capacity: usize, code_capacity: usize) -> Self {
+		ValidationCodeCache {
+			code_hashes: LruCache::new(code_hashes_capacity),
+			code: LruCache::new(code_capacity),
+		}
+	}
+
+	fn get(
+		&mut self,
+		relay_parent: Hash,
+		para: ParaId,
+		context_height: BlockNumber,
+	) -> Option<ValidationCode> {
+		let code_hash = *self.code_hashes.get(&(relay_parent, para, context_height))?;
+		self.code.get(&(para, code_hash)).cloned()
+	}
+
+	fn insert(
+		&mut self,
+		relay_parent: Hash,
+		para: ParaId,
+		context_height: BlockNumber,
+		code: ValidationCode,
+	) {
+		let code_hash = validation_code_hash(&code);
+		self.code_hashes.put((relay_parent, para, context_height), code_hash);
+		if self.code.get(&(para, code_hash)).is_none() {
+			self.code.put((para, code_hash), code);
+		}
+	}
+}
+
+/// Query the `ParachainHost` API version at a new leaf, if the leaf starts a session which we
+/// haven't seen yet.
+fn update_api_version<Client>(
 	client: &Client,
-	metrics: &Metrics,
-	relay_parent: Hash,
-	request: Request,
+	leaf: Hash,
+	api_version: &mut Option<(SessionIndex, u32)>,
 ) where
 	Client: ProvideRuntimeApi<Block>,
 	Client::Api: ParachainHost<Block>,
 {
+	let api = client.runtime_api();
+	let at = BlockId::Hash(leaf);
+
+	let session = match api.session_index_for_child(&at) {
+		Ok(session) => session,
+		Err(e) => {
+			log::debug!(target: LOG_TARGET, "Failed to fetch the session index at {}: {:?}", leaf, e);
+			return;
+		}
+	};
+
+	if api_version.map_or(false, |(known, _)| known >= session) {
+		return;
+	}
+
+	let version = Cell::new(None);
+	match api.has_api_with::<dyn ParachainHost<Block>, _>(&at, |v| { version.set(Some(v)); true }) {
+		Ok(_) => if let Some(version) = version.get() {
+			log::debug!(target: LOG_TARGET, "ParachainHost API version {} in session {}", version, session);
+			*api_version = Some((session, version));
+		},
+		Err(e) => {
+			log::debug!(target: LOG_TARGET, "Failed to fetch the API version at {}: {:?}", leaf, e);
+		}
+	}
+}
+
+/// The `ParachainHost` API version the runtime needs to have to serve the request.
+fn required_api_version(request: &Request) -> u32 {
+	match request {
+		Request::ActiveValidatorIndices(_) |
+		Request::ValidationCodeByHash(..) |
+		Request::FetchOnChainVotes(_) => 2,
+		Request::AvailabilityChunkShuffleSeed(_) => 3,
+		Request::HistoricalValidationCode(..) => 4,
+		Request::SessionExecutorParams(..) => 6,
+		Request::ClaimQueue(_) => 7,
+		Request::FullValidationDataAt(..) |
+		Request::AllowedRelayParents(_) => 8,
+		_ => 1,
+	}
+}
+
+/// An error of a runtime API call.
+pub trait CallError: std::fmt::Debug {
+	/// Whether the call failed because the state of the block it was made at is unavailable.
+	fn is_unknown_block(&self) -> bool;
+}
+
+impl CallError for sp_blockchain::Error {
+	fn is_unknown_block(&self) -> bool {
+		matches!(self, sp_blockchain::Error::UnknownBlock(_))
+	}
+}
+
+#[cfg(test)]
+impl CallError for String {
+	fn is_unknown_block(&self) -> bool {
+		false
+	}
+}
+
+/// Turn the error of a failed runtime API call into the error the requester is answered with.
+fn classify_error(
+	runtime_api_name: &'static str,
+	relay_parent: Hash,
+	e: impl CallError,
+) -> RuntimeApiError {
+	let error = if e.is_unknown_block() {
+		RuntimeApiError::UnknownBlock { runtime_api_name }
+	} else {
+		RuntimeApiError::Execution { runtime_api_name, source: format!("{:?}", e) }
+	};
+
+	log::debug!(target: LOG_TARGET, "Runtime API call at {} failed: {}", relay_parent, error);
+
+	error
+}
+
+/// Perform a call on the primary client and, if it fails for lack of the state of the block, on
+/// the fallback client, if any.
+fn call_with_fallback<Client, T, E: CallError>(
+	client: &Client,
+	fallback_client: Option<&Client>,
+	api_name: &'static str,
+	call: impl Fn(&Client) -> Result<T, E>,
+) -> Result<T, E> {
+	match (call(client), fallback_client) {
+		(Err(e), Some(fallback_client)) if e.is_unknown_block() => {
+			log::debug!(
+				target: LOG_TARGET,
+				"Runtime API call {} failed, retrying on the fallback client: {:?}",
+				api_name,
+				e,
+			);
+
+			call(fallback_client)
+		}
+		(res, _) => res,
+	}
+}
+
+/// Returns a task performing the runtime API call and a task answering the requester, either
+/// with the result of the call or with a timeout error.
+///
+/// If the runtime's `api_version` is known and too old to serve the request, no call is made
+/// and the requester is answered with `RuntimeApiError::NotSupported`.
+///
+/// Responses which reach the requester are noted in the `audit_log`, if any.
+fn make_runtime_api_request<Client>(
+	client: Arc<Client>,
+	fallback_client: Option<Arc<Client>>,
+	metrics: Metrics,
+	request_timeout: Duration,
+	audit_log: Option<AuditLog>,
+	api_version: Option<u32>,
+	relay_parent: Hash,
+	request: Request,
+) -> (RequestTask, RequestTask) where
+	Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	Client::Api: ParachainHost<Block> + BabeApi<Block>,
+	<Client::Api as ApiErrorExt>::Error: CallError,
+{
+	let supported = api_version.map_or(true, |v| v >= required_api_version(&request));
+
 	macro_rules! query {
-		($api_name:ident ($($param:expr),*), $sender:expr) => {{
+		($api_name:ident ($($param:expr),*), $sender:expr) => {
+			query!(
+				@with stringify!($api_name), [$($param),*], $sender,
+				|api, at| api.$api_name(at, $($param),*)
+			)
+		};
+		// query the runtime with an arbitrary combination of runtime API calls.
+		(@with $api_name:expr, [$($param:expr),*], $sender:expr, |$api:ident, $at:ident| $call:expr) => {{
 			let sender = $sender;
-			let api = client.runtime_api();
-			let res = api.$api_name(&BlockId::Hash(relay_parent), $($param),*)
-				.map_err(|e| RuntimeApiError::from(format!("{:?}", e)));
-			metrics.on_request(res.is_ok());
-			let _ = sender.send(res);
+
+			if !supported {
+				let respond = async move {
+					metrics.on_request_not_supported($api_name);
+					let _ = sender.send(Err(RuntimeApiError::NotSupported {
+						runtime_api_name: $api_name,
+					}));
+				};
+
+				return (future::ready(()).boxed() as RequestTask, respond.boxed() as RequestTask);
+			}
+
+			let (result_tx, result_rx) = oneshot::channel();
+			let call_metrics = metrics.clone();
+			let audited = audit_log.is_some();
+
+			let call = async move {
+				let res = call_with_fallback(
+					&*client,
+					fallback_client.as_deref(),
+					$api_name,
+					|client| {
+						let $api = client.runtime_api();
+						let $at = &BlockId::Hash(relay_parent);
+						$call
+					},
+				).map_err(|e| classify_error($api_name, relay_parent, e));
+				call_metrics.on_request($api_name, res.is_ok());
+				if let Err(ref e) = res {
+					call_metrics.on_error($api_name, e);
+				}
+
+				let audit_entry = match (audited, &res) {
+					(true, Ok(response)) => {
+						let mut request_params = Vec::new();
+						$( $param.encode_to(&mut request_params); )*
+						Some(AuditEntry::new(relay_parent, $api_name, &request_params, response))
+					}
+					_ => None,
+				};
+				let _ = result_tx.send((res, audit_entry));
+			};
+
+			let respond = async move {
+				let res = match future::select(result_rx, Delay::new(request_timeout)).await {
+					Either::Left((Ok((res, audit_entry)), _)) => {
+						if let (Some(audit_log), Some(audit_entry)) = (audit_log, audit_entry) {
+							audit_log.note(audit_entry);
+						}
+						res
+					}
+					Either::Left((Err(oneshot::Canceled), _)) => return,
+					Either::Right(_) => {
+						metrics.on_request_timeout($api_name);
+						Err(RuntimeApiError::Timeout)
+					}
+				};
+				let _ = sender.send(res);
+			};
+
+			(call.boxed() as RequestTask, respond.boxed() as RequestTask)
 		}}
 	}
 
 	match request {
 		Request::Validators(sender) => query!(validators(), sender),
+		Request::ActiveValidatorIndices(sender) => query!(active_validator_indices(), sender),
 		Request::ValidatorGroups(sender) => query!(validator_groups(), sender),
+		Request::GroupForCore(core_index, sender) =>
+			query!(@with "group_for_core", [core_index], sender, |api, at| {
+				api.validator_groups(at).and_then(|(validator_groups, group_rotation_info)| {
+					api.availability_cores(at).map(|cores| backing_group_for_core(
+						&validator_groups,
+						&group_rotation_info,
+						core_index,
+						cores.len(),
+					).map(|(group_index, validators)| (group_index, validators.to_vec())))
+				})
+			}),
 		Request::AvailabilityCores(sender) => query!(availability_cores(), sender),
 		Request::PersistedValidationData(para, assumption, sender) =>
 			query!(persisted_validation_data(para, assumption), sender),
 		Request::FullValidationData(para, assumption, sender) =>
 			query!(full_validation_data(para, assumption), sender),
+		Request::FullValidationDataAt(para, relay_parent_number, assumption, sender) =>
+			query!(full_validation_data_at(para, relay_parent_number, assumption), sender),
+		Request::AllowedRelayParents(sender) => query!(allowed_relay_parents(), sender),
 		Request::SessionIndexForChild(sender) => query!(session_index_for_child(), sender),
+		Request::AvailabilityChunkShuffleSeed(sender) =>
+			query!(availability_chunk_shuffle_seed(), sender),
 		Request::ValidationCode(para, assumption, sender) =>
 			query!(validation_code(para, assumption), sender),
+		Request::ValidationCodeByHash(hash, sender) =>
+			query!(validation_code_by_hash(hash), sender),
+		Request::HistoricalValidationCode(para, context_height, sender) =>
+			query!(historical_validation_code(para, context_height), sender),
+		Request::SessionExecutorParams(session_index, sender) =>
+			query!(session_executor_params(session_index), sender),
+		Request::ClaimQueue(sender) => query!(claim_queue(), sender),
 		Request::CandidatePendingAvailability(para, sender) =>
 			query!(candidate_pending_availability(para), sender),
 		Request::CandidateEvents(sender) => query!(candidate_events(), sender),
+		Request::FetchOnChainVotes(sender) => query!(on_chain_votes(), sender),
+		Request::BabeConfiguration(sender) => query!(configuration(), sender),
+		Request::CurrentBabeEpochStart(sender) => query!(current_epoch_start(), sender),
 	}
 }
 
 #[derive(Clone)]
 struct MetricsInner {
-	chain_api_requests: prometheus::CounterVec<prometheus::U64>,
+	runtime_api_requests: prometheus::CounterVec<prometheus::U64>,
+	runtime_api_errors: prometheus::CounterVec<prometheus::U64>,
 }
 
 /// Runtime API metrics.
@@ -133,13 +647,34 @@ struct MetricsInner {
 pub struct Metrics(Option<MetricsInner>);
 
 impl Metrics {
-	fn on_request(&self, succeeded: bool) {
+	fn on_request(&self, request_type: &'static str, succeeded: bool) {
 		if let Some(metrics) = &self.0 {
-			if succeeded {
-				metrics.chain_api_requests.with_label_values(&["succeeded"]).inc();
-			} else {
-				metrics.chain_api_requests.with_label_values(&["failed"]).inc();
-			}
+			let outcome = if succeeded { "succeeded" } else { "failed" };
+			metrics.runtime_api_requests.with_label_values(&[request_type, outcome]).inc();
+		}
+	}
+
+	fn on_error(&self, request_type: &'static str, error: &RuntimeApiError) {
+		if let Some(metrics) = &self.0 {
+			let class = match error {
+				RuntimeApiError::Execution { .. } => "execution",
+				RuntimeApiError::UnknownBlock { .. } => "unknown_block",
+				RuntimeApiError::Timeout => "timeout",
+				RuntimeApiError::NotSupported { .. } => "not_supported",
+			};
+			metrics.runtime_api_errors.with_label_values(&[request_type, class]).inc();
+		}
+	}
+
+	fn on_request_timeout(&self, request_type: &'static str) {
+		if let Some(metrics) = &self.0 {
+			metrics.runtime_api_requests.with_label_values(&[request_type, "timed_out"]).inc();
+		}
+	}
+
+	fn on_request_not_supported(&self, request_type: &'static str) {
+		if let Some(metrics) = &self.0 {
+			metrics.runtime_api_requests.with_label_values(&[request_type, "not_supported"]).inc();
 		}
 	}
 }
@@ -147,13 +682,23 @@ impl Metrics {
 impl metrics::Metrics for Metrics {
 	fn try_register(registry: &prometheus::Registry) -> Result<Self, prometheus::PrometheusError> {
 		let metrics = MetricsInner {
-			chain_api_requests: prometheus::register(
+			runtime_api_requests: prometheus::register(
 				prometheus::CounterVec::new(
 					prometheus::Opts::new(
 						"parachain_runtime_api_requests_total",
-						"Number of Runtime API requests served.",
+						"Number of Runtime API requests served, by request type and outcome.",
+					),
+					&["request_type", "outcome"],
+				)?,
+				registry,
+			)?,
+			runtime_api_errors: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_runtime_api_errors_total",
+						"Number of failed Runtime API calls, by request type and class of error.",
 					),
-					&["succeeded", "failed"],
+					&["request_type", "class"],
 				)?,
 				registry,
 			)?,
@@ -169,24 +714,41 @@ mod tests {
 	use polkadot_primitives::v1::{
 		ValidatorId, ValidatorIndex, GroupRotationInfo, CoreState, PersistedValidationData,
 		Id as ParaId, OccupiedCoreAssumption, ValidationData, SessionIndex, ValidationCode,
-		CommittedCandidateReceipt, CandidateEvent,
+		CommittedCandidateReceipt, CandidateEvent, ScrapedOnChainVotes, Hash, CoreIndex, GroupIndex,
+		Header, ExecutorParams,
 	};
 	use polkadot_node_subsystem_test_helpers as test_helpers;
+	use sp_consensus_babe::{
+		AllowedSlots, AuthorityId, BabeGenesisConfiguration, EquivocationProof,
+		OpaqueKeyOwnershipProof, SlotNumber,
+	};
 	use sp_core::testing::TaskExecutor;
 
-	use std::collections::HashMap;
+	use std::collections::{BTreeMap, HashMap};
 	use futures::channel::oneshot;
 
 	#[derive(Default, Clone)]
 	struct MockRuntimeApi {
 		validators: Vec<ValidatorId>,
+		active_validator_indices: Vec<ValidatorIndex>,
 		validator_groups: Vec<Vec<ValidatorIndex>>,
 		availability_cores: Vec<CoreState>,
 		validation_data: HashMap<ParaId, ValidationData>,
+		validation_data_at: HashMap<(ParaId, BlockNumber), ValidationData>,
+		allowed_relay_parents: Vec<(Hash, BlockNumber)>,
 		session_index_for_child: SessionIndex,
+		availability_chunk_shuffle_seed: [u8; 32],
 		validation_code: HashMap<ParaId, ValidationCode>,
+		validation_code_by_hash: HashMap<Hash, ValidationCode>,
+		historical_validation_code: HashMap<(ParaId, BlockNumber), ValidationCode>,
+		executor_params: ExecutorParams,
+		claim_queue: BTreeMap<CoreIndex, Vec<ParaId>>,
 		candidate_pending_availability: HashMap<ParaId, CommittedCandidateReceipt>,
 		candidate_events: Vec<CandidateEvent>,
+		on_chain_votes: Option<ScrapedOnChainVotes>,
+		babe_randomness: [u8; 32],
+		babe_epoch_start: SlotNumber,
+		validators_delay: Option<Duration>,
 	}
 
 	impl ProvideRuntimeApi<Block> for MockRuntimeApi {
@@ -202,9 +764,17 @@ mod tests {
 			type Error = String;
 
 			fn validators(&self) -> Vec<ValidatorId> {
+				if let Some(delay) = self.validators_delay {
+					std::thread::sleep(delay);
+				}
+
 				self.validators.clone()
 			}
 
+			fn active_validator_indices(&self) -> Vec<ValidatorIndex> {
+				self.active_validator_indices.clone()
+			}
+
 			fn validator_groups(&self) -> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo) {
 				(
 					self.validator_groups.clone(),
@@ -236,10 +806,27 @@ mod tests {
 				self.validation_data.get(&para).map(|l| l.clone())
 			}
 
+			fn full_validation_data_at(
+				&self,
+				para: ParaId,
+				relay_parent_number: BlockNumber,
+				_assumption: OccupiedCoreAssumption,
+			) -> Option<ValidationData> {
+				self.validation_data_at.get(&(para, relay_parent_number)).map(|l| l.clone())
+			}
+
+			fn allowed_relay_parents(&self) -> Vec<(Hash, BlockNumber)> {
+				self.allowed_relay_parents.clone()
+			}
+
 			fn session_index_for_child(&self) -> SessionIndex {
 				self.session_index_for_child.clone()
 			}
 
+			fn availability_chunk_shuffle_seed(&self) -> [u8; 32] {
+				self.availability_chunk_shuffle_seed
+			}
+
 			fn validation_code(
 				&self,
 				para: ParaId,
@@ -248,6 +835,30 @@ mod tests {
 				self.validation_code.get(&para).map(|c| c.clone())
 			}
 
+			fn validation_code_by_hash(&self, hash: Hash) -> Option<ValidationCode> {
+				self.validation_code_by_hash.get(&hash).map(|c| c.clone())
+			}
+
+			fn historical_validation_code(
+				&self,
+				para: ParaId,
+				context_height: BlockNumber,
+			) -> Option<ValidationCode> {
+				self.historical_validation_code.get(&(para, context_height)).map(|c| c.clone())
+			}
+
+			fn session_executor_params(&self, session_index: SessionIndex) -> Option<ExecutorParams> {
+				if session_index == self.session_index_for_child {
+					Some(self.executor_params.clone())
+				} else {
+					None
+				}
+			}
+
+			fn claim_queue(&self) -> BTreeMap<CoreIndex, Vec<ParaId>> {
+				self.claim_queue.clone()
+			}
+
 			fn candidate_pending_availability(
 				&self,
 				para: ParaId,
@@ -258,9 +869,69 @@ mod tests {
 			fn candidate_events(&self) -> Vec<CandidateEvent> {
 				self.candidate_events.clone()
 			}
+
+			fn on_chain_votes(&self) -> Option<ScrapedOnChainVotes> {
+				self.on_chain_votes.clone()
+			}
+		}
+
+		impl BabeApi<Block> for MockRuntimeApi {
+			type Error = String;
+
+			fn configuration(&self) -> BabeGenesisConfiguration {
+				BabeGenesisConfiguration {
+					slot_duration: 6000,
+					epoch_length: 600,
+					c: (1, 4),
+					genesis_authorities: Vec::new(),
+					randomness: self.babe_randomness,
+					allowed_slots: AllowedSlots::PrimaryAndSecondaryPlainSlots,
+				}
+			}
+
+			fn current_epoch_start(&self) -> SlotNumber {
+				self.babe_epoch_start
+			}
+
+			fn generate_key_ownership_proof(
+				&self,
+				_slot_number: SlotNumber,
+				_authority_id: AuthorityId,
+			) -> Option<OpaqueKeyOwnershipProof> {
+				None
+			}
+
+			fn submit_report_equivocation_unsigned_extrinsic(
+				&self,
+				_equivocation_proof: EquivocationProof<Header>,
+				_key_owner_proof: OpaqueKeyOwnershipProof,
+			) -> Option<()> {
+				None
+			}
 		}
 	}
 
+	#[test]
+	fn metrics_are_labeled_by_request_type_and_outcome() {
+		use metrics::Metrics as _;
+
+		let registry = prometheus::Registry::new();
+		let metrics = Metrics::try_register(&registry).unwrap();
+
+		metrics.on_request("validators", true);
+		metrics.on_request("validators", true);
+		metrics.on_request("candidate_events", false);
+
+		let inner = metrics.0.as_ref().unwrap();
+		let count = |request_type, outcome| inner.runtime_api_requests
+			.with_label_values(&[request_type, outcome])
+			.get();
+
+		assert_eq!(count("validators", "succeeded"), 2);
+		assert_eq!(count("validators", "failed"), 0);
+		assert_eq!(count("candidate_events", "failed"), 1);
+	}
+
 	#[test]
 	fn requests_validators() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
@@ -285,21 +956,23 @@ mod tests {
 	}
 
 	#[test]
-	fn requests_validator_groups() {
+	fn requests_active_validator_indices() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
-		let runtime_api = MockRuntimeApi::default();
+		let mut runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
 
+		runtime_api.active_validator_indices = vec![0, 2, 3];
+
 		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
 
 			ctx_handle.send(FromOverseer::Communication {
-				msg: RuntimeApiMessage::Request(relay_parent, Request::ValidatorGroups(tx))
+				msg: RuntimeApiMessage::Request(relay_parent, Request::ActiveValidatorIndices(tx))
 			}).await;
 
-			assert_eq!(rx.await.unwrap().unwrap().0, runtime_api.validator_groups);
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.active_validator_indices);
 
 			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
 		};
@@ -308,91 +981,319 @@ mod tests {
 	}
 
 	#[test]
-	fn requests_availability_cores() {
+	fn requests_unsupported_by_runtime_api_version() {
+		let runtime_api = Arc::new(MockRuntimeApi::default());
+		let relay_parent = [1; 32].into();
+
+		let (tx, rx) = oneshot::channel();
+		let (call, respond) = make_runtime_api_request(
+			runtime_api.clone(),
+			None,
+			Metrics(None),
+			DEFAULT_REQUEST_TIMEOUT,
+			None,
+			Some(1),
+			relay_parent,
+			Request::ActiveValidatorIndices(tx),
+		);
+
+		futures::executor::block_on(future::join(call, respond));
+		match rx.try_recv() {
+			Ok(Some(Err(RuntimeApiError::NotSupported { runtime_api_name }))) => {
+				assert_eq!(runtime_api_name, "active_validator_indices");
+			}
+			_ => panic!("expected a `NotSupported` error"),
+		}
+
+		let (tx, rx) = oneshot::channel();
+		let (call, respond) = make_runtime_api_request(
+			runtime_api.clone(),
+			None,
+			Metrics(None),
+			DEFAULT_REQUEST_TIMEOUT,
+			None,
+			Some(1),
+			relay_parent,
+			Request::Validators(tx),
+		);
+
+		futures::executor::block_on(future::join(call, respond));
+		assert_eq!(rx.try_recv().unwrap().unwrap().unwrap(), runtime_api.validators);
+	}
+
+	#[test]
+	fn audit_log_notes_delivered_responses() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
-		let runtime_api = MockRuntimeApi::default();
+		let mut runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
+		let para_a = 5.into();
+		let para_b = 6.into();
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		runtime_api.validation_code.insert(para_a, ValidationCode(vec![1, 2, 3]));
+
+		let audit_log = AuditLog::new(2);
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None))
+			.with_audit_log(audit_log.clone());
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
-			let (tx, rx) = oneshot::channel();
-
-			ctx_handle.send(FromOverseer::Communication {
-				msg: RuntimeApiMessage::Request(relay_parent, Request::AvailabilityCores(tx))
-			}).await;
-
-			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.availability_cores);
+			for &para in &[para_a, para_b, para_a] {
+				let (tx, rx) = oneshot::channel();
+				ctx_handle.send(FromOverseer::Communication {
+					msg: RuntimeApiMessage::Request(
+						relay_parent,
+						Request::ValidationCode(para, OccupiedCoreAssumption::Included, tx),
+					),
+				}).await;
+				rx.await.unwrap().unwrap();
+			}
 
 			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
 		};
 
 		futures::executor::block_on(future::join(subsystem_task, test_task));
+
+		let digest_for = |para: ParaId| {
+			let code = runtime_api.validation_code.get(&para).cloned();
+			BlakeTwo256::hash_of(&(
+				relay_parent,
+				"validation_code",
+				&(para, OccupiedCoreAssumption::Included).encode()[..],
+				&code,
+			))
+		};
+
+		// The oldest entry was dropped to make space for the last one.
+		let entries = audit_log.entries();
+		assert_eq!(entries.len(), 2);
+		assert!(entries.iter().all(|e| e.relay_parent == relay_parent));
+		assert!(entries.iter().all(|e| e.runtime_api_name == "validation_code"));
+		assert_eq!(entries[0].digest, digest_for(para_b));
+		assert_eq!(entries[1].digest, digest_for(para_a));
+		assert_ne!(entries[0].digest, entries[1].digest);
+
+		let mut exported = Vec::new();
+		audit_log.export(&mut exported).unwrap();
+		assert_eq!(
+			String::from_utf8(exported).unwrap(),
+			format!(
+				"{:?} validation_code {:?}\n{:?} validation_code {:?}\n",
+				relay_parent, digest_for(para_b), relay_parent, digest_for(para_a),
+			),
+		);
 	}
 
 	#[test]
-	fn requests_persisted_validation_data() {
+	fn audit_log_notes_cached_responses() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
 		let mut runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
-		let para_a = 5.into();
-		let para_b = 6.into();
+		let para = 5.into();
 
-		runtime_api.validation_data.insert(para_a, Default::default());
+		runtime_api.historical_validation_code.insert((para, 10), ValidationCode(vec![1, 2, 3]));
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let audit_log = AuditLog::new(10);
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None))
+			.with_audit_log(audit_log.clone());
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
-			let (tx, rx) = oneshot::channel();
-
-			ctx_handle.send(FromOverseer::Communication {
-				msg: RuntimeApiMessage::Request(
-					relay_parent,
-					Request::PersistedValidationData(para_a, OccupiedCoreAssumption::Included, tx)
-				),
-			}).await;
-
-			assert_eq!(rx.await.unwrap().unwrap(), Some(Default::default()));
-
-			let (tx, rx) = oneshot::channel();
-			ctx_handle.send(FromOverseer::Communication {
-				msg: RuntimeApiMessage::Request(
-					relay_parent,
-					Request::PersistedValidationData(para_b, OccupiedCoreAssumption::Included, tx)
-				),
-			}).await;
-
-			assert_eq!(rx.await.unwrap().unwrap(), None);
+			// the second request is answered from the cache.
+			for _ in 0..2 {
+				let (tx, rx) = oneshot::channel();
+				ctx_handle.send(FromOverseer::Communication {
+					msg: RuntimeApiMessage::Request(
+						relay_parent,
+						Request::HistoricalValidationCode(para, 10, tx),
+					),
+				}).await;
+				rx.await.unwrap().unwrap();
+			}
 
 			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
 		};
 
 		futures::executor::block_on(future::join(subsystem_task, test_task));
+
+		let entries = audit_log.entries();
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0], entries[1]);
+		assert_eq!(entries[0].runtime_api_name, "historical_validation_code");
+		assert_eq!(entries[0].digest, BlakeTwo256::hash_of(&(
+			relay_parent,
+			"historical_validation_code",
+			&(para, 10 as BlockNumber).encode()[..],
+			&Some(ValidationCode(vec![1, 2, 3])),
+		)));
 	}
 
 	#[test]
-	fn requests_full_validation_data() {
+	fn requests_time_out() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
 		let mut runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
-		let para_a = 5.into();
-		let para_b = 6.into();
 
-		runtime_api.validation_data.insert(para_a, Default::default());
+		runtime_api.validators_delay = Some(Duration::from_secs(1));
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None))
+			.with_request_timeout(Duration::from_millis(50));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
 
 			ctx_handle.send(FromOverseer::Communication {
-				msg: RuntimeApiMessage::Request(
-					relay_parent,
-					Request::FullValidationData(para_a, OccupiedCoreAssumption::Included, tx)
-				),
+				msg: RuntimeApiMessage::Request(relay_parent, Request::Validators(tx))
 			}).await;
 
-			assert_eq!(rx.await.unwrap().unwrap(), Some(Default::default()));
+			assert!(matches!(rx.await.unwrap(), Err(RuntimeApiError::Timeout)));
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_validator_groups() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::ValidatorGroups(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap().0, runtime_api.validator_groups);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_group_for_core() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.validator_groups = vec![vec![0, 1], vec![2]];
+		runtime_api.availability_cores = vec![CoreState::Free, CoreState::Free];
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::GroupForCore(CoreIndex(1), tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some((GroupIndex(1), vec![2])));
+
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::GroupForCore(CoreIndex(2), tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_availability_cores() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::AvailabilityCores(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.availability_cores);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_persisted_validation_data() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let para_a = 5.into();
+		let para_b = 6.into();
+
+		runtime_api.validation_data.insert(para_a, Default::default());
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::PersistedValidationData(para_a, OccupiedCoreAssumption::Included, tx)
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some(Default::default()));
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::PersistedValidationData(para_b, OccupiedCoreAssumption::Included, tx)
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_full_validation_data() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let para_a = 5.into();
+		let para_b = 6.into();
+
+		runtime_api.validation_data.insert(para_a, Default::default());
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::FullValidationData(para_a, OccupiedCoreAssumption::Included, tx)
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some(Default::default()));
 
 			let (tx, rx) = oneshot::channel();
 			ctx_handle.send(FromOverseer::Communication {
@@ -410,6 +1311,69 @@ mod tests {
 		futures::executor::block_on(future::join(subsystem_task, test_task));
 	}
 
+	#[test]
+	fn requests_full_validation_data_at() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let para_a = 5.into();
+
+		runtime_api.validation_data_at.insert((para_a, 9), Default::default());
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::FullValidationDataAt(para_a, 9, OccupiedCoreAssumption::Included, tx)
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some(Default::default()));
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::FullValidationDataAt(para_a, 8, OccupiedCoreAssumption::Included, tx)
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_allowed_relay_parents() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.allowed_relay_parents = vec![([1; 32].into(), 10), ([2; 32].into(), 9)];
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::AllowedRelayParents(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.allowed_relay_parents);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
 	#[test]
 	fn requests_session_index_for_child() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
@@ -433,6 +1397,120 @@ mod tests {
 		futures::executor::block_on(future::join(subsystem_task, test_task));
 	}
 
+	#[test]
+	fn requests_availability_chunk_shuffle_seed() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.availability_chunk_shuffle_seed = [7; 32];
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::AvailabilityChunkShuffleSeed(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.availability_chunk_shuffle_seed);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_session_executor_params() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.session_index_for_child = 2;
+		runtime_api.executor_params.heap_pages = 2048;
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::SessionExecutorParams(2, tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some(runtime_api.executor_params.clone()));
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::SessionExecutorParams(1, tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_claim_queue() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.claim_queue.insert(CoreIndex(0), vec![ParaId::from(1), ParaId::from(1)]);
+		runtime_api.claim_queue.insert(CoreIndex(2), vec![ParaId::from(3)]);
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::ClaimQueue(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.claim_queue);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_babe_epoch_data() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.babe_randomness = [3; 32];
+		runtime_api.babe_epoch_start = 1200;
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::BabeConfiguration(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap().randomness, runtime_api.babe_randomness);
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::CurrentBabeEpochStart(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.babe_epoch_start);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
 	#[test]
 	fn requests_validation_code() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
@@ -473,6 +1551,99 @@ mod tests {
 		futures::executor::block_on(future::join(subsystem_task, test_task));
 	}
 
+	#[test]
+	fn requests_historical_validation_code() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let para_a = 5.into();
+		let para_b = 6.into();
+
+		runtime_api.historical_validation_code.insert((para_a, 10), ValidationCode(vec![1, 2, 3]));
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			for _ in 0..2 {
+				let (tx, rx) = oneshot::channel();
+
+				ctx_handle.send(FromOverseer::Communication {
+					msg: RuntimeApiMessage::Request(
+						relay_parent,
+						Request::HistoricalValidationCode(para_a, 10, tx),
+					),
+				}).await;
+
+				assert_eq!(rx.await.unwrap().unwrap(), Some(ValidationCode(vec![1, 2, 3])));
+			}
+
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::HistoricalValidationCode(para_b, 10, tx),
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn validation_code_cache_stores_code_once() {
+		let relay_parent = [1; 32].into();
+		let para = 5.into();
+		let code = ValidationCode(vec![1, 2, 3]);
+		let mut cache = ValidationCodeCache::new(10, 10);
+
+		cache.insert(relay_parent, para, 10, code.clone());
+		cache.insert(relay_parent, para, 11, code.clone());
+
+		assert_eq!(cache.get(relay_parent, para, 10), Some(code.clone()));
+		assert_eq!(cache.get(relay_parent, para, 11), Some(code));
+		assert_eq!(cache.get(relay_parent, para, 12), None);
+		assert_eq!(cache.code.len(), 1);
+	}
+
+	#[test]
+	fn validation_code_cache_is_keyed_by_relay_parent() {
+		let relay_parent_a = [1; 32].into();
+		let relay_parent_b = [2; 32].into();
+		let para = 5.into();
+		let mut cache = ValidationCodeCache::new(10, 10);
+
+		cache.insert(relay_parent_a, para, 10, ValidationCode(vec![1, 2, 3]));
+		cache.insert(relay_parent_b, para, 10, ValidationCode(vec![4, 5, 6]));
+
+		assert_eq!(cache.get(relay_parent_a, para, 10), Some(ValidationCode(vec![1, 2, 3])));
+		assert_eq!(cache.get(relay_parent_b, para, 10), Some(ValidationCode(vec![4, 5, 6])));
+		assert_eq!(cache.get([3; 32].into(), para, 10), None);
+	}
+
+	#[test]
+	fn validation_code_cache_drops_least_recently_used() {
+		let relay_parent = [1; 32].into();
+		let para = 5.into();
+		let mut cache = ValidationCodeCache::new(2, 2);
+
+		cache.insert(relay_parent, para, 10, ValidationCode(vec![1]));
+		cache.insert(relay_parent, para, 11, ValidationCode(vec![2]));
+		// using the first entry makes the second one the least recently used.
+		assert!(cache.get(relay_parent, para, 10).is_some());
+		cache.insert(relay_parent, para, 12, ValidationCode(vec![3]));
+
+		assert_eq!(cache.get(relay_parent, para, 10), Some(ValidationCode(vec![1])));
+		assert_eq!(cache.get(relay_parent, para, 11), None);
+		assert_eq!(cache.get(relay_parent, para, 12), Some(ValidationCode(vec![3])));
+		assert_eq!(cache.code_hashes.len(), 2);
+		assert_eq!(cache.code.len(), 2);
+	}
+
 	#[test]
 	fn requests_candidate_pending_availability() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
@@ -536,4 +1707,135 @@ mod tests {
 
 		futures::executor::block_on(future::join(subsystem_task, test_task));
 	}
+
+	#[test]
+	fn requests_validation_code_by_hash() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let code = ValidationCode(vec![1, 2, 3]);
+		let known_hash = [2; 32].into();
+		let unknown_hash = [3; 32].into();
+
+		runtime_api.validation_code_by_hash.insert(known_hash, code.clone());
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::ValidationCodeByHash(known_hash, tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some(code));
+
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::ValidationCodeByHash(unknown_hash, tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_on_chain_votes() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.on_chain_votes = Some(ScrapedOnChainVotes {
+			session: 1,
+			backing_validators_per_candidate: Vec::new(),
+		});
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::FetchOnChainVotes(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.on_chain_votes);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[derive(Debug, PartialEq)]
+	enum TestCallError {
+		StatePruned,
+		Execution,
+	}
+
+	impl CallError for TestCallError {
+		fn is_unknown_block(&self) -> bool {
+			*self == TestCallError::StatePruned
+		}
+	}
+
+	#[test]
+	fn failed_calls_are_retried_on_fallback_client() {
+		// clients are represented by whether they have the state.
+		let call = |has_state: &bool| if *has_state { Ok(1) } else { Err(TestCallError::StatePruned) };
+
+		assert_eq!(call_with_fallback(&false, Some(&true), "test", call), Ok(1));
+		assert_eq!(
+			call_with_fallback(&false, Some(&false), "test", call),
+			Err(TestCallError::StatePruned),
+		);
+		assert_eq!(call_with_fallback(&false, None, "test", call), Err(TestCallError::StatePruned));
+	}
+
+	#[test]
+	fn fallback_client_is_not_used_if_call_succeeds() {
+		let calls = Cell::new(0);
+		let call = |has_state: &bool| {
+			calls.set(calls.get() + 1);
+			if *has_state { Ok(1) } else { Err(TestCallError::StatePruned) }
+		};
+
+		assert_eq!(call_with_fallback(&true, Some(&true), "test", call), Ok(1));
+		assert_eq!(calls.get(), 1);
+	}
+
+	#[test]
+	fn fallback_client_is_not_used_if_call_fails_in_runtime() {
+		let calls = Cell::new(0);
+		let call = |has_state: &bool| {
+			calls.set(calls.get() + 1);
+			if *has_state { Ok(1) } else { Err(TestCallError::Execution) }
+		};
+
+		assert_eq!(
+			call_with_fallback(&false, Some(&true), "test", call),
+			Err(TestCallError::Execution),
+		);
+		assert_eq!(calls.get(), 1);
+	}
+
+	#[test]
+	fn call_errors_are_classified() {
+		let unknown_block = sp_blockchain::Error::UnknownBlock("pruned".into());
+		assert!(matches!(
+			classify_error("test", Hash::repeat_byte(1), unknown_block),
+			RuntimeApiError::UnknownBlock { runtime_api_name: "test" },
+		));
+
+		let execution = sp_blockchain::Error::Msg("runtime panicked".into());
+		assert!(matches!(
+			classify_error("test", Hash::repeat_byte(1), execution),
+			RuntimeApiError::Execution { runtime_api_name: "test", .. },
+		));
+	}
 }
@@ -0,0 +1,107 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Multi-node integration tests for Polkadot.
+//!
+//! The helpers here spawn a small network of in-process test nodes, all connected to the first
+//! one, and wait for the network to make progress. The tests themselves live in `tests/` and are
+//! `#[ignore]`d, as they take a while to run; run them with
+//! `cargo test -p polkadot-integration-tests -- --ignored`.
+
+#![warn(missing_docs)]
+
+use futures::{future, prelude::*};
+use polkadot_primitives::v0::{Block, BlockNumber};
+use polkadot_test_service::{run_validator_node, PolkadotTestNode};
+use sc_client_api::{BlockchainEvents, backend::AbstractClient};
+use service::{TaskExecutor, TaskManager, TFullBackend};
+use sp_blockchain::HeaderBackend;
+use sp_keyring::Sr25519Keyring;
+
+/// The validators of the test chain spec.
+pub const VALIDATORS: [Sr25519Keyring; 3] = [
+	Sr25519Keyring::Alice,
+	Sr25519Keyring::Bob,
+	Sr25519Keyring::Charlie,
+];
+
+/// Spawn one test node per key. The first node acts as the boot node of all the others.
+pub fn spawn_network(
+	task_executor: TaskExecutor,
+	keys: &[Sr25519Keyring],
+) -> Vec<PolkadotTestNode<TaskManager, impl AbstractClient<Block, TFullBackend<Block>>>> {
+	let mut nodes = Vec::with_capacity(keys.len());
+	let mut boot_nodes = Vec::new();
+
+	for key in keys {
+		let node = run_validator_node(task_executor.clone(), *key, || {}, boot_nodes.clone());
+		if boot_nodes.is_empty() {
+			boot_nodes.push(node.addr.clone());
+		}
+
+		log::info!("Spawned test node {:?} at {}", key, node.addr.multiaddr);
+		nodes.push(node);
+	}
+
+	nodes
+}
+
+/// Drive `fut` to completion, panicking if any of the nodes' essential tasks fails first.
+pub async fn run_until<C, F: Future>(
+	nodes: &mut [PolkadotTestNode<TaskManager, C>],
+	fut: F,
+) -> F::Output {
+	let failed = future::select_all(nodes.iter_mut().map(|node| node.task_manager.future()));
+
+	match future::select(fut.boxed_local(), failed).await {
+		future::Either::Left((output, _)) => output,
+		future::Either::Right(((res, index, _), _)) =>
+			panic!("test node {} failed: {:?}", index, res),
+	}
+}
+
+/// Wait until the client's best block is at least `number`.
+pub async fn wait_for_best<C>(client: &C, number: BlockNumber)
+where
+	C: BlockchainEvents<Block> + HeaderBackend<Block>,
+{
+	let mut imports = client.import_notification_stream();
+	while client.info().best_number < number {
+		if imports.next().await.is_none() {
+			return;
+		}
+	}
+}
+
+/// Wait until the client's finalized block is at least `number`.
+pub async fn wait_for_finalized<C>(client: &C, number: BlockNumber)
+where
+	C: BlockchainEvents<Block> + HeaderBackend<Block>,
+{
+	let mut finality = client.finality_notification_stream();
+	while client.info().finalized_number < number {
+		if finality.next().await.is_none() {
+			return;
+		}
+	}
+}
+
+/// Cleanly shut down all the nodes.
+pub async fn shutdown<C>(nodes: Vec<PolkadotTestNode<TaskManager, C>>) {
+	for mut node in nodes {
+		node.task_manager.clean_shutdown().await;
+	}
+}
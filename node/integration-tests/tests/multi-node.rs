@@ -0,0 +1,53 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use futures::future;
+use polkadot_integration_tests::*;
+use service::TaskExecutor;
+use sp_keyring::Sr25519Keyring;
+
+#[substrate_test_utils::test]
+#[ignore]
+async fn validators_produce_and_finalize_blocks(task_executor: TaskExecutor) {
+	let mut nodes = spawn_network(task_executor, &VALIDATORS);
+
+	let clients: Vec<_> = nodes.iter().map(|node| node.client.clone()).collect();
+
+	let progress = future::join_all(clients.iter().map(|client| async move {
+		wait_for_best(&**client, 5).await;
+		wait_for_finalized(&**client, 3).await;
+	}));
+	run_until(&mut nodes, progress).await;
+
+	shutdown(nodes).await;
+}
+
+#[substrate_test_utils::test]
+#[ignore]
+async fn full_node_follows_validators(task_executor: TaskExecutor) {
+	let mut keys = VALIDATORS.to_vec();
+	keys.push(Sr25519Keyring::Dave);
+	let mut nodes = spawn_network(task_executor, &keys);
+
+	let dave = nodes.last().expect("spawned four nodes; qed").client.clone();
+	let progress = async {
+		wait_for_best(&*dave, 5).await;
+		wait_for_finalized(&*dave, 3).await;
+	};
+	run_until(&mut nodes, progress).await;
+
+	shutdown(nodes).await;
+}
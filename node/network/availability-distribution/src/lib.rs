@@ -21,6 +21,10 @@
 //! Gossip messages received from other peers are verified and gossiped to interested
 //! peers. Verified in this context means, the erasure chunks contained merkle proof
 //! is checked.
+//!
+//! Each validator stores the chunk it is responsible for, which is determined by the
+//! per-core shuffle of chunk indices seeded by the runtime, rather than the chunk with its
+//! own validator index.
 
 use codec::{Decode, Encode};
 use futures::{channel::oneshot, FutureExt};
@@ -33,15 +37,15 @@ use sp_core::{
 use sc_keystore as keystore;
 
 use log::{trace, warn};
-use polkadot_erasure_coding::branch_hash;
+use polkadot_erasure_coding::{branch_hash, chunk_index};
 use polkadot_primitives::v1::{
 	PARACHAIN_KEY_TYPE_ID,
-	BlakeTwo256, CommittedCandidateReceipt, CoreState, ErasureChunk,
+	BlakeTwo256, CommittedCandidateReceipt, CoreIndex, CoreState, ErasureChunk,
 	Hash as Hash, HashT, Id as ParaId,
 	ValidatorId, ValidatorIndex, SessionIndex,
 };
 use polkadot_subsystem::messages::{
-	AllMessages, AvailabilityDistributionMessage, NetworkBridgeMessage, RuntimeApiMessage,
+	AvailabilityDistributionMessage, NetworkBridgeMessage, RuntimeApiMessage,
 	RuntimeApiRequest, AvailabilityStoreMessage, ChainApiMessage,
 };
 use polkadot_subsystem::{
@@ -145,6 +149,12 @@ struct PerCandidate {
 
 	/// If this node is a validator, note the index in the validator set.
 	validator_index: Option<ValidatorIndex>,
+
+	/// The availability core the candidate is occupying.
+	core_index: Option<CoreIndex>,
+
+	/// If this node is a validator, note the index of the chunk it is responsible for.
+	chunk_index: Option<u32>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -196,6 +206,7 @@ impl ProtocolState {
 		relay_parent: Hash,
 		validators: Vec<ValidatorId>,
 		validator_index: Option<ValidatorIndex>,
+		chunk_shuffle_seed: Option<[u8; 32]>,
 	) -> Result<()>
 	where
 		Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
@@ -213,6 +224,15 @@ impl ProtocolState {
 				.or_default();
 			per_candidate.validator_index = validator_index.clone();
 			per_candidate.validators = validators.clone();
+			per_candidate.chunk_index = match (validator_index, per_candidate.core_index) {
+				(Some(validator_index), Some(core_index)) => chunk_index(
+					chunk_shuffle_seed.as_ref(),
+					core_index,
+					validators.len(),
+					validator_index,
+				),
+				_ => None,
+			};
 
 			self
 				.receipts
@@ -345,11 +365,12 @@ where
 	for added in added.iter() {
 		let added = **added;
 		let validators = query_validators(ctx, added).await?;
+		let chunk_shuffle_seed = query_chunk_shuffle_seed(ctx, added).await?;
 		let validator_index = obtain_our_validator_index(
 			&validators,
 			keystore.clone(),
 		);
-		state.add_relay_parent(ctx, added, validators, validator_index).await?;
+		state.add_relay_parent(ctx, added, validators, validator_index, chunk_shuffle_seed).await?;
 	}
 
 	// handle all candidates
@@ -495,11 +516,9 @@ where
 			message.erasure_chunk,
 		);
 
-		ctx.send_message(AllMessages::NetworkBridge(
-			NetworkBridgeMessage::SendValidationMessage(
-				peers.clone(),
-				protocol_v1::ValidationProtocol::AvailabilityDistribution(wire_message),
-			),
+		ctx.send_message(NetworkBridgeMessage::SendValidationMessage(
+			peers.clone(),
+			protocol_v1::ValidationProtocol::AvailabilityDistribution(wire_message),
 		))
 		.await
 		.map_err::<Error, _>(Into::into)?;
@@ -649,9 +668,9 @@ where
 		} else {
 			modify_reputation(ctx, origin, BENEFIT_VALID_MESSAGE_FIRST).await?;
 
-			// save the chunk for our index
-			if let Some(validator_index) = per_candidate.validator_index {
-				if message.erasure_chunk.index == validator_index {
+			// save the chunk we are responsible for
+			if let Some(chunk_index) = per_candidate.chunk_index {
+				if message.erasure_chunk.index == chunk_index {
 					if let Err(_e) = store_chunk(
 						ctx,
 						message.candidate_hash.clone(),
@@ -743,7 +762,7 @@ impl AvailabilityDistributionSubsystem {
 				})) => {
 					// handled at view change
 				}
-				FromOverseer::Signal(OverseerSignal::BlockFinalized(_)) => {}
+				FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
 				FromOverseer::Signal(OverseerSignal::Conclude) => {
 					return Ok(());
 				}
@@ -767,22 +786,24 @@ where
 }
 
 /// Obtain all live candidates based on an iterator of relay heads.
+///
+/// Returns a map from candidate hash -> (occupied core, receipt)
 async fn query_live_candidates_without_ancestors<Context>(
 	ctx: &mut Context,
 	relay_parents: impl IntoIterator<Item = Hash>,
-) -> Result<HashSet<CommittedCandidateReceipt>>
+) -> Result<HashMap<Hash, (CoreIndex, CommittedCandidateReceipt)>>
 where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let iter = relay_parents.into_iter();
 	let hint = iter.size_hint();
 
-	let mut live_candidates = HashSet::with_capacity(hint.1.unwrap_or(hint.0));
+	let mut live_candidates = HashMap::with_capacity(hint.1.unwrap_or(hint.0));
 	for relay_parent in iter {
-		let paras = query_para_ids(ctx, relay_parent).await?;
-		for para in paras {
+		let occupied_cores = query_occupied_cores(ctx, relay_parent).await?;
+		for (core_index, para) in occupied_cores {
 			if let Some(ccr) = query_pending_availability(ctx, relay_parent, para).await? {
-				live_candidates.insert(ccr);
+				live_candidates.insert(ccr.hash(), (core_index, ccr));
 			}
 		}
 	}
@@ -851,40 +872,47 @@ where
 			unknown.into_iter().zip(
 				receipts
 					.into_iter()
-					.map(|receipt| (receipt.hash(), receipt)),
+					.map(|(receipt_hash, (core_index, receipt))| {
+						state.per_candidate.entry(receipt_hash).or_default().core_index = Some(core_index);
+						(receipt_hash, receipt)
+					}),
 			),
 		);
 	}
 	Ok(live_candidates)
 }
 
-/// Query all para IDs.
-async fn query_para_ids<Context>(ctx: &mut Context, relay_parent: Hash) -> Result<Vec<ParaId>>
+/// Query the occupied availability cores along with the para IDs occupying them.
+async fn query_occupied_cores<Context>(
+	ctx: &mut Context,
+	relay_parent: Hash,
+) -> Result<Vec<(CoreIndex, ParaId)>>
 where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+	ctx.send_message(RuntimeApiMessage::Request(
 		relay_parent,
 		RuntimeApiRequest::AvailabilityCores(tx),
-	)))
+	))
 	.await
 	.map_err::<Error, _>(Into::into)?;
 
-	let all_para_ids: Vec<_> = rx
+	let all_cores: Vec<_> = rx
 		.await??;
 
-	let occupied_para_ids = all_para_ids
+	let occupied_cores = all_cores
 		.into_iter()
-		.filter_map(|core_state| {
+		.enumerate()
+		.filter_map(|(idx, core_state)| {
 			if let CoreState::Occupied(occupied) = core_state {
-				Some(occupied.para_id)
+				Some((CoreIndex(idx as u32), occupied.para_id))
 			} else {
 				None
 			}
 		})
 		.collect();
-	Ok(occupied_para_ids)
+	Ok(occupied_cores)
 }
 
 /// Modify the reputation of a peer based on its behavior.
@@ -898,9 +926,7 @@ where
 		rep,
 		peer
 	);
-	ctx.send_message(AllMessages::NetworkBridge(
-		NetworkBridgeMessage::ReportPeer(peer, rep),
-	))
+	ctx.send_message(NetworkBridgeMessage::ReportPeer(peer, rep))
 	.await
 	.map_err::<Error, _>(Into::into)
 }
@@ -914,9 +940,7 @@ where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	ctx.send_message(AllMessages::AvailabilityStore(
-		AvailabilityStoreMessage::QueryDataAvailability(candidate_hash, tx),
-	))
+	ctx.send_message(AvailabilityStoreMessage::QueryDataAvailability(candidate_hash, tx))
 	.await?;
 	rx.await.map_err::<Error, _>(Into::into)
 }
@@ -931,9 +955,7 @@ where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	ctx.send_message(AllMessages::AvailabilityStore(
-			AvailabilityStoreMessage::QueryChunk(candidate_hash, validator_index, tx),
-		))
+	ctx.send_message(AvailabilityStoreMessage::QueryChunk(candidate_hash, validator_index, tx))
 		.await?;
 	rx.await.map_err::<Error, _>(Into::into)
 }
@@ -949,9 +971,7 @@ where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	ctx.send_message(AllMessages::AvailabilityStore(
-		AvailabilityStoreMessage::StoreChunk(candidate_hash, validator_index, erasure_chunk, tx),
-	)).await?;
+	ctx.send_message(AvailabilityStoreMessage::StoreChunk(candidate_hash, validator_index, erasure_chunk, tx)).await?;
 	rx.await.map_err::<Error, _>(Into::into)
 }
 
@@ -965,10 +985,10 @@ where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+	ctx.send_message(RuntimeApiMessage::Request(
 			relay_parent,
 			RuntimeApiRequest::CandidatePendingAvailability(para, tx),
-		)))
+		))
 		.await?;
 	rx.await?
 		.map_err::<Error, _>(Into::into)
@@ -983,10 +1003,10 @@ where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	let query_validators = AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+	let query_validators = RuntimeApiMessage::Request(
 		relay_parent,
 		RuntimeApiRequest::Validators(tx),
-	));
+	);
 
 	ctx.send_message(query_validators)
 		.await?;
@@ -994,6 +1014,30 @@ where
 		.map_err::<Error, _>(Into::into)
 }
 
+/// Query the seed used to shuffle the assignment of erasure chunks to validators.
+///
+/// Runtimes without chunk shuffling have no seed, and keep the identity mapping of chunks to
+/// validators.
+async fn query_chunk_shuffle_seed<Context>(
+	ctx: &mut Context,
+	relay_parent: Hash,
+) -> Result<Option<[u8; 32]>>
+where
+	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(RuntimeApiMessage::Request(
+			relay_parent,
+			RuntimeApiRequest::AvailabilityChunkShuffleSeed(tx),
+		))
+		.await?;
+	match rx.await? {
+		Ok(chunk_shuffle_seed) => Ok(Some(chunk_shuffle_seed)),
+		Err(RuntimeApiError::NotSupported { .. }) => Ok(None),
+		Err(e) => Err(e.into()),
+	}
+}
+
 /// Query the hash of the `K` ancestors
 async fn query_k_ancestors<Context>(
 	ctx: &mut Context,
@@ -1004,11 +1048,11 @@ where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	let query_ancestors = AllMessages::ChainApi(ChainApiMessage::Ancestors {
+	let query_ancestors = ChainApiMessage::Ancestors {
 		hash: relay_parent,
 		k,
 		response_channel: tx,
-	});
+	};
 
 	ctx.send_message(query_ancestors)
 		.await?;
@@ -1025,10 +1069,10 @@ where
 	Context: SubsystemContext<Message = AvailabilityDistributionMessage>,
 {
 	let (tx, rx) = oneshot::channel();
-	let query_session_idx_for_child = AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+	let query_session_idx_for_child = RuntimeApiMessage::Request(
 		relay_parent,
 		RuntimeApiRequest::SessionIndexForChild(tx),
-	));
+	);
 
 	ctx.send_message(query_session_idx_for_child)
 		.await?;
@@ -15,6 +15,7 @@
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::*;
+use polkadot_subsystem::messages::AllMessages;
 use assert_matches::assert_matches;
 use polkadot_erasure_coding::{branches, obtain_chunks_v1 as obtain_chunks};
 use polkadot_primitives::v1::{
@@ -148,6 +149,7 @@ struct TestState {
 	ancestors: Vec<Hash>,
 	availability_cores: Vec<CoreState>,
 	persisted_validation_data: PersistedValidationData,
+	chunk_shuffle_seed: [u8; 32],
 }
 
 fn validator_pubkeys(val_ids: &[Sr25519Keyring]) -> Vec<ValidatorId> {
@@ -228,6 +230,7 @@ impl Default for TestState {
 			relay_parent,
 			ancestors,
 			validator_index,
+			chunk_shuffle_seed: [0x55; 32],
 		}
 	}
 }
@@ -423,6 +426,7 @@ fn reputation_verification() {
 			relay_parent: current,
 			ancestors,
 			validator_index: _,
+			chunk_shuffle_seed,
 		} = test_state.clone();
 
 		let _ = validator_groups;
@@ -467,6 +471,18 @@ fn reputation_verification() {
 			}
 		);
 
+		// obtain the chunk shuffle seed per relay parent
+		assert_matches!(
+			overseer_recv(&mut virtual_overseer).await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				relay_parent,
+				RuntimeApiRequest::AvailabilityChunkShuffleSeed(tx),
+			)) => {
+				assert_eq!(relay_parent, current);
+				tx.send(Ok(chunk_shuffle_seed)).unwrap();
+			}
+		);
+
 		let genesis = Hash::repeat_byte(0xAA);
 		// query of k ancestors, we only provide one
 		assert_matches!(
@@ -708,10 +724,19 @@ fn reputation_verification() {
 
 		delay!(100);
 
+		// this node is `Ferdie`, the first validator, and candidate A occupies the first core
+		let our_chunk_index = chunk_index(
+			Some(&chunk_shuffle_seed),
+			CoreIndex(0),
+			test_state.validators.len(),
+			0,
+		).unwrap();
+		let other_chunk_index = (our_chunk_index + 1) % test_state.validators.len() as u32;
+
 		let valid: AvailabilityGossipMessage = make_valid_availability_gossip(
 			&test_state,
 			candidates[0].hash(),
-			2,
+			other_chunk_index,
 			pov_block_a.clone(),
 		);
 
@@ -742,6 +767,58 @@ fn reputation_verification() {
 			);
 		}
 
+		{
+			// valid chunk this node is responsible for (first, from b)
+			let ours: AvailabilityGossipMessage = make_valid_availability_gossip(
+				&test_state,
+				candidates[0].hash(),
+				our_chunk_index,
+				pov_block_a.clone(),
+			);
+
+			overseer_send(
+				&mut virtual_overseer,
+				AvailabilityDistributionMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::PeerMessage(
+						peer_b.clone(),
+						chunk_protocol_message(ours.clone()),
+					),
+				),
+			)
+			.await;
+
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::NetworkBridge(
+					NetworkBridgeMessage::ReportPeer(
+						peer,
+						rep
+					)
+				) => {
+					assert_eq!(peer, peer_b);
+					assert_eq!(rep, BENEFIT_VALID_MESSAGE_FIRST);
+				}
+			);
+
+			// which gets stored
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::AvailabilityStore(
+					AvailabilityStoreMessage::StoreChunk(
+						candidate_hash,
+						idx,
+						chunk,
+						tx,
+					)
+				) => {
+					assert_eq!(candidate_hash, candidates[0].hash());
+					assert_eq!(idx, our_chunk_index);
+					assert_eq!(chunk, ours.erasure_chunk);
+					tx.send(Ok(())).unwrap();
+				}
+			);
+		}
+
 		{
 			// valid (duplicate, from b)
 			overseer_send(
@@ -19,9 +19,12 @@
 //! In case this node is a validator, gossips its own signed availability bitfield
 //! for a particular relay parent.
 //! Independently of that, gossips on received messages from peers to other interested peers.
+//! On large validator sets, received messages are only relayed to a random sample of the
+//! interested peers.
 
 use codec::{Decode, Encode};
 use futures::{channel::oneshot, FutureExt};
+use futures_timer::Delay;
 
 use log::{trace, warn};
 use polkadot_subsystem::messages::*;
@@ -30,7 +33,10 @@ use polkadot_subsystem::{
 };
 use polkadot_primitives::v1::{Hash, SignedAvailabilityBitfield, SigningContext, ValidatorId};
 use polkadot_node_network_protocol::{v1 as protocol_v1, PeerId, NetworkBridgeEvent, View, ReputationChange};
+use rand::seq::SliceRandom;
+use std::cmp;
 use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
 const COST_SIGNATURE_INVALID: ReputationChange =
 	ReputationChange::new(-100, "Bitfield signature invalid");
@@ -82,6 +88,9 @@ struct ProtocolState {
 	/// to determine what is relevant to them.
 	peer_views: HashMap<PeerId, View>,
 
+	/// The time at which each active peer connected.
+	peer_connected_at: HashMap<PeerId, Instant>,
+
 	/// Our current view.
 	view: View,
 
@@ -129,6 +138,21 @@ impl PerRelayParentData {
 
 const TARGET: &'static str = "bitd";
 
+/// How often we check whether bitfields need to be re-sent.
+const RESEND_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long finality needs to have made no progress before we start re-sending bitfields.
+const FINALITY_STALL_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// For how long after connecting a peer is sent bitfields again while finality is stalled.
+const RESEND_PEER_WINDOW: Duration = Duration::from_secs(120);
+
+/// The number of validators up to which relayed bitfields are flooded to all interested peers.
+const FLOOD_MAX_VALIDATORS: usize = 100;
+
+/// The minimum number of peers a bitfield is relayed to when it isn't flooded.
+const MIN_RELAY_FANOUT: usize = 4;
+
 /// The bitfield distribution subsystem.
 pub struct BitfieldDistribution;
 
@@ -140,8 +164,33 @@ impl BitfieldDistribution {
 	{
 		// work: process incoming messages from the overseer and process accordingly.
 		let mut state = ProtocolState::default();
+		let mut last_finalized = Instant::now();
+		let mut resend_timer = Delay::new(RESEND_INTERVAL).fuse();
 		loop {
-			let message = ctx.recv().await?;
+			let message = {
+				let recv = ctx.recv().fuse();
+				futures::pin_mut!(recv);
+
+				futures::select! {
+					message = recv => Some(message?),
+					_ = resend_timer => None,
+				}
+			};
+
+			let message = match message {
+				Some(message) => message,
+				None => {
+					resend_timer = Delay::new(RESEND_INTERVAL).fuse();
+
+					if last_finalized.elapsed() >= FINALITY_STALL_THRESHOLD {
+						trace!(target: TARGET, "Finality stalled, resending bitfields to new peers");
+						resend_to_new_peers(&mut ctx, &mut state).await?;
+					}
+
+					continue;
+				}
+			};
+
 			match message {
 				FromOverseer::Communication {
 					msg: BitfieldDistributionMessage::DistributeBitfield(hash, signed_availability),
@@ -187,8 +236,9 @@ impl BitfieldDistribution {
 						// defer the cleanup to the view change
 					}
 				}
-				FromOverseer::Signal(OverseerSignal::BlockFinalized(hash)) => {
-					trace!(target: TARGET, "Block finalized {:?}", hash);
+				FromOverseer::Signal(OverseerSignal::BlockFinalized(hash, number)) => {
+					trace!(target: TARGET, "Block finalized {:?} ({})", hash, number);
+					last_finalized = Instant::now();
 				}
 				FromOverseer::Signal(OverseerSignal::Conclude) => {
 					trace!(target: TARGET, "Conclude");
@@ -209,9 +259,7 @@ where
 	Context: SubsystemContext<Message = BitfieldDistributionMessage>,
 {
 	trace!(target: TARGET, "Reputation change of {:?} for peer {:?}", rep, peer);
-	ctx.send_message(AllMessages::NetworkBridge(
-		NetworkBridgeMessage::ReportPeer(peer, rep),
-	))
+	ctx.send_message(NetworkBridgeMessage::ReportPeer(peer, rep))
 	.await
 }
 
@@ -260,54 +308,84 @@ where
 		signed_availability,
 	};
 
-	relay_message(ctx, job_data, peer_views, validator, msg).await?;
+	// our own bitfield is always sent to all interested peers.
+	relay_message(ctx, job_data, peer_views, validator, msg, true).await?;
 
 	Ok(())
 }
 
+/// Choose the peers to relay a bitfield to out of the peers interested in it.
+///
+/// Up to `FLOOD_MAX_VALIDATORS` validators, or if `flood` is set, all interested peers are chosen.
+/// Otherwise, peers already known to have the bitfield are skipped and a random sample of the
+/// others is chosen, of about the square root of their number but at least `MIN_RELAY_FANOUT`.
+/// As every peer relays a new bitfield onwards in turn, this still reaches all peers with high
+/// probability, at a fraction of the messages.
+fn relay_targets(
+	job_data: &PerRelayParentData,
+	validator: &ValidatorId,
+	mut interested_peers: Vec<PeerId>,
+	flood: bool,
+) -> Vec<PeerId> {
+	if flood || job_data.validator_set.len() <= FLOOD_MAX_VALIDATORS {
+		return interested_peers;
+	}
+
+	interested_peers.retain(|peer| !job_data.message_received_from_peer
+		.get(peer)
+		.map_or(false, |received| received.contains(validator))
+	);
+
+	let fanout = cmp::max(MIN_RELAY_FANOUT, (interested_peers.len() as f64).sqrt().ceil() as usize);
+	if interested_peers.len() > fanout {
+		interested_peers.partial_shuffle(&mut rand::thread_rng(), fanout);
+		interested_peers.truncate(fanout);
+	}
+
+	interested_peers
+}
+
 /// Distribute a given valid and signature checked bitfield message.
 ///
-/// Can be originated by another subsystem or received via network from another peer.
+/// Can be originated by another subsystem or received via network from another peer. Unless
+/// `flood` is set, the message may only be sent to some of the interested peers, see
+/// `relay_targets`.
 async fn relay_message<Context>(
 	ctx: &mut Context,
 	job_data: &mut PerRelayParentData,
 	peer_views: &mut HashMap<PeerId, View>,
 	validator: ValidatorId,
 	message: BitfieldGossipMessage,
+	flood: bool,
 ) -> SubsystemResult<()>
 where
 	Context: SubsystemContext<Message = BitfieldDistributionMessage>,
 {
 	// notify the overseer about a new and valid signed bitfield
-	ctx.send_message(AllMessages::Provisioner(
-		ProvisionerMessage::ProvisionableData(ProvisionableData::Bitfield(
-			message.relay_parent.clone(),
-			message.signed_availability.clone(),
-		)),
-	))
+	ctx.send_message(ProvisionerMessage::ProvisionableData(ProvisionableData::Bitfield(
+		message.relay_parent.clone(),
+		message.signed_availability.clone(),
+	)))
 	.await?;
 
-	let message_sent_to_peer = &mut (job_data.message_sent_to_peer);
-
-	// pass on the bitfield distribution to all interested peers
+	// pass on the bitfield distribution to interested peers
 	let interested_peers = peer_views
 		.iter()
-		.filter_map(|(peer, view)| {
-			// check interest in the peer in this message's relay parent
-			if view.contains(&message.relay_parent) {
-				// track the message as sent for this peer
-				message_sent_to_peer
-					.entry(peer.clone())
-					.or_default()
-					.insert(validator.clone());
-
-				Some(peer.clone())
-			} else {
-				None
-			}
-		})
+		// check interest in the peer in this message's relay parent
+		.filter(|(_peer, view)| view.contains(&message.relay_parent))
+		.map(|(peer, _view)| peer.clone())
 		.collect::<Vec<PeerId>>();
 
+	let interested_peers = relay_targets(job_data, &validator, interested_peers, flood);
+
+	// track the message as sent for these peers
+	for peer in &interested_peers {
+		job_data.message_sent_to_peer
+			.entry(peer.clone())
+			.or_default()
+			.insert(validator.clone());
+	}
+
 	if interested_peers.is_empty() {
 		trace!(
 			target: TARGET,
@@ -315,11 +393,9 @@ where
 			message.relay_parent
 		);
 	} else {
-		ctx.send_message(AllMessages::NetworkBridge(
-			NetworkBridgeMessage::SendValidationMessage(
-				interested_peers,
-				message.into_validation_protocol(),
-			),
+		ctx.send_message(NetworkBridgeMessage::SendValidationMessage(
+			interested_peers,
+			message.into_validation_protocol(),
 		))
 		.await?;
 	}
@@ -402,7 +478,7 @@ where
 		}
 		one_per_validator.insert(validator.clone(), message.clone());
 
-		relay_message(ctx, job_data, &mut state.peer_views, validator, message).await?;
+		relay_message(ctx, job_data, &mut state.peer_views, validator, message, false).await?;
 
 		modify_reputation(ctx, origin, BENEFIT_VALID_MESSAGE_FIRST).await
 	} else {
@@ -423,10 +499,12 @@ where
 	match bridge_message {
 		NetworkBridgeEvent::PeerConnected(peerid, _role) => {
 			// insert if none already present
+			state.peer_connected_at.entry(peerid.clone()).or_insert_with(Instant::now);
 			state.peer_views.entry(peerid).or_default();
 		}
 		NetworkBridgeEvent::PeerDisconnected(peerid) => {
 			// get rid of superfluous data
+			state.peer_connected_at.remove(&peerid);
 			state.peer_views.remove(&peerid);
 		}
 		NetworkBridgeEvent::PeerViewChange(peerid, view) => {
@@ -523,6 +601,58 @@ where
 	Ok(())
 }
 
+/// Send the bitfields at the relay parents in their views that peers which connected recently
+/// neither sent us nor got from us yet.
+///
+/// Bitfields are sent to a peer when its view changes. If we only learn about a relay parent after
+/// a peer has put it into its view, which is common when many nodes restart at once, the peer
+/// would otherwise not get them until it changes its view again.
+async fn resend_to_new_peers<Context>(
+	ctx: &mut Context,
+	state: &mut ProtocolState,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = BitfieldDistributionMessage>,
+{
+	let new_peers: Vec<PeerId> = state.peer_connected_at
+		.iter()
+		.filter(|(_peer, connected_at)| connected_at.elapsed() <= RESEND_PEER_WINDOW)
+		.map(|(peer, _connected_at)| peer.clone())
+		.collect();
+
+	for peer in new_peers {
+		let view = match state.peer_views.get(&peer) {
+			Some(view) => view,
+			None => continue,
+		};
+
+		let missing: Vec<(ValidatorId, BitfieldGossipMessage)> = view.0
+			.iter()
+			.filter_map(|relay_parent| state.per_relay_parent.get(relay_parent))
+			.flat_map(|job_data| {
+				let peer = &peer;
+				job_data.one_per_validator
+					.iter()
+					.filter(move |(validator, _message)| {
+						let known = |set: Option<&HashSet<ValidatorId>>| {
+							set.map_or(false, |set| set.contains(*validator))
+						};
+
+						!known(job_data.message_sent_to_peer.get(peer)) &&
+							!known(job_data.message_received_from_peer.get(peer))
+					})
+					.map(|(validator, message)| (validator.clone(), message.clone()))
+			})
+			.collect();
+
+		for (validator, message) in missing {
+			send_tracked_gossip_message(ctx, state, peer.clone(), validator, message).await?;
+		}
+	}
+
+	Ok(())
+}
+
 /// Send a gossip message and track it in the per relay parent data.
 async fn send_tracked_gossip_message<Context>(
 	ctx: &mut Context,
@@ -546,11 +676,9 @@ where
 		.or_default()
 		.insert(validator.clone());
 
-	ctx.send_message(AllMessages::NetworkBridge(
-		NetworkBridgeMessage::SendValidationMessage(
-			vec![dest],
-			message.into_validation_protocol(),
-		),
+	ctx.send_message(NetworkBridgeMessage::SendValidationMessage(
+		vec![dest],
+		message.into_validation_protocol(),
 	))
 	.await?;
 
@@ -582,15 +710,15 @@ where
 	let (validators_tx, validators_rx) = oneshot::channel();
 	let (session_tx, session_rx) = oneshot::channel();
 
-	let query_validators = AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+	let query_validators = RuntimeApiMessage::Request(
 		relay_parent.clone(),
 		RuntimeApiRequest::Validators(validators_tx),
-	));
+	);
 
-	let query_signing = AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+	let query_signing = RuntimeApiMessage::Request(
 		relay_parent.clone(),
 		RuntimeApiRequest::SessionIndexForChild(session_tx),
-	));
+	);
 
 	ctx.send_messages(std::iter::once(query_validators).chain(std::iter::once(query_signing)))
 		.await?;
@@ -669,6 +797,7 @@ mod test {
 				.into_iter()
 				.map(|peer| (peer, view!(relay_parent)))
 				.collect(),
+			peer_connected_at: hashmap!{},
 			view: view!(relay_parent),
 		}
 	}
@@ -1083,4 +1212,141 @@ mod test {
 
 		});
 	}
+
+	#[test]
+	fn bitfields_are_resent_to_new_peers_only() {
+		let hash_a: Hash = [0; 32].into();
+
+		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
+
+		let signing_context = SigningContext {
+			session_index: 1,
+			parent_hash: hash_a.clone(),
+		};
+
+		let (validator_pair, _seed) = ValidatorPair::generate();
+		let validator = validator_pair.public();
+
+		let payload = AvailabilityBitfield(bitvec![bitvec::order::Lsb0, u8; 1u8; 32]);
+		let signed =
+			Signed::<AvailabilityBitfield>::sign(payload, &signing_context, 0, &validator_pair);
+
+		let msg = BitfieldGossipMessage {
+			relay_parent: hash_a.clone(),
+			signed_availability: signed.clone(),
+		};
+
+		let pool = sp_core::testing::TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<BitfieldDistributionMessage, _>(pool);
+
+		let mut state = prewarmed_state(
+			validator.clone(),
+			signing_context.clone(),
+			msg.clone(),
+			vec![peer_a.clone(), peer_b.clone()],
+		);
+		state.peer_connected_at = hashmap! {
+			peer_a.clone() => Instant::now(),
+			peer_b.clone() => Instant::now() - RESEND_PEER_WINDOW * 2,
+		};
+
+		executor::block_on(async move {
+			launch!(resend_to_new_peers(&mut ctx, &mut state));
+			// bitfields the peer already got are not sent again.
+			launch!(resend_to_new_peers(&mut ctx, &mut state));
+			drop(ctx);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::NetworkBridge(
+					NetworkBridgeMessage::SendValidationMessage(peers, send_msg),
+				) => {
+					assert_eq!(peers, vec![peer_a.clone()]);
+					assert_eq!(send_msg, msg.clone().into_validation_protocol());
+				}
+			);
+
+			assert!(handle.try_recv().await.is_none());
+			assert!(
+				state.per_relay_parent[&hash_a].message_sent_to_peer[&peer_a].contains(&validator),
+			);
+		});
+	}
+
+	#[test]
+	fn relayed_bitfields_are_sampled_on_large_validator_sets() {
+		let hash_a: Hash = [0; 32].into();
+
+		let (mut state, signing_context, validator_pair) =
+			state_with_view(view![hash_a], hash_a.clone());
+		let validator = validator_pair.public();
+
+		state.per_relay_parent.get_mut(&hash_a).unwrap().validator_set =
+			vec![validator.clone(); FLOOD_MAX_VALIDATORS + 1];
+
+		let peers: Vec<PeerId> = (0..20).map(|_| PeerId::random()).collect();
+		for peer in &peers {
+			state.peer_views.insert(peer.clone(), view![hash_a]);
+		}
+		let origin = peers[0].clone();
+
+		let payload = AvailabilityBitfield(bitvec![bitvec::order::Lsb0, u8; 1u8; 32]);
+		let signed_bitfield =
+			Signed::<AvailabilityBitfield>::sign(payload, &signing_context, 0, &validator_pair);
+
+		let msg = BitfieldGossipMessage {
+			relay_parent: hash_a.clone(),
+			signed_availability: signed_bitfield.clone(),
+		};
+
+		let pool = sp_core::testing::TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<BitfieldDistributionMessage, _>(pool);
+
+		executor::block_on(async move {
+			launch!(handle_network_msg(
+				&mut ctx,
+				&mut state,
+				NetworkBridgeEvent::PeerMessage(origin.clone(), msg.clone().into_network_message()),
+			));
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::Provisioner(ProvisionerMessage::ProvisionableData(
+					ProvisionableData::Bitfield(hash, signed)
+				)) => {
+					assert_eq!(hash, hash_a);
+					assert_eq!(signed, signed_bitfield)
+				}
+			);
+
+			// the origin is skipped, leaving 19 peers of which 5 are sampled.
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::NetworkBridge(
+					NetworkBridgeMessage::SendValidationMessage(targets, send_msg),
+				) => {
+					assert_eq!(targets.len(), 5);
+					assert!(!targets.contains(&origin));
+					assert_eq!(send_msg, msg.clone().into_validation_protocol());
+
+					let sent_to = &state.per_relay_parent[&hash_a].message_sent_to_peer;
+					assert_eq!(sent_to.len(), 5);
+					assert!(targets.iter().all(|peer| sent_to[peer].contains(&validator)));
+				}
+			);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::NetworkBridge(
+					NetworkBridgeMessage::ReportPeer(peer, rep)
+				) => {
+					assert_eq!(peer, origin);
+					assert_eq!(rep, BENEFIT_VALID_MESSAGE_FIRST)
+				}
+			);
+		});
+	}
 }
@@ -30,7 +30,7 @@ use polkadot_subsystem::{
 	SubsystemResult,
 };
 use polkadot_subsystem::messages::{
-	NetworkBridgeMessage, AllMessages, AvailabilityDistributionMessage,
+	NetworkBridgeMessage, NetworkBridgeOutgoingMessages, AvailabilityDistributionMessage,
 	BitfieldDistributionMessage, PoVDistributionMessage, StatementDistributionMessage,
 	CollatorProtocolMessage,
 };
@@ -259,7 +259,7 @@ fn action_from_overseer_message(
 			NetworkBridgeMessage::ConnectToValidators(peer_set, validators, res)
 				=> Action::ConnectToValidators(peer_set, validators, res),
 		},
-		Ok(FromOverseer::Signal(OverseerSignal::BlockFinalized(_)))
+		Ok(FromOverseer::Signal(OverseerSignal::BlockFinalized(..)))
 			=> Action::Nop,
 		Err(e) => {
 			log::warn!(target: TARGET, "Shutting down Network Bridge due to error {:?}", e);
@@ -501,19 +501,19 @@ async fn dispatch_validation_events_to_all<I>(
 		I::IntoIter: Send,
 {
 	let messages_for = |event: NetworkBridgeEvent<protocol_v1::ValidationProtocol>| {
-		let a = std::iter::once(event.focus().ok().map(|m| AllMessages::AvailabilityDistribution(
+		let a = std::iter::once(event.focus().ok().map(|m| NetworkBridgeOutgoingMessages::AvailabilityDistribution(
 			AvailabilityDistributionMessage::NetworkBridgeUpdateV1(m)
 		)));
 
-		let b = std::iter::once(event.focus().ok().map(|m| AllMessages::BitfieldDistribution(
+		let b = std::iter::once(event.focus().ok().map(|m| NetworkBridgeOutgoingMessages::BitfieldDistribution(
 			BitfieldDistributionMessage::NetworkBridgeUpdateV1(m)
 		)));
 
-		let p = std::iter::once(event.focus().ok().map(|m| AllMessages::PoVDistribution(
+		let p = std::iter::once(event.focus().ok().map(|m| NetworkBridgeOutgoingMessages::PoVDistribution(
 			PoVDistributionMessage::NetworkBridgeUpdateV1(m)
 		)));
 
-		let s = std::iter::once(event.focus().ok().map(|m| AllMessages::StatementDistribution(
+		let s = std::iter::once(event.focus().ok().map(|m| NetworkBridgeOutgoingMessages::StatementDistribution(
 			StatementDistributionMessage::NetworkBridgeUpdateV1(m)
 		)));
 
@@ -532,7 +532,7 @@ async fn dispatch_collation_events_to_all<I>(
 		I::IntoIter: Send,
 {
 	let messages_for = |event: NetworkBridgeEvent<protocol_v1::CollationProtocol>| {
-		event.focus().ok().map(|m| AllMessages::CollatorProtocol(
+		event.focus().ok().map(|m| NetworkBridgeOutgoingMessages::CollatorProtocol(
 			CollatorProtocolMessage::NetworkBridgeUpdateV1(m)
 		))
 	};
@@ -728,7 +728,9 @@ mod tests {
 	use parking_lot::Mutex;
 	use assert_matches::assert_matches;
 
-	use polkadot_subsystem::messages::{StatementDistributionMessage, BitfieldDistributionMessage};
+	use polkadot_subsystem::messages::{
+		AllMessages, StatementDistributionMessage, BitfieldDistributionMessage,
+	};
 	use polkadot_node_subsystem_test_helpers::{
 		SingleItemSink, SingleItemStream, TestSubsystemContextHandle,
 	};
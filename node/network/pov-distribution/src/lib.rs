@@ -20,11 +20,12 @@
 //! among validators.
 
 use polkadot_primitives::v1::{Hash, PoV, CandidateDescriptor};
+use node_primitives::{check_pov, InvalidCandidate};
 use polkadot_subsystem::{
 	ActiveLeavesUpdate, OverseerSignal, SubsystemContext, Subsystem, SubsystemResult, FromOverseer, SpawnedSubsystem,
 };
 use polkadot_subsystem::messages::{
-	PoVDistributionMessage, RuntimeApiMessage, RuntimeApiRequest, AllMessages, NetworkBridgeMessage,
+	PoVDistributionMessage, RuntimeApiMessage, RuntimeApiRequest, NetworkBridgeMessage,
 };
 use polkadot_node_network_protocol::{
 	v1 as protocol_v1, ReputationChange as Rep, NetworkBridgeEvent, PeerId, View,
@@ -38,6 +39,7 @@ use std::sync::Arc;
 
 const COST_APPARENT_FLOOD: Rep = Rep::new(-500, "Peer appears to be flooding us with PoV requests");
 const COST_UNEXPECTED_POV: Rep = Rep::new(-500, "Peer sent us an unexpected PoV");
+const COST_OVERSIZED_POV: Rep = Rep::new(-1000, "Peer sent us a PoV over the size limit");
 const COST_AWAITED_NOT_IN_VIEW: Rep
 	= Rep::new(-100, "Peer claims to be awaiting something outside of its view");
 
@@ -113,10 +115,10 @@ async fn handle_signal(
 		OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated }) => {
 			for relay_parent in activated {
 				let (vals_tx, vals_rx) = oneshot::channel();
-				ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				ctx.send_message(RuntimeApiMessage::Request(
 					relay_parent,
 					RuntimeApiRequest::Validators(vals_tx),
-				))).await?;
+				)).await?;
 
 				let n_validators = match vals_rx.await? {
 					Ok(v) => v.len(),
@@ -148,7 +150,7 @@ async fn handle_signal(
 
 			Ok(false)
 		}
-		OverseerSignal::BlockFinalized(_) => Ok(false),
+		OverseerSignal::BlockFinalized(..) => Ok(false),
 	}
 }
 
@@ -174,10 +176,10 @@ async fn notify_all_we_are_awaiting(
 
 	let payload = awaiting_message(relay_parent, vec![pov_hash]);
 
-	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+	ctx.send_message(NetworkBridgeMessage::SendValidationMessage(
 		peers_to_send,
 		payload,
-	))).await
+	)).await
 }
 
 /// Notify one peer about everything we're awaiting at a given relay-parent.
@@ -198,10 +200,10 @@ async fn notify_one_we_are_awaiting_many(
 
 	let payload = awaiting_message(relay_parent, awaiting_hashes);
 
-	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+	ctx.send_message(NetworkBridgeMessage::SendValidationMessage(
 		vec![peer.clone()],
 		payload,
-	))).await
+	)).await
 }
 
 /// Distribute a PoV to peers who are awaiting it.
@@ -229,10 +231,10 @@ async fn distribute_to_awaiting(
 
 	let payload = send_pov_message(relay_parent, pov_hash, pov.clone());
 
-	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+	ctx.send_message(NetworkBridgeMessage::SendValidationMessage(
 		peers_to_send,
 		payload,
-	))).await
+	)).await
 }
 
 /// Handles a `FetchPoV` message.
@@ -321,7 +323,7 @@ async fn report_peer(
 	peer: PeerId,
 	rep: Rep,
 ) -> SubsystemResult<()> {
-	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))).await
+	ctx.send_message(NetworkBridgeMessage::ReportPeer(peer, rep)).await
 }
 
 /// Handle a notification from a peer that they are awaiting some PoVs.
@@ -362,9 +364,7 @@ async fn handle_awaiting(
 			// Otherwise, we note that the peer is awaiting the PoV.
 			if let Some(pov) = relay_parent_state.known.get(&pov_hash) {
 				let payload = send_pov_message(relay_parent, pov_hash, (&**pov).clone());
-				ctx.send_message(AllMessages::NetworkBridge(
-					NetworkBridgeMessage::SendValidationMessage(vec![peer.clone()], payload)
-				)).await?;
+				ctx.send_message(NetworkBridgeMessage::SendValidationMessage(vec![peer.clone()], payload)).await?;
 			} else {
 				peer_awaiting.insert(pov_hash);
 			}
@@ -405,10 +405,23 @@ async fn handle_incoming_pov(
 			Some(f) => f,
 		};
 
-		let hash = pov.hash();
-		if hash != pov_hash {
-			report_peer(ctx, peer, COST_UNEXPECTED_POV).await?;
-			return Ok(());
+		match check_pov(&pov, &pov_hash) {
+			Ok(()) => {}
+			Err(InvalidCandidate::ParamsTooLarge(size)) => {
+				log::debug!(
+					target: "pov_distribution",
+					"Peer {:?} sent PoV {} of {} bytes, over the size limit",
+					peer,
+					pov_hash,
+					size,
+				);
+				report_peer(ctx, peer, COST_OVERSIZED_POV).await?;
+				return Ok(());
+			}
+			Err(_) => {
+				report_peer(ctx, peer, COST_UNEXPECTED_POV).await?;
+				return Ok(());
+			}
 		}
 
 		let pov = Arc::new(pov);
@@ -555,8 +568,9 @@ async fn run(
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use polkadot_subsystem::messages::AllMessages;
 	use futures::executor;
-	use polkadot_primitives::v1::BlockData;
+	use polkadot_primitives::v1::{BlockData, MAX_POV_SIZE};
 	use assert_matches::assert_matches;
 
 	fn make_pov(data: Vec<u8>) -> PoV {
@@ -967,6 +981,74 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn peer_punished_for_sending_oversized_pov() {
+		let hash_a: Hash = [0; 32].into();
+
+		let peer_a = PeerId::random();
+
+		let (pov_send, _) = oneshot::channel();
+
+		let pov = make_pov(vec![0; MAX_POV_SIZE as usize + 1]);
+		let pov_hash = pov.hash();
+
+		let mut state = State {
+			relay_parent_state: {
+				let mut s = HashMap::new();
+				let mut b = BlockBasedState {
+					known: HashMap::new(),
+					fetching: HashMap::new(),
+					n_validators: 10,
+				};
+
+				// pov is being fetched.
+				b.fetching.insert(pov_hash, vec![pov_send]);
+
+				s.insert(hash_a, b);
+				s
+			},
+			peer_state: {
+				let mut s = HashMap::new();
+
+				s.insert(
+					peer_a.clone(),
+					make_peer_state(vec![(hash_a, vec![])]),
+				);
+
+				s
+			},
+			our_view: View(vec![hash_a]),
+		};
+
+		let pool = sp_core::testing::TaskExecutor::new();
+		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+
+		executor::block_on(async move {
+			// Peer A answers our request with the awaited PoV, which is over the size limit.
+			handle_network_update(
+				&mut state,
+				&mut ctx,
+				NetworkBridgeEvent::PeerMessage(
+					peer_a.clone(),
+					send_pov_message(hash_a, pov_hash, pov.clone()),
+				).focus().unwrap(),
+			).await.unwrap();
+
+			// didn't complete our sender.
+			assert_eq!(state.relay_parent_state[&hash_a].fetching[&pov_hash].len(), 1);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::NetworkBridge(
+					NetworkBridgeMessage::ReportPeer(peer, rep)
+				) => {
+					assert_eq!(peer, peer_a);
+					assert_eq!(rep, COST_OVERSIZED_POV);
+				}
+			);
+		});
+	}
+
 	#[test]
 	fn peer_punished_for_sending_unexpected_pov() {
 		let hash_a: Hash = [0; 32].into();
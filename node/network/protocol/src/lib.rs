@@ -175,7 +175,7 @@ pub mod v1 {
 		Hash, CollatorId, Id as ParaId, ErasureChunk, CandidateReceipt,
 		SignedAvailabilityBitfield, PoV,
 	};
-	use polkadot_node_primitives::SignedFullStatement;
+	use polkadot_node_primitives::UncheckedSignedFullStatement;
 	use parity_scale_codec::{Encode, Decode};
 	use std::convert::TryFrom;
 	use super::RequestId;
@@ -214,7 +214,7 @@ pub mod v1 {
 	pub enum StatementDistributionMessage {
 		/// A signed full statement under a given relay-parent.
 		#[codec(index = "0")]
-		Statement(Hash, SignedFullStatement)
+		Statement(Hash, UncheckedSignedFullStatement)
 	}
 
 	/// Network messages used by the collator protocol subsystem
@@ -24,10 +24,10 @@ use polkadot_subsystem::{
 	ActiveLeavesUpdate, FromOverseer, OverseerSignal,
 };
 use polkadot_subsystem::messages::{
-	AllMessages, NetworkBridgeMessage, StatementDistributionMessage, CandidateBackingMessage,
-	RuntimeApiMessage, RuntimeApiRequest,
+	NetworkBridgeMessage, StatementDistributionMessage, CandidateBackingMessage,
+	RuntimeApiMessage, RuntimeApiRequest, ChainApiMessage,
 };
-use node_primitives::SignedFullStatement;
+use node_primitives::{SignedFullStatement, UncheckedSignedFullStatement};
 use polkadot_primitives::v1::{
 	Hash, CompactStatement, ValidatorIndex, ValidatorId, SigningContext, ValidatorSignature,
 };
@@ -37,14 +37,26 @@ use polkadot_node_network_protocol::{
 
 use futures::prelude::*;
 use futures::channel::oneshot;
+use futures_timer::Delay;
 use indexmap::IndexSet;
+use kvdb::{KeyValueDB, DBTransaction};
+use kvdb_rocksdb::{Database, DatabaseConfig};
+use parity_scale_codec::{Encode, Decode};
 
 use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 const COST_UNEXPECTED_STATEMENT: Rep = Rep::new(-100, "Unexpected Statement");
 const COST_INVALID_SIGNATURE: Rep = Rep::new(-500, "Invalid Statement Signature");
 const COST_DUPLICATE_STATEMENT: Rep = Rep::new(-250, "Statement sent more than once by peer");
 const COST_APPARENT_FLOOD: Rep = Rep::new(-1000, "Peer appears to be flooding us with statements");
+const COST_EXCESSIVE_SECONDED: Rep = Rep::new(
+	-500,
+	"Peer sent more Seconded statements of a validator than allowed",
+);
 
 const BENEFIT_VALID_STATEMENT: Rep = Rep::new(5, "Peer provided a valid statement");
 const BENEFIT_VALID_STATEMENT_FIRST: Rep = Rep::new(
@@ -59,8 +71,53 @@ const BENEFIT_VALID_STATEMENT_FIRST: Rep = Rep::new(
 /// Typically we will only keep 1, but when a validator equivocates we will need to track 2.
 const VC_THRESHOLD: usize = 2;
 
+/// How often we check whether statements need to be re-sent.
+const RESEND_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long finality needs to have made no progress before we start re-sending statements.
+const FINALITY_STALL_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// For how long after connecting a peer is sent statements again while finality is stalled.
+const RESEND_PEER_WINDOW: Duration = Duration::from_secs(120);
+
+/// The column of the statement store holding the statements.
+const STORE_COLUMN: u32 = 0;
+
+/// Configuration for the persistent statement store.
+pub struct StoreConfig {
+	/// Path to the database.
+	pub path: PathBuf,
+}
+
 /// The statement distribution subsystem.
-pub struct StatementDistribution;
+#[derive(Default)]
+pub struct StatementDistribution {
+	store: Option<StatementStore>,
+}
+
+impl StatementDistribution {
+	/// Create a new statement distribution subsystem which keeps statements in memory only.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create a new statement distribution subsystem which additionally keeps the statements
+	/// for non-finalized relay parents on disk, so they are known again after a restart.
+	pub fn new_with_store(config: StoreConfig) -> io::Result<Self> {
+		let db_config = DatabaseConfig::with_columns(1);
+
+		let path = config.path.to_str().ok_or_else(|| io::Error::new(
+			io::ErrorKind::Other,
+			format!("Bad database path: {:?}", config.path),
+		))?;
+
+		let db = Database::open(&db_config, &path)?;
+
+		Ok(Self {
+			store: Some(StatementStore { db: Arc::new(db) }),
+		})
+	}
+}
 
 impl<C> Subsystem<C> for StatementDistribution
 	where C: SubsystemContext<Message=StatementDistributionMessage>
@@ -72,9 +129,71 @@ impl<C> Subsystem<C> for StatementDistribution
 		// within `run`.
 		SpawnedSubsystem {
 			name: "statement-distribution-subsystem",
-			future: run(ctx).map(|_| ()).boxed(),
+			future: run(ctx, self.store).map(|_| ()).boxed(),
+		}
+	}
+}
+
+/// Signed statements we have accepted, persisted by relay parent.
+///
+/// Failing to read or write the store is not fatal: the statements are also gossiped, the store
+/// only saves us from waiting for that after a restart.
+struct StatementStore {
+	db: Arc<dyn KeyValueDB>,
+}
+
+impl StatementStore {
+	fn statement_key(relay_parent: &Hash, statement: &SignedFullStatement) -> Vec<u8> {
+		let mut key = relay_parent.as_ref().to_vec();
+		(statement.payload().to_compact(), statement.validator_index()).encode_to(&mut key);
+		key
+	}
+
+	/// Persist a statement we have accepted under the given relay parent.
+	fn write(&self, relay_parent: &Hash, statement: &SignedFullStatement) {
+		let mut tx = DBTransaction::new();
+		tx.put_vec(
+			STORE_COLUMN,
+			&Self::statement_key(relay_parent, statement),
+			statement.as_unchecked().encode(),
+		);
+
+		if let Err(e) = self.db.write(tx) {
+			log::warn!(target: "statement_distribution", "Failed to persist statement: {:?}", e);
 		}
 	}
+
+	/// Load all statements persisted under the given relay parent. Seconded statements come first.
+	fn load(&self, relay_parent: &Hash) -> Vec<UncheckedSignedFullStatement> {
+		let mut statements = self.db.iter_with_prefix(STORE_COLUMN, relay_parent.as_ref())
+			.filter_map(|(_, v)| UncheckedSignedFullStatement::decode(&mut &v[..]).ok())
+			.collect::<Vec<_>>();
+
+		statements.sort_by_key(|s| match s.payload().to_compact() {
+			CompactStatement::Candidate(_) => 0,
+			CompactStatement::Valid(_) | CompactStatement::Invalid(_) => 1,
+		});
+
+		statements
+	}
+
+	/// Remove all statements persisted under the given relay parent.
+	fn remove(&self, relay_parent: &Hash) {
+		let mut tx = DBTransaction::new();
+		tx.delete_prefix(STORE_COLUMN, relay_parent.as_ref());
+
+		if let Err(e) = self.db.write(tx) {
+			log::warn!(target: "statement_distribution", "Failed to remove statements: {:?}", e);
+		}
+	}
+
+	/// All relay parents we have statements persisted for.
+	fn relay_parents(&self) -> HashSet<Hash> {
+		self.db.iter(STORE_COLUMN)
+			.filter(|(k, _)| k.len() > Hash::len_bytes())
+			.map(|(k, _)| Hash::from_slice(&k[..Hash::len_bytes()]))
+			.collect()
+	}
 }
 
 /// Tracks our impression of a single peer's view of the candidates a validator has seconded
@@ -219,7 +338,7 @@ impl PeerRelayParentKnowledge {
 					.note_remote(h.clone());
 
 				if !allowed_remote {
-					return Err(COST_UNEXPECTED_STATEMENT);
+					return Err(COST_EXCESSIVE_SECONDED);
 				}
 
 				h
@@ -253,6 +372,7 @@ impl PeerRelayParentKnowledge {
 struct PeerData {
 	view: View,
 	view_knowledge: HashMap<Hash, PeerRelayParentKnowledge>,
+	connected_at: Instant,
 }
 
 impl PeerData {
@@ -464,16 +584,14 @@ impl ActiveHeadData {
 fn check_statement_signature(
 	head: &ActiveHeadData,
 	relay_parent: Hash,
-	statement: &SignedFullStatement,
-) -> Result<(), ()> {
+	statement: UncheckedSignedFullStatement,
+) -> Result<SignedFullStatement, ()> {
 	let signing_context = SigningContext {
 		session_index: head.session_index,
 		parent_hash: relay_parent,
 	};
 
-	head.validators.get(statement.validator_index() as usize)
-		.ok_or(())
-		.and_then(|v| statement.check_signature(&signing_context, v))
+	SignedFullStatement::check(statement, &signing_context, &head.validators).map_err(|_| ())
 }
 
 /// Places the statement in storage if it is new, and then
@@ -483,6 +601,7 @@ fn check_statement_signature(
 async fn circulate_statement_and_dependents(
 	peers: &mut HashMap<PeerId, PeerData>,
 	active_heads: &mut HashMap<Hash, ActiveHeadData>,
+	store: Option<&StatementStore>,
 	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
 	relay_parent: Hash,
 	statement: SignedFullStatement,
@@ -493,10 +612,16 @@ async fn circulate_statement_and_dependents(
 		// The borrow of `active_head` needs to encompass only this (Rust) statement.
 		let outputs: Option<(Hash, Vec<PeerId>)> = {
 			match active_head.note_statement(statement) {
-				NotedStatement::Fresh(stored) => Some((
-					stored.compact().candidate_hash().clone(),
-					circulate_statement(peers, ctx, relay_parent, stored).await?,
-				)),
+				NotedStatement::Fresh(stored) => {
+					if let Some(store) = store {
+						store.write(&relay_parent, &stored.statement);
+					}
+
+					Some((
+						stored.compact().candidate_hash().clone(),
+						circulate_statement(peers, ctx, relay_parent, stored).await?,
+					))
+				}
 				_ => None,
 			}
 		};
@@ -527,7 +652,7 @@ fn statement_message(relay_parent: Hash, statement: SignedFullStatement)
 	-> protocol_v1::ValidationProtocol
 {
 	protocol_v1::ValidationProtocol::StatementDistribution(
-		protocol_v1::StatementDistributionMessage::Statement(relay_parent, statement)
+		protocol_v1::StatementDistributionMessage::Statement(relay_parent, statement.into_unchecked())
 	)
 }
 
@@ -552,10 +677,10 @@ async fn circulate_statement(
 	// Send all these peers the initial statement.
 	if !peers_to_send.is_empty() {
 		let payload = statement_message(relay_parent, stored.statement.clone());
-		ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+		ctx.send_message(NetworkBridgeMessage::SendValidationMessage(
 			peers_to_send.keys().cloned().collect(),
 			payload,
-		))).await?;
+		)).await?;
 	}
 
 	Ok(peers_to_send.into_iter().filter_map(|(peer, needs_dependent)| if needs_dependent {
@@ -581,9 +706,7 @@ async fn send_statements_about(
 				statement.statement.clone(),
 			);
 
-			ctx.send_message(AllMessages::NetworkBridge(
-				NetworkBridgeMessage::SendValidationMessage(vec![peer.clone()], payload)
-			)).await?;
+			ctx.send_message(NetworkBridgeMessage::SendValidationMessage(vec![peer.clone()], payload)).await?;
 		}
 	}
 
@@ -605,9 +728,39 @@ async fn send_statements(
 				statement.statement.clone(),
 			);
 
-			ctx.send_message(AllMessages::NetworkBridge(
-				NetworkBridgeMessage::SendValidationMessage(vec![peer.clone()], payload)
-			)).await?;
+			ctx.send_message(NetworkBridgeMessage::SendValidationMessage(vec![peer.clone()], payload)).await?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Send all statements at the relay-parents in their views that peers which connected recently
+/// don't know about yet.
+///
+/// Statements are sent to a peer when its view changes. If we only learn about a relay-parent after
+/// a peer has put it into its view, which is common when many nodes restart at once, the peer
+/// would otherwise not get them until it changes its view again.
+async fn resend_to_new_peers(
+	peers: &mut HashMap<PeerId, PeerData>,
+	active_heads: &HashMap<Hash, ActiveHeadData>,
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+) -> SubsystemResult<()> {
+	for (peer, peer_data) in peers.iter_mut() {
+		if peer_data.connected_at.elapsed() > RESEND_PEER_WINDOW {
+			continue;
+		}
+
+		for relay_parent in peer_data.view.0.clone() {
+			if let Some(active_head) = active_heads.get(&relay_parent) {
+				send_statements(
+					peer.clone(),
+					peer_data,
+					ctx,
+					relay_parent,
+					active_head,
+				).await?;
+			}
 		}
 	}
 
@@ -619,9 +772,7 @@ async fn report_peer(
 	peer: PeerId,
 	rep: Rep,
 ) -> SubsystemResult<()> {
-	ctx.send_message(AllMessages::NetworkBridge(
-		NetworkBridgeMessage::ReportPeer(peer, rep)
-	)).await
+	ctx.send_message(NetworkBridgeMessage::ReportPeer(peer, rep)).await
 }
 
 // Handle an incoming wire message. Returns a reference to a newly-stored statement
@@ -656,9 +807,10 @@ async fn handle_incoming_message<'a>(
 	};
 
 	// check the signature on the statement.
-	if let Err(()) = check_statement_signature(&active_head, relay_parent, &statement) {
-		return report_peer(ctx, peer, COST_INVALID_SIGNATURE).await.map(|_| None);
-	}
+	let statement = match check_statement_signature(&active_head, relay_parent, statement) {
+		Ok(statement) => statement,
+		Err(()) => return report_peer(ctx, peer, COST_INVALID_SIGNATURE).await.map(|_| None),
+	};
 
 	// Ensure the statement is stored in the peer data.
 	//
@@ -739,6 +891,7 @@ async fn update_peer_view_and_send_unlocked(
 async fn handle_network_update(
 	peers: &mut HashMap<PeerId, PeerData>,
 	active_heads: &mut HashMap<Hash, ActiveHeadData>,
+	store: Option<&StatementStore>,
 	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
 	our_view: &mut View,
 	update: NetworkBridgeEvent<protocol_v1::StatementDistributionMessage>,
@@ -748,6 +901,7 @@ async fn handle_network_update(
 			peers.insert(peer, PeerData {
 				view: Default::default(),
 				view_knowledge: Default::default(),
+				connected_at: Instant::now(),
 			});
 
 			Ok(())
@@ -769,11 +923,13 @@ async fn handle_network_update(
 					).await?;
 
 					if let Some((relay_parent, new)) = new_stored {
+						if let Some(store) = store {
+							store.write(&relay_parent, &new.statement);
+						}
+
 						// When we receive a new message from a peer, we forward it to the
 						// candidate backing subsystem.
-						let message = AllMessages::CandidateBacking(
-							CandidateBackingMessage::Statement(relay_parent, new.statement.clone())
-						);
+						let message = CandidateBackingMessage::Statement(relay_parent, new.statement.clone());
 						ctx.send_message(message).await?;
 					}
 
@@ -815,33 +971,119 @@ async fn handle_network_update(
 
 }
 
+/// Remove the persisted statements of all relay parents which are finalized or unknown.
+async fn prune_statement_store(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	store: &StatementStore,
+) -> SubsystemResult<()> {
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(ChainApiMessage::FinalizedBlockNumber(tx)).await?;
+
+	let finalized = match rx.await? {
+		Ok(number) => number,
+		Err(e) => {
+			log::warn!(
+				target: "statement_distribution",
+				"Failed to fetch the finalized block number to prune the statement store: {:?}",
+				e,
+			);
+			return Ok(());
+		}
+	};
+
+	for relay_parent in store.relay_parents() {
+		let (tx, rx) = oneshot::channel();
+		ctx.send_message(ChainApiMessage::BlockNumber(relay_parent, tx))
+			.await?;
+
+		match rx.await? {
+			Ok(Some(number)) if number > finalized => {}
+			_ => store.remove(&relay_parent),
+		}
+	}
+
+	Ok(())
+}
+
+/// Import the statements persisted for a newly active relay parent and hand the ones we didn't
+/// know about to candidate backing.
+async fn import_stored_statements(
+	active_head: &mut ActiveHeadData,
+	store: &StatementStore,
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	relay_parent: Hash,
+) -> SubsystemResult<()> {
+	for statement in store.load(&relay_parent) {
+		// The validator set is the same as when the statement was stored, but we check
+		// anyway as the database isn't trusted any more than the network.
+		let statement = match check_statement_signature(&*active_head, relay_parent, statement) {
+			Ok(statement) => statement,
+			Err(()) => continue,
+		};
+
+		if let NotedStatement::Fresh(stored) = active_head.note_statement(statement) {
+			ctx.send_message(CandidateBackingMessage::Statement(relay_parent, stored.statement.clone())).await?;
+		}
+	}
+
+	Ok(())
+}
+
 async fn run(
 	mut ctx: impl SubsystemContext<Message = StatementDistributionMessage>,
+	store: Option<StatementStore>,
 ) -> SubsystemResult<()> {
 	let mut peers: HashMap<PeerId, PeerData> = HashMap::new();
 	let mut our_view = View::default();
 	let mut active_heads: HashMap<Hash, ActiveHeadData> = HashMap::new();
+	let mut last_finalized = Instant::now();
+	let mut resend_timer = Delay::new(RESEND_INTERVAL).fuse();
+
+	if let Some(ref store) = store {
+		prune_statement_store(&mut ctx, store).await?;
+	}
 
 	loop {
-		let message = ctx.recv().await?;
+		let message = {
+			let recv = ctx.recv().fuse();
+			futures::pin_mut!(recv);
+
+			futures::select! {
+				message = recv => Some(message?),
+				_ = resend_timer => None,
+			}
+		};
+
+		let message = match message {
+			Some(message) => message,
+			None => {
+				resend_timer = Delay::new(RESEND_INTERVAL).fuse();
+
+				if last_finalized.elapsed() >= FINALITY_STALL_THRESHOLD {
+					resend_to_new_peers(&mut peers, &active_heads, &mut ctx).await?;
+				}
+
+				continue;
+			}
+		};
+
 		match message {
-			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, .. })) => {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate {
+				activated,
+				deactivated,
+			})) => {
 				for relay_parent in activated {
 					let (validators, session_index) = {
 						let (val_tx, val_rx) = oneshot::channel();
 						let (session_tx, session_rx) = oneshot::channel();
 
-						let val_message = AllMessages::RuntimeApi(
-							RuntimeApiMessage::Request(
-								relay_parent,
-								RuntimeApiRequest::Validators(val_tx),
-							),
+						let val_message = RuntimeApiMessage::Request(
+							relay_parent,
+							RuntimeApiRequest::Validators(val_tx),
 						);
-						let session_message = AllMessages::RuntimeApi(
-							RuntimeApiMessage::Request(
-								relay_parent,
-								RuntimeApiRequest::SessionIndexForChild(session_tx),
-							),
+						let session_message = RuntimeApiMessage::Request(
+							relay_parent,
+							RuntimeApiRequest::SessionIndexForChild(session_tx),
 						);
 
 						ctx.send_messages(
@@ -865,12 +1107,22 @@ async fn run(
 						}
 					};
 
-					active_heads.entry(relay_parent)
+					let active_head = active_heads.entry(relay_parent)
 						.or_insert(ActiveHeadData::new(validators, session_index));
+
+					if let Some(ref store) = store {
+						import_stored_statements(active_head, store, &mut ctx, relay_parent).await?;
+					}
+				}
+
+				if let Some(ref store) = store {
+					for relay_parent in deactivated {
+						store.remove(&relay_parent);
+					}
 				}
 			}
-			FromOverseer::Signal(OverseerSignal::BlockFinalized(_block_hash)) => {
-				// do nothing
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {
+				last_finalized = Instant::now();
 			}
 			FromOverseer::Signal(OverseerSignal::Conclude) => break,
 			FromOverseer::Communication { msg } => match msg {
@@ -878,6 +1130,7 @@ async fn run(
 					circulate_statement_and_dependents(
 						&mut peers,
 						&mut active_heads,
+						store.as_ref(),
 						&mut ctx,
 						relay_parent,
 						statement,
@@ -886,6 +1139,7 @@ async fn run(
 					handle_network_update(
 						&mut peers,
 						&mut active_heads,
+						store.as_ref(),
 						&mut ctx,
 						&mut our_view,
 						event,
@@ -899,6 +1153,7 @@ async fn run(
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use polkadot_subsystem::messages::AllMessages;
 	use sp_keyring::Sr25519Keyring;
 	use node_primitives::Statement;
 	use polkadot_primitives::v1::CommittedCandidateReceipt;
@@ -1000,6 +1255,89 @@ mod tests {
 		assert_matches!(noted, NotedStatement::Fresh(_));
 	}
 
+	#[test]
+	fn stored_statements_are_imported_for_active_head() {
+		let validators = vec![
+			Sr25519Keyring::Alice.public().into(),
+			Sr25519Keyring::Bob.public().into(),
+		];
+		let parent_hash: Hash = [1; 32].into();
+		let other_parent_hash: Hash = [2; 32].into();
+
+		let session_index = 1;
+		let signing_context = SigningContext {
+			parent_hash,
+			session_index,
+		};
+
+		let candidate = {
+			let mut c = CommittedCandidateReceipt::default();
+			c.descriptor.relay_parent = parent_hash;
+			c.descriptor.para_id = 1.into();
+			c
+		};
+
+		let seconded = SignedFullStatement::sign(
+			Statement::Seconded(candidate.clone()),
+			&signing_context,
+			0,
+			&Sr25519Keyring::Alice.pair().into(),
+		);
+		let valid = SignedFullStatement::sign(
+			Statement::Valid(candidate.hash()),
+			&signing_context,
+			1,
+			&Sr25519Keyring::Bob.pair().into(),
+		);
+
+		let store = StatementStore { db: Arc::new(kvdb_memorydb::create(1)) };
+
+		// written in an order in which they couldn't be noted.
+		store.write(&parent_hash, &valid);
+		store.write(&parent_hash, &seconded);
+		store.write(&other_parent_hash, &seconded);
+
+		assert_eq!(
+			store.relay_parents(),
+			vec![parent_hash, other_parent_hash].into_iter().collect::<HashSet<_>>(),
+		);
+
+		let pool = sp_core::testing::TaskExecutor::new();
+		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let mut head_data = ActiveHeadData::new(validators, session_index);
+
+		executor::block_on(async move {
+			import_stored_statements(&mut head_data, &store, &mut ctx, parent_hash).await.unwrap();
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::CandidateBacking(
+					CandidateBackingMessage::Statement(r, s)
+				) => {
+					assert_eq!(r, parent_hash);
+					assert_eq!(s, seconded);
+				}
+			);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::CandidateBacking(
+					CandidateBackingMessage::Statement(r, s)
+				) => {
+					assert_eq!(r, parent_hash);
+					assert_eq!(s, valid);
+				}
+			);
+
+			assert_eq!(head_data.statements().count(), 2);
+
+			store.remove(&parent_hash);
+
+			assert!(store.load(&parent_hash).is_empty());
+			assert_eq!(store.load(&other_parent_hash).len(), 1);
+		});
+	}
+
 	#[test]
 	fn note_local_works() {
 		let hash_a: Hash = [1; 32].into();
@@ -1128,7 +1466,7 @@ mod tests {
 
 		assert_eq!(
 			knowledge.receive(&(CompactStatement::Candidate(hash_c), 0), 3),
-			Err(COST_UNEXPECTED_STATEMENT),
+			Err(COST_EXCESSIVE_SECONDED),
 		);
 
 		// Last, make sure that already-known statements are disregarded.
@@ -1218,6 +1556,7 @@ mod tests {
 
 				k
 			},
+			connected_at: Instant::now(),
 		};
 
 		let pool = sp_core::testing::TaskExecutor::new();
@@ -1301,6 +1640,7 @@ mod tests {
 		let peer_data_from_view = |view: View| PeerData {
 			view: view.clone(),
 			view_knowledge: view.0.iter().map(|v| (v.clone(), Default::default())).collect(),
+			connected_at: Instant::now(),
 		};
 
 		let mut peer_data: HashMap<_, _> = vec![
@@ -1382,4 +1722,77 @@ mod tests {
 			)
 		});
 	}
+
+	#[test]
+	fn statements_are_resent_to_new_peers_only() {
+		let hash_a = [1; 32].into();
+
+		let candidate = {
+			let mut c = CommittedCandidateReceipt::default();
+			c.descriptor.relay_parent = hash_a;
+			c.descriptor.para_id = 1.into();
+			c
+		};
+
+		let session_index = 1;
+		let signing_context = SigningContext {
+			parent_hash: hash_a,
+			session_index,
+		};
+
+		let mut active_heads = HashMap::new();
+		active_heads.insert(hash_a, {
+			let validators = vec![Sr25519Keyring::Alice.public().into()];
+			let mut data = ActiveHeadData::new(validators, session_index);
+
+			let noted = data.note_statement(SignedFullStatement::sign(
+				Statement::Seconded(candidate),
+				&signing_context,
+				0,
+				&Sr25519Keyring::Alice.pair().into(),
+			));
+
+			assert_matches!(noted, NotedStatement::Fresh(_));
+
+			data
+		});
+
+		let peer_data_connected_at = |connected_at| PeerData {
+			view: View(vec![hash_a]),
+			view_knowledge: vec![(hash_a, Default::default())].into_iter().collect(),
+			connected_at,
+		};
+
+		let new_peer = PeerId::random();
+		let old_peer = PeerId::random();
+
+		let mut peers: HashMap<_, _> = vec![
+			(new_peer.clone(), peer_data_connected_at(Instant::now())),
+			(old_peer.clone(), peer_data_connected_at(Instant::now() - RESEND_PEER_WINDOW * 2)),
+		].into_iter().collect();
+
+		let pool = sp_core::testing::TaskExecutor::new();
+		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+
+		executor::block_on(async move {
+			resend_to_new_peers(&mut peers, &active_heads, &mut ctx).await.unwrap();
+			// statements the peer already got are not sent again.
+			resend_to_new_peers(&mut peers, &active_heads, &mut ctx).await.unwrap();
+			drop(ctx);
+
+			let statement = active_heads.get(&hash_a).unwrap().statements().next().unwrap();
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+					to,
+					payload,
+				)) => {
+					assert_eq!(to, vec![new_peer]);
+					assert_eq!(payload, statement_message(hash_a, statement.statement.clone()));
+				}
+			);
+
+			assert!(handle.try_recv().await.is_none());
+		});
+	}
 }
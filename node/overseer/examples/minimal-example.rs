@@ -31,11 +31,11 @@ use polkadot_primitives::v1::{BlockData, PoV};
 use polkadot_overseer::{Overseer, AllSubsystems};
 
 use polkadot_subsystem::{
-	Subsystem, SubsystemContext, DummySubsystem,
+	Subsystem, SubsystemContext,
 	SpawnedSubsystem, FromOverseer,
 };
 use polkadot_subsystem::messages::{
-	CandidateValidationMessage, CandidateBackingMessage, AllMessages,
+	CandidateValidationMessage, CandidateBackingMessage,
 };
 
 struct Subsystem1;
@@ -60,14 +60,12 @@ impl Subsystem1 {
 			Delay::new(Duration::from_secs(1)).await;
 			let (tx, _) = oneshot::channel();
 
-			ctx.send_message(AllMessages::CandidateValidation(
-				CandidateValidationMessage::ValidateFromChainState(
-					Default::default(),
-					PoV {
-						block_data: BlockData(Vec::new()),
-					}.into(),
-					tx,
-				)
+			ctx.send_message(CandidateValidationMessage::ValidateFromChainState(
+				Default::default(),
+				PoV {
+					block_data: BlockData(Vec::new()),
+				}.into(),
+				tx,
 			)).await.unwrap();
 		}
 	}
@@ -145,23 +143,9 @@ fn main() {
 			Delay::new(Duration::from_secs(1)).await;
 		});
 
-		let all_subsystems = AllSubsystems {
-			candidate_validation: Subsystem2,
-			candidate_backing: Subsystem1,
-			candidate_selection: DummySubsystem,
-			statement_distribution: DummySubsystem,
-			availability_distribution: DummySubsystem,
-			bitfield_signing: DummySubsystem,
-			bitfield_distribution: DummySubsystem,
-			provisioner: DummySubsystem,
-			pov_distribution: DummySubsystem,
-			runtime_api: DummySubsystem,
-			availability_store: DummySubsystem,
-			network_bridge: DummySubsystem,
-			chain_api: DummySubsystem,
-			collation_generation: DummySubsystem,
-			collator_protocol: DummySubsystem,
-		};
+		let all_subsystems = AllSubsystems::dummy()
+			.replace_candidate_validation(Subsystem2)
+			.replace_candidate_backing(Subsystem1);
 		let (overseer, _handler) = Overseer::new(
 			vec![],
 			all_subsystems,
@@ -57,9 +57,10 @@
 use std::fmt::Debug;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::task::Poll;
-use std::time::Duration;
-use std::collections::{hash_map, HashMap};
+use std::time::{Duration, Instant};
+use std::collections::{hash_map, HashMap, HashSet};
 
 use futures::channel::{mpsc, oneshot};
 use futures::{
@@ -73,6 +74,7 @@ use streamunordered::{StreamYield, StreamUnordered};
 
 use polkadot_primitives::v1::{Block, BlockNumber, Hash};
 use client::{BlockImportNotification, BlockchainEvents, FinalityNotification};
+use sp_consensus::SyncOracle;
 
 use polkadot_subsystem::messages::{
 	CandidateValidationMessage, CandidateBackingMessage,
@@ -80,10 +82,11 @@ use polkadot_subsystem::messages::{
 	AvailabilityDistributionMessage, BitfieldSigningMessage, BitfieldDistributionMessage,
 	ProvisionerMessage, PoVDistributionMessage, RuntimeApiMessage,
 	AvailabilityStoreMessage, NetworkBridgeMessage, AllMessages, CollationGenerationMessage, CollatorProtocolMessage,
+	AssociateOutgoing,
 };
 pub use polkadot_subsystem::{
 	Subsystem, SubsystemContext, OverseerSignal, FromOverseer, SubsystemError, SubsystemResult,
-	SpawnedSubsystem, ActiveLeavesUpdate,
+	SpawnedSubsystem, ActiveLeavesUpdate, DummySubsystem, OutgoingMessages,
 	metrics::{self, prometheus},
 };
 use polkadot_node_primitives::SpawnNamed;
@@ -91,10 +94,36 @@ use polkadot_node_primitives::SpawnNamed;
 
 // A capacity of bounded channels inside the overseer.
 const CHANNEL_CAPACITY: usize = 1024;
-// A graceful `Overseer` teardown time delay.
-const STOP_DELAY: u64 = 1;
+// The time a subsystem has to conclude when the `Overseer` stops.
+const STOP_DELAY: Duration = Duration::from_secs(1);
+// The time a subsystem with persistent state has to conclude, so it can finish its writes.
+const PERSISTENT_STOP_DELAY: Duration = Duration::from_secs(5);
+
+/// The stages the subsystems are concluded in when the `Overseer` stops.
+///
+/// Subsystems which may cause writes to persistent state are concluded first, then the subsystems
+/// holding persistent state and finally those giving access to the network and the chain, which
+/// all others may depend on.
+const STOP_STAGES: &[&[SubsystemKind]] = &[
+	&[
+		SubsystemKind::CandidateValidation,
+		SubsystemKind::CandidateBacking,
+		SubsystemKind::CandidateSelection,
+		SubsystemKind::AvailabilityDistribution,
+		SubsystemKind::BitfieldSigning,
+		SubsystemKind::BitfieldDistribution,
+		SubsystemKind::Provisioner,
+		SubsystemKind::PoVDistribution,
+		SubsystemKind::CollationGeneration,
+		SubsystemKind::CollatorProtocol,
+	],
+	&[SubsystemKind::StatementDistribution, SubsystemKind::AvailabilityStore],
+	&[SubsystemKind::RuntimeApi, SubsystemKind::ChainApi, SubsystemKind::NetworkBridge],
+];
 // Target for logs.
 const LOG_TARGET: &'static str = "overseer";
+// Target for the logs of routed messages.
+const MESSAGES_LOG_TARGET: &'static str = "overseer::messages";
 
 
 /// A type of messages that are sent from [`Subsystem`] to [`Overseer`].
@@ -224,14 +253,21 @@ impl OverseerHandler {
 /// Glues together the [`Overseer`] and `BlockchainEvents` by forwarding
 /// import and finality notifications into the [`OverseerHandler`].
 ///
+/// Blocks imported while the `sync_oracle` reports a major sync are not forwarded. They are
+/// historic blocks, e.g. downloaded after syncing from a snapshot, so subsystems shouldn't do
+/// availability or backing work for them. Finality notifications are always forwarded, so the
+/// subsystems start out from the latest finalized block once the major sync is over.
+///
 /// [`Overseer`]: struct.Overseer.html
 /// [`OverseerHandler`]: struct.OverseerHandler.html
 pub async fn forward_events<P: BlockchainEvents<Block>>(
 	client: Arc<P>,
 	mut handler: OverseerHandler,
+	mut sync_oracle: impl SyncOracle,
 ) -> SubsystemResult<()> {
 	let mut finality = client.finality_notification_stream();
 	let mut imports = client.import_notification_stream();
+	let mut was_syncing = false;
 
 	loop {
 		select! {
@@ -246,7 +282,20 @@ pub async fn forward_events<P: BlockchainEvents<Block>>(
 			i = imports.next() => {
 				match i {
 					Some(block) => {
-						handler.block_imported(block.into()).await?;
+						let is_syncing = sync_oracle.is_major_syncing();
+						if is_syncing != was_syncing {
+							log::info!(
+								target: LOG_TARGET,
+								"Major sync {}, block #{}",
+								if is_syncing { "started: ignoring imported blocks" } else { "done" },
+								block.header.number,
+							);
+							was_syncing = is_syncing;
+						}
+
+						if !is_syncing {
+							handler.block_imported(block.into()).await?;
+						}
 					}
 					None => break,
 				}
@@ -270,11 +319,35 @@ impl Debug for ToOverseer {
 	}
 }
 
+/// A message queued for a subsystem, along with the time the overseer started sending it.
+#[derive(Debug)]
+struct TimedMessage<M> {
+	msg: FromOverseer<M>,
+	sent_at: Instant,
+}
+
 /// A running instance of some [`Subsystem`].
 ///
 /// [`Subsystem`]: trait.Subsystem.html
 struct SubsystemInstance<M> {
-	tx: mpsc::Sender<FromOverseer<M>>,
+	tx: mpsc::Sender<TimedMessage<M>>,
+	/// The number of messages sent to the subsystem that it didn't receive yet.
+	queued: Arc<AtomicUsize>,
+	meters: SubsystemMeters,
+}
+
+impl<M> SubsystemInstance<M> {
+	async fn send(&mut self, msg: FromOverseer<M>) -> SubsystemResult<()> {
+		let queued = self.queued.fetch_add(1, Ordering::Relaxed);
+		self.meters.on_message_enqueued(queued);
+
+		let res = self.tx.send(TimedMessage { msg, sent_at: Instant::now() }).await;
+		if res.is_err() {
+			self.queued.fetch_sub(1, Ordering::Relaxed);
+		}
+
+		res.map_err(Into::into)
+	}
 }
 
 /// A context type that is given to the [`Subsystem`] upon spawning.
@@ -286,24 +359,46 @@ struct SubsystemInstance<M> {
 /// [`SubsystemJob`]: trait.SubsystemJob.html
 #[derive(Debug)]
 pub struct OverseerSubsystemContext<M>{
-	rx: mpsc::Receiver<FromOverseer<M>>,
+	rx: mpsc::Receiver<TimedMessage<M>>,
 	tx: mpsc::Sender<ToOverseer>,
+	queued: Arc<AtomicUsize>,
+	/// When the message the subsystem is currently handling was sent.
+	in_flight: Option<Instant>,
+	meters: SubsystemMeters,
+}
+
+impl<M> OverseerSubsystemContext<M> {
+	// A subsystem asks for its next message once it is done handling the previous one.
+	fn on_message_handled(&mut self) {
+		if let Some(sent_at) = self.in_flight.take() {
+			self.meters.on_message_processed(sent_at.elapsed());
+		}
+	}
+
+	fn on_message_received(&mut self, msg: TimedMessage<M>) -> FromOverseer<M> {
+		self.queued.fetch_sub(1, Ordering::Relaxed);
+		self.in_flight = Some(msg.sent_at);
+		msg.msg
+	}
 }
 
 #[async_trait::async_trait]
-impl<M: Send + 'static> SubsystemContext for OverseerSubsystemContext<M> {
+impl<M: AssociateOutgoing + Send + 'static> SubsystemContext for OverseerSubsystemContext<M> {
 	type Message = M;
 
 	async fn try_recv(&mut self) -> Result<Option<FromOverseer<M>>, ()> {
+		self.on_message_handled();
 		match poll!(self.rx.next()) {
-			Poll::Ready(Some(msg)) => Ok(Some(msg)),
+			Poll::Ready(Some(msg)) => Ok(Some(self.on_message_received(msg))),
 			Poll::Ready(None) => Err(()),
 			Poll::Pending => Ok(None),
 		}
 	}
 
 	async fn recv(&mut self) -> SubsystemResult<FromOverseer<M>> {
-		self.rx.next().await.ok_or(SubsystemError)
+		self.on_message_handled();
+		let msg = self.rx.next().await.ok_or(SubsystemError)?;
+		Ok(self.on_message_received(msg))
 	}
 
 	async fn spawn(&mut self, name: &'static str, s: Pin<Box<dyn Future<Output = ()> + Send>>)
@@ -324,14 +419,23 @@ impl<M: Send + 'static> SubsystemContext for OverseerSubsystemContext<M> {
 		}).await.map_err(Into::into)
 	}
 
-	async fn send_message(&mut self, msg: AllMessages) -> SubsystemResult<()> {
-		self.tx.send(ToOverseer::SubsystemMessage(msg)).await.map_err(Into::into)
+	async fn send_message<T>(&mut self, msg: T) -> SubsystemResult<()>
+		where T: Into<OutgoingMessages<M>> + Send
+	{
+		let msg: OutgoingMessages<M> = msg.into();
+		self.tx.send(ToOverseer::SubsystemMessage(msg.into())).await.map_err(Into::into)
 	}
 
 	async fn send_messages<T>(&mut self, msgs: T) -> SubsystemResult<()>
-		where T: IntoIterator<Item = AllMessages> + Send, T::IntoIter: Send
+		where
+			T: IntoIterator + Send,
+			T::IntoIter: Send,
+			T::Item: Into<OutgoingMessages<M>> + Send,
 	{
-		let mut msgs = stream::iter(msgs.into_iter().map(ToOverseer::SubsystemMessage).map(Ok));
+		let mut msgs = stream::iter(msgs.into_iter().map(|msg| {
+			let msg: OutgoingMessages<M> = msg.into();
+			Ok(ToOverseer::SubsystemMessage(msg.into()))
+		}));
 		self.tx.send_all(&mut msgs).await.map_err(Into::into)
 	}
 }
@@ -347,6 +451,15 @@ struct OverseenSubsystem<M> {
 	instance: Option<SubsystemInstance<M>>,
 }
 
+impl<M> OverseenSubsystem<M> {
+	/// Send `Conclude` to the subsystem, if it is running.
+	async fn conclude(&mut self) {
+		if let Some(ref mut s) = self.instance {
+			let _ = s.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		}
+	}
+}
+
 /// The `Overseer` itself.
 pub struct Overseer<S: SpawnNamed> {
 	/// A candidate validation subsystem.
@@ -398,7 +511,8 @@ pub struct Overseer<S: SpawnNamed> {
 	s: S,
 
 	/// Here we keep handles to spawned subsystems to be notified when they terminate.
-	running_subsystems: FuturesUnordered<BoxFuture<'static, ()>>,
+	/// Each handle resolves to the name of its subsystem.
+	running_subsystems: FuturesUnordered<BoxFuture<'static, SubsystemKind>>,
 
 	/// Gather running subsystms' outbound streams into one.
 	running_subsystems_rx: StreamUnordered<mpsc::Receiver<ToOverseer>>,
@@ -419,6 +533,9 @@ pub struct Overseer<S: SpawnNamed> {
 
 	/// Various Prometheus metrics.
 	metrics: Metrics,
+
+	/// Whether to log every message routed between subsystems.
+	log_messages: bool,
 }
 
 /// This struct is passed as an argument to create a new instance of an [`Overseer`].
@@ -465,11 +582,328 @@ pub struct AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG
 	pub collator_protocol: CP,
 }
 
+impl AllSubsystems<DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem,
+	DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem,
+	DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem>
+{
+	/// Create a new instance of [`AllSubsystems`].
+	///
+	/// Each subsystem is set to [`DummySubsystem`]. Use the `replace_*` methods to plug in
+	/// the real subsystems.
+	pub fn dummy() -> Self {
+		Self {
+			candidate_validation: DummySubsystem,
+			candidate_backing: DummySubsystem,
+			candidate_selection: DummySubsystem,
+			statement_distribution: DummySubsystem,
+			availability_distribution: DummySubsystem,
+			bitfield_signing: DummySubsystem,
+			bitfield_distribution: DummySubsystem,
+			provisioner: DummySubsystem,
+			pov_distribution: DummySubsystem,
+			runtime_api: DummySubsystem,
+			availability_store: DummySubsystem,
+			network_bridge: DummySubsystem,
+			chain_api: DummySubsystem,
+			collation_generation: DummySubsystem,
+			collator_protocol: DummySubsystem,
+		}
+	}
+}
+
+/// Generates the `replace_*` methods of [`AllSubsystems`].
+///
+/// `fields` lists every field of [`AllSubsystems`], each method names the field it replaces
+/// and the type of [`AllSubsystems`] it returns.
+macro_rules! replace_subsystems {
+	(
+		fields: $fields:tt;
+		$( $(#[$meta:meta])* fn $name:ident($field:ident) -> AllSubsystems<$($out:ident),*>; )*
+	) => {
+		$( replace_subsystems!(@method $(#[$meta])* $name, $field, ($($out),*), $fields); )*
+	};
+	(@method $(#[$meta:meta])* $name:ident, $field:ident, ($($out:ident),*), { $($all:ident),* $(,)? }) => {
+		$(#[$meta])*
+		pub fn $name<NEW>(self, replacement: NEW) -> AllSubsystems<$($out),*> {
+			let AllSubsystems { $($all),* } = self;
+			drop($field);
+			let $field = replacement;
+			AllSubsystems { $($all),* }
+		}
+	};
+}
+
+impl<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>
+	AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>
+{
+	replace_subsystems! {
+		fields: {
+			candidate_validation, candidate_backing, candidate_selection, statement_distribution,
+			availability_distribution, bitfield_signing, bitfield_distribution, provisioner,
+			pov_distribution, runtime_api, availability_store, network_bridge, chain_api,
+			collation_generation, collator_protocol,
+		};
+
+		/// Replace the candidate validation subsystem in `self`.
+		fn replace_candidate_validation(candidate_validation)
+			-> AllSubsystems<NEW, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the candidate backing subsystem in `self`.
+		fn replace_candidate_backing(candidate_backing)
+			-> AllSubsystems<CV, NEW, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the candidate selection subsystem in `self`.
+		fn replace_candidate_selection(candidate_selection)
+			-> AllSubsystems<CV, CB, NEW, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the statement distribution subsystem in `self`.
+		fn replace_statement_distribution(statement_distribution)
+			-> AllSubsystems<CV, CB, CS, NEW, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the availability distribution subsystem in `self`.
+		fn replace_availability_distribution(availability_distribution)
+			-> AllSubsystems<CV, CB, CS, SD, NEW, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the bitfield signing subsystem in `self`.
+		fn replace_bitfield_signing(bitfield_signing)
+			-> AllSubsystems<CV, CB, CS, SD, AD, NEW, BD, P, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the bitfield distribution subsystem in `self`.
+		fn replace_bitfield_distribution(bitfield_distribution)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, NEW, P, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the provisioner subsystem in `self`.
+		fn replace_provisioner(provisioner)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, NEW, PoVD, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the PoV distribution subsystem in `self`.
+		fn replace_pov_distribution(pov_distribution)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, NEW, RA, AS, NB, CA, CG, CP>;
+
+		/// Replace the runtime API subsystem in `self`.
+		fn replace_runtime_api(runtime_api)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, NEW, AS, NB, CA, CG, CP>;
+
+		/// Replace the availability store subsystem in `self`.
+		fn replace_availability_store(availability_store)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, NEW, NB, CA, CG, CP>;
+
+		/// Replace the network bridge subsystem in `self`.
+		fn replace_network_bridge(network_bridge)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NEW, CA, CG, CP>;
+
+		/// Replace the Chain API subsystem in `self`.
+		fn replace_chain_api(chain_api)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, NEW, CG, CP>;
+
+		/// Replace the Collation Generation subsystem in `self`.
+		fn replace_collation_generation(collation_generation)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, NEW, CP>;
+
+		/// Replace the Collator Protocol subsystem in `self`.
+		fn replace_collator_protocol(collator_protocol)
+			-> AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, NEW>;
+	}
+
+	/// Replace each subsystem named in `disabled` with a [`DummySubsystem`].
+	///
+	/// This lets node operators isolate misbehaving subsystems. Only the subsystems for which
+	/// [`SubsystemKind::is_disableable`] holds can be disabled.
+	pub fn disable(self, disabled: &[String]) -> Result<AllSubsystems<
+		MaybeDisabled<CV>, MaybeDisabled<CB>, MaybeDisabled<CS>, MaybeDisabled<SD>, MaybeDisabled<AD>,
+		MaybeDisabled<BS>, MaybeDisabled<BD>, MaybeDisabled<P>, MaybeDisabled<PoVD>, MaybeDisabled<RA>,
+		MaybeDisabled<AS>, MaybeDisabled<NB>, MaybeDisabled<CA>, MaybeDisabled<CG>, MaybeDisabled<CP>,
+	>, CannotDisable> {
+		let mut disabled_kinds = HashSet::new();
+		for name in disabled {
+			match name.parse::<SubsystemKind>() {
+				Ok(kind) if kind.is_disableable() => { disabled_kinds.insert(kind); }
+				_ => return Err(CannotDisable(name.clone())),
+			}
+		}
+
+		let is_disabled = |kind: SubsystemKind| disabled_kinds.contains(&kind);
+
+		Ok(AllSubsystems {
+			candidate_validation: MaybeDisabled::new(self.candidate_validation, is_disabled(SubsystemKind::CandidateValidation)),
+			candidate_backing: MaybeDisabled::new(self.candidate_backing, is_disabled(SubsystemKind::CandidateBacking)),
+			candidate_selection: MaybeDisabled::new(self.candidate_selection, is_disabled(SubsystemKind::CandidateSelection)),
+			statement_distribution: MaybeDisabled::new(
+				self.statement_distribution,
+				is_disabled(SubsystemKind::StatementDistribution),
+			),
+			availability_distribution: MaybeDisabled::new(
+				self.availability_distribution,
+				is_disabled(SubsystemKind::AvailabilityDistribution),
+			),
+			bitfield_signing: MaybeDisabled::new(self.bitfield_signing, is_disabled(SubsystemKind::BitfieldSigning)),
+			bitfield_distribution: MaybeDisabled::new(
+				self.bitfield_distribution,
+				is_disabled(SubsystemKind::BitfieldDistribution),
+			),
+			provisioner: MaybeDisabled::new(self.provisioner, is_disabled(SubsystemKind::Provisioner)),
+			pov_distribution: MaybeDisabled::new(self.pov_distribution, is_disabled(SubsystemKind::PoVDistribution)),
+			runtime_api: MaybeDisabled::new(self.runtime_api, is_disabled(SubsystemKind::RuntimeApi)),
+			availability_store: MaybeDisabled::new(self.availability_store, is_disabled(SubsystemKind::AvailabilityStore)),
+			network_bridge: MaybeDisabled::new(self.network_bridge, is_disabled(SubsystemKind::NetworkBridge)),
+			chain_api: MaybeDisabled::new(self.chain_api, is_disabled(SubsystemKind::ChainApi)),
+			collation_generation: MaybeDisabled::new(
+				self.collation_generation,
+				is_disabled(SubsystemKind::CollationGeneration),
+			),
+			collator_protocol: MaybeDisabled::new(self.collator_protocol, is_disabled(SubsystemKind::CollatorProtocol)),
+		})
+	}
+}
+
+/// The subsystems run by the [`Overseer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubsystemKind {
+	/// The candidate validation subsystem.
+	CandidateValidation,
+	/// The candidate backing subsystem.
+	CandidateBacking,
+	/// The candidate selection subsystem.
+	CandidateSelection,
+	/// The statement distribution subsystem.
+	StatementDistribution,
+	/// The availability distribution subsystem.
+	AvailabilityDistribution,
+	/// The bitfield signing subsystem.
+	BitfieldSigning,
+	/// The bitfield distribution subsystem.
+	BitfieldDistribution,
+	/// The provisioner subsystem.
+	Provisioner,
+	/// The PoV distribution subsystem.
+	PoVDistribution,
+	/// The runtime API subsystem.
+	RuntimeApi,
+	/// The availability store subsystem.
+	AvailabilityStore,
+	/// The network bridge subsystem.
+	NetworkBridge,
+	/// The chain API subsystem.
+	ChainApi,
+	/// The collation generation subsystem.
+	CollationGeneration,
+	/// The collator protocol subsystem.
+	CollatorProtocol,
+}
+
+impl SubsystemKind {
+	/// All subsystems.
+	pub const ALL: [SubsystemKind; 15] = [
+		SubsystemKind::CandidateValidation,
+		SubsystemKind::CandidateBacking,
+		SubsystemKind::CandidateSelection,
+		SubsystemKind::StatementDistribution,
+		SubsystemKind::AvailabilityDistribution,
+		SubsystemKind::BitfieldSigning,
+		SubsystemKind::BitfieldDistribution,
+		SubsystemKind::Provisioner,
+		SubsystemKind::PoVDistribution,
+		SubsystemKind::RuntimeApi,
+		SubsystemKind::AvailabilityStore,
+		SubsystemKind::NetworkBridge,
+		SubsystemKind::ChainApi,
+		SubsystemKind::CollationGeneration,
+		SubsystemKind::CollatorProtocol,
+	];
+
+	/// The name of the subsystem, which its task and metrics are labelled with and which
+	/// [`AllSubsystems::disable`] accepts.
+	pub fn name(self) -> &'static str {
+		match self {
+			SubsystemKind::CandidateValidation => "candidate-validation",
+			SubsystemKind::CandidateBacking => "candidate-backing",
+			SubsystemKind::CandidateSelection => "candidate-selection",
+			SubsystemKind::StatementDistribution => "statement-distribution",
+			SubsystemKind::AvailabilityDistribution => "availability-distribution",
+			SubsystemKind::BitfieldSigning => "bitfield-signing",
+			SubsystemKind::BitfieldDistribution => "bitfield-distribution",
+			SubsystemKind::Provisioner => "provisioner",
+			SubsystemKind::PoVDistribution => "pov-distribution",
+			SubsystemKind::RuntimeApi => "runtime-api",
+			SubsystemKind::AvailabilityStore => "availability-store",
+			SubsystemKind::NetworkBridge => "network-bridge",
+			SubsystemKind::ChainApi => "chain-api",
+			SubsystemKind::CollationGeneration => "collation-generation",
+			SubsystemKind::CollatorProtocol => "collator-protocol",
+		}
+	}
+
+	/// Whether [`AllSubsystems::disable`] accepts the subsystem.
+	///
+	/// The remaining subsystems serve requests that the rest of the node can't do without.
+	pub fn is_disableable(self) -> bool {
+		match self {
+			SubsystemKind::RuntimeApi |
+			SubsystemKind::AvailabilityStore |
+			SubsystemKind::NetworkBridge |
+			SubsystemKind::ChainApi => false,
+			_ => true,
+		}
+	}
+
+	/// The time the subsystem has to conclude when the `Overseer` stops.
+	fn stop_deadline(self) -> Duration {
+		match self {
+			SubsystemKind::AvailabilityStore | SubsystemKind::StatementDistribution => PERSISTENT_STOP_DELAY,
+			_ => STOP_DELAY,
+		}
+	}
+}
+
+impl std::str::FromStr for SubsystemKind {
+	type Err = ();
+
+	fn from_str(name: &str) -> Result<Self, ()> {
+		SubsystemKind::ALL.iter().copied().find(|kind| kind.name() == name).ok_or(())
+	}
+}
+
+/// The error returned when asked to disable a subsystem that doesn't exist or can't be disabled.
+#[derive(Debug)]
+pub struct CannotDisable(pub String);
+
+/// A subsystem that may have been disabled, in which case a [`DummySubsystem`] runs in its place.
+pub enum MaybeDisabled<S> {
+	/// The subsystem runs as usual.
+	Enabled(S),
+	/// The subsystem has been disabled.
+	Disabled,
+}
+
+impl<S> MaybeDisabled<S> {
+	fn new(subsystem: S, disabled: bool) -> Self {
+		if disabled {
+			MaybeDisabled::Disabled
+		} else {
+			MaybeDisabled::Enabled(subsystem)
+		}
+	}
+}
+
+impl<C: SubsystemContext, S: Subsystem<C>> Subsystem<C> for MaybeDisabled<S> {
+	type Metrics = S::Metrics;
+
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		match self {
+			MaybeDisabled::Enabled(subsystem) => subsystem.start(ctx),
+			MaybeDisabled::Disabled => DummySubsystem.start(ctx),
+		}
+	}
+}
+
 /// Overseer Prometheus metrics.
 #[derive(Clone)]
 struct MetricsInner {
 	activated_heads_total: prometheus::Counter<prometheus::U64>,
 	deactivated_heads_total: prometheus::Counter<prometheus::U64>,
+	message_processing_time: prometheus::HistogramVec,
+	channel_occupancy: prometheus::HistogramVec,
+	conclude_deadline_exceeded: prometheus::CounterVec<prometheus::U64>,
 }
 
 #[derive(Default, Clone)]
@@ -487,6 +921,49 @@ impl Metrics {
 			metrics.deactivated_heads_total.inc();
 		}
 	}
+
+	fn on_conclude_deadline_exceeded(&self, subsystem: &'static str) {
+		if let Some(metrics) = &self.0 {
+			metrics.conclude_deadline_exceeded.with_label_values(&[subsystem]).inc();
+		}
+	}
+
+	fn subsystem_meters(&self, subsystem: &'static str) -> SubsystemMeters {
+		SubsystemMeters(self.0.as_ref().map(|metrics| SubsystemMetersInner {
+			message_processing_time: metrics.message_processing_time.with_label_values(&[subsystem]),
+			channel_occupancy: metrics.channel_occupancy.with_label_values(&[subsystem]),
+		}))
+	}
+}
+
+#[derive(Clone)]
+struct SubsystemMetersInner {
+	message_processing_time: prometheus::Histogram,
+	channel_occupancy: prometheus::Histogram,
+}
+
+/// The [`Metrics`] of a single subsystem, shared by the overseer and the subsystem's context.
+#[derive(Default, Clone)]
+struct SubsystemMeters(Option<SubsystemMetersInner>);
+
+impl SubsystemMeters {
+	fn on_message_enqueued(&self, queued: usize) {
+		if let Some(meters) = &self.0 {
+			meters.channel_occupancy.observe(queued as f64);
+		}
+	}
+
+	fn on_message_processed(&self, took: Duration) {
+		if let Some(meters) = &self.0 {
+			meters.message_processing_time.observe(took.as_secs_f64());
+		}
+	}
+}
+
+impl Debug for SubsystemMeters {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "SubsystemMeters")
+	}
 }
 
 impl metrics::Metrics for Metrics {
@@ -506,6 +983,36 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			message_processing_time: prometheus::register(
+				prometheus::HistogramVec::new(
+					prometheus::HistogramOpts::new(
+						"parachain_subsystem_message_processing_time",
+						"Time from a message being sent to a subsystem until the subsystem handled it, in seconds.",
+					),
+					&["subsystem_name"],
+				)?,
+				registry,
+			)?,
+			channel_occupancy: prometheus::register(
+				prometheus::HistogramVec::new(
+					prometheus::HistogramOpts::new(
+						"parachain_subsystem_channel_occupancy",
+						"Number of messages queued for a subsystem when sending it another one.",
+					).buckets(vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0, 512.0, 1024.0]),
+					&["subsystem_name"],
+				)?,
+				registry,
+			)?,
+			conclude_deadline_exceeded: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_subsystem_conclude_deadline_exceeded_total",
+						"Number of times a subsystem didn't conclude in time when the overseer stopped.",
+					),
+					&["subsystem_name"],
+				)?,
+				registry,
+			)?,
 		};
 		Ok(Metrics(Some(metrics)))
 	}
@@ -551,7 +1058,7 @@ where
 	/// # use futures_timer::Delay;
 	/// # use polkadot_overseer::{Overseer, AllSubsystems};
 	/// # use polkadot_subsystem::{
-	/// #     Subsystem, DummySubsystem, SpawnedSubsystem, SubsystemContext,
+	/// #     Subsystem, SpawnedSubsystem, SubsystemContext,
 	/// #     messages::CandidateValidationMessage,
 	/// # };
 	///
@@ -579,23 +1086,8 @@ where
 	///
 	/// # fn main() { executor::block_on(async move {
 	/// let spawner = sp_core::testing::TaskExecutor::new();
-	/// let all_subsystems = AllSubsystems {
-	///     candidate_validation: ValidationSubsystem,
-	///     candidate_backing: DummySubsystem,
-	///     candidate_selection: DummySubsystem,
-	///     statement_distribution: DummySubsystem,
-	///     availability_distribution: DummySubsystem,
-	///     bitfield_signing: DummySubsystem,
-	///     bitfield_distribution: DummySubsystem,
-	///     provisioner: DummySubsystem,
-	///     pov_distribution: DummySubsystem,
-	///     runtime_api: DummySubsystem,
-	///     availability_store: DummySubsystem,
-	///     network_bridge: DummySubsystem,
-	///     chain_api: DummySubsystem,
-	///     collation_generation: DummySubsystem,
-	///     collator_protocol: DummySubsystem,
-	/// };
+	/// let all_subsystems = AllSubsystems::dummy()
+	///     .replace_candidate_validation(ValidationSubsystem);
 	/// let (overseer, _handler) = Overseer::new(
 	///     vec![],
 	///     all_subsystems,
@@ -645,6 +1137,8 @@ where
 			events_tx: events_tx.clone(),
 		};
 
+		let metrics = <Metrics as metrics::Metrics>::register(prometheus_registry);
+
 		let mut running_subsystems_rx = StreamUnordered::new();
 		let mut running_subsystems = FuturesUnordered::new();
 
@@ -652,6 +1146,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::CandidateValidation,
 			all_subsystems.candidate_validation,
 		)?;
 
@@ -659,6 +1155,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::CandidateBacking,
 			all_subsystems.candidate_backing,
 		)?;
 
@@ -666,6 +1164,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::CandidateSelection,
 			all_subsystems.candidate_selection,
 		)?;
 
@@ -673,6 +1173,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::StatementDistribution,
 			all_subsystems.statement_distribution,
 		)?;
 
@@ -680,6 +1182,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::AvailabilityDistribution,
 			all_subsystems.availability_distribution,
 		)?;
 
@@ -687,6 +1191,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::BitfieldSigning,
 			all_subsystems.bitfield_signing,
 		)?;
 
@@ -694,6 +1200,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::BitfieldDistribution,
 			all_subsystems.bitfield_distribution,
 		)?;
 
@@ -701,6 +1209,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::Provisioner,
 			all_subsystems.provisioner,
 		)?;
 
@@ -708,6 +1218,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::PoVDistribution,
 			all_subsystems.pov_distribution,
 		)?;
 
@@ -715,6 +1227,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::RuntimeApi,
 			all_subsystems.runtime_api,
 		)?;
 
@@ -722,6 +1236,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::AvailabilityStore,
 			all_subsystems.availability_store,
 		)?;
 
@@ -729,6 +1245,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::NetworkBridge,
 			all_subsystems.network_bridge,
 		)?;
 
@@ -736,6 +1254,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::ChainApi,
 			all_subsystems.chain_api,
 		)?;
 
@@ -743,6 +1263,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::CollationGeneration,
 			all_subsystems.collation_generation,
 		)?;
 
@@ -751,6 +1273,8 @@ where
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemKind::CollatorProtocol,
 			all_subsystems.collator_protocol,
 		)?;
 
@@ -761,7 +1285,6 @@ where
 
 		let active_leaves = HashMap::new();
 
-		let metrics = <Metrics as metrics::Metrics>::register(prometheus_registry);
 		let activation_external_listeners = HashMap::new();
 
 		let this = Self {
@@ -788,85 +1311,85 @@ where
 			leaves,
 			active_leaves,
 			metrics,
+			log_messages: false,
 		};
 
 		Ok((this, handler))
 	}
 
+	/// Log every message routed between subsystems under the `overseer::messages` target.
+	///
+	/// Messages are logged at the `trace` level. Payloads such as PoVs and validation code are
+	/// logged by their size and first few bytes rather than their contents.
+	pub fn with_message_logging(mut self, enabled: bool) -> Self {
+		self.log_messages = enabled;
+		self
+	}
+
 	// Stop the overseer.
+	//
+	// The subsystems are concluded in the order of `STOP_STAGES`. Each stage is given until the
+	// deadline of its slowest subsystem to conclude before the next one is concluded.
 	async fn stop(mut self) {
-		if let Some(ref mut s) = self.candidate_validation_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.candidate_backing_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.candidate_selection_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.statement_distribution_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.availability_distribution_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.bitfield_signing_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.bitfield_distribution_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.provisioner_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.pov_distribution_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.runtime_api_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
+		let mut concluded = HashSet::new();
 
-		if let Some(ref mut s) = self.availability_store_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
-
-		if let Some(ref mut s) = self.network_bridge_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
+		for stage in STOP_STAGES {
+			for &kind in stage.iter() {
+				self.conclude_subsystem(kind).await;
+			}
 
-		if let Some(ref mut s) = self.chain_api_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
+			let started = Instant::now();
+			let deadline = stage.iter().map(|kind| kind.stop_deadline()).max().unwrap_or(STOP_DELAY);
+			let mut stop_delay = Delay::new(deadline).fuse();
 
-		if let Some(ref mut s) = self.collator_protocol_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
+			while !stage.iter().all(|kind| concluded.contains(kind)) {
+				select! {
+					kind = self.running_subsystems.next() => match kind {
+						Some(kind) => {
+							if started.elapsed() > kind.stop_deadline() {
+								self.on_conclude_deadline_exceeded(kind);
+							}
+							concluded.insert(kind);
+						}
+						None => break,
+					},
+					_ = stop_delay => break,
+				}
+			}
 
-		if let Some(ref mut s) = self.collation_generation_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+			for &kind in stage.iter().filter(|kind| !concluded.contains(*kind)) {
+				self.on_conclude_deadline_exceeded(kind);
+			}
 		}
+	}
 
-		let mut stop_delay = Delay::new(Duration::from_secs(STOP_DELAY)).fuse();
+	fn on_conclude_deadline_exceeded(&self, kind: SubsystemKind) {
+		log::warn!(
+			target: LOG_TARGET,
+			"Subsystem {} didn't conclude within {:?}",
+			kind.name(),
+			kind.stop_deadline(),
+		);
+		self.metrics.on_conclude_deadline_exceeded(kind.name());
+	}
 
-		loop {
-			select! {
-				_ = self.running_subsystems.next() => {
-					if self.running_subsystems.is_empty() {
-						break;
-					}
-				},
-				_ = stop_delay => break,
-				complete => break,
-			}
+	async fn conclude_subsystem(&mut self, kind: SubsystemKind) {
+		match kind {
+			SubsystemKind::CandidateValidation => self.candidate_validation_subsystem.conclude().await,
+			SubsystemKind::CandidateBacking => self.candidate_backing_subsystem.conclude().await,
+			SubsystemKind::CandidateSelection => self.candidate_selection_subsystem.conclude().await,
+			SubsystemKind::StatementDistribution => self.statement_distribution_subsystem.conclude().await,
+			SubsystemKind::AvailabilityDistribution => self.availability_distribution_subsystem.conclude().await,
+			SubsystemKind::BitfieldSigning => self.bitfield_signing_subsystem.conclude().await,
+			SubsystemKind::BitfieldDistribution => self.bitfield_distribution_subsystem.conclude().await,
+			SubsystemKind::Provisioner => self.provisioner_subsystem.conclude().await,
+			SubsystemKind::PoVDistribution => self.pov_distribution_subsystem.conclude().await,
+			SubsystemKind::RuntimeApi => self.runtime_api_subsystem.conclude().await,
+			SubsystemKind::AvailabilityStore => self.availability_store_subsystem.conclude().await,
+			SubsystemKind::NetworkBridge => self.network_bridge_subsystem.conclude().await,
+			SubsystemKind::ChainApi => self.chain_api_subsystem.conclude().await,
+			SubsystemKind::CollationGeneration => self.collation_generation_subsystem.conclude().await,
+			SubsystemKind::CollatorProtocol => self.collator_protocol_subsystem.conclude().await,
 		}
 	}
 
@@ -921,7 +1444,7 @@ where
 
 			// Some subsystem exited? It's time to panic.
 			if let Poll::Ready(Some(finished)) = poll!(self.running_subsystems.next()) {
-				log::error!(target: LOG_TARGET, "Subsystem finished unexpectedly {:?}", finished);
+				log::error!(target: LOG_TARGET, "Subsystem {} finished unexpectedly", finished.name());
 				self.stop().await;
 				return Err(SubsystemError);
 			}
@@ -978,150 +1501,154 @@ where
 
 		self.broadcast_signal(OverseerSignal::ActiveLeaves(update)).await?;
 
-		self.broadcast_signal(OverseerSignal::BlockFinalized(block.hash)).await?;
+		self.broadcast_signal(OverseerSignal::BlockFinalized(block.hash, block.number)).await?;
 
 		Ok(())
 	}
 
 	async fn broadcast_signal(&mut self, signal: OverseerSignal) -> SubsystemResult<()> {
 		if let Some(ref mut s) = self.candidate_validation_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.candidate_backing_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.candidate_selection_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.statement_distribution_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.availability_distribution_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.bitfield_distribution_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.bitfield_signing_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.provisioner_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.pov_distribution_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.runtime_api_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.availability_store_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.network_bridge_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.chain_api_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.collator_protocol_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		if let Some(ref mut s) = self.collation_generation_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+			s.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
 		Ok(())
 	}
 
 	async fn route_message(&mut self, msg: AllMessages) {
+		if self.log_messages {
+			log::trace!(target: MESSAGES_LOG_TARGET, "{:?}", msg);
+		}
+
 		match msg {
 			AllMessages::CandidateValidation(msg) => {
 				if let Some(ref mut s) = self.candidate_validation_subsystem.instance {
-					let _= s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::CandidateBacking(msg) => {
 				if let Some(ref mut s) = self.candidate_backing_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::CandidateSelection(msg) => {
 				if let Some(ref mut s) = self.candidate_selection_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::StatementDistribution(msg) => {
 				if let Some(ref mut s) = self.statement_distribution_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::AvailabilityDistribution(msg) => {
 				if let Some(ref mut s) = self.availability_distribution_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::BitfieldDistribution(msg) => {
 				if let Some(ref mut s) = self.bitfield_distribution_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::BitfieldSigning(msg) => {
 				if let Some(ref mut s) = self.bitfield_signing_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication{ msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::Provisioner(msg) => {
 				if let Some(ref mut s) = self.provisioner_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::PoVDistribution(msg) => {
 				if let Some(ref mut s) = self.pov_distribution_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::RuntimeApi(msg) => {
 				if let Some(ref mut s) = self.runtime_api_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::AvailabilityStore(msg) => {
 				if let Some(ref mut s) = self.availability_store_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::NetworkBridge(msg) => {
 				if let Some(ref mut s) = self.network_bridge_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::ChainApi(msg) => {
 				if let Some(ref mut s) = self.chain_api_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::CollationGeneration(msg) => {
 				if let Some(ref mut s) = self.collation_generation_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 			AllMessages::CollatorProtocol(msg) => {
 				if let Some(ref mut s) = self.collator_protocol_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+					let _ = s.send(FromOverseer::Communication { msg }).await;
 				}
 			}
 		}
@@ -1177,14 +1704,26 @@ where
 
 fn spawn<S: SpawnNamed, M: Send + 'static>(
 	spawner: &mut S,
-	futures: &mut FuturesUnordered<BoxFuture<'static, ()>>,
+	futures: &mut FuturesUnordered<BoxFuture<'static, SubsystemKind>>,
 	streams: &mut StreamUnordered<mpsc::Receiver<ToOverseer>>,
+	metrics: &Metrics,
+	kind: SubsystemKind,
 	s: impl Subsystem<OverseerSubsystemContext<M>>,
 ) -> SubsystemResult<OverseenSubsystem<M>> {
+	let meters = metrics.subsystem_meters(kind.name());
 	let (to_tx, to_rx) = mpsc::channel(CHANNEL_CAPACITY);
 	let (from_tx, from_rx) = mpsc::channel(CHANNEL_CAPACITY);
-	let ctx = OverseerSubsystemContext { rx: to_rx, tx: from_tx };
-	let SpawnedSubsystem { future, name } = s.start(ctx);
+	let queued = Arc::new(AtomicUsize::new(0));
+	let ctx = OverseerSubsystemContext {
+		rx: to_rx,
+		tx: from_tx,
+		queued: queued.clone(),
+		in_flight: None,
+		meters: meters.clone(),
+	};
+	// The task is named after the subsystem as the overseer knows it, so task names line up
+	// with the subsystem labels of the metrics and with `--disable-subsystem`.
+	let SpawnedSubsystem { future, .. } = s.start(ctx);
 
 	let (tx, rx) = oneshot::channel();
 
@@ -1193,13 +1732,15 @@ fn spawn<S: SpawnNamed, M: Send + 'static>(
 		let _ = tx.send(());
 	});
 
-	spawner.spawn(name, fut);
+	spawner.spawn(kind.name(), fut);
 
 	streams.push(from_rx);
-	futures.push(Box::pin(rx.map(|_| ())));
+	futures.push(Box::pin(rx.map(move |_| kind)));
 
 	let instance = Some(SubsystemInstance {
 		tx: to_tx,
+		queued,
+		meters,
 	});
 
 	Ok(OverseenSubsystem {
@@ -1214,7 +1755,6 @@ mod tests {
 	use futures::{executor, pin_mut, select, channel::mpsc, FutureExt};
 
 	use polkadot_primitives::v1::{BlockData, CollatorPair, PoV};
-	use polkadot_subsystem::DummySubsystem;
 	use polkadot_subsystem::messages::RuntimeApiRequest;
 	use polkadot_node_primitives::{Collation, CollationGenerationConfig};
 	use polkadot_node_network_protocol::{PeerId, ReputationChange, NetworkBridgeEvent};
@@ -1272,14 +1812,12 @@ mod tests {
 						if c < 10 {
 							let (tx, _) = oneshot::channel();
 							ctx.send_message(
-								AllMessages::CandidateValidation(
-									CandidateValidationMessage::ValidateFromChainState(
-										Default::default(),
-										PoV {
-											block_data: BlockData(Vec::new()),
-										}.into(),
-										tx,
-									)
+								CandidateValidationMessage::ValidateFromChainState(
+									Default::default(),
+									PoV {
+										block_data: BlockData(Vec::new()),
+									}.into(),
+									tx,
 								)
 							).await.unwrap();
 							c += 1;
@@ -1319,6 +1857,39 @@ mod tests {
 		}
 	}
 
+	// Reports its name once concluded, or never concludes if `stall` is set.
+	struct ConcludeRecorder {
+		name: &'static str,
+		stall: bool,
+		concluded: mpsc::UnboundedSender<&'static str>,
+	}
+
+	impl<C: SubsystemContext> Subsystem<C> for ConcludeRecorder {
+		type Metrics = ();
+
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			let ConcludeRecorder { name, stall, concluded } = self;
+			SpawnedSubsystem {
+				name: "conclude-recorder",
+				future: Box::pin(async move {
+					loop {
+						match ctx.recv().await {
+							Ok(FromOverseer::Signal(OverseerSignal::Conclude)) => break,
+							Err(_) => return,
+							_ => continue,
+						}
+					}
+
+					if stall {
+						drop(concluded);
+						futures::future::pending::<()>().await;
+					} else {
+						let _ = concluded.unbounded_send(name);
+					}
+				}),
+			}
+		}
+	}
 
 	// Checks that a minimal configuration of two jobs can run and exchange messages.
 	#[test]
@@ -1329,23 +1900,9 @@ mod tests {
 			let (s1_tx, mut s1_rx) = mpsc::channel(64);
 			let (s2_tx, mut s2_rx) = mpsc::channel(64);
 
-			let all_subsystems = AllSubsystems {
-				candidate_validation: TestSubsystem1(s1_tx),
-				candidate_backing: TestSubsystem2(s2_tx),
-				candidate_selection: DummySubsystem,
-				statement_distribution: DummySubsystem,
-				availability_distribution: DummySubsystem,
-				bitfield_signing: DummySubsystem,
-				bitfield_distribution: DummySubsystem,
-				provisioner: DummySubsystem,
-				pov_distribution: DummySubsystem,
-				runtime_api: DummySubsystem,
-				availability_store: DummySubsystem,
-				network_bridge: DummySubsystem,
-				chain_api: DummySubsystem,
-				collation_generation: DummySubsystem,
-				collator_protocol: DummySubsystem,
-			};
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem1(s1_tx))
+				.replace_candidate_backing(TestSubsystem2(s2_tx));
 			let (overseer, mut handler) = Overseer::new(
 				vec![],
 				all_subsystems,
@@ -1413,23 +1970,7 @@ mod tests {
 				number: 3,
 			};
 
-			let all_subsystems = AllSubsystems {
-				collation_generation: DummySubsystem,
-				candidate_validation: DummySubsystem,
-				candidate_backing: DummySubsystem,
-				candidate_selection: DummySubsystem,
-				collator_protocol: DummySubsystem,
-				statement_distribution: DummySubsystem,
-				availability_distribution: DummySubsystem,
-				bitfield_signing: DummySubsystem,
-				bitfield_distribution: DummySubsystem,
-				provisioner: DummySubsystem,
-				pov_distribution: DummySubsystem,
-				runtime_api: DummySubsystem,
-				availability_store: DummySubsystem,
-				network_bridge: DummySubsystem,
-				chain_api: DummySubsystem,
-			};
+			let all_subsystems = AllSubsystems::dummy();
 			let registry = prometheus::Registry::new();
 			let (overseer, mut handler) = Overseer::new(
 				vec![first_block],
@@ -1457,6 +1998,174 @@ mod tests {
 		});
 	}
 
+	// Checks the per-subsystem message metrics are recorded.
+	#[test]
+	fn overseer_subsystem_metrics_work() {
+		let spawner = sp_core::testing::TaskExecutor::new();
+
+		executor::block_on(async move {
+			let (s1_tx, mut s1_rx) = mpsc::channel(64);
+			let (s2_tx, _) = mpsc::channel(64);
+
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem1(s1_tx))
+				.replace_candidate_backing(TestSubsystem2(s2_tx));
+			let registry = prometheus::Registry::new();
+			let (overseer, mut handler) = Overseer::new(
+				vec![],
+				all_subsystems,
+				Some(&registry),
+				spawner,
+			).unwrap();
+			let overseer_fut = overseer.run().fuse();
+
+			pin_mut!(overseer_fut);
+
+			let mut received = 0;
+			loop {
+				select! {
+					res = overseer_fut => {
+						assert!(res.is_ok());
+						break;
+					},
+					s1_next = s1_rx.next() => {
+						if s1_next.is_some() {
+							received += 1;
+							if received == 10 {
+								handler.stop().await.unwrap();
+							}
+						}
+					},
+					complete => break,
+				}
+			}
+
+			let histogram_count = |name: &str| {
+				registry.gather()
+					.into_iter()
+					.find(|family| family.get_name() == name)
+					.expect("metric is registered")
+					.get_metric()
+					.iter()
+					.find(|m| m.get_label().iter().any(|l| l.get_value() == "candidate-validation"))
+					.expect("candidate validation received messages")
+					.get_histogram()
+					.get_sample_count()
+			};
+
+			// 10 validation requests, followed by `Conclude`.
+			assert_eq!(histogram_count("parachain_subsystem_channel_occupancy"), 11);
+			// `Conclude` isn't handled, as the subsystem doesn't ask for the next message afterwards.
+			assert_eq!(histogram_count("parachain_subsystem_message_processing_time"), 10);
+		});
+	}
+
+	// Checks that subsystems are concluded in stages and that deadlines are tracked.
+	#[test]
+	fn overseer_concludes_subsystems_in_stages() {
+		let spawner = sp_core::testing::TaskExecutor::new();
+
+		executor::block_on(async move {
+			let (concluded_tx, concluded_rx) = mpsc::unbounded();
+			let recorder = |name, stall| ConcludeRecorder { name, stall, concluded: concluded_tx.clone() };
+
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_availability_store(recorder("availability-store", false))
+				.replace_network_bridge(recorder("network-bridge", false))
+				.replace_candidate_backing(recorder("candidate-backing", false))
+				.replace_candidate_validation(recorder("candidate-validation", true));
+			drop(concluded_tx);
+
+			let registry = prometheus::Registry::new();
+			let (overseer, mut handler) = Overseer::new(
+				vec![],
+				all_subsystems,
+				Some(&registry),
+				spawner,
+			).unwrap();
+			let overseer_fut = overseer.run().fuse();
+
+			pin_mut!(overseer_fut);
+
+			handler.stop().await.unwrap();
+			assert!(overseer_fut.await.is_ok());
+
+			let concluded: Vec<_> = concluded_rx.collect().await;
+			assert_eq!(concluded, vec!["candidate-backing", "availability-store", "network-bridge"]);
+
+			let exceeded: Vec<_> = registry.gather()
+				.into_iter()
+				.find(|family| family.get_name() == "parachain_subsystem_conclude_deadline_exceeded_total")
+				.expect("metric is registered")
+				.get_metric()
+				.iter()
+				.map(|m| (m.get_label()[0].get_value().to_owned(), m.get_counter().get_value() as u64))
+				.collect();
+			assert_eq!(exceeded, vec![("candidate-validation".to_owned(), 1)]);
+		});
+	}
+
+	#[test]
+	fn disabled_subsystem_is_not_started() {
+		let spawner = sp_core::testing::TaskExecutor::new();
+
+		executor::block_on(async move {
+			let (s1_tx, mut s1_rx) = mpsc::channel(64);
+			let (s2_tx, _) = mpsc::channel(64);
+
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem1(s1_tx))
+				.replace_candidate_backing(TestSubsystem2(s2_tx))
+				.disable(&["candidate-validation".into()])
+				.unwrap();
+			let (overseer, mut handler) = Overseer::new(
+				vec![],
+				all_subsystems,
+				None,
+				spawner,
+			).unwrap();
+			let overseer_fut = overseer.run().fuse();
+
+			pin_mut!(overseer_fut);
+
+			// The disabled subsystem has been dropped along with its sender.
+			assert!(s1_rx.next().await.is_none());
+
+			handler.stop().await.unwrap();
+			assert!(overseer_fut.await.is_ok());
+		});
+	}
+
+	#[test]
+	fn only_non_critical_subsystems_can_be_disabled() {
+		let cannot_disable = |disabled: &[String]| AllSubsystems::dummy().disable(disabled).err().map(|e| e.0);
+
+		assert_eq!(
+			cannot_disable(&["collator-protocol".into(), "runtime-api".into()]),
+			Some("runtime-api".to_owned()),
+		);
+		assert_eq!(
+			cannot_disable(&["no-such-subsystem".into()]),
+			Some("no-such-subsystem".to_owned()),
+		);
+		assert!(AllSubsystems::dummy().disable(&["collator-protocol".into()]).is_ok());
+	}
+
+	#[test]
+	fn subsystem_kinds_are_parsed_from_their_names() {
+		for &kind in SubsystemKind::ALL.iter() {
+			assert_eq!(kind.name().parse::<SubsystemKind>(), Ok(kind));
+		}
+		assert_eq!("no-such-subsystem".parse::<SubsystemKind>(), Err(()));
+
+		// every subsystem is concluded in exactly one stage.
+		let mut staged: Vec<_> = STOP_STAGES.iter().flat_map(|stage| stage.iter().copied()).collect();
+		staged.sort_by_key(|kind| kind.name());
+		let mut all = SubsystemKind::ALL.to_vec();
+		all.sort_by_key(|kind| kind.name());
+		assert_eq!(staged, all);
+	}
+
 	fn extract_metrics(registry: &prometheus::Registry) -> (u64, u64) {
 		let gather = registry.gather();
 		assert_eq!(gather[0].get_name(), "parachain_activated_heads_total");
@@ -1475,23 +2184,9 @@ mod tests {
 
 		executor::block_on(async move {
 			let (s1_tx, _) = mpsc::channel(64);
-			let all_subsystems = AllSubsystems {
-				candidate_validation: TestSubsystem1(s1_tx),
-				candidate_backing: TestSubsystem4,
-				candidate_selection: DummySubsystem,
-				statement_distribution: DummySubsystem,
-				availability_distribution: DummySubsystem,
-				bitfield_signing: DummySubsystem,
-				bitfield_distribution: DummySubsystem,
-				provisioner: DummySubsystem,
-				pov_distribution: DummySubsystem,
-				runtime_api: DummySubsystem,
-				availability_store: DummySubsystem,
-				network_bridge: DummySubsystem,
-				chain_api: DummySubsystem,
-				collation_generation: DummySubsystem,
-				collator_protocol: DummySubsystem,
-			};
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem1(s1_tx))
+				.replace_candidate_backing(TestSubsystem4);
 			let (overseer, _handle) = Overseer::new(
 				vec![],
 				all_subsystems,
@@ -1599,23 +2294,9 @@ mod tests {
 
 			let (tx_5, mut rx_5) = mpsc::channel(64);
 			let (tx_6, mut rx_6) = mpsc::channel(64);
-			let all_subsystems = AllSubsystems {
-				candidate_validation: TestSubsystem5(tx_5),
-				candidate_backing: TestSubsystem6(tx_6),
-				candidate_selection: DummySubsystem,
-				statement_distribution: DummySubsystem,
-				availability_distribution: DummySubsystem,
-				bitfield_signing: DummySubsystem,
-				bitfield_distribution: DummySubsystem,
-				provisioner: DummySubsystem,
-				pov_distribution: DummySubsystem,
-				runtime_api: DummySubsystem,
-				availability_store: DummySubsystem,
-				network_bridge: DummySubsystem,
-				chain_api: DummySubsystem,
-				collation_generation: DummySubsystem,
-				collator_protocol: DummySubsystem,
-			};
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem5(tx_5))
+				.replace_candidate_backing(TestSubsystem6(tx_6));
 			let (overseer, mut handler) = Overseer::new(
 				vec![first_block],
 				all_subsystems,
@@ -1704,23 +2385,9 @@ mod tests {
 			let (tx_5, mut rx_5) = mpsc::channel(64);
 			let (tx_6, mut rx_6) = mpsc::channel(64);
 
-			let all_subsystems = AllSubsystems {
-				candidate_validation: TestSubsystem5(tx_5),
-				candidate_backing: TestSubsystem6(tx_6),
-				candidate_selection: DummySubsystem,
-				statement_distribution: DummySubsystem,
-				availability_distribution: DummySubsystem,
-				bitfield_signing: DummySubsystem,
-				bitfield_distribution: DummySubsystem,
-				provisioner: DummySubsystem,
-				pov_distribution: DummySubsystem,
-				runtime_api: DummySubsystem,
-				availability_store: DummySubsystem,
-				network_bridge: DummySubsystem,
-				chain_api: DummySubsystem,
-				collation_generation: DummySubsystem,
-				collator_protocol: DummySubsystem,
-			};
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem5(tx_5))
+				.replace_candidate_backing(TestSubsystem6(tx_6));
 			// start with two forks of different height.
 			let (overseer, mut handler) = Overseer::new(
 				vec![first_block, second_block],
@@ -1747,7 +2414,7 @@ mod tests {
 					deactivated: [first_block_hash, second_block_hash].as_ref().into(),
 					..Default::default()
 				}),
-				OverseerSignal::BlockFinalized(third_block_hash),
+				OverseerSignal::BlockFinalized(third_block_hash, 3),
 			];
 
 			loop {
@@ -1846,7 +2513,11 @@ mod tests {
 	fn test_candidate_validation_msg() -> CandidateValidationMessage {
 		let (sender, _) = oneshot::channel();
 		let pov = Arc::new(PoV { block_data: BlockData(Vec::new()) });
-		CandidateValidationMessage::ValidateFromChainState(Default::default(), pov, sender)
+		CandidateValidationMessage::ValidateFromChainState(
+			Default::default(),
+			pov,
+			sender,
+		)
 	}
 
 	fn test_candidate_backing_msg() -> CandidateBackingMessage {
@@ -1868,6 +2539,7 @@ mod tests {
 			key: CollatorPair::generate().0,
 			collator: Box::new(|_| Box::new(TestCollator)),
 			para_id: Default::default(),
+			scheduled_only: true,
 		})
 	}
 	struct TestCollator;
@@ -2012,4 +2684,15 @@ mod tests {
 			}
 		});
 	}
+
+	#[test]
+	fn replacing_subsystems_keeps_the_others() {
+		let all_subsystems = AllSubsystems::dummy()
+			.replace_candidate_validation(1u8)
+			.replace_collator_protocol(2u16);
+
+		assert_eq!(all_subsystems.candidate_validation, 1);
+		assert_eq!(all_subsystems.collator_protocol, 2);
+		let _: DummySubsystem = all_subsystems.runtime_api;
+	}
 }
@@ -24,9 +24,9 @@ use futures::Future;
 use parity_scale_codec::{Decode, Encode};
 use polkadot_primitives::v1::{
 	Hash, CommittedCandidateReceipt, CandidateReceipt, CompactStatement,
-	EncodeAs, Signed, SigningContext, ValidatorIndex, ValidatorId,
-	UpwardMessage, Balance, ValidationCode, PersistedValidationData, ValidationData,
-	HeadData, PoV, CollatorPair, Id as ParaId,
+	EncodeAs, Signed, SigningContext, ValidatorIndex, ValidatorId, ValidatorPair, ValidatorSignature,
+	UpwardMessage, OutboundHrmpMessage, Balance, ValidationCode, PersistedValidationData, ValidationData,
+	HeadData, PoV, CollatorPair, Id as ParaId, BlockNumber, MAX_POV_SIZE,
 };
 use polkadot_statement_table::{
 	generic::{
@@ -75,13 +75,93 @@ impl EncodeAs<CompactStatement> for Statement {
 	}
 }
 
+/// A signed payload whose signature is known to be valid.
+///
+/// Unlike the [`Signed`] type it wraps, this can't be decoded: it can only be obtained by signing
+/// with a local validator key or by checking the signature of a [`Signed`] value received from
+/// elsewhere. Within the node, prefer passing this around so that unchecked signatures
+/// can't circulate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedSigned<Payload, RealPayload = Payload>(Signed<Payload, RealPayload>);
+
+impl<Payload: EncodeAs<RealPayload>, RealPayload: Encode> CheckedSigned<Payload, RealPayload> {
+	/// Create a `CheckedSigned` from already existing parts, checking the signature.
+	pub fn new<H: Encode>(
+		payload: Payload,
+		validator_index: ValidatorIndex,
+		signature: ValidatorSignature,
+		context: &SigningContext<H>,
+		key: &ValidatorId,
+	) -> Option<Self> {
+		Signed::new(payload, validator_index, signature, context, key).map(Self)
+	}
+
+	/// Sign this payload with the given context and key, storing the validator index.
+	pub fn sign<H: Encode>(
+		payload: Payload,
+		context: &SigningContext<H>,
+		validator_index: ValidatorIndex,
+		key: &ValidatorPair,
+	) -> Self {
+		Self(Signed::sign(payload, context, validator_index, key))
+	}
+
+	/// Check the signature of `unchecked` against the key of the validator it claims to be
+	/// signed by.
+	///
+	/// Gives back the unchecked value if the validator index is out of bounds or
+	/// the signature is invalid.
+	pub fn check<H: Encode>(
+		unchecked: Signed<Payload, RealPayload>,
+		context: &SigningContext<H>,
+		validators: &[ValidatorId],
+	) -> Result<Self, Signed<Payload, RealPayload>> {
+		let valid = validators.get(unchecked.validator_index() as usize)
+			.map_or(false, |key| unchecked.check_signature(context, key).is_ok());
+
+		if valid {
+			Ok(Self(unchecked))
+		} else {
+			Err(unchecked)
+		}
+	}
+
+	/// Immutably access the underlying signed value, e.g. for encoding.
+	pub fn as_unchecked(&self) -> &Signed<Payload, RealPayload> {
+		&self.0
+	}
+
+	/// Discard the guarantee that the signature was checked, e.g. to send it over the network.
+	pub fn into_unchecked(self) -> Signed<Payload, RealPayload> {
+		self.0
+	}
+
+	/// Discard signing data, get the payload.
+	pub fn into_payload(self) -> Payload {
+		self.0.into_payload()
+	}
+}
+
+impl<Payload, RealPayload> std::ops::Deref for CheckedSigned<Payload, RealPayload> {
+	type Target = Signed<Payload, RealPayload>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
 /// A statement, the corresponding signature, and the index of the sender.
 ///
 /// Signing context and validator set should be apparent from context.
 ///
 /// This statement is "full" in the sense that the `Seconded` variant includes the candidate receipt.
 /// Only the compact `SignedStatement` is suitable for submission to the chain.
-pub type SignedFullStatement = Signed<Statement, CompactStatement>;
+///
+/// The signature of this statement has been checked. See [`CheckedSigned`].
+pub type SignedFullStatement = CheckedSigned<Statement, CompactStatement>;
+
+/// A [`SignedFullStatement`] whose signature hasn't been checked, as it is sent over the network.
+pub type UncheckedSignedFullStatement = Signed<Statement, CompactStatement>;
 
 /// A misbehaviour report.
 #[derive(Debug, Clone)]
@@ -114,7 +194,7 @@ pub struct FromTableMisbehavior {
 }
 
 /// Outputs of validating a candidate.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ValidationOutputs {
 	/// The head-data produced by validation.
 	pub head_data: HeadData,
@@ -122,6 +202,11 @@ pub struct ValidationOutputs {
 	pub validation_data: PersistedValidationData,
 	/// Upward messages to the relay chain.
 	pub upward_messages: Vec<UpwardMessage>,
+	/// Horizontal messages sent to other paras.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<ParaId>>,
+	/// The relay-chain block number up to which, inclusive, inbound horizontal messages were
+	/// processed.
+	pub hrmp_watermark: BlockNumber,
 	/// Fees paid to the validators of the relay-chain.
 	pub fees: Balance,
 	/// The new validation code submitted by the execution, if any.
@@ -129,10 +214,16 @@ pub struct ValidationOutputs {
 }
 
 /// Candidate invalidity details
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InvalidCandidate {
-	/// Failed to execute.`validate_block`. This includes function panicking.
+	/// Failed to execute `validate_block` for reasons other than those below.
 	ExecutionError(String),
+	/// `validate_block` trapped, e.g. by panicking.
+	ExecutionTrap(String),
+	/// `validate_block` failed to decode the validation parameters.
+	ParamsDecodeFailure,
+	/// The head-data produced by `validate_block` doesn't match the one committed to.
+	HeadDataMismatch,
 	/// Execution timeout.
 	Timeout,
 	/// Validation input is over the limit.
@@ -153,10 +244,32 @@ pub enum InvalidCandidate {
 	HeadDataTooLarge(u64),
 	/// Code upgrade triggered but not allowed.
 	CodeUpgradeNotAllowed,
+	/// More upward messages sent than permitted.
+	TooManyUpwardMessages(u64),
+	/// Upward messages are over the limit in total.
+	UpwardMessagesTooLarge(u64),
+}
+
+/// Check that a PoV received from elsewhere is not larger than [`MAX_POV_SIZE`] and matches
+/// the PoV hash it was requested or announced with.
+///
+/// This is meant to be done wherever a PoV enters the node, so that oversized or mismatched
+/// PoVs never reach the validation pipeline.
+pub fn check_pov(pov: &PoV, expected_hash: &Hash) -> Result<(), InvalidCandidate> {
+	let encoded_size = pov.encoded_size();
+	if encoded_size > MAX_POV_SIZE as usize {
+		return Err(InvalidCandidate::ParamsTooLarge(encoded_size as u64));
+	}
+
+	if &pov.hash() != expected_hash {
+		return Err(InvalidCandidate::HashMismatch);
+	}
+
+	Ok(())
 }
 
 /// Result of the validation of the candidate.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ValidationResult {
 	/// Candidate is valid. The validation process yields these outputs.
 	Valid(ValidationOutputs),
@@ -164,6 +277,14 @@ pub enum ValidationResult {
 	Invalid(InvalidCandidate),
 }
 
+/// The randomness about a relay-chain block which was derived from the VRF output of its
+/// BABE pre-digest. This is the input of the `RelayVRFModulo` and `RelayVRFDelay` assignment
+/// criteria of approval checking.
+///
+/// Only the author of the block knew the story before the block was produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct RelayVrfStory(pub [u8; 32]);
+
 impl std::convert::TryFrom<FromTableMisbehavior> for MisbehaviorReport {
 	type Error = ();
 
@@ -272,6 +393,11 @@ pub struct Collation {
 	pub fees: Balance,
 	/// Messages destined to be interpreted by the Relay chain itself.
 	pub upward_messages: Vec<UpwardMessage>,
+	/// Horizontal messages sent to other paras.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<ParaId>>,
+	/// The relay-chain block number up to which, inclusive, inbound horizontal messages were
+	/// processed.
+	pub hrmp_watermark: BlockNumber,
 	/// New validation code.
 	pub new_validation_code: Option<ValidationCode>,
 	/// The head-data produced as a result of execution.
@@ -288,6 +414,10 @@ pub struct CollationGenerationConfig {
 	pub collator: Box<dyn Fn(&ValidationData) -> Box<dyn Future<Output = Collation> + Unpin + Send> + Send + Sync>,
 	/// The parachain that this collator collates for
 	pub para_id: ParaId,
+	/// Only collate when the para is scheduled on a free core at the relay-parent. Otherwise,
+	/// collations are also built ahead of time on occupied cores which the para is next up on
+	/// once the candidate occupying them becomes available.
+	pub scheduled_only: bool,
 }
 
 impl std::fmt::Debug for CollationGenerationConfig {
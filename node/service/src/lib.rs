@@ -26,10 +26,9 @@ use polkadot_primitives::v1::{AccountId, Nonce, Balance};
 use service::{error::Error as ServiceError};
 use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
 use sc_executor::native_executor_instance;
-use log::info;
+use log::{info, warn};
 use sp_blockchain::HeaderBackend;
 use polkadot_overseer::{self as overseer, AllSubsystems, BlockInfo, Overseer, OverseerHandler};
-use polkadot_subsystem::DummySubsystem;
 use polkadot_node_core_proposer::ProposerFactory;
 use sp_trie::PrefixedMemoryDB;
 use sp_core::traits::SpawnNamed;
@@ -277,33 +276,52 @@ fn new_partial<RuntimeApi, Executor>(config: &mut Configuration) -> Result<
 
 fn real_overseer<S: SpawnNamed>(
 	leaves: impl IntoIterator<Item = BlockInfo>,
+	disabled_subsystems: &[String],
+	log_subsystem_messages: bool,
 	prometheus_registry: Option<&Registry>,
 	s: S,
 ) -> Result<(Overseer<S>, OverseerHandler), ServiceError> {
-	let all_subsystems = AllSubsystems {
-		candidate_validation: DummySubsystem,
-		candidate_backing: DummySubsystem,
-		candidate_selection: DummySubsystem,
-		statement_distribution: DummySubsystem,
-		availability_distribution: DummySubsystem,
-		bitfield_signing: DummySubsystem,
-		bitfield_distribution: DummySubsystem,
-		provisioner: DummySubsystem,
-		pov_distribution: DummySubsystem,
-		runtime_api: DummySubsystem,
-		availability_store: DummySubsystem,
-		network_bridge: DummySubsystem,
-		chain_api: DummySubsystem,
-		collation_generation: DummySubsystem,
-		collator_protocol: DummySubsystem,
-	};
+	// the real subsystems aren't wired in yet, so disabling one of them only swaps a dummy for a
+	// dummy. The names are still checked, so that invalid ones are reported at startup.
+	let all_subsystems = AllSubsystems::dummy()
+		.disable(disabled_subsystems)
+		.map_err(|e| ServiceError::Other(format!("Cannot disable subsystem {:?}", e.0)))?;
 
 	Overseer::new(
 		leaves,
 		all_subsystems,
 		prometheus_registry,
 		s,
-	).map_err(|e| ServiceError::Other(format!("Failed to create an Overseer: {:?}", e)))
+	)
+		.map(|(overseer, handler)| (overseer.with_message_logging(log_subsystem_messages), handler))
+		.map_err(|e| ServiceError::Other(format!("Failed to create an Overseer: {:?}", e)))
+}
+
+/// Stop the overseer once the process is asked to terminate, so that it concludes the subsystems
+/// in order instead of dropping them in the middle of their work.
+#[cfg(all(unix, feature = "full-node"))]
+async fn stop_overseer_on_terminate(mut handler: OverseerHandler) {
+	use tokio::signal::unix::{signal, SignalKind};
+
+	let mut sigterm = match signal(SignalKind::terminate()) {
+		Ok(sigterm) => sigterm,
+		Err(e) => {
+			warn!("Can't listen for SIGTERM, the overseer won't be stopped on it: {}", e);
+			return;
+		}
+	};
+
+	if sigterm.recv().await.is_some() {
+		info!("Received SIGTERM, stopping the overseer");
+		if let Err(e) = handler.stop().await {
+			warn!("Failed to stop the overseer: {:?}", e);
+		}
+	}
+}
+
+#[cfg(all(not(unix), feature = "full-node"))]
+async fn stop_overseer_on_terminate(_handler: OverseerHandler) {
+	futures::future::pending::<()>().await
 }
 
 #[cfg(feature = "full-node")]
@@ -314,6 +332,8 @@ fn new_full<RuntimeApi, Executor>(
 	_authority_discovery_enabled: bool,
 	_slot_duration: u64,
 	grandpa_pause: Option<(u32, u32)>,
+	disabled_subsystems: Vec<String>,
+	log_subsystem_messages: bool,
 ) -> Result<(
 	TaskManager,
 	Arc<FullClient<RuntimeApi, Executor>>,
@@ -402,24 +422,36 @@ fn new_full<RuntimeApi, Executor>(
 		})
 		.collect();
 
-	let (overseer, handler) = real_overseer(leaves, prometheus_registry.as_ref(), spawner)?;
+	let (overseer, handler) = real_overseer(
+		leaves,
+		&disabled_subsystems,
+		log_subsystem_messages,
+		prometheus_registry.as_ref(),
+		spawner,
+	)?;
 	let handler_clone = handler.clone();
+	let stop_handler = handler.clone();
+	let overseer_sync_oracle = network.clone();
 
 	task_manager.spawn_essential_handle().spawn_blocking("overseer", Box::pin(async move {
 		use futures::{pin_mut, select, FutureExt};
 
-		let forward = overseer::forward_events(overseer_client, handler);
+		let forward = overseer::forward_events(overseer_client, handler, overseer_sync_oracle);
 
 		let forward = forward.fuse();
 		let overseer_fut = overseer.run().fuse();
+		let stop_on_terminate = stop_overseer_on_terminate(stop_handler).fuse();
 
 		pin_mut!(overseer_fut);
 		pin_mut!(forward);
+		pin_mut!(stop_on_terminate);
 
 		loop {
 			select! {
 				_ = forward => break,
 				_ = overseer_fut => break,
+				// keep running the overseer until it has concluded the subsystems.
+				_ = stop_on_terminate => {},
 				complete => break,
 			}
 		}
@@ -661,6 +693,8 @@ pub fn polkadot_new_full(
 	authority_discovery_enabled: bool,
 	slot_duration: u64,
 	grandpa_pause: Option<(u32, u32)>,
+	disabled_subsystems: Vec<String>,
+	log_subsystem_messages: bool,
 )
 	-> Result<(
 		TaskManager,
@@ -679,6 +713,8 @@ pub fn polkadot_new_full(
 		authority_discovery_enabled,
 		slot_duration,
 		grandpa_pause,
+		disabled_subsystems,
+		log_subsystem_messages,
 	)?;
 
 	Ok((components, client, FullNodeHandles))
@@ -693,6 +729,8 @@ pub fn kusama_new_full(
 	authority_discovery_enabled: bool,
 	slot_duration: u64,
 	grandpa_pause: Option<(u32, u32)>,
+	disabled_subsystems: Vec<String>,
+	log_subsystem_messages: bool,
 ) -> Result<(
 		TaskManager,
 		Arc<impl PolkadotClient<
@@ -711,6 +749,8 @@ pub fn kusama_new_full(
 		authority_discovery_enabled,
 		slot_duration,
 		grandpa_pause,
+		disabled_subsystems,
+		log_subsystem_messages,
 	)?;
 
 	Ok((components, client, FullNodeHandles))
@@ -725,6 +765,8 @@ pub fn westend_new_full(
 	authority_discovery_enabled: bool,
 	slot_duration: u64,
 	grandpa_pause: Option<(u32, u32)>,
+	disabled_subsystems: Vec<String>,
+	log_subsystem_messages: bool,
 )
 	-> Result<(
 		TaskManager,
@@ -743,6 +785,8 @@ pub fn westend_new_full(
 		authority_discovery_enabled,
 		slot_duration,
 		grandpa_pause,
+		disabled_subsystems,
+		log_subsystem_messages,
 	)?;
 
 	Ok((components, client, FullNodeHandles))
@@ -765,3 +809,69 @@ pub fn westend_new_light(config: Configuration, ) -> Result<TaskManager, Service
 {
 	new_light::<westend_runtime::RuntimeApi, KusamaExecutor>(config)
 }
+
+/// Build a new light node.
+pub fn build_light(config: Configuration) -> Result<TaskManager, ServiceError> {
+	if config.chain_spec.is_kusama() {
+		kusama_new_light(config)
+	} else if config.chain_spec.is_westend() {
+		westend_new_light(config)
+	} else {
+		polkadot_new_light(config)
+	}
+}
+
+/// Build a new full node.
+///
+/// The subsystems named in `disabled_subsystems` are replaced by dummies, see
+/// [`AllSubsystems::disable`]. As the overseer only runs dummy subsystems for now, this only
+/// checks the names. With `log_subsystem_messages`, the overseer logs every message
+/// it routes.
+#[cfg(feature = "full-node")]
+pub fn build_full(
+	config: Configuration,
+	collating_for: Option<(CollatorId, ParaId)>,
+	authority_discovery_enabled: bool,
+	grandpa_pause: Option<(u32, u32)>,
+	disabled_subsystems: Vec<String>,
+	log_subsystem_messages: bool,
+) -> Result<(TaskManager, FullNodeHandles), ServiceError> {
+	// Neither the maximum block data size nor the slot duration are used by `new_full`.
+	let max_block_data_size = None;
+	let slot_duration = 0;
+
+	if config.chain_spec.is_kusama() {
+		kusama_new_full(
+			config,
+			collating_for,
+			max_block_data_size,
+			authority_discovery_enabled,
+			slot_duration,
+			grandpa_pause,
+			disabled_subsystems,
+			log_subsystem_messages,
+		).map(|(task_manager, _, handles)| (task_manager, handles))
+	} else if config.chain_spec.is_westend() {
+		westend_new_full(
+			config,
+			collating_for,
+			max_block_data_size,
+			authority_discovery_enabled,
+			slot_duration,
+			grandpa_pause,
+			disabled_subsystems,
+			log_subsystem_messages,
+		).map(|(task_manager, _, handles)| (task_manager, handles))
+	} else {
+		polkadot_new_full(
+			config,
+			collating_for,
+			max_block_data_size,
+			authority_discovery_enabled,
+			slot_duration,
+			grandpa_pause,
+			disabled_subsystems,
+			log_subsystem_messages,
+		).map(|(task_manager, _, handles)| (task_manager, handles))
+	}
+}
@@ -16,8 +16,10 @@
 
 //! Utilities for testing subsystems.
 
-use polkadot_node_subsystem::messages::AllMessages;
-use polkadot_node_subsystem::{FromOverseer, SubsystemContext, SubsystemError, SubsystemResult};
+use polkadot_node_subsystem::messages::{AllMessages, AssociateOutgoing};
+use polkadot_node_subsystem::{
+	FromOverseer, OutgoingMessages, SubsystemContext, SubsystemError, SubsystemResult,
+};
 
 use futures::channel::mpsc;
 use futures::poll;
@@ -153,7 +155,7 @@ pub struct TestSubsystemContext<M, S> {
 }
 
 #[async_trait::async_trait]
-impl<M: Send + 'static, S: SpawnNamed + Send + 'static> SubsystemContext
+impl<M: AssociateOutgoing + Send + 'static, S: SpawnNamed + Send + 'static> SubsystemContext
 	for TestSubsystemContext<M, S>
 {
 	type Message = M;
@@ -186,9 +188,13 @@ impl<M: Send + 'static, S: SpawnNamed + Send + 'static> SubsystemContext
 		Ok(())
 	}
 
-	async fn send_message(&mut self, msg: AllMessages) -> SubsystemResult<()> {
+	async fn send_message<T>(&mut self, msg: T) -> SubsystemResult<()>
+	where
+		T: Into<OutgoingMessages<M>> + Send,
+	{
+		let msg: OutgoingMessages<M> = msg.into();
 		self.tx
-			.send(msg)
+			.send(msg.into())
 			.await
 			.expect("test overseer no longer live");
 		Ok(())
@@ -196,10 +202,14 @@ impl<M: Send + 'static, S: SpawnNamed + Send + 'static> SubsystemContext
 
 	async fn send_messages<T>(&mut self, msgs: T) -> SubsystemResult<()>
 	where
-		T: IntoIterator<Item = AllMessages> + Send,
+		T: IntoIterator + Send,
 		T::IntoIter: Send,
+		T::Item: Into<OutgoingMessages<M>> + Send,
 	{
-		let mut iter = stream::iter(msgs.into_iter().map(Ok));
+		let mut iter = stream::iter(msgs.into_iter().map(|msg| {
+			let msg: OutgoingMessages<M> = msg.into();
+			Ok::<AllMessages, _>(msg.into())
+		}));
 		self.tx
 			.send_all(&mut iter)
 			.await
@@ -0,0 +1,208 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A utility for determining which blocks a subsystem has yet to process when a new leaf is
+//! activated.
+//!
+//! Subsystems which need to process every block, rather than only the active leaves, keep track
+//! of the blocks they have already seen. When a new leaf is activated, the chain is walked back
+//! through the Chain API until a known block or the lower bound, typically the last finalized
+//! block, is reached.
+
+use polkadot_node_subsystem::{
+	messages::ChainApiMessage, OutgoingMessages, SubsystemContext,
+};
+use polkadot_primitives::v1::{BlockNumber, Hash, Header};
+use futures::channel::oneshot;
+
+use crate::Error;
+
+/// The number of ancestors requested from the Chain API at a time.
+const ANCESTRY_STEP: usize = 4;
+
+/// Determine the blocks which are new given the newly activated leaf `head` with the given
+/// `header`.
+///
+/// The chain ending in `head` is walked back until a block for which `is_known` returns `true`,
+/// a block with a number not greater than `lower_bound_number` or a block whose header is
+/// unavailable is reached. The blocks visited before that, which includes `head` itself, are
+/// returned along with their headers, ordered from the oldest to the newest.
+pub async fn determine_new_blocks<Context>(
+	ctx: &mut Context,
+	is_known: impl Fn(&Hash) -> bool,
+	head: Hash,
+	header: &Header,
+	lower_bound_number: BlockNumber,
+) -> Result<Vec<(Hash, Header)>, Error>
+where
+	Context: SubsystemContext,
+	ChainApiMessage: Into<OutgoingMessages<Context::Message>>,
+{
+	let is_new = |hash: &Hash, header: &Header| header.number > lower_bound_number && !is_known(hash);
+
+	if !is_new(&head, header) {
+		return Ok(Vec::new());
+	}
+
+	let mut new_blocks = vec![(head, header.clone())];
+
+	'outer: loop {
+		let (last_hash, last_header) = new_blocks.last().expect("new_blocks is never empty; qed");
+		if last_header.number <= lower_bound_number + 1 {
+			// the parent of the last block is at the lower bound.
+			break
+		}
+
+		let k = ANCESTRY_STEP.min((last_header.number - lower_bound_number - 1) as usize);
+		let (tx, rx) = oneshot::channel();
+		ctx.send_message(ChainApiMessage::Ancestors {
+			hash: *last_hash,
+			k,
+			response_channel: tx,
+		}).await?;
+		let ancestors = rx.await??;
+		if ancestors.is_empty() {
+			break
+		}
+
+		let (tx, rx) = oneshot::channel();
+		ctx.send_message(ChainApiMessage::BlockHeaders(ancestors.clone(), tx)).await?;
+		let headers = rx.await??;
+
+		for (hash, header) in ancestors.into_iter().zip(headers) {
+			match header {
+				Some(header) if is_new(&hash, &header) => new_blocks.push((hash, header)),
+				_ => break 'outer,
+			}
+		}
+	}
+
+	new_blocks.reverse();
+	Ok(new_blocks)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_node_subsystem::messages::{AllMessages, ProvisionerMessage};
+	use polkadot_node_subsystem_test_helpers::make_subsystem_context;
+	use futures::{executor, future};
+	use std::collections::HashSet;
+
+	// a chain of `n` blocks on top of genesis, returned from genesis to the tip.
+	fn chain(n: BlockNumber) -> Vec<(Hash, Header)> {
+		let mut chain = Vec::new();
+		let mut parent_hash = Hash::zero();
+		for number in 0..=n {
+			let header = Header {
+				parent_hash,
+				number,
+				state_root: Default::default(),
+				extrinsics_root: Default::default(),
+				digest: Default::default(),
+			};
+			parent_hash = header.hash();
+			chain.push((parent_hash, header));
+		}
+		chain
+	}
+
+	// answer Chain API requests from the given chain until the requester is done.
+	async fn answer_chain_api(
+		mut handle: polkadot_node_subsystem_test_helpers::TestSubsystemContextHandle<ProvisionerMessage>,
+		chain: Vec<(Hash, Header)>,
+	) {
+		let position = |hash: &Hash| chain.iter().position(|(h, _)| h == hash);
+
+		while let Some(msg) = handle.try_recv().await {
+			match msg {
+				AllMessages::ChainApi(ChainApiMessage::Ancestors { hash, k, response_channel }) => {
+					let ancestors = position(&hash)
+						.map(|pos| chain[..pos].iter().rev().take(k).map(|(h, _)| *h).collect())
+						.unwrap_or_default();
+					let _ = response_channel.send(Ok(ancestors));
+				}
+				AllMessages::ChainApi(ChainApiMessage::BlockHeaders(hashes, response_channel)) => {
+					let headers = hashes.iter()
+						.map(|hash| position(hash).map(|pos| chain[pos].1.clone()))
+						.collect();
+					let _ = response_channel.send(Ok(headers));
+				}
+				msg => panic!("unexpected message: {:?}", msg),
+			}
+		}
+	}
+
+	fn determine(
+		chain: Vec<(Hash, Header)>,
+		known: HashSet<Hash>,
+		lower_bound_number: BlockNumber,
+	) -> Vec<(Hash, Header)> {
+		let (mut ctx, handle) = make_subsystem_context::<ProvisionerMessage, _>(
+			sp_core::testing::TaskExecutor::new(),
+		);
+		let (head, header) = chain.last().cloned().unwrap();
+
+		let (new_blocks, _) = executor::block_on(future::join(
+			async move {
+				determine_new_blocks(
+					&mut ctx,
+					|hash| known.contains(hash),
+					head,
+					&header,
+					lower_bound_number,
+				).await.unwrap()
+			},
+			answer_chain_api(handle, chain),
+		));
+
+		new_blocks
+	}
+
+	#[test]
+	fn walks_back_to_lower_bound() {
+		let chain = chain(10);
+
+		assert_eq!(determine(chain.clone(), HashSet::new(), 3), chain[4..].to_vec());
+		assert_eq!(determine(chain.clone(), HashSet::new(), 0), chain[1..].to_vec());
+		assert!(determine(chain.clone(), HashSet::new(), 10).is_empty());
+	}
+
+	#[test]
+	fn stops_at_known_block() {
+		let chain = chain(10);
+		let known = vec![chain[6].0].into_iter().collect();
+
+		assert_eq!(determine(chain.clone(), known, 0), chain[7..].to_vec());
+	}
+
+	#[test]
+	fn known_head_yields_nothing() {
+		let chain = chain(10);
+		let known = vec![chain[10].0].into_iter().collect();
+
+		assert!(determine(chain, known, 0).is_empty());
+	}
+
+	#[test]
+	fn stops_where_ancestry_ends() {
+		let mut chain = chain(10);
+		// blocks below the 5th are unknown to the Chain API.
+		let tail = chain.split_off(5);
+
+		assert_eq!(determine(tail.clone(), HashSet::new(), 0), tail);
+	}
+}
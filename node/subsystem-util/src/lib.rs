@@ -23,7 +23,8 @@
 use polkadot_node_subsystem::{
 	errors::{ChainApiError, RuntimeApiError},
 	messages::{AllMessages, RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender},
-	FromOverseer, SpawnedSubsystem, Subsystem, SubsystemContext, SubsystemError, SubsystemResult,
+	FromOverseer, OutgoingMessages, SpawnedSubsystem, Subsystem, SubsystemContext, SubsystemError,
+	SubsystemResult,
 	metrics,
 };
 use futures::{
@@ -38,26 +39,35 @@ use futures_timer::Delay;
 use keystore::KeyStorePtr;
 use parity_scale_codec::Encode;
 use pin_project::{pin_project, pinned_drop};
+use polkadot_node_primitives::CheckedSigned;
+use parking_lot::Mutex;
 use polkadot_primitives::v1::{
-	CandidateEvent, CommittedCandidateReceipt, CoreState, EncodeAs, PersistedValidationData,
-	GroupRotationInfo, Hash, Id as ParaId, ValidationData, OccupiedCoreAssumption,
-	SessionIndex, Signed, SigningContext, ValidationCode, ValidatorId, ValidatorIndex,
-	ValidatorPair,
+	BlockNumber, CandidateEvent, CommittedCandidateReceipt, CoreIndex, CoreState, EncodeAs,
+	ExecutorParams, PersistedValidationData, GroupIndex, GroupRotationInfo, Hash, Id as ParaId, ValidationData,
+	OccupiedCoreAssumption, ScrapedOnChainVotes, SessionIndex, Signed, SigningContext,
+	ValidationCode, ValidatorId, ValidatorIndex, ValidatorPair,
 };
+use sp_consensus_babe::{BabeGenesisConfiguration, SlotNumber};
 use sp_core::{Pair, traits::SpawnNamed};
 use std::{
-	collections::HashMap,
+	collections::{BTreeMap, HashMap},
 	convert::{TryFrom, TryInto},
 	marker::Unpin,
 	pin::Pin,
+	sync::Arc,
 	time::Duration,
 };
 use streamunordered::{StreamUnordered, StreamYield};
 
+pub mod determine_new_blocks;
+
+pub use determine_new_blocks::determine_new_blocks;
+
 /// These reexports are required so that external crates can use the `delegated_subsystem` macro properly.
 pub mod reexports {
 	pub use sp_core::traits::SpawnNamed;
 	pub use polkadot_node_subsystem::{
+		OutgoingMessages,
 		SpawnedSubsystem,
 		Subsystem,
 		SubsystemContext,
@@ -182,14 +192,26 @@ macro_rules! specialize_requests {
 
 specialize_requests! {
 	fn request_validators() -> Vec<ValidatorId>; Validators;
+	fn request_active_validator_indices() -> Vec<ValidatorIndex>; ActiveValidatorIndices;
 	fn request_validator_groups() -> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo); ValidatorGroups;
+	fn request_group_for_core(core_index: CoreIndex) -> Option<(GroupIndex, Vec<ValidatorIndex>)>; GroupForCore;
 	fn request_availability_cores() -> Vec<CoreState>; AvailabilityCores;
 	fn request_full_validation_data(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationData>; FullValidationData;
+	fn request_full_validation_data_at(para_id: ParaId, relay_parent_number: BlockNumber, assumption: OccupiedCoreAssumption) -> Option<ValidationData>; FullValidationDataAt;
+	fn request_allowed_relay_parents() -> Vec<(Hash, BlockNumber)>; AllowedRelayParents;
 	fn request_persisted_validation_data(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<PersistedValidationData>; PersistedValidationData;
 	fn request_session_index_for_child() -> SessionIndex; SessionIndexForChild;
+	fn request_availability_chunk_shuffle_seed() -> [u8; 32]; AvailabilityChunkShuffleSeed;
 	fn request_validation_code(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationCode>; ValidationCode;
+	fn request_validation_code_by_hash(hash: Hash) -> Option<ValidationCode>; ValidationCodeByHash;
+	fn request_historical_validation_code(para_id: ParaId, context_height: BlockNumber) -> Option<ValidationCode>; HistoricalValidationCode;
+	fn request_session_executor_params(session_index: SessionIndex) -> Option<ExecutorParams>; SessionExecutorParams;
+	fn request_claim_queue() -> BTreeMap<CoreIndex, Vec<ParaId>>; ClaimQueue;
 	fn request_candidate_pending_availability(para_id: ParaId) -> Option<CommittedCandidateReceipt>; CandidatePendingAvailability;
 	fn request_candidate_events() -> Vec<CandidateEvent>; CandidateEvents;
+	fn request_on_chain_votes() -> Option<ScrapedOnChainVotes>; FetchOnChainVotes;
+	fn request_babe_configuration() -> BabeGenesisConfiguration; BabeConfiguration;
+	fn request_current_babe_epoch_start() -> SlotNumber; CurrentBabeEpochStart;
 }
 
 /// Request some data from the `RuntimeApi` via a SubsystemContext.
@@ -201,16 +223,11 @@ async fn request_from_runtime_ctx<RequestBuilder, Context, Response>(
 where
 	RequestBuilder: FnOnce(RuntimeApiSender<Response>) -> RuntimeApiRequest,
 	Context: SubsystemContext,
+	RuntimeApiMessage: Into<OutgoingMessages<Context::Message>>,
 {
 	let (tx, rx) = oneshot::channel();
 
-	ctx
-		.send_message(
-			AllMessages::RuntimeApi(RuntimeApiMessage::Request(parent, request_builder(tx)))
-				.try_into()
-				.map_err(|err| Error::SenderConversion(format!("{:?}", err)))?,
-		)
-		.await?;
+	ctx.send_message(RuntimeApiMessage::Request(parent, request_builder(tx))).await?;
 
 	Ok(rx)
 }
@@ -232,13 +249,17 @@ macro_rules! specialize_requests_ctx {
 		#[doc = "Request `"]
 		#[doc = $doc_name]
 		#[doc = "` from the runtime via a `SubsystemContext`"]
-		pub async fn $func_name<Context: SubsystemContext>(
+		pub async fn $func_name<Context>(
 			parent: Hash,
 			$(
 				$param_name: $param_ty,
 			)*
 			ctx: &mut Context,
-		) -> Result<RuntimeApiReceiver<$return_ty>, Error> {
+		) -> Result<RuntimeApiReceiver<$return_ty>, Error>
+		where
+			Context: SubsystemContext,
+			RuntimeApiMessage: Into<OutgoingMessages<Context::Message>>,
+		{
 			request_from_runtime_ctx(parent, ctx, |tx| RuntimeApiRequest::$request_variant(
 				$( $param_name, )* tx
 			)).await
@@ -265,14 +286,45 @@ macro_rules! specialize_requests_ctx {
 
 specialize_requests_ctx! {
 	fn request_validators_ctx() -> Vec<ValidatorId>; Validators;
+	fn request_active_validator_indices_ctx() -> Vec<ValidatorIndex>; ActiveValidatorIndices;
 	fn request_validator_groups_ctx() -> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo); ValidatorGroups;
+	fn request_group_for_core_ctx(core_index: CoreIndex) -> Option<(GroupIndex, Vec<ValidatorIndex>)>; GroupForCore;
 	fn request_availability_cores_ctx() -> Vec<CoreState>; AvailabilityCores;
 	fn request_full_validation_data_ctx(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationData>; FullValidationData;
+	fn request_full_validation_data_at_ctx(para_id: ParaId, relay_parent_number: BlockNumber, assumption: OccupiedCoreAssumption) -> Option<ValidationData>; FullValidationDataAt;
+	fn request_allowed_relay_parents_ctx() -> Vec<(Hash, BlockNumber)>; AllowedRelayParents;
 	fn request_persisted_validation_data_ctx(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<PersistedValidationData>; PersistedValidationData;
 	fn request_session_index_for_child_ctx() -> SessionIndex; SessionIndexForChild;
+	fn request_availability_chunk_shuffle_seed_ctx() -> [u8; 32]; AvailabilityChunkShuffleSeed;
 	fn request_validation_code_ctx(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationCode>; ValidationCode;
+	fn request_validation_code_by_hash_ctx(hash: Hash) -> Option<ValidationCode>; ValidationCodeByHash;
+	fn request_historical_validation_code_ctx(para_id: ParaId, context_height: BlockNumber) -> Option<ValidationCode>; HistoricalValidationCode;
+	fn request_session_executor_params_ctx(session_index: SessionIndex) -> Option<ExecutorParams>; SessionExecutorParams;
+	fn request_claim_queue_ctx() -> BTreeMap<CoreIndex, Vec<ParaId>>; ClaimQueue;
 	fn request_candidate_pending_availability_ctx(para_id: ParaId) -> Option<CommittedCandidateReceipt>; CandidatePendingAvailability;
 	fn request_candidate_events_ctx() -> Vec<CandidateEvent>; CandidateEvents;
+	fn request_on_chain_votes_ctx() -> Option<ScrapedOnChainVotes>; FetchOnChainVotes;
+	fn request_babe_configuration_ctx() -> BabeGenesisConfiguration; BabeConfiguration;
+	fn request_current_babe_epoch_start_ctx() -> SlotNumber; CurrentBabeEpochStart;
+}
+
+/// Find the group assigned to back candidates on the core with the given index, out of `n_cores`
+/// cores, along with the validators in it.
+///
+/// `validator_groups` and `group_rotation_info` are as returned by `request_validator_groups`.
+/// Returns `None` if the core doesn't exist or the runtime didn't provide the group for it.
+pub fn backing_group_for_core<'a>(
+	validator_groups: &'a [Vec<ValidatorIndex>],
+	group_rotation_info: &GroupRotationInfo,
+	core_index: CoreIndex,
+	n_cores: usize,
+) -> Option<(GroupIndex, &'a [ValidatorIndex])> {
+	if core_index.0 as usize >= n_cores {
+		return None;
+	}
+
+	let group_index = group_rotation_info.group_for_core(core_index, n_cores);
+	validator_groups.get(group_index.0 as usize).map(|group| (group_index, &group[..]))
 }
 
 /// From the given set of validators, find the first key we can sign with, if any.
@@ -283,6 +335,16 @@ pub fn signing_key(validators: &[ValidatorId], keystore: &KeyStorePtr) -> Option
 		.find_map(|v| keystore.key_pair::<ValidatorPair>(&v).ok())
 }
 
+/// The number of most recent sessions a [`ValidatorKeyCache`] keeps.
+const VALIDATOR_KEY_CACHE_SESSIONS: SessionIndex = 2;
+
+/// The local validator key and index, per session.
+///
+/// Finding the local key means querying the keystore for each key of the validator set,
+/// which only needs to happen once per session. Clones share the same cache.
+#[derive(Clone, Default)]
+pub struct ValidatorKeyCache(Arc<Mutex<HashMap<SessionIndex, Option<(ValidatorPair, ValidatorIndex)>>>>);
+
 /// Local validator information
 ///
 /// It can be created if the local node is a validator in the context of a particular
@@ -322,6 +384,32 @@ impl Validator {
 		Self::construct(&validators, signing_context, keystore)
 	}
 
+	/// Like [`Validator::new`], but the local key is looked up in `cache` first.
+	pub async fn new_cached<FromJob>(
+		parent: Hash,
+		keystore: KeyStorePtr,
+		cache: &ValidatorKeyCache,
+		mut sender: mpsc::Sender<FromJob>,
+	) -> Result<Self, Error>
+	where
+		FromJob: TryFrom<AllMessages>,
+		<FromJob as TryFrom<AllMessages>>::Error: std::fmt::Debug,
+	{
+		let (validators, session_index) = futures::try_join!(
+			request_validators(parent, &mut sender).await?,
+			request_session_index_for_child(parent, &mut sender).await?,
+		)?;
+
+		let signing_context = SigningContext {
+			session_index: session_index?,
+			parent_hash: parent,
+		};
+
+		let validators = validators?;
+
+		Self::construct_cached(&validators, signing_context, keystore, cache)
+	}
+
 	/// Construct a validator instance without performing runtime fetches.
 	///
 	/// This can be useful if external code also needs the same data.
@@ -345,6 +433,41 @@ impl Validator {
 		})
 	}
 
+	/// Like [`Validator::construct`], but the keystore is only scanned if `cache` doesn't know
+	/// the local key for the session of `signing_context` yet.
+	///
+	/// `validators` must be the validator set of that session.
+	pub fn construct_cached(
+		validators: &[ValidatorId],
+		signing_context: SigningContext,
+		keystore: KeyStorePtr,
+		cache: &ValidatorKeyCache,
+	) -> Result<Self, Error> {
+		let session_index = signing_context.session_index;
+		let mut sessions = cache.0.lock();
+
+		let entry = match sessions.get(&session_index) {
+			Some(entry) => entry.clone(),
+			None => {
+				let entry = Self::construct(validators, signing_context.clone(), keystore)
+					.ok()
+					.map(|v| (v.key, v.index));
+
+				sessions.retain(|s, _| s.saturating_add(VALIDATOR_KEY_CACHE_SESSIONS) > session_index);
+				sessions.insert(session_index, entry.clone());
+				entry
+			}
+		};
+
+		let (key, index) = entry.ok_or(Error::NotAValidator)?;
+
+		Ok(Validator {
+			signing_context,
+			key,
+			index,
+		})
+	}
+
 	/// Get this validator's id.
 	pub fn id(&self) -> ValidatorId {
 		self.key.public()
@@ -364,8 +487,8 @@ impl Validator {
 	pub fn sign<Payload: EncodeAs<RealPayload>, RealPayload: Encode>(
 		&self,
 		payload: Payload,
-	) -> Signed<Payload, RealPayload> {
-		Signed::sign(payload, &self.signing_context, self.index, &self.key)
+	) -> CheckedSigned<Payload, RealPayload> {
+		CheckedSigned::sign(payload, &self.signing_context, self.index, &self.key)
 	}
 
 	/// Validate the payload with this validator
@@ -456,7 +579,9 @@ pub trait JobTrait: Unpin {
 	/// The `delegate_subsystem!` macro should take care of this.
 	type Metrics: 'static + metrics::Metrics + Send;
 
-	/// Name of the job, i.e. `CandidateBackingJob`
+	/// Name of the job, i.e. `candidate-backing-job`.
+	///
+	/// Every instance of the job is spawned as a task with this name.
 	const NAME: &'static str;
 
 	/// Run a job for the parent block indicated
@@ -679,6 +804,7 @@ where
 	Job: 'static + JobTrait,
 	Job::RunArgs: Clone,
 	Job::ToJob: TryFrom<AllMessages> + TryFrom<<Context as SubsystemContext>::Message> + Sync,
+	Job::FromJob: Into<OutgoingMessages<<Context as SubsystemContext>::Message>>,
 {
 	/// Creates a new `Subsystem`.
 	pub fn new(spawner: Spawner, run_args: Job::RunArgs, metrics: Job::Metrics) -> Self {
@@ -836,7 +962,7 @@ where
 					}
 				}
 			}
-			Ok(Signal(BlockFinalized(_))) => {}
+			Ok(Signal(BlockFinalized(..))) => {}
 			Err(err) => {
 				log::error!("error receiving message from subsystem context: {:?}", err);
 				Self::fwd_err(None, Error::from(err).into(), err_tx).await;
@@ -853,7 +979,7 @@ where
 		err_tx: &mut Option<mpsc::Sender<(Option<Hash>, JobsError<Job::Error>)>>,
 	) {
 		let msg = outgoing.expect("the Jobs stream never ends; qed");
-		if let Err(e) = ctx.send_message(msg.into()).await {
+		if let Err(e) = ctx.send_message(msg).await {
 			Self::fwd_err(None, Error::from(e).into(), err_tx).await;
 		}
 	}
@@ -864,6 +990,7 @@ where
 	Spawner: SpawnNamed + Send + Clone + Unpin + 'static,
 	Context: SubsystemContext,
 	<Context as SubsystemContext>::Message: Into<Job::ToJob>,
+	Job::FromJob: Into<OutgoingMessages<<Context as SubsystemContext>::Message>>,
 	Job: 'static + JobTrait + Send,
 	Job::RunArgs: Clone + Sync,
 	Job::ToJob: TryFrom<AllMessages> + Sync,
@@ -882,7 +1009,7 @@ where
 		});
 
 		SpawnedSubsystem {
-			name: Job::NAME.strip_suffix("Job").unwrap_or(Job::NAME),
+			name: Job::NAME.strip_suffix("-job").unwrap_or(Job::NAME),
 			future,
 		}
 	}
@@ -943,6 +1070,9 @@ macro_rules! delegated_subsystem {
 			Spawner: Clone + $crate::reexports::SpawnNamed + Send + Unpin,
 			Context: $crate::reexports::SubsystemContext,
 			<Context as $crate::reexports::SubsystemContext>::Message: Into<$to_job>,
+			<$job as $crate::JobTrait>::FromJob: Into<$crate::reexports::OutgoingMessages<
+				<Context as $crate::reexports::SubsystemContext>::Message
+			>>,
 		{
 			#[doc = "Creates a new "]
 			#[doc = $subsystem_name]
@@ -963,6 +1093,9 @@ macro_rules! delegated_subsystem {
 			Spawner: $crate::reexports::SpawnNamed + Send + Clone + Unpin + 'static,
 			Context: $crate::reexports::SubsystemContext,
 			<Context as $crate::reexports::SubsystemContext>::Message: Into<$to_job>,
+			<$job as $crate::JobTrait>::FromJob: Into<$crate::reexports::OutgoingMessages<
+				<Context as $crate::reexports::SubsystemContext>::Message
+			>>,
 		{
 			type Metrics = $metrics;
 
@@ -975,9 +1108,15 @@ macro_rules! delegated_subsystem {
 
 #[cfg(test)]
 mod tests {
-	use super::{Error as UtilError, JobManager, JobTrait, JobsError, ToJobTrait};
+	use super::{
+		Error as UtilError, JobManager, JobTrait, JobsError, ToJobTrait, Validator, ValidatorKeyCache,
+		backing_group_for_core,
+	};
 	use polkadot_node_subsystem::{
-		messages::{AllMessages, CandidateSelectionMessage},
+		messages::{
+			AllMessages, CandidateSelectionMessage, CandidateSelectionOutgoingMessages,
+			CollatorProtocolMessage,
+		},
 		ActiveLeavesUpdate, FromOverseer, OverseerSignal, SpawnedSubsystem, Subsystem,
 	};
 	use assert_matches::assert_matches;
@@ -987,7 +1126,10 @@ mod tests {
 		stream::{self, StreamExt},
 		future, Future, FutureExt, SinkExt,
 	};
-	use polkadot_primitives::v1::Hash;
+	use polkadot_primitives::v1::{
+		CoreIndex, GroupIndex, GroupRotationInfo, Hash, SigningContext, ValidatorId, ValidatorPair,
+	};
+	use sp_keyring::Sr25519Keyring;
 	use polkadot_node_subsystem_test_helpers::{self as test_helpers, make_subsystem_context, TimeoutExt as _};
 	use std::{collections::HashMap, convert::TryFrom, pin::Pin, time::Duration};
 
@@ -1057,8 +1199,15 @@ mod tests {
 
 	impl From<FromJob> for AllMessages {
 		fn from(from_job: FromJob) -> AllMessages {
+			CandidateSelectionOutgoingMessages::from(from_job).into()
+		}
+	}
+
+	// FromJob must also be convertable into the outgoing messages of the subsystem running the job.
+	impl From<FromJob> for CandidateSelectionOutgoingMessages {
+		fn from(from_job: FromJob) -> CandidateSelectionOutgoingMessages {
 			match from_job {
-				FromJob::Test => AllMessages::CandidateSelection(CandidateSelectionMessage::default()),
+				FromJob::Test => CollatorProtocolMessage::CollateOn(Default::default()).into(),
 			}
 		}
 	}
@@ -1086,7 +1235,7 @@ mod tests {
 		type RunArgs = HashMap<Hash, Vec<FromJob>>;
 		type Metrics = ();
 
-		const NAME: &'static str = "FakeCandidateSelectionJob";
+		const NAME: &'static str = "fake-candidate-selection-job";
 
 		/// Run a job for the parent block indicated
 		//
@@ -1184,7 +1333,7 @@ mod tests {
 				.await;
 			assert_matches!(
 				overseer_handle.recv().await,
-				AllMessages::CandidateSelection(_)
+				AllMessages::CollatorProtocol(_)
 			);
 			overseer_handle
 				.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
@@ -1249,7 +1398,7 @@ mod tests {
 			// the subsystem is still alive
 			assert_matches!(
 				overseer_handle.recv().await,
-				AllMessages::CandidateSelection(_)
+				AllMessages::CollatorProtocol(_)
 			);
 
 			overseer_handle
@@ -1261,6 +1410,91 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn validator_key_is_cached_per_session() {
+		let keystore = keystore::Store::new_in_memory();
+		keystore
+			.write()
+			.insert_ephemeral_from_seed::<ValidatorPair>(&Sr25519Keyring::Bob.to_seed())
+			.expect("Insert key into keystore");
+
+		let validators: Vec<ValidatorId> = [Sr25519Keyring::Alice, Sr25519Keyring::Bob]
+			.iter()
+			.map(|k| k.public().into())
+			.collect();
+		let signing_context = |session_index| SigningContext { session_index, parent_hash: Hash::zero() };
+		let cache = ValidatorKeyCache::default();
+
+		let validator = Validator::construct_cached(
+			&validators,
+			signing_context(1),
+			keystore.clone(),
+			&cache,
+		).unwrap();
+		assert_eq!(validator.index(), 1);
+
+		// the validator set isn't looked at again within the session.
+		let validator = Validator::construct_cached(&[], signing_context(1), keystore.clone(), &cache)
+			.unwrap();
+		assert_eq!(validator.index(), 1);
+		assert_eq!(validator.signing_context().session_index, 1);
+
+		// but it is in a new session.
+		assert_matches!(
+			Validator::construct_cached(&[], signing_context(2), keystore, &cache),
+			Err(UtilError::NotAValidator)
+		);
+	}
+
+	#[test]
+	fn rotated_validator_key_is_picked_up_on_new_session() {
+		let keystore = keystore::Store::new_in_memory();
+		keystore
+			.write()
+			.insert_ephemeral_from_seed::<ValidatorPair>(&Sr25519Keyring::Bob.to_seed())
+			.expect("Insert key into keystore");
+
+		let signing_context = |session_index| SigningContext { session_index, parent_hash: Hash::zero() };
+		let cache = ValidatorKeyCache::default();
+
+		let validators: Vec<ValidatorId> = [Sr25519Keyring::Alice, Sr25519Keyring::Bob]
+			.iter()
+			.map(|k| k.public().into())
+			.collect();
+		let validator = Validator::construct_cached(
+			&validators,
+			signing_context(1),
+			keystore.clone(),
+			&cache,
+		).unwrap();
+		assert_eq!(validator.id(), ValidatorId::from(Sr25519Keyring::Bob.public()));
+		assert_eq!(validator.index(), 1);
+
+		// the session keys are rotated and the new key becomes active in the next session.
+		keystore
+			.write()
+			.insert_ephemeral_from_seed::<ValidatorPair>(&Sr25519Keyring::Charlie.to_seed())
+			.expect("Insert key into keystore");
+
+		let validators: Vec<ValidatorId> = [Sr25519Keyring::Charlie, Sr25519Keyring::Alice]
+			.iter()
+			.map(|k| k.public().into())
+			.collect();
+		let validator = Validator::construct_cached(
+			&validators,
+			signing_context(2),
+			keystore.clone(),
+			&cache,
+		).unwrap();
+		assert_eq!(validator.id(), ValidatorId::from(Sr25519Keyring::Charlie.public()));
+		assert_eq!(validator.index(), 0);
+
+		// entries of old sessions are dropped.
+		let _ = Validator::construct_cached(&validators, signing_context(3), keystore, &cache);
+		assert!(!cache.0.lock().contains_key(&1));
+		assert!(cache.0.lock().contains_key(&2));
+	}
+
 	#[test]
 	fn test_subsystem_impl_and_name_derivation() {
 		let pool = sp_core::testing::TaskExecutor::new();
@@ -1268,6 +1502,53 @@ mod tests {
 
 		let SpawnedSubsystem { name, .. } =
 			FakeCandidateSelectionSubsystem::new(pool, HashMap::new(), ()).start(context);
-		assert_eq!(name, "FakeCandidateSelection");
+		assert_eq!(name, "fake-candidate-selection");
+	}
+
+	#[test]
+	fn backing_group_for_core_follows_rotation() {
+		let validator_groups = vec![vec![0, 1], vec![2, 3], vec![4]];
+		let mut group_rotation_info = GroupRotationInfo {
+			session_start_block: 10,
+			group_rotation_frequency: 5,
+			now: 10,
+		};
+
+		let group_for = |info: &GroupRotationInfo, core| {
+			backing_group_for_core(&validator_groups, info, CoreIndex(core), 3)
+		};
+
+		assert_eq!(group_for(&group_rotation_info, 0), Some((GroupIndex(0), &[0, 1][..])));
+		assert_eq!(group_for(&group_rotation_info, 2), Some((GroupIndex(2), &[4][..])));
+
+		// one rotation later, every core is assigned the next group.
+		group_rotation_info.now = 16;
+		assert_eq!(group_for(&group_rotation_info, 0), Some((GroupIndex(1), &[2, 3][..])));
+		assert_eq!(group_for(&group_rotation_info, 2), Some((GroupIndex(0), &[0, 1][..])));
+
+		// groups never rotate with a frequency of 0.
+		group_rotation_info.group_rotation_frequency = 0;
+		assert_eq!(group_for(&group_rotation_info, 1), Some((GroupIndex(1), &[2, 3][..])));
+	}
+
+	#[test]
+	fn backing_group_for_core_handles_missing_cores_and_groups() {
+		let group_rotation_info = GroupRotationInfo {
+			session_start_block: 0,
+			group_rotation_frequency: 0,
+			now: 0,
+		};
+
+		// there is no such core.
+		assert_eq!(
+			backing_group_for_core(&[vec![0], vec![1]], &group_rotation_info, CoreIndex(2), 2),
+			None,
+		);
+
+		// there are more cores than groups.
+		assert_eq!(
+			backing_group_for_core(&[vec![0]], &group_rotation_info, CoreIndex(1), 2),
+			None,
+		);
 	}
 }
@@ -18,17 +18,40 @@
 
 /// A description of an error causing the runtime API request to be unservable.
 #[derive(Debug, Clone)]
-pub struct RuntimeApiError(String);
-
-impl From<String> for RuntimeApiError {
-	fn from(s: String) -> Self {
-		RuntimeApiError(s)
-	}
+pub enum RuntimeApiError {
+	/// The runtime API call failed.
+	Execution {
+		/// The name of the runtime API function.
+		runtime_api_name: &'static str,
+		/// A description of the error the call failed with.
+		source: String,
+	},
+	/// The state of the block the runtime API was called at isn't available, most likely because
+	/// it has been pruned.
+	UnknownBlock {
+		/// The name of the runtime API function.
+		runtime_api_name: &'static str,
+	},
+	/// The runtime API call didn't complete in time and was abandoned.
+	Timeout,
+	/// The runtime doesn't implement the `ParachainHost` API version required by the request.
+	NotSupported {
+		/// The name of the runtime API function.
+		runtime_api_name: &'static str,
+	},
 }
 
 impl core::fmt::Display for RuntimeApiError {
 	fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
-		write!(f, "{}", self.0)
+		match self {
+			RuntimeApiError::Execution { runtime_api_name, source } =>
+				write!(f, "runtime API `{}` failed: {}", runtime_api_name, source),
+			RuntimeApiError::UnknownBlock { runtime_api_name } =>
+				write!(f, "state for runtime API `{}` is unavailable", runtime_api_name),
+			RuntimeApiError::Timeout => write!(f, "runtime API request timed out"),
+			RuntimeApiError::NotSupported { runtime_api_name } =>
+				write!(f, "runtime API `{}` is not supported by the runtime", runtime_api_name),
+		}
 	}
 }
 
@@ -55,3 +78,22 @@ impl core::fmt::Display for ChainApiError {
 		write!(f, "{}", self.msg)
 	}
 }
+
+/// A description of an error causing `AvailableData` not to be stored by the availability store.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreAvailableDataError {
+	/// The erasure root of the data doesn't match the expected erasure root.
+	InvalidErasureRoot,
+	/// Writing the data to the store failed.
+	Failed,
+}
+
+impl core::fmt::Display for StoreAvailableDataError {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+		match self {
+			StoreAvailableDataError::InvalidErasureRoot =>
+				write!(f, "erasure root of the available data doesn't match the expected one"),
+			StoreAvailableDataError::Failed => write!(f, "failed to store the available data"),
+		}
+	}
+}
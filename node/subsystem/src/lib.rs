@@ -30,11 +30,11 @@ use futures::prelude::*;
 use futures::channel::{mpsc, oneshot};
 use futures::future::BoxFuture;
 
-use polkadot_primitives::v1::Hash;
+use polkadot_primitives::v1::{BlockNumber, Hash};
 use async_trait::async_trait;
 use smallvec::SmallVec;
 
-use crate::messages::AllMessages;
+use crate::messages::AssociateOutgoing;
 
 pub mod errors;
 pub mod messages;
@@ -84,8 +84,12 @@ impl PartialEq for ActiveLeavesUpdate {
 pub enum OverseerSignal {
 	/// Subsystems should adjust their jobs to start and stop work on appropriate block hashes.
 	ActiveLeaves(ActiveLeavesUpdate),
-	/// `Subsystem` is informed of a finalized block by its block hash.
-	BlockFinalized(Hash),
+	/// `Subsystem` is informed of a finalized block by its block hash and number.
+	///
+	/// Finality may advance by several blocks at once, in which case only the last finalized
+	/// block is signalled. The blocks finalized in between can be found by comparing the
+	/// number with the one of the previous signal and querying the Chain API for the gap.
+	BlockFinalized(Hash, BlockNumber),
 	/// Conclude the work of the `Overseer` and all `Subsystem`s.
 	Conclude,
 }
@@ -166,7 +170,7 @@ pub type SubsystemResult<T> = Result<T, SubsystemError>;
 pub trait SubsystemContext: Send + 'static {
 	/// The message type of this context. Subsystems launched with this context will expect
 	/// to receive messages of this type.
-	type Message: Send;
+	type Message: AssociateOutgoing + Send;
 
 	/// Try to asynchronously receive a message.
 	///
@@ -188,13 +192,24 @@ pub trait SubsystemContext: Send + 'static {
 	) -> SubsystemResult<()>;
 
 	/// Send a direct message to some other `Subsystem`, routed based on message type.
-	async fn send_message(&mut self, msg: AllMessages) -> SubsystemResult<()>;
+	///
+	/// Only messages in the outgoing set of [`Self::Message`] can be sent.
+	async fn send_message<T>(&mut self, msg: T) -> SubsystemResult<()>
+		where T: Into<OutgoingMessages<Self::Message>> + Send;
 
 	/// Send multiple direct messages to other `Subsystem`s, routed based on message type.
+	///
+	/// Only messages in the outgoing set of [`Self::Message`] can be sent.
 	async fn send_messages<T>(&mut self, msgs: T) -> SubsystemResult<()>
-		where T: IntoIterator<Item = AllMessages> + Send, T::IntoIter: Send;
+		where
+			T: IntoIterator + Send,
+			T::IntoIter: Send,
+			T::Item: Into<OutgoingMessages<Self::Message>> + Send;
 }
 
+/// The set of messages a subsystem receiving `M` may send.
+pub type OutgoingMessages<M> = <M as AssociateOutgoing>::OutgoingMessages;
+
 /// A trait that describes the [`Subsystem`]s that can run on the [`Overseer`].
 ///
 /// It is generic over the message type circulating in the system.
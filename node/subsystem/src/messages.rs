@@ -28,17 +28,19 @@ use polkadot_node_network_protocol::{
 	v1 as protocol_v1, NetworkBridgeEvent, ReputationChange, PeerId, PeerSet,
 };
 use polkadot_node_primitives::{
-	CollationGenerationConfig, MisbehaviorReport, SignedFullStatement, ValidationResult,
+	CollationGenerationConfig, MisbehaviorReport, RelayVrfStory, SignedFullStatement, ValidationResult,
 };
 use polkadot_primitives::v1::{
 	AvailableData, BackedCandidate, BlockNumber, CandidateDescriptor, CandidateEvent,
-	CandidateReceipt, CollatorId, CommittedCandidateReceipt,
-	CoreState, ErasureChunk, GroupRotationInfo, Hash, Id as ParaId,
-	OccupiedCoreAssumption, PersistedValidationData, PoV, SessionIndex, SignedAvailabilityBitfield,
-	TransientValidationData, ValidationCode, ValidatorId, ValidationData, ValidatorIndex,
-	ValidatorSignature,
+	CandidateReceipt, CollatorId, CommittedCandidateReceipt, ExecutorParams,
+	CoreIndex, CoreState, ErasureChunk, GroupIndex, GroupRotationInfo, Hash, Header, Id as ParaId,
+	OccupiedCoreAssumption, PersistedValidationData, PoV, ScrapedOnChainVotes, SessionIndex,
+	SignedAvailabilityBitfield,
+	TransientValidationData, ValidationCode, ValidatorId, ValidationData,
+	ValidatorIndex, ValidatorSignature,
 };
-use std::sync::Arc;
+use sp_consensus_babe::{BabeGenesisConfiguration, SlotNumber};
+use std::{collections::BTreeMap, sync::Arc};
 
 /// A notification of a new backed candidate.
 #[derive(Debug)]
@@ -118,15 +120,28 @@ pub enum CandidateValidationMessage {
 		Arc<PoV>,
 		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
 	),
+	/// Validate a candidate built on one of the allowed relay parents of the given leaf using the
+	/// relay-chain state of the leaf.
+	///
+	/// The `PersistedValidationData` and `ValidationCode` are gathered as for
+	/// `ValidateFromChainState`, but in the context of the relay parent's number rather than its
+	/// state. If the relay parent isn't allowed at the leaf, the candidate is invalid.
+	ValidateFromChainStateAt(
+		Hash,
+		CandidateDescriptor,
+		Arc<PoV>,
+		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
+	),
 	/// Validate a candidate with provided, exhaustive parameters for validation.
 	///
-	/// Explicitly provide the `PersistedValidationData` and `ValidationCode` so this can do full
-	/// validation without needing to access the state of the relay-chain. Optionally provide the
-	/// `TransientValidationData` for further checks on the outputs.
+	/// Explicitly provide the `PersistedValidationData`, `ValidationCode` and `ExecutorParams`
+	/// so this can do full validation without needing to access the state of the relay-chain.
+	/// Optionally provide the `TransientValidationData` for further checks on the outputs.
 	ValidateFromExhaustive(
 		PersistedValidationData,
 		Option<TransientValidationData>,
 		ValidationCode,
+		ExecutorParams,
 		CandidateDescriptor,
 		Arc<PoV>,
 		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
@@ -138,7 +153,8 @@ impl CandidateValidationMessage {
 	pub fn relay_parent(&self) -> Option<Hash> {
 		match self {
 			Self::ValidateFromChainState(_, _, _) => None,
-			Self::ValidateFromExhaustive(_, _, _, _, _, _) => None,
+			Self::ValidateFromChainStateAt(leaf, _, _, _) => Some(*leaf),
+			Self::ValidateFromExhaustive(_, _, _, _, _, _, _) => None,
 		}
 	}
 }
@@ -274,15 +290,21 @@ pub enum AvailabilityStoreMessage {
 	/// megabytes of data to get a single bit of information.
 	QueryDataAvailability(Hash, oneshot::Sender<bool>),
 
-	/// Query an `ErasureChunk` from the AV store by the candidate hash and validator index.
+	/// Query an `ErasureChunk` from the AV store by the candidate hash and chunk index.
 	QueryChunk(Hash, ValidatorIndex, oneshot::Sender<Option<ErasureChunk>>),
 
-	/// Query whether an `ErasureChunk` exists within the AV Store.
+	/// Query the indices of the `ErasureChunk`s stored for a candidate, in ascending order.
 	///
 	/// This is useful in cases like bitfield signing, when existence
 	/// matters, but we don't want to necessarily pass around large
-	/// quantities of data to get a single bit of information.
-	QueryChunkAvailability(Hash, ValidatorIndex, oneshot::Sender<bool>),
+	/// quantities of data to get a few bits of information.
+	QueryChunkAvailability(Hash, oneshot::Sender<Vec<ValidatorIndex>>),
+
+	/// Query all `ErasureChunk`s stored for a candidate, in ascending order of their index.
+	///
+	/// Unlike `QueryChunk`, this never loads the `AvailableData` to derive chunks which
+	/// aren't stored.
+	QueryAllChunks(Hash, oneshot::Sender<Vec<ErasureChunk>>),
 
 	/// Store an `ErasureChunk` in the AV store.
 	///
@@ -290,10 +312,25 @@ pub enum AvailabilityStoreMessage {
 	StoreChunk(Hash, ValidatorIndex, ErasureChunk, oneshot::Sender<Result<(), ()>>),
 
 	/// Store a `AvailableData` in the AV store.
-	/// If `ValidatorIndex` is present store corresponding chunk also.
+	/// If a chunk index is present store corresponding chunk also.
 	///
-	/// Return `Ok(())` if the store operation succeeded, `Err(())` if it failed.
-	StoreAvailableData(Hash, Option<ValidatorIndex>, u32, AvailableData, oneshot::Sender<Result<(), ()>>),
+	/// The data is erasure-coded into `n_validators` chunks before anything is stored, and is
+	/// rejected with `InvalidErasureRoot` if the root of those chunks
+	/// isn't `expected_erasure_root`.
+	StoreAvailableData {
+		/// The hash the data is stored under.
+		candidate_hash: Hash,
+		/// The index of the chunk to store along with the data, if any.
+		chunk_index: Option<ValidatorIndex>,
+		/// The number of validators to erasure-code the data for.
+		n_validators: u32,
+		/// The data to store.
+		available_data: AvailableData,
+		/// The erasure root committed to by the candidate.
+		expected_erasure_root: Hash,
+		/// The response channel.
+		tx: oneshot::Sender<Result<(), crate::errors::StoreAvailableDataError>>,
+	},
 }
 
 impl AvailabilityStoreMessage {
@@ -303,9 +340,10 @@ impl AvailabilityStoreMessage {
 			Self::QueryAvailableData(hash, _) => Some(*hash),
 			Self::QueryDataAvailability(hash, _) => Some(*hash),
 			Self::QueryChunk(hash, _, _) => Some(*hash),
-			Self::QueryChunkAvailability(hash, _, _) => Some(*hash),
+			Self::QueryChunkAvailability(hash, _) => Some(*hash),
+			Self::QueryAllChunks(hash, _) => Some(*hash),
 			Self::StoreChunk(hash, _, _, _) => Some(*hash),
-			Self::StoreAvailableData(hash, _, _, _, _) => Some(*hash),
+			Self::StoreAvailableData { candidate_hash, .. } => Some(*candidate_hash),
 		}
 	}
 }
@@ -338,6 +376,30 @@ pub enum ChainApiMessage {
 		/// The response channel.
 		response_channel: ChainApiResponseChannel<Vec<Hash>>,
 	},
+	/// Subscribe to block finality.
+	/// Every block finalized after the subscription is sent as `(hash, number)`
+	/// until the receiving end is dropped.
+	FinalizedBlockStream(mpsc::UnboundedSender<(Hash, BlockNumber)>),
+	/// Request the relay VRF story of the block with the given hash.
+	/// Returns `None` if the block is not present in the db or if its BABE pre-digest carries no
+	/// VRF output, as for blocks authored in secondary plain slots.
+	RelayVrfStory(Hash, ChainApiResponseChannel<Option<RelayVrfStory>>),
+	/// Request the headers of the blocks with the given hashes.
+	/// The response channel returns a `Vec` of the same size as the request, with `None`
+	/// for each block which is not present in the db.
+	BlockHeaders(Vec<Hash>, ChainApiResponseChannel<Vec<Option<Header>>>),
+	/// Request the hash of the block with the given number on the chain ending in the block
+	/// with the hash `descendant_of`, which is itself included.
+	/// Returns `None` if `descendant_of` is not present in the db, its number is lower than the
+	/// requested one or the chain has a gap.
+	FindBlockHashAtNumber {
+		/// The number of the block in question.
+		number: BlockNumber,
+		/// The hash of the block whose chain the block is searched on.
+		descendant_of: Hash,
+		/// The response channel.
+		response_channel: ChainApiResponseChannel<Option<Hash>>,
+	},
 }
 
 impl ChainApiMessage {
@@ -355,8 +417,14 @@ pub type RuntimeApiSender<T> = oneshot::Sender<Result<T, crate::errors::RuntimeA
 pub enum RuntimeApiRequest {
 	/// Get the current validator set.
 	Validators(RuntimeApiSender<Vec<ValidatorId>>),
+	/// Get the indices, within the session's full validator set, of the validators taking part
+	/// in parachain consensus. Ordered the same as `Validators`.
+	ActiveValidatorIndices(RuntimeApiSender<Vec<ValidatorIndex>>),
 	/// Get the validator groups and group rotation info.
 	ValidatorGroups(RuntimeApiSender<(Vec<Vec<ValidatorIndex>>, GroupRotationInfo)>),
+	/// Get the index and the validators of the group assigned to back candidates on the given
+	/// core, or `None` if there is no such core or group.
+	GroupForCore(CoreIndex, RuntimeApiSender<Option<(GroupIndex, Vec<ValidatorIndex>)>>),
 	/// Get information on all availability cores.
 	AvailabilityCores(RuntimeApiSender<Vec<CoreState>>),
 	/// Get the persisted validation data for a particular para, taking the given
@@ -375,17 +443,49 @@ pub enum RuntimeApiRequest {
 		OccupiedCoreAssumption,
 		RuntimeApiSender<Option<ValidationData>>,
 	),
+	/// Get the full validation data for a particular para, for a candidate built on the allowed
+	/// relay parent with the given number, taking the given `OccupiedCoreAssumption`.
+	FullValidationDataAt(
+		ParaId,
+		BlockNumber,
+		OccupiedCoreAssumption,
+		RuntimeApiSender<Option<ValidationData>>,
+	),
+	/// Get the relay parents candidates backed in a child of the block may be built on, along
+	/// with their numbers, most recent first.
+	AllowedRelayParents(RuntimeApiSender<Vec<(Hash, BlockNumber)>>),
 	/// Get the session index that a child of the block will have.
 	SessionIndexForChild(RuntimeApiSender<SessionIndex>),
+	/// Get the seed used to shuffle the assignment of erasure chunks to validators in the
+	/// session a child of the block will have.
+	AvailabilityChunkShuffleSeed(RuntimeApiSender<[u8; 32]>),
 	/// Get the validation code for a para, taking the given `OccupiedCoreAssumption`, which
 	/// will inform on how the validation data should be computed if the para currently
 	/// occupies a core.
 	ValidationCode(ParaId, OccupiedCoreAssumption, RuntimeApiSender<Option<ValidationCode>>),
+	/// Get validation code by its hash, either past, current or future code.
+	ValidationCodeByHash(Hash, RuntimeApiSender<Option<ValidationCode>>),
+	/// Get the validation code used by a para for candidates in the context of the relay-chain
+	/// block with the given number, which must not be after the block the request is made at.
+	HistoricalValidationCode(ParaId, BlockNumber, RuntimeApiSender<Option<ValidationCode>>),
+	/// Get the parameters of the environment validation functions are executed in during the
+	/// given session, which must be the one expected at a child of the block.
+	SessionExecutorParams(SessionIndex, RuntimeApiSender<Option<ExecutorParams>>),
+	/// Get the paras each core is assigned to for the next `scheduling_lookahead` blocks,
+	/// starting with the child of the block.
+	ClaimQueue(RuntimeApiSender<BTreeMap<CoreIndex, Vec<ParaId>>>),
 	/// Get a the candidate pending availability for a particular parachain by parachain / core index
 	CandidatePendingAvailability(ParaId, RuntimeApiSender<Option<CommittedCandidateReceipt>>),
 	/// Get all events concerning candidates (backing, inclusion, time-out) in the parent of
 	/// the block in whose state this request is executed.
 	CandidateEvents(RuntimeApiSender<Vec<CandidateEvent>>),
+	/// Get the backing votes processed on-chain by the inclusion inherent of the block in
+	/// whose state this request is executed.
+	FetchOnChainVotes(RuntimeApiSender<Option<ScrapedOnChainVotes>>),
+	/// Get the BABE configuration, including the randomness of the current epoch.
+	BabeConfiguration(RuntimeApiSender<BabeGenesisConfiguration>),
+	/// Get the slot at which the current BABE epoch started.
+	CurrentBabeEpochStart(RuntimeApiSender<SlotNumber>),
 }
 
 /// A message to the Runtime API subsystem.
@@ -546,3 +646,154 @@ pub enum AllMessages {
 	/// Message for the Collation Generation subsystem
 	CollationGeneration(CollationGenerationMessage),
 }
+
+/// Associates the message type a subsystem receives with the set of messages that subsystem
+/// is allowed to send.
+///
+/// A [`SubsystemContext`](crate::SubsystemContext) only accepts messages which can be converted
+/// into the outgoing set of its own message type, so sending a message to a subsystem which isn't
+/// in that set is a compile error.
+pub trait AssociateOutgoing {
+	/// The messages which may be sent by a subsystem receiving `Self`.
+	type OutgoingMessages: Into<AllMessages> + std::fmt::Debug + Send;
+}
+
+/// Declare the outgoing message set of each subsystem message type.
+///
+/// For every `IncomingMessage => OutgoingMessages { Variant(Message), ... }` entry this generates
+/// the `OutgoingMessages` enum, conversions into it from each of the allowed message types,
+/// the conversion into `AllMessages` and the [`AssociateOutgoing`] implementation for
+/// `IncomingMessage`. Variants must be named after the matching `AllMessages` variant.
+macro_rules! outgoing_messages {
+	($(
+		$(#[$meta:meta])*
+		$incoming:ty => $outgoing:ident { $( $variant:ident($message:ty) ),* $(,)? }
+	)*) => { $(
+		$(#[$meta])*
+		#[derive(Debug)]
+		pub enum $outgoing {
+			$(
+				/// Message for the corresponding subsystem.
+				$variant($message),
+			)*
+		}
+
+		$(
+			impl From<$message> for $outgoing {
+				fn from(msg: $message) -> Self {
+					$outgoing::$variant(msg)
+				}
+			}
+		)*
+
+		impl From<$outgoing> for AllMessages {
+			fn from(msg: $outgoing) -> Self {
+				match msg {
+					$( $outgoing::$variant(msg) => AllMessages::$variant(msg), )*
+				}
+			}
+		}
+
+		impl AssociateOutgoing for $incoming {
+			type OutgoingMessages = $outgoing;
+		}
+	)* }
+}
+
+outgoing_messages! {
+	/// Messages the candidate validation subsystem may send.
+	CandidateValidationMessage => CandidateValidationOutgoingMessages {
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the candidate backing subsystem may send.
+	CandidateBackingMessage => CandidateBackingOutgoingMessages {
+		AvailabilityStore(AvailabilityStoreMessage),
+		RuntimeApi(RuntimeApiMessage),
+		CandidateValidation(CandidateValidationMessage),
+		CandidateSelection(CandidateSelectionMessage),
+		Provisioner(ProvisionerMessage),
+		PoVDistribution(PoVDistributionMessage),
+		StatementDistribution(StatementDistributionMessage),
+	}
+
+	/// Messages the candidate selection subsystem may send.
+	CandidateSelectionMessage => CandidateSelectionOutgoingMessages {
+		CandidateBacking(CandidateBackingMessage),
+		CollatorProtocol(CollatorProtocolMessage),
+	}
+
+	/// Messages the Chain API subsystem may send.
+	ChainApiMessage => ChainApiOutgoingMessages {}
+
+	/// Messages the Collator Protocol subsystem may send.
+	CollatorProtocolMessage => CollatorProtocolOutgoingMessages {
+		CandidateSelection(CandidateSelectionMessage),
+		NetworkBridge(NetworkBridgeMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the statement distribution subsystem may send.
+	StatementDistributionMessage => StatementDistributionOutgoingMessages {
+		CandidateBacking(CandidateBackingMessage),
+		ChainApi(ChainApiMessage),
+		NetworkBridge(NetworkBridgeMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the availability distribution subsystem may send.
+	AvailabilityDistributionMessage => AvailabilityDistributionOutgoingMessages {
+		AvailabilityStore(AvailabilityStoreMessage),
+		ChainApi(ChainApiMessage),
+		NetworkBridge(NetworkBridgeMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the bitfield distribution subsystem may send.
+	BitfieldDistributionMessage => BitfieldDistributionOutgoingMessages {
+		NetworkBridge(NetworkBridgeMessage),
+		Provisioner(ProvisionerMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the bitfield signing subsystem may send.
+	BitfieldSigningMessage => BitfieldSigningOutgoingMessages {
+		AvailabilityStore(AvailabilityStoreMessage),
+		BitfieldDistribution(BitfieldDistributionMessage),
+		CandidateBacking(CandidateBackingMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the Provisioner subsystem may send.
+	ProvisionerMessage => ProvisionerOutgoingMessages {
+		ChainApi(ChainApiMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the PoV Distribution subsystem may send.
+	PoVDistributionMessage => PoVDistributionOutgoingMessages {
+		NetworkBridge(NetworkBridgeMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+
+	/// Messages the Runtime API subsystem may send.
+	RuntimeApiMessage => RuntimeApiOutgoingMessages {}
+
+	/// Messages the availability store subsystem may send.
+	AvailabilityStoreMessage => AvailabilityStoreOutgoingMessages {}
+
+	/// Messages the network bridge subsystem may send.
+	NetworkBridgeMessage => NetworkBridgeOutgoingMessages {
+		AvailabilityDistribution(AvailabilityDistributionMessage),
+		BitfieldDistribution(BitfieldDistributionMessage),
+		CollatorProtocol(CollatorProtocolMessage),
+		PoVDistribution(PoVDistributionMessage),
+		StatementDistribution(StatementDistributionMessage),
+	}
+
+	/// Messages the Collation Generation subsystem may send.
+	CollationGenerationMessage => CollationGenerationOutgoingMessages {
+		CollatorProtocol(CollatorProtocolMessage),
+		RuntimeApi(RuntimeApiMessage),
+	}
+}
@@ -21,7 +21,7 @@
 mod chain_spec;
 
 pub use chain_spec::*;
-use futures::future::Future;
+use futures::{future::{self, Future}, StreamExt};
 use polkadot_primitives::v0::{
 	Block, Hash, CollatorId, Id as ParaId,
 };
@@ -95,19 +95,30 @@ impl ClientHandle for TestClient {
 	}
 }
 
+/// The collator id of the given key.
+pub fn collator_id(key: Sr25519Keyring) -> CollatorId {
+	key.public().into()
+}
+
 /// Create a Polkadot `Configuration`. By default an in-memory socket will be used, therefore you need to provide boot
 /// nodes if you want the future node to be connected to other nodes. The `storage_update_func` can be used to make
-/// adjustements to the runtime before the node starts.
+/// adjustements to the runtime before the node starts. Validators run with the authority role, other nodes as full
+/// nodes.
 pub fn node_config(
 	storage_update_func: impl Fn(),
 	task_executor: TaskExecutor,
 	key: Sr25519Keyring,
 	boot_nodes: Vec<MultiaddrWithPeerId>,
+	is_validator: bool,
 ) -> Configuration {
 	let base_path = BasePath::new_temp_dir().expect("could not create temporary directory");
 	let root = base_path.path();
-	let role = Role::Authority {
-		sentry_nodes: Vec::new(),
+	let role = if is_validator {
+		Role::Authority {
+			sentry_nodes: Vec::new(),
+		}
+	} else {
+		Role::Full
 	};
 	let key_seed = key.to_seed();
 	let mut spec = polkadot_local_testnet_config();
@@ -191,10 +202,10 @@ pub fn node_config(
 	}
 }
 
-/// Run a Polkadot test node using the Polkadot test runtime. The node will be using an in-memory socket, therefore you
-/// need to provide boot nodes if you want it to be connected to other nodes. The `storage_update_func` can be used to
-/// make adjustements to the runtime before the node starts.
-pub fn run_test_node(
+/// Run a Polkadot test validator node using the Polkadot test runtime. The node will be using an in-memory socket,
+/// therefore you need to provide boot nodes if you want it to be connected to other nodes. The `storage_update_func`
+/// can be used to make adjustements to the runtime before the node starts.
+pub fn run_validator_node(
 	task_executor: TaskExecutor,
 	key: Sr25519Keyring,
 	storage_update_func: impl Fn(),
@@ -203,11 +214,39 @@ pub fn run_test_node(
 	TaskManager,
 	impl AbstractClient<Block, TFullBackend<Block>>,
 > {
-	let config = node_config(storage_update_func, task_executor, key, boot_nodes);
+	let config = node_config(storage_update_func, task_executor, key, boot_nodes, true);
+
+	run_node(config, None)
+}
+
+/// Run a Polkadot test node collating for `para_id` with the collator id of `key`, see [`run_validator_node`]. The
+/// node doesn't author relay chain blocks.
+pub fn run_collator_node(
+	task_executor: TaskExecutor,
+	key: Sr25519Keyring,
+	storage_update_func: impl Fn(),
+	boot_nodes: Vec<MultiaddrWithPeerId>,
+	para_id: ParaId,
+) -> PolkadotTestNode<
+	TaskManager,
+	impl AbstractClient<Block, TFullBackend<Block>>,
+> {
+	let config = node_config(storage_update_func, task_executor, key, boot_nodes, false);
+
+	run_node(config, Some((collator_id(key), para_id)))
+}
+
+fn run_node(
+	config: Configuration,
+	collating_for: Option<(CollatorId, ParaId)>,
+) -> PolkadotTestNode<
+	TaskManager,
+	impl AbstractClient<Block, TFullBackend<Block>>,
+> {
 	let multiaddr = config.network.listen_addresses[0].clone();
 	let authority_discovery_enabled = false;
 	let (task_manager, client, handles, network, rpc_handlers) =
-		polkadot_test_new_full(config, None, authority_discovery_enabled)
+		polkadot_test_new_full(config, collating_for, authority_discovery_enabled)
 			.expect("could not create Polkadot test service");
 
 	let peer_id = network.local_peer_id().clone();
@@ -299,3 +338,19 @@ where
 		self.client.wait_for_blocks(count)
 	}
 }
+
+impl<S, C> PolkadotTestNode<S, C>
+where
+	C: BlockchainEvents<Block> + HeaderBackend<Block>,
+{
+	/// Wait for `count` more blocks to be finalized in the node. Like [`wait_for_blocks`](Self::wait_for_blocks),
+	/// this will not return if finality stalls.
+	pub fn wait_for_finality(&self, count: u32) -> impl Future<Output = ()> {
+		let target = self.client.info().finalized_number + count;
+		let client = self.client.clone();
+
+		self.client.finality_notification_stream()
+			.take_while(move |_| future::ready(client.info().finalized_number < target))
+			.for_each(|_| future::ready(()))
+	}
+}
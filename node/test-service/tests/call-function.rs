@@ -20,7 +20,7 @@ use sp_keyring::Sr25519Keyring::{Alice, Bob};
 
 #[substrate_test_utils::test]
 async fn call_function_actually_work(task_executor: TaskExecutor) {
-	let alice = run_test_node(task_executor, Alice, || {}, Vec::new());
+	let alice = run_validator_node(task_executor, Alice, || {}, Vec::new());
 
 	let function = polkadot_test_runtime::Call::Balances(pallet_balances::Call::transfer(
 		Default::default(),
@@ -44,11 +44,37 @@ impl From<Vec<u8>> for HeadData {
 	}
 }
 
+/// The number of leading bytes of a payload shown by [`BytesSummary`].
+const BYTES_SUMMARY_PREFIX_LEN: usize = 8;
+
+/// Formats a byte payload by its size and first few bytes rather than its contents.
+///
+/// Used for the `Debug` output of payloads such as validation code and block data, which can be
+/// megabytes in size. Nothing is hashed, so formatting is cheap regardless of the size.
+pub struct BytesSummary<'a>(pub &'a [u8]);
+
+impl<'a> sp_std::fmt::Debug for BytesSummary<'a> {
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "{} bytes, starting with 0x", self.0.len())?;
+		for byte in self.0.iter().take(BYTES_SUMMARY_PREFIX_LEN) {
+			write!(f, "{:02x}", byte)?;
+		}
+
+		Ok(())
+	}
+}
+
 /// Parachain validation code.
-#[derive(Default, PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+#[derive(Default, PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize, Hash))]
 pub struct ValidationCode(#[cfg_attr(feature = "std", serde(with="bytes"))] pub Vec<u8>);
 
+impl sp_std::fmt::Debug for ValidationCode {
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		f.debug_tuple("ValidationCode").field(&BytesSummary(&self.0)).finish()
+	}
+}
+
 impl From<Vec<u8>> for ValidationCode {
 	fn from(code: Vec<u8>) -> Self {
 		ValidationCode(code)
@@ -59,9 +85,16 @@ impl From<Vec<u8>> for ValidationCode {
 ///
 /// Contains everything required to validate para-block, may contain block and witness data.
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
-#[cfg_attr(feature = "std", derive(Serialize, Deserialize, Debug))]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct BlockData(#[cfg_attr(feature = "std", serde(with="bytes"))] pub Vec<u8>);
 
+#[cfg(feature = "std")]
+impl std::fmt::Debug for BlockData {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_tuple("BlockData").field(&BytesSummary(&self.0)).finish()
+	}
+}
+
 /// Unique identifier of a parachain.
 #[derive(
 	Clone, CompactAs, Copy, Decode, Default, Encode, Eq,
@@ -225,6 +258,59 @@ pub struct UpwardMessage {
 	pub data: Vec<u8>,
 }
 
+/// A type that uniquely identifies an HRMP channel. An HRMP channel is established between two
+/// paras. In text, we use the notation `(A, B)` to specify a channel between A and B. The channels
+/// are unidirectional, meaning that `(A, B)` and `(B, A)` refer to different channels.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Hash))]
+pub struct HrmpChannelId {
+	/// The para that acts as the sender in this channel.
+	pub sender: Id,
+	/// The para that acts as the recipient in this channel.
+	pub recipient: Id,
+}
+
+/// A horizontal message as seen by its sender.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Hash))]
+pub struct OutboundHrmpMessage<Id> {
+	/// The para that will get this message in its downward message queue.
+	pub recipient: Id,
+	/// The message payload.
+	pub data: Vec<u8>,
+}
+
+/// A horizontal message as seen by its recipient.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct InboundHrmpMessage<BlockNumber> {
+	/// The relay-chain block number at which the message was put into the channel.
+	pub sent_at: BlockNumber,
+	/// The message payload.
+	pub data: Vec<u8>,
+}
+
+/// Parameters of the environment the validation function of a para is executed in.
+///
+/// These are fixed for a session, so that all validators execute the candidates of a session
+/// in the same environment and changes to it take effect at session boundaries.
+#[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
+pub struct ExecutorParams {
+	/// The number of 64 KiB pages of heap memory available to the validation function.
+	pub heap_pages: u64,
+	/// The maximum time the execution of the validation function may take, in milliseconds.
+	pub execution_timeout_ms: u64,
+}
+
+impl Default for ExecutorParams {
+	fn default() -> Self {
+		ExecutorParams {
+			heap_pages: 1024,
+			execution_timeout_ms: 5_000,
+		}
+	}
+}
+
 /// Validation parameters for evaluating the parachain validity function.
 // TODO: balance downloads (https://github.com/paritytech/polkadot/issues/220)
 #[derive(PartialEq, Eq, Decode)]
@@ -253,8 +339,13 @@ pub struct ValidationResult {
 	pub new_validation_code: Option<ValidationCode>,
 	/// Upward messages send by the Parachain.
 	pub upward_messages: Vec<UpwardMessage>,
+	/// Horizontal messages sent by the Parachain, sorted ascending by recipient.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<Id>>,
 	/// Number of downward messages that were processed by the Parachain.
 	///
 	/// It is expected that the Parachain processes them from first to last.
 	pub processed_downward_messages: u32,
+	/// The relay chain block number up to which, inclusive, the Parachain processed its inbound
+	/// horizontal messages.
+	pub hrmp_watermark: RelayChainBlockNumber,
 }
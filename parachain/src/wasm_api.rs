@@ -16,6 +16,11 @@
 
 //! Utilities for writing parachain WASM.
 
+/// The message validation functions panic with when they fail to decode their parameters.
+///
+/// The validation host looks for it to tell such failures apart from other traps.
+pub const PARAMS_DECODE_FAILURE: &str = "Invalid input data";
+
 /// Load the validation params from memory when implementing a Rust parachain.
 ///
 /// Offset and length must have been provided by the validation
@@ -26,7 +31,7 @@ pub unsafe fn load_params(params: *const u8, len: usize)
 {
 	let mut slice = sp_std::slice::from_raw_parts(params, len);
 
-	codec::Decode::decode(&mut slice).expect("Invalid input data")
+	codec::Decode::decode(&mut slice).expect(PARAMS_DECODE_FAILURE)
 }
 
 /// Allocate the validation result in memory, getting the return-pointer back.
@@ -21,14 +21,16 @@
 //! a WASM VM for re-execution of a parachain candidate.
 
 use std::any::{TypeId, Any};
-use crate::primitives::{ValidationParams, ValidationResult};
+use crate::primitives::{ExecutorParams, ValidationParams, ValidationResult};
 use codec::{Decode, Encode};
 use sp_core::{storage::{ChildInfo, TrackedStorageKey}, traits::{CallInWasm, SpawnNamed}};
 use sp_externalities::Extensions;
 use sp_wasm_interface::HostFunctions as _;
 
 #[cfg(not(any(target_os = "android", target_os = "unknown")))]
-pub use validation_host::{run_worker, ValidationPool, EXECUTION_TIMEOUT_SEC};
+pub use validation_host::{
+	run_worker, ValidationPool, EXECUTION_TIMEOUT_SEC, DEFAULT_WORKER_MEMORY_LIMIT,
+};
 
 mod validation_host;
 
@@ -50,6 +52,11 @@ impl ValidationPool {
 	pub fn new() -> Self {
 		ValidationPool { _inner: () }
 	}
+
+	/// Create a new `ValidationPool`. The memory limit is ignored.
+	pub fn with_memory_limit(_: Option<u64>) -> Self {
+		Self::new()
+	}
 }
 
 /// A stub function defined when compiling for Android or WASM.
@@ -97,6 +104,13 @@ pub enum InvalidCandidate {
 	BadReturn,
 	#[display(fmt = "Validation function timeout.")]
 	Timeout,
+	/// The validation function trapped, e.g. by panicking.
+	#[display(fmt = "Validation function trapped: {}", _0)]
+	#[from(ignore)]
+	Trap(String),
+	/// The validation function failed to decode its parameters.
+	#[display(fmt = "Validation function failed to decode its parameters.")]
+	ParamsDecodeFailure,
 	#[display(fmt = "External WASM execution error: {}", _0)]
 	ExternalWasmExecutor(String),
 }
@@ -128,26 +142,28 @@ impl std::error::Error for ValidationError {
 	}
 }
 
-/// Validate a candidate under the given validation code.
+/// Validate a candidate under the given validation code, in an environment set up according to
+/// the given executor parameters.
 ///
 /// This will fail if the validation code is not a proper parachain validation module.
 pub fn validate_candidate(
 	validation_code: &[u8],
 	params: ValidationParams,
+	executor_params: &ExecutorParams,
 	options: ExecutionMode<'_>,
 	spawner: impl SpawnNamed + 'static,
 ) -> Result<ValidationResult, ValidationError> {
 	match options {
 		ExecutionMode::Local => {
-			validate_candidate_internal(validation_code, &params.encode(), spawner)
+			validate_candidate_internal(validation_code, &params.encode(), executor_params, spawner)
 		},
 		#[cfg(not(any(target_os = "android", target_os = "unknown")))]
 		ExecutionMode::Remote(pool) => {
-			pool.validate_candidate(validation_code, params, false)
+			pool.validate_candidate(validation_code, params, executor_params, false)
 		},
 		#[cfg(not(any(target_os = "android", target_os = "unknown")))]
 		ExecutionMode::RemoteTest(pool) => {
-			pool.validate_candidate(validation_code, params, true)
+			pool.validate_candidate(validation_code, params, executor_params, true)
 		},
 		#[cfg(any(target_os = "android", target_os = "unknown"))]
 		ExecutionMode::Remote(_pool) =>
@@ -172,15 +188,19 @@ type HostFunctions = sp_io::SubstrateHostFunctions;
 /// Validate a candidate under the given validation code.
 ///
 /// This will fail if the validation code is not a proper parachain validation module.
+///
+/// The execution timeout of the executor parameters is not enforced, as in-process execution
+/// can't be interrupted.
 pub fn validate_candidate_internal(
 	validation_code: &[u8],
 	encoded_call_data: &[u8],
+	executor_params: &ExecutorParams,
 	spawner: impl SpawnNamed + 'static,
 ) -> Result<ValidationResult, ValidationError> {
 	let executor = sc_executor::WasmExecutor::new(
 		sc_executor::WasmExecutionMethod::Interpreted,
 		// TODO: Make sure we don't use more than 1GB: https://github.com/paritytech/polkadot/issues/699
-		Some(1024),
+		Some(executor_params.heap_pages),
 		HostFunctions::host_functions(),
 		8
 	);
@@ -198,12 +218,26 @@ pub fn validate_candidate_internal(
 		encoded_call_data,
 		&mut ext,
 		sp_core::traits::MissingHostFunctions::Allow,
-	).map_err(|e| ValidationError::InvalidCandidate(e.into()))?;
+	).map_err(|e| ValidationError::InvalidCandidate(execution_error(e)))?;
 
 	ValidationResult::decode(&mut &res[..])
 		.map_err(|_| ValidationError::InvalidCandidate(InvalidCandidate::BadReturn).into())
 }
 
+/// Tell the validation function trapping, and failing to decode its parameters in particular,
+/// apart from other errors of the executor.
+fn execution_error(e: sc_executor::error::Error) -> InvalidCandidate {
+	use sc_executor::error::Error;
+
+	match e {
+		Error::RuntimePanicked(msg) if msg.contains(crate::wasm_api::PARAMS_DECODE_FAILURE) =>
+			InvalidCandidate::ParamsDecodeFailure,
+		Error::RuntimePanicked(msg) => InvalidCandidate::Trap(msg),
+		Error::Trap(trap) => InvalidCandidate::Trap(trap.to_string()),
+		e => InvalidCandidate::WasmExecutor(e),
+	}
+}
+
 /// The validation externalities that will panic on any storage related access. They just provide
 /// access to the parachain extension.
 struct ValidationExternalities(Extensions);
@@ -16,9 +16,9 @@
 
 #![cfg(not(any(target_os = "android", target_os = "unknown")))]
 
-use std::{process, env, sync::Arc, sync::atomic};
+use std::{process, env, sync::Arc, sync::atomic, time::{Duration, Instant}};
 use codec::{Decode, Encode};
-use crate::primitives::{ValidationParams, ValidationResult};
+use crate::primitives::{ExecutorParams, ValidationParams, ValidationResult};
 use super::{
 	validate_candidate_internal, ValidationError, InvalidCandidate, InternalError,
 	MAX_CODE_MEM, MAX_RUNTIME_MEM, MAX_VALIDATION_RESULT_HEADER_MEM,
@@ -34,13 +34,23 @@ const WORKER_ARGS_TEST: &[&'static str] = &["--nocapture", "validation_worker"];
 const WORKER_ARG: &'static str = "validation-worker";
 const WORKER_ARGS: &[&'static str] = &[WORKER_ARG];
 
-/// Execution timeout in seconds;
+/// Timeout, in seconds, for a newly spawned worker to signal through the shared memory that it
+/// is ready to receive candidates.
+///
+/// The time waited on the shared memory for the result of a validation is instead bounded by the
+/// `execution_timeout_ms` of the `ExecutorParams` of the session.
 #[cfg(debug_assertions)]
 pub const EXECUTION_TIMEOUT_SEC: u64 =  30;
 
 #[cfg(not(debug_assertions))]
 pub const EXECUTION_TIMEOUT_SEC: u64 =  5;
 
+/// Default limit of the memory a validation worker may allocate, in bytes.
+///
+/// Compiling and running adversarial code can otherwise take gigabytes.
+/// Only enforced on Linux, see `limit_worker_memory` for what is counted.
+pub const DEFAULT_WORKER_MEMORY_LIMIT: u64 = 2 * 1024 * 1024 * 1024; // 2 GiB
+
 enum Event {
 	CandidateReady = 0,
 	ResultReady = 1,
@@ -77,8 +87,21 @@ const DEFAULT_NUM_HOSTS: usize = 8;
 impl ValidationPool {
 	/// Creates a validation pool with the default configuration.
 	pub fn new() -> ValidationPool {
+		Self::with_memory_limit(Some(DEFAULT_WORKER_MEMORY_LIMIT))
+	}
+
+	/// Creates a validation pool whose workers may allocate at most `limit` bytes
+	/// of memory. `None` disables the limit.
+	///
+	/// A worker exceeding the limit fails to allocate and dies. This is reported as an
+	/// internal error rather than an invalid candidate, since a worker may also die for
+	/// reasons unrelated to the candidate.
+	pub fn with_memory_limit(limit: Option<u64>) -> ValidationPool {
 		ValidationPool {
-			hosts: Arc::new((0..DEFAULT_NUM_HOSTS).map(|_| Default::default()).collect()),
+			hosts: Arc::new((0..DEFAULT_NUM_HOSTS).map(|_| Mutex::new(ValidationHost {
+				memory_limit: limit,
+				..Default::default()
+			})).collect()),
 		}
 	}
 
@@ -90,16 +113,17 @@ impl ValidationPool {
 		&self,
 		validation_code: &[u8],
 		params: ValidationParams,
+		executor_params: &ExecutorParams,
 		test_mode: bool,
 	) -> Result<ValidationResult, ValidationError> {
 		for host in self.hosts.iter() {
 			if let Some(mut host) = host.try_lock() {
-				return host.validate_candidate(validation_code, params, test_mode);
+				return host.validate_candidate(validation_code, params, executor_params, test_mode);
 			}
 		}
 
 		// all workers are busy, just wait for the first one
-		self.hosts[0].lock().validate_candidate(validation_code, params, test_mode)
+		self.hosts[0].lock().validate_candidate(validation_code, params, executor_params, test_mode)
 	}
 }
 
@@ -163,13 +187,22 @@ pub fn run_worker(mem_id: &str) -> Result<(), String> {
 				let (call_data, _) = rest.split_at_mut(MAX_RUNTIME_MEM);
 				let (call_data, _) = call_data.split_at_mut(header.params_size as usize);
 
-				let result = validate_candidate_internal(code, call_data, task_executor.clone());
+				let result = validate_candidate_internal(
+					code,
+					call_data,
+					&header.executor_params,
+					task_executor.clone(),
+				);
 				debug!("{} Candidate validated: {:?}", process::id(), result);
 
 				match result {
 					Ok(r) => ValidationResultHeader::Ok(r),
 					Err(ValidationError::Internal(e)) =>
 						ValidationResultHeader::Error(WorkerValidationError::InternalError(e.to_string())),
+					Err(ValidationError::InvalidCandidate(InvalidCandidate::Trap(msg))) =>
+						ValidationResultHeader::Error(WorkerValidationError::Trap(msg)),
+					Err(ValidationError::InvalidCandidate(InvalidCandidate::ParamsDecodeFailure)) =>
+						ValidationResultHeader::Error(WorkerValidationError::ParamsDecodeFailure),
 					Err(ValidationError::InvalidCandidate(e)) =>
 						ValidationResultHeader::Error(WorkerValidationError::ValidationError(e.to_string())),
 				}
@@ -189,12 +222,15 @@ pub fn run_worker(mem_id: &str) -> Result<(), String> {
 struct ValidationHeader {
 	code_size: u64,
 	params_size: u64,
+	executor_params: ExecutorParams,
 }
 
 #[derive(Encode, Decode, Debug)]
 enum WorkerValidationError {
 	InternalError(String),
 	ValidationError(String),
+	Trap(String),
+	ParamsDecodeFailure,
 }
 
 #[derive(Encode, Decode, Debug)]
@@ -210,6 +246,7 @@ struct ValidationHost {
 	worker: Option<process::Child>,
 	memory: Option<SharedMem>,
 	id: u32,
+	memory_limit: Option<u64>,
 }
 
 impl Drop for ValidationHost {
@@ -246,21 +283,41 @@ impl ValidationHost {
 		debug!("Starting worker at {:?}", self_path);
 		let mut args = if test_mode { WORKER_ARGS_TEST.to_vec() } else { WORKER_ARGS.to_vec() };
 		args.push(memory.get_os_path());
-		let worker = process::Command::new(self_path)
-			.args(args)
-			.stdin(process::Stdio::piped())
-			.spawn()?;
+		let mut command = process::Command::new(self_path);
+		command.args(args).stdin(process::Stdio::piped());
+		if let Some(limit) = self.memory_limit {
+			limit_worker_memory(&mut command, limit);
+		}
+		let worker = command.spawn()?;
 		self.id = worker.id();
 		self.worker = Some(worker);
 
-		memory.wait(
-			Event::WorkerReady as usize,
-			shared_memory::Timeout::Sec(EXECUTION_TIMEOUT_SEC as usize),
-		)?;
+		let deadline = Instant::now() + Duration::from_secs(EXECUTION_TIMEOUT_SEC);
+		// Wait in short intervals so that a worker that died on startup is noticed early.
+		while let Err(e) = memory.wait(Event::WorkerReady as usize, shared_memory::Timeout::Sec(1)) {
+			if let Some(status) = self.worker_exit_status() {
+				return Err(worker_exit_error(status, self.memory_limit));
+			}
+
+			if Instant::now() >= deadline {
+				if let Some(mut worker) = self.worker.take() {
+					worker.kill().ok();
+				}
+				return Err(e.into());
+			}
+		}
 		self.memory = Some(memory);
 		Ok(())
 	}
 
+	/// The status of the worker if it has exited, in which case it is forgotten.
+	fn worker_exit_status(&mut self) -> Option<process::ExitStatus> {
+		let status = self.worker.as_mut().and_then(|worker| worker.try_wait().ok().flatten())?;
+		debug!("{} Worker exited: {}", self.id, status);
+		self.worker = None;
+		Some(status)
+	}
+
 	/// Validate a candidate under the given validation code.
 	///
 	/// This will fail if the validation code is not a proper parachain validation module.
@@ -268,6 +325,7 @@ impl ValidationHost {
 		&mut self,
 		validation_code: &[u8],
 		params: ValidationParams,
+		executor_params: &ExecutorParams,
 		test_mode: bool,
 	) -> Result<ValidationResult, ValidationError> {
 		if validation_code.len() > MAX_CODE_MEM {
@@ -295,6 +353,7 @@ impl ValidationHost {
 			let header = ValidationHeader {
 				code_size: validation_code.len() as u64,
 				params_size: encoded_params.len() as u64,
+				executor_params: executor_params.clone(),
 			};
 
 			header.encode_to(&mut header_buf);
@@ -305,15 +364,20 @@ impl ValidationHost {
 			.map_err(|e| ValidationError::Internal(e.into()))?;
 
 		debug!("{} Waiting for results", self.id);
-		match memory.wait(Event::ResultReady as usize, shared_memory::Timeout::Sec(EXECUTION_TIMEOUT_SEC as usize)) {
-			Err(e) => {
+		let deadline = Instant::now() + Duration::from_millis(executor_params.execution_timeout_ms);
+		// Wait in short intervals so that a worker that died is noticed early.
+		while let Err(e) = memory.wait(Event::ResultReady as usize, shared_memory::Timeout::Sec(1)) {
+			if let Some(status) = self.worker_exit_status() {
+				return Err(ValidationError::Internal(worker_exit_error(status, self.memory_limit)));
+			}
+
+			if Instant::now() >= deadline {
 				debug!("Worker timeout: {:?}", e);
 				if let Some(mut worker) = self.worker.take() {
 					worker.kill().ok();
 				}
 				return Err(ValidationError::InvalidCandidate(InvalidCandidate::Timeout));
 			}
-			Ok(()) => {}
 		}
 
 		{
@@ -340,7 +404,76 @@ impl ValidationHost {
 					debug!("{} External validation error: {}", self.id, e);
 					Err(ValidationError::InvalidCandidate(InvalidCandidate::ExternalWasmExecutor(e)))
 				}
+				ValidationResultHeader::Error(WorkerValidationError::Trap(msg)) => {
+					debug!("{} Validation function trapped: {}", self.id, msg);
+					Err(ValidationError::InvalidCandidate(InvalidCandidate::Trap(msg)))
+				}
+				ValidationResultHeader::Error(WorkerValidationError::ParamsDecodeFailure) => {
+					debug!("{} Validation function failed to decode its parameters", self.id);
+					Err(ValidationError::InvalidCandidate(InvalidCandidate::ParamsDecodeFailure))
+				}
 			}
 		}
 	}
 }
+
+/// The error to report for a worker which exited while it was expected to run.
+///
+/// The candidate isn't necessarily at fault, so this is always an internal error. An aborted
+/// worker may have run out of its memory limit, but it may as well have hit a bug or run out
+/// of memory on the host, so it doesn't make the candidate invalid either.
+fn worker_exit_error(status: process::ExitStatus, memory_limit: Option<u64>) -> InternalError {
+	InternalError::WasmWorker(match memory_limit {
+		Some(limit) if aborted(&status) => format!(
+			"Worker aborted, possibly by exceeding the memory limit of {} bytes: {}",
+			limit,
+			status,
+		),
+		_ => format!("Worker exited unexpectedly: {}", status),
+	})
+}
+
+/// Limit the memory of the worker spawned by `command` to `limit` bytes.
+///
+/// `RLIMIT_DATA` is used rather than `RLIMIT_AS`. Since Linux 4.7 it counts the data segment
+/// and all private writable mappings, whether or not their pages were touched, but neither
+/// shared mappings, such as the shared memory region, nor inaccessible reservations, such as
+/// the guard pages of wasm linear memories. Older kernels only count the data segment grown
+/// by `brk`, which leaves most allocations unlimited. Once over the limit, allocations fail
+/// and the worker aborts.
+#[cfg(target_os = "linux")]
+fn limit_worker_memory(command: &mut process::Command, limit: u64) {
+	use std::os::unix::process::CommandExt;
+
+	let rlimit = libc::rlimit {
+		rlim_cur: limit as libc::rlim_t,
+		rlim_max: limit as libc::rlim_t,
+	};
+	// SAFETY: `setrlimit` is async-signal-safe and `rlimit` is copied into the closure.
+	unsafe {
+		command.pre_exec(move || {
+			if libc::setrlimit(libc::RLIMIT_DATA, &rlimit) != 0 {
+				return Err(std::io::Error::last_os_error());
+			}
+			Ok(())
+		});
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+fn limit_worker_memory(_: &mut process::Command, limit: u64) {
+	debug!("Worker memory limit of {} bytes is not supported on this platform", limit);
+}
+
+/// Whether the worker was aborted, which is what happens when an allocation fails.
+#[cfg(target_os = "linux")]
+fn aborted(status: &process::ExitStatus) -> bool {
+	use std::os::unix::process::ExitStatusExt;
+
+	status.signal() == Some(libc::SIGABRT)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn aborted(_: &process::ExitStatus) -> bool {
+	false
+}
@@ -0,0 +1,45 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Collator for the adder test parachain.
+
+use structopt::StructOpt;
+use test_parachain_adder_collator::Collator;
+
+/// Sub-commands supported by the collator.
+#[derive(Debug, StructOpt)]
+enum Cli {
+	/// Export the genesis state of the parachain, hex encoded.
+	#[structopt(name = "export-genesis-state")]
+	ExportGenesisState,
+
+	/// Export the genesis wasm of the parachain, hex encoded.
+	#[structopt(name = "export-genesis-wasm")]
+	ExportGenesisWasm,
+}
+
+fn main() {
+	let collator = Collator::new();
+
+	match Cli::from_args() {
+		Cli::ExportGenesisState => {
+			println!("0x{}", hex::encode(collator.genesis_head()));
+		}
+		Cli::ExportGenesisWasm => {
+			println!("0x{}", hex::encode(collator.validation_code()));
+		}
+	}
+}
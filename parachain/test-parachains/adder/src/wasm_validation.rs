@@ -38,7 +38,9 @@ pub extern fn validate_block(params: *const u8, len: usize) -> u64 {
 				head_data: GenericHeadData(new_head.encode()),
 				new_validation_code: None,
 				upward_messages: sp_std::vec::Vec::new(),
+				horizontal_messages: sp_std::vec::Vec::new(),
 				processed_downward_messages: 0,
+				hrmp_watermark: params.relay_chain_height,
 			}
 		),
 		Err(_) => panic!("execution failure"),
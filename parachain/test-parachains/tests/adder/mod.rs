@@ -75,6 +75,7 @@ pub fn execute_good_on_parent() {
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
 		},
+		&Default::default(),
 		parachain::wasm_executor::ExecutionMode::RemoteTest(&pool),
 		sp_core::testing::TaskExecutor::new(),
 	).unwrap();
@@ -113,6 +114,7 @@ fn execute_good_chain_on_parent() {
 				relay_chain_height: number as RelayChainBlockNumber + 1,
 				hrmp_mqc_heads: Vec::new(),
 			},
+			&Default::default(),
 			parachain::wasm_executor::ExecutionMode::RemoteTest(&pool),
 			sp_core::testing::TaskExecutor::new(),
 		).unwrap();
@@ -152,6 +154,7 @@ fn execute_bad_on_parent() {
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
 		},
+		&Default::default(),
 		parachain::wasm_executor::ExecutionMode::RemoteTest(&pool),
 		sp_core::testing::TaskExecutor::new(),
 	).unwrap_err();
@@ -0,0 +1,82 @@
+// Copyright 2017-2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Test parachain with configurable PoV size and validation compute time.
+
+use parachain::primitives::{
+	BlockData as GenericBlockData,
+	HeadData as GenericHeadData,
+	ValidationParams,
+};
+use codec::{Decode, Encode};
+use undying::{BlockData, HeadData, State, execute_transition};
+
+fn validate(parent_head: &HeadData, block_data: &BlockData) -> Result<HeadData, ()> {
+	let pool = parachain::wasm_executor::ValidationPool::new();
+
+	parachain::wasm_executor::validate_candidate(
+		undying::wasm_binary_unwrap(),
+		ValidationParams {
+			parent_head: GenericHeadData(parent_head.encode()),
+			block_data: GenericBlockData(block_data.encode()),
+			relay_chain_height: 1,
+			hrmp_mqc_heads: Vec::new(),
+		},
+		&Default::default(),
+		parachain::wasm_executor::ExecutionMode::RemoteTest(&pool),
+		sp_core::testing::TaskExecutor::new(),
+	)
+		.map(|ret| HeadData::decode(&mut &ret.head_data.0[..]).unwrap())
+		.map_err(|_| ())
+}
+
+#[test]
+fn execute_good_on_parent_with_large_pov() {
+	let parent_head = HeadData {
+		number: 0,
+		parent_hash: [0; 32],
+		post_state: State::default().hash(),
+	};
+
+	let block_data = BlockData {
+		state: State::default(),
+		compute_rounds: 1000,
+		padding: vec![0; 1024 * 1024],
+	};
+
+	let new_head = validate(&parent_head, &block_data).unwrap();
+
+	assert_eq!(new_head.number, 1);
+	assert_eq!(new_head.parent_hash, parent_head.hash());
+	assert_eq!(new_head.post_state, execute_transition(&State::default(), 1000).hash());
+}
+
+#[test]
+fn execute_bad_on_parent() {
+	let parent_head = HeadData {
+		number: 0,
+		parent_hash: [0; 32],
+		post_state: State::default().hash(),
+	};
+
+	let block_data = BlockData {
+		state: State { counter: 1, digest: [0; 32] }, // start state is wrong.
+		compute_rounds: 1,
+		padding: Vec::new(),
+	};
+
+	assert!(validate(&parent_head, &block_data).is_err());
+}
@@ -18,8 +18,8 @@
 
 use crate::adder;
 use parachain::{
-	primitives::{BlockData, ValidationParams},
-	wasm_executor::{ValidationError, InvalidCandidate, EXECUTION_TIMEOUT_SEC},
+	primitives::{BlockData, ExecutorParams, ValidationParams},
+	wasm_executor::{ValidationError, InvalidCandidate},
 };
 
 #[test]
@@ -34,6 +34,7 @@ fn terminates_on_timeout() {
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
 		},
+		&Default::default(),
 		parachain::wasm_executor::ExecutionMode::RemoteTest(&pool),
 		sp_core::testing::TaskExecutor::new(),
 	);
@@ -46,6 +47,29 @@ fn terminates_on_timeout() {
 	adder::execute_good_on_parent();
 }
 
+#[test]
+fn worker_out_of_memory_is_internal_error() {
+	// far too little for the worker to run at all.
+	let pool = parachain::wasm_executor::ValidationPool::with_memory_limit(Some(1024 * 1024));
+
+	let result = parachain::wasm_executor::validate_candidate(
+		halt::wasm_binary_unwrap(),
+		ValidationParams {
+			block_data: BlockData(Vec::new()),
+			parent_head: Default::default(),
+			relay_chain_height: 1,
+			hrmp_mqc_heads: Vec::new(),
+		},
+		&Default::default(),
+		parachain::wasm_executor::ExecutionMode::RemoteTest(&pool),
+		sp_core::testing::TaskExecutor::new(),
+	);
+	match result {
+		Err(ValidationError::Internal(_)) => {},
+		r => panic!("{:?}", r),
+	}
+}
+
 #[test]
 fn parallel_execution() {
 	let pool = parachain::wasm_executor::ValidationPool::new();
@@ -62,6 +86,7 @@ fn parallel_execution() {
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
 		},
+		&Default::default(),
 		parachain::wasm_executor::ExecutionMode::RemoteTest(&pool2),
 		sp_core::testing::TaskExecutor::new(),
 	).ok());
@@ -73,13 +98,15 @@ fn parallel_execution() {
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
 		},
+		&Default::default(),
 		parachain::wasm_executor::ExecutionMode::RemoteTest(&pool),
 		sp_core::testing::TaskExecutor::new(),
 	);
 	thread.join().unwrap();
-	// total time should be < 2 x EXECUTION_TIMEOUT_SEC
+	// total time should be < 2 x the execution timeout
+	let execution_timeout_ms = ExecutorParams::default().execution_timeout_ms;
 	assert!(
 		std::time::Instant::now().duration_since(start)
-		< std::time::Duration::from_secs(EXECUTION_TIMEOUT_SEC * 2)
+		< std::time::Duration::from_millis(execution_timeout_ms * 2)
 	);
 }
@@ -0,0 +1,219 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Collator for the undying test parachain.
+//!
+//! Every block built by the collator carries `pov_size` bytes of padding and asks the validation
+//! function to perform `compute_rounds` hashing rounds, which makes the collator suitable for
+//! load-testing availability with large PoVs and validation with expensive candidates.
+
+use std::{collections::HashMap, sync::Arc};
+
+use codec::{Encode, Decode};
+use futures::future;
+use parking_lot::Mutex;
+use polkadot_node_primitives::{Collation, CollationGenerationConfig};
+use polkadot_primitives::v1::{
+	BlockData as GenericBlockData, CollatorId, CollatorPair, Id as ParaId, PoV, ValidationData,
+};
+use sp_core::Pair;
+use test_parachain_undying::{execute, execute_transition, BlockData, HeadData, State};
+
+/// The state of the undying parachain as seen by the collator.
+struct ChainState {
+	head_to_state: HashMap<Arc<HeadData>, State>,
+	number_to_head: HashMap<u64, Arc<HeadData>>,
+	/// The number of the best block built so far.
+	best_block: u64,
+	/// The size of the padding added to every block.
+	pov_size: usize,
+	/// The number of hashing rounds every block performs.
+	compute_rounds: u32,
+}
+
+impl ChainState {
+	/// Init the genesis state.
+	fn genesis(pov_size: usize, compute_rounds: u32) -> Self {
+		let genesis_state = State::default();
+		let genesis_head = Arc::new(HeadData {
+			number: 0,
+			parent_hash: Default::default(),
+			post_state: genesis_state.hash(),
+		});
+
+		Self {
+			head_to_state: vec![(genesis_head.clone(), genesis_state)].into_iter().collect(),
+			number_to_head: vec![(0, genesis_head)].into_iter().collect(),
+			best_block: 0,
+			pov_size,
+			compute_rounds,
+		}
+	}
+
+	/// Advance the state and produce a new block based on the given `parent_head`.
+	///
+	/// Returns the new [`BlockData`] and the new [`HeadData`].
+	fn advance(&mut self, parent_head: HeadData) -> (BlockData, HeadData) {
+		self.best_block = parent_head.number;
+
+		let state = self.head_to_state.get(&parent_head).cloned().unwrap_or_else(|| {
+			panic!("unknown parent head {:?}, the collator can only build on its own blocks", parent_head.number)
+		});
+
+		let block = BlockData {
+			state,
+			compute_rounds: self.compute_rounds,
+			padding: vec![0; self.pov_size],
+		};
+
+		let new_head = execute(parent_head.hash(), parent_head, &block)
+			.expect("blocks built by the collator are valid; qed");
+		let new_head_arc = Arc::new(new_head.clone());
+
+		self.head_to_state.insert(new_head_arc.clone(), execute_transition(&block.state, block.compute_rounds));
+		self.number_to_head.insert(new_head.number, new_head_arc);
+
+		(block, new_head)
+	}
+}
+
+/// The collator of the undying parachain.
+pub struct Collator {
+	state: Arc<Mutex<ChainState>>,
+	key: CollatorPair,
+}
+
+impl Collator {
+	/// Create a new collator instance with the state initialized as genesis.
+	///
+	/// Every block will carry `pov_size` bytes of padding and perform `compute_rounds` hashing
+	/// rounds when validated.
+	pub fn new(pov_size: usize, compute_rounds: u32) -> Self {
+		Self {
+			state: Arc::new(Mutex::new(ChainState::genesis(pov_size, compute_rounds))),
+			key: CollatorPair::generate().0,
+		}
+	}
+
+	/// Get the SCALE encoded genesis head of the undying parachain.
+	pub fn genesis_head(&self) -> Vec<u8> {
+		self.state.lock().number_to_head.get(&0).expect("genesis exists; qed").encode()
+	}
+
+	/// Get the validation code of the undying parachain.
+	pub fn validation_code(&self) -> &[u8] {
+		test_parachain_undying::wasm_binary_unwrap()
+	}
+
+	/// Get the collator id.
+	pub fn collator_id(&self) -> CollatorId {
+		self.key.public()
+	}
+
+	/// The number of the best block built so far.
+	pub fn best_block(&self) -> u64 {
+		self.state.lock().best_block
+	}
+
+	/// Create the collation function.
+	///
+	/// This collation function can be plugged into the overseer to generate collations for the
+	/// undying parachain.
+	pub fn create_collation_function(
+		&self,
+	) -> Box<dyn Fn(&ValidationData) -> Box<dyn future::Future<Output = Collation> + Unpin + Send> + Send + Sync> {
+		let state = self.state.clone();
+
+		Box::new(move |validation_data| {
+			let parent = HeadData::decode(&mut &validation_data.persisted.parent_head.0[..])
+				.expect("decodes parent head");
+
+			let (block_data, head_data) = state.lock().advance(parent);
+
+			let collation = Collation {
+				fees: 0,
+				upward_messages: Vec::new(),
+				horizontal_messages: Vec::new(),
+				hrmp_watermark: validation_data.persisted.block_number,
+				new_validation_code: None,
+				head_data: head_data.encode().into(),
+				proof_of_validity: PoV { block_data: GenericBlockData(block_data.encode()) },
+			};
+
+			Box::new(future::ready(collation))
+		})
+	}
+
+	/// The configuration to initialize the collation generation subsystem with, in order to
+	/// collate for `para_id`.
+	pub fn collation_generation_config(&self, para_id: ParaId) -> CollationGenerationConfig {
+		CollationGenerationConfig {
+			key: self.key.clone(),
+			collator: self.create_collation_function(),
+			para_id,
+			scheduled_only: true,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use futures::executor::block_on;
+	use polkadot_primitives::v1::PersistedValidationData;
+
+	fn validation_data(parent_head: Vec<u8>) -> ValidationData {
+		ValidationData {
+			persisted: PersistedValidationData {
+				parent_head: parent_head.into(),
+				..Default::default()
+			},
+			transient: Default::default(),
+		}
+	}
+
+	#[test]
+	fn collator_builds_valid_chain() {
+		let collator = Collator::new(1024, 10);
+		let collation_function = collator.create_collation_function();
+
+		let mut parent_head = collator.genesis_head();
+		for number in 1..=3 {
+			let collation = block_on(collation_function(&validation_data(parent_head.clone())));
+
+			let parent = HeadData::decode(&mut &parent_head[..]).unwrap();
+			let block_data = BlockData::decode(&mut &collation.proof_of_validity.block_data.0[..]).unwrap();
+			let head = execute(parent.hash(), parent, &block_data).unwrap();
+
+			assert_eq!(head.encode(), collation.head_data.0);
+			assert_eq!(head.number, number);
+			assert_eq!(block_data.state.counter, number - 1);
+
+			parent_head = collation.head_data.0;
+		}
+	}
+
+	#[test]
+	fn pov_size_is_respected() {
+		let collator = Collator::new(1024 * 1024, 0);
+		let collation_function = collator.create_collation_function();
+
+		let collation = block_on(collation_function(&validation_data(collator.genesis_head())));
+
+		assert!(collation.proof_of_validity.block_data.0.len() >= 1024 * 1024);
+	}
+}
@@ -0,0 +1,61 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Collator for the undying test parachain.
+
+use structopt::StructOpt;
+use test_parachain_undying_collator::Collator;
+
+/// Sub-commands supported by the collator.
+#[derive(Debug, StructOpt)]
+enum Cli {
+	/// Export the genesis state of the parachain, hex encoded.
+	#[structopt(name = "export-genesis-state")]
+	ExportGenesisState,
+
+	/// Export the genesis wasm of the parachain, hex encoded.
+	#[structopt(name = "export-genesis-wasm")]
+	ExportGenesisWasm,
+}
+
+/// Options of the blocks built by the collator.
+#[derive(Debug, StructOpt)]
+struct Opt {
+	/// The size of the padding added to every PoV, in bytes.
+	#[structopt(long, default_value = "0")]
+	pov_size: usize,
+
+	/// The number of hashing rounds performed by the validation function for every block.
+	#[structopt(long, default_value = "1")]
+	compute_rounds: u32,
+
+	#[structopt(subcommand)]
+	cmd: Cli,
+}
+
+fn main() {
+	let opt = Opt::from_args();
+	let collator = Collator::new(opt.pov_size, opt.compute_rounds);
+
+	match opt.cmd {
+		Cli::ExportGenesisState => {
+			println!("0x{}", hex::encode(collator.genesis_head()));
+		}
+		Cli::ExportGenesisWasm => {
+			println!("0x{}", hex::encode(collator.validation_code()));
+		}
+	}
+}
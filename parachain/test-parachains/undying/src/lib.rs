@@ -0,0 +1,126 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Test parachain for which the size of the PoV and the time spent in validation can be
+//! configured per block. Used to load-test availability and candidate validation.
+
+#![no_std]
+
+#![cfg_attr(not(feature = "std"), feature(core_intrinsics, lang_items, core_panic_info, alloc_error_handler))]
+
+use codec::{Encode, Decode};
+use sp_std::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+mod wasm_validation;
+
+#[cfg(not(feature = "std"))]
+#[global_allocator]
+static ALLOC: dlmalloc::GlobalDlmalloc = dlmalloc::GlobalDlmalloc;
+
+// Make the WASM binary available.
+#[cfg(feature = "std")]
+include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
+
+#[cfg(feature = "std")]
+/// Wasm binary unwrapped. If built with `BUILD_DUMMY_WASM_BINARY`, the function panics.
+pub fn wasm_binary_unwrap() -> &'static [u8] {
+	WASM_BINARY.expect("Development wasm binary is not available. Testing is only \
+						supported with the flag disabled.")
+}
+
+/// Head data for this parachain.
+#[derive(Default, Clone, Hash, Eq, PartialEq, Encode, Decode)]
+pub struct HeadData {
+	/// Block number
+	pub number: u64,
+	/// parent block keccak256
+	pub parent_hash: [u8; 32],
+	/// hash of post-execution state.
+	pub post_state: [u8; 32],
+}
+
+impl HeadData {
+	pub fn hash(&self) -> [u8; 32] {
+		tiny_keccak::keccak256(&self.encode())
+	}
+}
+
+/// The state of this parachain.
+#[derive(Default, Clone, Hash, Eq, PartialEq, Encode, Decode)]
+pub struct State {
+	/// Number of blocks executed so far.
+	pub counter: u64,
+	/// The result of the hashing rounds performed by the last block.
+	pub digest: [u8; 32],
+}
+
+impl State {
+	pub fn hash(&self) -> [u8; 32] {
+		tiny_keccak::keccak256(&self.encode())
+	}
+}
+
+/// Block data for this parachain.
+#[derive(Default, Clone, Encode, Decode)]
+pub struct BlockData {
+	/// State to begin from.
+	pub state: State,
+	/// Number of keccak256 rounds to perform on the state digest. Controls the time spent in
+	/// validation.
+	pub compute_rounds: u32,
+	/// Arbitrary bytes which are ignored by the state transition. Controls the size of the PoV.
+	pub padding: Vec<u8>,
+}
+
+/// Start state mismatched with parent header's state hash.
+#[derive(Debug)]
+pub struct StateMismatch;
+
+/// Execute a block body on top of given parent head, producing new parent head
+/// if valid.
+pub fn execute(
+	parent_hash: [u8; 32],
+	parent_head: HeadData,
+	block_data: &BlockData,
+) -> Result<HeadData, StateMismatch> {
+	debug_assert_eq!(parent_hash, parent_head.hash());
+
+	if block_data.state.hash() != parent_head.post_state {
+		return Err(StateMismatch);
+	}
+
+	let new_state = execute_transition(&block_data.state, block_data.compute_rounds);
+
+	Ok(HeadData {
+		number: parent_head.number + 1,
+		parent_hash,
+		post_state: new_state.hash(),
+	})
+}
+
+/// Apply the state transition of a block performing `compute_rounds` hashing rounds.
+pub fn execute_transition(state: &State, compute_rounds: u32) -> State {
+	let mut digest = state.digest;
+	for _ in 0..compute_rounds {
+		digest = tiny_keccak::keccak256(&digest);
+	}
+
+	State {
+		counter: state.counter.wrapping_add(1),
+		digest,
+	}
+}
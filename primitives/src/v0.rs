@@ -612,7 +612,7 @@ pub struct AvailableData {
 
 /// A chunk of erasure-encoded block data.
 #[derive(PartialEq, Eq, Clone, Encode, Decode, Default)]
-#[cfg_attr(feature = "std", derive(Serialize, Deserialize, Debug, Hash))]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize, Hash))]
 pub struct ErasureChunk {
 	/// The erasure-encoded chunk of data belonging to the candidate block.
 	pub chunk: Vec<u8>,
@@ -622,6 +622,17 @@ pub struct ErasureChunk {
 	pub proof: Vec<Vec<u8>>,
 }
 
+#[cfg(feature = "std")]
+impl std::fmt::Debug for ErasureChunk {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("ErasureChunk")
+			.field("chunk", &polkadot_parachain::primitives::BytesSummary(&self.chunk))
+			.field("index", &self.index)
+			.field("proof_nodes", &self.proof.len())
+			.finish()
+	}
+}
+
 /// Statements that can be made about parachain candidates. These are the
 /// actual values that are signed.
 #[derive(Clone, PartialEq, Eq, Encode, Decode)]
@@ -17,6 +17,7 @@
 //! V1 Primitives.
 
 use sp_std::prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use parity_scale_codec::{Encode, Decode};
 use bitvec::vec::BitVec;
 
@@ -37,7 +38,8 @@ pub use polkadot_core_primitives::v1::{
 // Export some polkadot-parachain primitives
 pub use polkadot_parachain::primitives::{
 	Id, ParachainDispatchOrigin, LOWEST_USER_ID, UpwardMessage, HeadData, BlockData,
-	ValidationCode,
+	ValidationCode, HrmpChannelId, OutboundHrmpMessage, InboundHrmpMessage, AccountIdConversion,
+	ExecutorParams,
 };
 
 // Export some basic parachain primitives from v0.
@@ -56,6 +58,9 @@ pub use sp_staking::SessionIndex;
 /// Unique identifier for the Inclusion Inherent
 pub const INCLUSION_INHERENT_IDENTIFIER: InherentIdentifier = *b"inclusn0";
 
+/// The maximum encoded size of a PoV, in bytes.
+pub const MAX_POV_SIZE: u32 = 5 * 1024 * 1024;
+
 /// Get a collator signature payload on a relay-parent, block-data combo.
 pub fn collator_signature_payload<H: AsRef<[u8]>>(
 	relay_parent: &H,
@@ -274,6 +279,11 @@ impl<N: Encode> PersistedValidationData<N> {
 	}
 }
 
+/// Compute the blake2-256 hash of validation code, which identifies the code.
+pub fn validation_code_hash(code: &ValidationCode) -> Hash {
+	BlakeTwo256::hash(&code.0[..])
+}
+
 /// Validation data for checking outputs of the validation-function.
 /// As such, they also inform the collator about how to construct the candidate.
 ///
@@ -286,6 +296,10 @@ pub struct TransientValidationData<N = BlockNumber> {
 	pub max_code_size: u32,
 	/// The maximum head-data size permitted, in bytes.
 	pub max_head_data_size: u32,
+	/// The maximum number of upward messages permitted.
+	pub max_upward_message_num: u32,
+	/// The maximum total size of the data of upward messages permitted, in bytes.
+	pub max_upward_message_size: u32,
 	/// The balance of the parachain at the moment of validation.
 	pub balance: Balance,
 	/// Whether the parachain is allowed to upgrade its validation code.
@@ -310,12 +324,17 @@ pub struct CandidateCommitments {
 	pub fees: Balance,
 	/// Messages destined to be interpreted by the Relay chain itself.
 	pub upward_messages: Vec<UpwardMessage>,
+	/// Horizontal messages sent by the parachain, sorted ascending by recipient.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<Id>>,
 	/// The root of a block's erasure encoding Merkle tree.
 	pub erasure_root: Hash,
 	/// New validation code.
 	pub new_validation_code: Option<ValidationCode>,
 	/// The head-data produced as a result of execution.
 	pub head_data: HeadData,
+	/// The relay-chain block number up to which, inclusive, the parachain processed its inbound
+	/// horizontal messages.
+	pub hrmp_watermark: BlockNumber,
 }
 
 impl CandidateCommitments {
@@ -634,12 +653,41 @@ pub enum CandidateEvent<H = Hash> {
 	CandidateTimedOut(CandidateReceipt<H>, HeadData),
 }
 
+/// The backing votes processed on-chain in a block.
+#[derive(Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(PartialEq, Debug))]
+pub struct ScrapedOnChainVotes<H = Hash> {
+	/// The session the votes were processed in.
+	pub session: SessionIndex,
+	/// The receipts of the candidates backed in the block, along with the indices of the
+	/// validators that backed them.
+	pub backing_validators_per_candidate: Vec<(CandidateReceipt<H>, Vec<ValidatorIndex>)>,
+}
+
 sp_api::decl_runtime_apis! {
 	/// The API for querying the state of parachains on-chain.
+	///
+	/// Version history:
+	/// - 1: the initial set of functions.
+	/// - 2: adds `active_validator_indices`, `validation_code_by_hash` and `on_chain_votes`.
+	/// - 3: adds `availability_chunk_shuffle_seed`.
+	/// - 4: adds `historical_validation_code`.
+	/// - 5: adds `upgrade_restriction_signal`. Removed since, as candidate validation already
+	///   refuses code upgrades the runtime would reject. The version is kept so that the later
+	///   ones keep their meaning.
+	/// - 6: adds `session_executor_params`.
+	/// - 7: adds `claim_queue`.
+	/// - 8: adds `full_validation_data_at` and `allowed_relay_parents`.
+	#[api_version(8)]
 	pub trait ParachainHost<H: Decode = Hash, N: Decode = BlockNumber> {
 		/// Get the current validators.
 		fn validators() -> Vec<ValidatorId>;
 
+		/// Get the indices, within the session's full validator set, of the validators taking part
+		/// in parachain consensus. These are ordered the same as the keys returned by `validators`,
+		/// so the rest of the session's validators are relay-chain-only validators.
+		fn active_validator_indices() -> Vec<ValidatorIndex>;
+
 		/// Returns the validator groups and rotation info localized based on the block whose state
 		/// this is invoked on. Note that `now` in the `GroupRotationInfo` should be the successor of
 		/// the number of the block.
@@ -657,6 +705,21 @@ sp_api::decl_runtime_apis! {
 		fn full_validation_data(para_id: Id, assumption: OccupiedCoreAssumption)
 			-> Option<ValidationData<N>>;
 
+		/// Like `full_validation_data`, but for a candidate built on the allowed relay parent with
+		/// the given number rather than on the block itself.
+		///
+		/// Returns `None` if either the para is not registered or the assumption is `Freed`
+		/// and the para already occupies a core.
+		fn full_validation_data_at(
+			para_id: Id,
+			relay_parent_number: N,
+			assumption: OccupiedCoreAssumption,
+		) -> Option<ValidationData<N>>;
+
+		/// Returns the relay parents candidates backed in a child of the block may be built on,
+		/// along with their numbers, most recent first. The first one is the block itself.
+		fn allowed_relay_parents() -> Vec<(H, N)>;
+
 		/// Yields the persisted validation data for the given ParaId along with an assumption that
 		/// should be used if the para currently occupies a core.
 		///
@@ -670,6 +733,10 @@ sp_api::decl_runtime_apis! {
 		/// This can be used to instantiate a `SigningContext`.
 		fn session_index_for_child() -> SessionIndex;
 
+		/// Returns the seed used to shuffle the assignment of erasure chunks to validators during
+		/// the session expected at a child of the block.
+		fn availability_chunk_shuffle_seed() -> [u8; 32];
+
 		/// Fetch the validation code used by a para, making the given `OccupiedCoreAssumption`.
 		///
 		/// Returns `None` if either the para is not registered or the assumption is `Freed`
@@ -677,6 +744,31 @@ sp_api::decl_runtime_apis! {
 		fn validation_code(para_id: Id, assumption: OccupiedCoreAssumption)
 			-> Option<ValidationCode>;
 
+		/// Fetch validation code by its hash, as computed by [`validation_code_hash`].
+		///
+		/// Past code is kept for the acceptance period after it was replaced. Returns `None` if
+		/// the code is unknown or was already pruned.
+		fn validation_code_by_hash(hash: Hash) -> Option<ValidationCode>;
+
+		/// Fetch the validation code used by a para for candidates in the context of the relay-chain
+		/// block with the given number, which must not be after the block this is invoked on.
+		///
+		/// Returns `None` if the para is not registered or the code has already been pruned.
+		fn historical_validation_code(para_id: Id, context_height: N) -> Option<ValidationCode>;
+
+		/// Get the parameters of the environment validation functions are executed in during the
+		/// given session. Returns `None` if the session is not the one expected at a child of the
+		/// block.
+		fn session_executor_params(session_index: SessionIndex) -> Option<ExecutorParams>;
+
+		/// Yields the paras each core is assigned to for the next `scheduling_lookahead` blocks,
+		/// starting with the child of the block. Cores without any upcoming assignment are left
+		/// out.
+		///
+		/// Parathread claims which are not backed in time are requeued, possibly on another core,
+		/// so the assignments of parathread cores beyond the first one are only a best guess.
+		fn claim_queue() -> BTreeMap<CoreIndex, Vec<Id>>;
+
 		/// Get the receipt of a candidate pending availability. This returns `Some` for any paras
 		/// assigned to occupied cores in `availability_cores` and `None` otherwise.
 		fn candidate_pending_availability(para_id: Id) -> Option<CommittedCandidateReceipt<H>>;
@@ -686,6 +778,12 @@ sp_api::decl_runtime_apis! {
 		// initialization.
 		#[skip_initialize_block]
 		fn candidate_events() -> Vec<CandidateEvent<H>>;
+
+		/// Get the backing votes processed on-chain by the inclusion inherent of the block.
+		// NOTE: this needs to skip block initialization, as the votes are only known once the
+		// inherent has been applied.
+		#[skip_initialize_block]
+		fn on_chain_votes() -> Option<ScrapedOnChainVotes<H>>;
 	}
 }
 
@@ -733,6 +831,15 @@ mod tests {
 		assert_eq!(info.last_rotation_at(), 0);
 	}
 
+	#[test]
+	fn pov_debug_output_summarizes_block_data() {
+		let pov = PoV { block_data: BlockData(vec![42; 1024]) };
+		let formatted = format!("{:?}", pov);
+
+		assert!(formatted.contains("1024 bytes, starting with 0x2a2a2a2a2a2a2a2a)"), "{}", formatted);
+		assert!(!formatted.contains("42, 42"), "{}", formatted);
+	}
+
 	#[test]
 	fn collator_signature_payload_is_valid() {
 		// if this fails, collator signature verification code has to be updated.
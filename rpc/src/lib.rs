@@ -18,6 +18,8 @@
 
 #![warn(missing_docs)]
 
+pub mod parachain;
+
 use std::sync::Arc;
 
 use polkadot_primitives::v0::{Block, BlockNumber, AccountId, Nonce, Balance, Hash};
@@ -26,7 +28,7 @@ use txpool_api::TransactionPool;
 use sp_blockchain::{HeaderBackend, HeaderMetadata, Error as BlockChainError};
 use sp_consensus::SelectChain;
 use sp_consensus_babe::BabeApi;
-use sc_client_api::light::{Fetcher, RemoteBlockchain};
+use sc_client_api::{ProofProvider, light::{Fetcher, RemoteBlockchain}};
 use sc_consensus_babe::Epoch;
 use sp_block_builder::BlockBuilder;
 pub use sc_rpc::DenyUnsafe;
@@ -89,6 +91,7 @@ pub struct FullDeps<C, P, SC> {
 pub fn create_full<C, P, SC>(deps: FullDeps<C, P, SC>) -> RpcExtension where
 	C: ProvideRuntimeApi<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error=BlockChainError>,
+	C: ProofProvider<Block>,
 	C: Send + Sync + 'static,
 	C::Api: frame_rpc_system::AccountNonceApi<Block, AccountId, Nonce>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
@@ -101,6 +104,7 @@ pub fn create_full<C, P, SC>(deps: FullDeps<C, P, SC>) -> RpcExtension where
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
 	use sc_finality_grandpa_rpc::{GrandpaApi, GrandpaRpcHandler};
 	use sc_consensus_babe_rpc::BabeRpcHandler;
+	use parachain::{Parachain, ParachainApi};
 
 	let mut io = jsonrpc_core::IoHandler::default();
 	let FullDeps {
@@ -129,6 +133,9 @@ pub fn create_full<C, P, SC>(deps: FullDeps<C, P, SC>) -> RpcExtension where
 	io.extend_with(
 		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone()))
 	);
+	io.extend_with(
+		ParachainApi::to_delegate(Parachain::new(client.clone()))
+	);
 	io.extend_with(
 		sc_consensus_babe_rpc::BabeApi::to_delegate(
 			BabeRpcHandler::new(
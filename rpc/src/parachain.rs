@@ -0,0 +1,110 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPCs which let parachain nodes and bridges verify relay-chain state without
+//! having access to a relay-chain full node of their own.
+
+use std::sync::Arc;
+
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use polkadot_primitives::v0::{Block, Hash};
+use sc_client_api::ProofProvider;
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
+use sp_core::{Bytes, storage::StorageKey};
+use sp_runtime::generic::BlockId;
+
+/// The maximum number of keys a single proof can be requested for.
+pub const MAX_PROOF_KEYS: usize = 64;
+
+/// The maximum size of a proof, in bytes.
+pub const MAX_PROOF_SIZE: usize = 1024 * 1024;
+
+/// The error code for requests exceeding [`MAX_PROOF_KEYS`] or [`MAX_PROOF_SIZE`].
+const PROOF_TOO_LARGE: i64 = 1;
+/// The error code for proofs that could not be generated.
+const PROOF_FAILED: i64 = 2;
+
+/// A proof of relay-chain storage.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayStateProof {
+	/// The block the storage was read at.
+	pub at: Hash,
+	/// The trie nodes proving the values of the requested keys, each one included once.
+	pub proof: Vec<Bytes>,
+}
+
+/// Relay-chain state RPCs for parachains.
+#[rpc]
+pub trait ParachainApi {
+	/// Prove the values of the given relay-chain storage `keys` at block `at`, or at the best
+	/// block if `at` isn't given.
+	///
+	/// Typical keys are the head of a para or the MQC head of its downward message queue.
+	#[rpc(name = "parachain_proveRelayState")]
+	fn prove_relay_state(&self, keys: Vec<StorageKey>, at: Option<Hash>) -> Result<RelayStateProof>;
+}
+
+/// An implementation of [`ParachainApi`] backed by the client of a full node.
+pub struct Parachain<C> {
+	client: Arc<C>,
+}
+
+impl<C> Parachain<C> {
+	/// Create a new instance of the parachain RPCs.
+	pub fn new(client: Arc<C>) -> Self {
+		Parachain { client }
+	}
+}
+
+impl<C> ParachainApi for Parachain<C> where
+	C: ProofProvider<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+{
+	fn prove_relay_state(&self, keys: Vec<StorageKey>, at: Option<Hash>) -> Result<RelayStateProof> {
+		if keys.len() > MAX_PROOF_KEYS {
+			return Err(error(
+				PROOF_TOO_LARGE,
+				format!("At most {} keys can be proven at once, got {}", MAX_PROOF_KEYS, keys.len()),
+			));
+		}
+
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		let proof = self.client
+			.read_proof(&BlockId::Hash(at), &mut keys.iter().map(|key| key.0.as_ref()))
+			.map_err(|e| error(PROOF_FAILED, format!("Failed to prove relay state at {}: {:?}", at, e)))?;
+
+		let proof: Vec<Bytes> = proof.iter_nodes().map(Into::into).collect();
+		let size: usize = proof.iter().map(|node| node.len()).sum();
+		if size > MAX_PROOF_SIZE {
+			return Err(error(
+				PROOF_TOO_LARGE,
+				format!("The proof is {} bytes, exceeding the limit of {} bytes", size, MAX_PROOF_SIZE),
+			));
+		}
+
+		Ok(RelayStateProof { at, proof })
+	}
+}
+
+fn error(code: i64, message: String) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(code),
+		message,
+		data: None,
+	}
+}
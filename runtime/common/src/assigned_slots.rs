@@ -0,0 +1,518 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Permissioned assignment of parachain slots, bypassing the auction system.
+//!
+//! Governance may assign a *permanent* slot to a parathread, upgrading it to a parachain until
+//! the slot is unassigned again, or a *temporary* slot. Temporary slot holders take turns: at the
+//! start of every lease period, at most `MaxTemporarySlotPerLeasePeriod` of them are upgraded to
+//! parachains for `period_count` lease periods each, preferring the ones which have waited
+//! longest. This is intended for common-good chains and for test networks.
+
+use sp_std::prelude::*;
+use sp_runtime::traits::{Zero, Saturating};
+use codec::{Encode, Decode};
+use frame_support::{
+	decl_module, decl_storage, decl_event, decl_error, ensure,
+	storage::IterableStorageMap,
+	traits::{Get, EnsureOrigin},
+	weights::{DispatchClass, Weight},
+};
+use primitives::v1::Id as ParaId;
+use runtime_parachains::paras;
+
+type LeasePeriodOf<T> = <T as frame_system::Trait>::BlockNumber;
+
+/// The module's configuration trait.
+pub trait Trait: paras::Trait {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+	/// The origin which may assign and unassign slots.
+	type AssignSlotOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The number of blocks over which a single period lasts.
+	type LeasePeriod: Get<Self::BlockNumber>;
+
+	/// The maximum number of permanent slots that may be assigned.
+	type MaxPermanentSlots: Get<u32>;
+
+	/// The maximum number of temporary slots that may be assigned.
+	type MaxTemporarySlots: Get<u32>;
+
+	/// The maximum number of temporary slots which are leased at the same time.
+	type MaxTemporarySlotPerLeasePeriod: Get<u32>;
+}
+
+/// Information about a temporary slot.
+#[derive(Clone, Eq, PartialEq, Default, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct TemporarySlot<LeasePeriod> {
+	/// The number of consecutive lease periods the para is a parachain for on each turn.
+	pub period_count: u32,
+	/// The lease period the current turn started at, if the para is currently leased.
+	pub active_since: Option<LeasePeriod>,
+	/// The last lease period the para was leased for, if ever.
+	pub last_lease: Option<LeasePeriod>,
+	/// The number of lease periods the para was leased for so far.
+	pub lease_count: u32,
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as AssignedSlots {
+		/// Paras holding a permanent slot, together with the lease period of the assignment.
+		pub PermanentSlots get(fn permanent_slots):
+			map hasher(twox_64_concat) ParaId => Option<LeasePeriodOf<T>>;
+
+		/// The number of permanent slots currently assigned.
+		pub PermanentSlotCount get(fn permanent_slot_count): u32;
+
+		/// Paras holding a temporary slot.
+		pub TemporarySlots get(fn temporary_slots):
+			map hasher(twox_64_concat) ParaId => Option<TemporarySlot<LeasePeriodOf<T>>>;
+
+		/// The number of temporary slots currently assigned.
+		pub TemporarySlotCount get(fn temporary_slot_count): u32;
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where
+		LeasePeriod = LeasePeriodOf<T>,
+	{
+		/// A permanent slot was assigned to a para. [para_id]
+		PermanentSlotAssigned(ParaId),
+		/// A temporary slot was assigned to a para. [para_id]
+		TemporarySlotAssigned(ParaId),
+		/// A para's slot was unassigned. [para_id]
+		SlotUnassigned(ParaId),
+		/// A para holding a temporary slot started its turn as a parachain. [para_id, lease_period]
+		TemporarySlotLeased(ParaId, LeasePeriod),
+		/// A para holding a temporary slot ended its turn as a parachain. [para_id, lease_period]
+		TemporarySlotExpired(ParaId, LeasePeriod),
+	}
+);
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The para is not a registered parathread.
+		ParaNotParathread,
+		/// The para already holds a slot.
+		SlotAlreadyAssigned,
+		/// The para doesn't hold a slot.
+		SlotNotAssigned,
+		/// All permanent slots are assigned already.
+		MaxPermanentSlotsExceeded,
+		/// All temporary slots are assigned already.
+		MaxTemporarySlotsExceeded,
+		/// A temporary slot must last for at least one lease period per turn.
+		ZeroPeriodCount,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		fn on_initialize(n: T::BlockNumber) -> Weight {
+			let lease_period = T::LeasePeriod::get();
+			if (n % lease_period).is_zero() {
+				Self::manage_lease_period_start((n / lease_period).into())
+			} else {
+				0
+			}
+		}
+
+		/// Assign a permanent slot to a parathread. It is upgraded to a parachain at the next
+		/// session change and stays one until the slot is unassigned.
+		#[weight = (100_000_000, DispatchClass::Operational)]
+		pub fn assign_perm_parachain_slot(origin, id: ParaId) {
+			T::AssignSlotOrigin::ensure_origin(origin)?;
+			Self::ensure_can_assign(id)?;
+			ensure!(
+				Self::permanent_slot_count() < T::MaxPermanentSlots::get(),
+				Error::<T>::MaxPermanentSlotsExceeded,
+			);
+
+			<paras::Module<T>>::schedule_parathread_upgrade(id);
+			<PermanentSlots<T>>::insert(id, Self::lease_period_index());
+			PermanentSlotCount::mutate(|c| *c += 1);
+
+			Self::deposit_event(RawEvent::PermanentSlotAssigned(id));
+		}
+
+		/// Assign a temporary slot to a parathread. It is leased `period_count` consecutive lease
+		/// periods at a time, taking turns with the other holders of temporary slots. Turns only
+		/// start at the beginning of a lease period.
+		#[weight = (100_000_000, DispatchClass::Operational)]
+		pub fn assign_temp_parachain_slot(origin, id: ParaId, #[compact] period_count: u32) {
+			T::AssignSlotOrigin::ensure_origin(origin)?;
+			Self::ensure_can_assign(id)?;
+			ensure!(period_count > 0, Error::<T>::ZeroPeriodCount);
+			ensure!(
+				Self::temporary_slot_count() < T::MaxTemporarySlots::get(),
+				Error::<T>::MaxTemporarySlotsExceeded,
+			);
+
+			<TemporarySlots<T>>::insert(id, TemporarySlot { period_count, ..Default::default() });
+			TemporarySlotCount::mutate(|c| *c += 1);
+
+			Self::deposit_event(RawEvent::TemporarySlotAssigned(id));
+		}
+
+		/// Unassign the slot of a para. If it is currently leased, it is downgraded to a
+		/// parathread at the next session change.
+		#[weight = (100_000_000, DispatchClass::Operational)]
+		pub fn unassign_parachain_slot(origin, id: ParaId) {
+			T::AssignSlotOrigin::ensure_origin(origin)?;
+
+			if <PermanentSlots<T>>::take(id).is_some() {
+				PermanentSlotCount::mutate(|c| *c = c.saturating_sub(1));
+				<paras::Module<T>>::schedule_parachain_downgrade(id);
+			} else if let Some(slot) = <TemporarySlots<T>>::take(id) {
+				TemporarySlotCount::mutate(|c| *c = c.saturating_sub(1));
+				if slot.active_since.is_some() {
+					<paras::Module<T>>::schedule_parachain_downgrade(id);
+				}
+			} else {
+				Err(Error::<T>::SlotNotAssigned)?
+			}
+
+			Self::deposit_event(RawEvent::SlotUnassigned(id));
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Returns the current lease period.
+	fn lease_period_index() -> LeasePeriodOf<T> {
+		(<frame_system::Module<T>>::block_number() / T::LeasePeriod::get()).into()
+	}
+
+	/// Ensure that a slot may be assigned to the given para.
+	fn ensure_can_assign(id: ParaId) -> Result<(), Error<T>> {
+		ensure!(<paras::Module<T>>::is_parathread(id), Error::<T>::ParaNotParathread);
+		ensure!(
+			!<PermanentSlots<T>>::contains_key(id) && !<TemporarySlots<T>>::contains_key(id),
+			Error::<T>::SlotAlreadyAssigned,
+		);
+		Ok(())
+	}
+
+	/// A new lease period is beginning. Rotate the temporary slots: end the turns which are over
+	/// and fill the free places with the paras which have waited longest.
+	fn manage_lease_period_start(lease_period_index: LeasePeriodOf<T>) -> Weight {
+		let mut slots = <TemporarySlots<T>>::iter().collect::<Vec<_>>();
+		let mut weight = T::DbWeight::get().reads(slots.len() as Weight);
+
+		// Paras whose turn is over compete for the free places again, but after everyone who has
+		// been waiting.
+		let is_expired = |slot: &TemporarySlot<LeasePeriodOf<T>>| slot.active_since.map_or(false, |since| {
+			since.saturating_add(slot.period_count.into()) <= lease_period_index
+		});
+		let continuing = slots.iter()
+			.filter(|(_, slot)| slot.active_since.is_some() && !is_expired(slot))
+			.count() as u32;
+		let mut free = T::MaxTemporarySlotPerLeasePeriod::get().saturating_sub(continuing);
+
+		slots.sort_by_key(|(id, slot)| (slot.last_lease, *id));
+
+		for (id, mut slot) in slots {
+			let was_active = slot.active_since.is_some();
+			let expired = is_expired(&slot);
+
+			if was_active && !expired {
+				// Continuing its turn.
+			} else if free > 0 && (was_active || <paras::Module<T>>::schedule_parathread_upgrade(id)) {
+				free -= 1;
+				slot.active_since = Some(lease_period_index);
+				Self::deposit_event(RawEvent::TemporarySlotLeased(id, lease_period_index));
+			} else if was_active {
+				<paras::Module<T>>::schedule_parachain_downgrade(id);
+				slot.active_since = None;
+				Self::deposit_event(RawEvent::TemporarySlotExpired(id, lease_period_index));
+			} else {
+				continue;
+			}
+
+			if slot.active_since.is_some() {
+				slot.last_lease = Some(lease_period_index);
+				slot.lease_count += 1;
+			}
+
+			<TemporarySlots<T>>::insert(id, slot);
+			weight += T::DbWeight::get().reads_writes(2, 2);
+		}
+
+		weight
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use sp_runtime::{Perbill, traits::{BlakeTwo256, IdentityLookup}};
+	use frame_support::{
+		impl_outer_origin, parameter_types, assert_ok, assert_noop,
+		traits::{OnInitialize, OnFinalize},
+	};
+	use frame_system::EnsureRoot;
+	use primitives::v1::{BlockNumber, Header};
+	use runtime_parachains::{configuration, paras::ParaGenesisArgs};
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u32 = 250;
+		pub const MaximumBlockWeight: u32 = 4 * 1024 * 1024;
+		pub const MaximumBlockLength: u32 = 4 * 1024 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	}
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Call = ();
+		type Index = u64;
+		type BlockNumber = BlockNumber;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+	}
+
+	impl configuration::Trait for Test { }
+
+	impl paras::Trait for Test { }
+
+	parameter_types! {
+		pub const LeasePeriod: BlockNumber = 10;
+		pub const MaxPermanentSlots: u32 = 2;
+		pub const MaxTemporarySlots: u32 = 3;
+		pub const MaxTemporarySlotPerLeasePeriod: u32 = 1;
+	}
+
+	impl Trait for Test {
+		type Event = ();
+		type AssignSlotOrigin = EnsureRoot<u64>;
+		type LeasePeriod = LeasePeriod;
+		type MaxPermanentSlots = MaxPermanentSlots;
+		type MaxTemporarySlots = MaxTemporarySlots;
+		type MaxTemporarySlotPerLeasePeriod = MaxTemporarySlotPerLeasePeriod;
+	}
+
+	type System = frame_system::Module<Test>;
+	type Paras = paras::Module<Test>;
+	type AssignedSlots = Module<Test>;
+
+	fn parathread(id: u32) -> (ParaId, ParaGenesisArgs) {
+		(id.into(), ParaGenesisArgs {
+			genesis_head: vec![id as u8].into(),
+			validation_code: vec![id as u8].into(),
+			parachain: false,
+		})
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		paras::GenesisConfig::<Test> {
+			paras: (1..=5).map(parathread).collect(),
+			..Default::default()
+		}.assimilate_storage(&mut t).unwrap();
+		t.into()
+	}
+
+	fn run_to_block(n: BlockNumber) {
+		while System::block_number() < n {
+			System::on_finalize(System::block_number());
+			System::set_block_number(System::block_number() + 1);
+			System::on_initialize(System::block_number());
+			AssignedSlots::on_initialize(System::block_number());
+		}
+	}
+
+	fn leased(id: u32) -> bool {
+		AssignedSlots::temporary_slots(ParaId::from(id)).map_or(false, |s| s.active_since.is_some())
+	}
+
+	#[test]
+	fn assign_perm_slot_works() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+
+			assert_noop!(
+				AssignedSlots::assign_perm_parachain_slot(Origin::signed(1), 1.into()),
+				sp_runtime::DispatchError::BadOrigin,
+			);
+			assert_noop!(
+				AssignedSlots::assign_perm_parachain_slot(Origin::root(), 9.into()),
+				Error::<Test>::ParaNotParathread,
+			);
+
+			assert_ok!(AssignedSlots::assign_perm_parachain_slot(Origin::root(), 1.into()));
+			assert_eq!(AssignedSlots::permanent_slots(ParaId::from(1)), Some(0));
+			assert_eq!(AssignedSlots::permanent_slot_count(), 1);
+			assert_eq!(Paras::upcoming_upgrades(), vec![ParaId::from(1)]);
+
+			assert_noop!(
+				AssignedSlots::assign_perm_parachain_slot(Origin::root(), 1.into()),
+				Error::<Test>::SlotAlreadyAssigned,
+			);
+			assert_noop!(
+				AssignedSlots::assign_temp_parachain_slot(Origin::root(), 1.into(), 1),
+				Error::<Test>::SlotAlreadyAssigned,
+			);
+
+			assert_ok!(AssignedSlots::assign_perm_parachain_slot(Origin::root(), 2.into()));
+			assert_noop!(
+				AssignedSlots::assign_perm_parachain_slot(Origin::root(), 3.into()),
+				Error::<Test>::MaxPermanentSlotsExceeded,
+			);
+		});
+	}
+
+	#[test]
+	fn assign_temp_slot_checks_limits() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+
+			assert_noop!(
+				AssignedSlots::assign_temp_parachain_slot(Origin::root(), 1.into(), 0),
+				Error::<Test>::ZeroPeriodCount,
+			);
+
+			for id in 1..=3 {
+				assert_ok!(AssignedSlots::assign_temp_parachain_slot(Origin::root(), id.into(), 1));
+			}
+			assert_eq!(AssignedSlots::temporary_slot_count(), 3);
+			assert_noop!(
+				AssignedSlots::assign_temp_parachain_slot(Origin::root(), 4.into(), 1),
+				Error::<Test>::MaxTemporarySlotsExceeded,
+			);
+
+			// Nothing is leased before the next lease period.
+			assert!(Paras::upcoming_upgrades().is_empty());
+		});
+	}
+
+	#[test]
+	fn temp_slots_rotate_per_lease_period() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+
+			assert_ok!(AssignedSlots::assign_temp_parachain_slot(Origin::root(), 2.into(), 2));
+			assert_ok!(AssignedSlots::assign_temp_parachain_slot(Origin::root(), 1.into(), 1));
+
+			// Neither has been leased yet, so the lower ID goes first.
+			run_to_block(10);
+			assert!(leased(1));
+			assert!(!leased(2));
+			assert_eq!(Paras::upcoming_upgrades(), vec![ParaId::from(1)]);
+
+			// Para 1's single lease period is over, so para 2 gets its two.
+			run_to_block(20);
+			assert!(!leased(1));
+			assert!(leased(2));
+			assert_eq!(Paras::upcoming_downgrades(), vec![ParaId::from(1)]);
+			assert_eq!(Paras::upcoming_upgrades(), vec![ParaId::from(2)]);
+
+			run_to_block(30);
+			assert!(leased(2));
+			assert!(!leased(1));
+
+			run_to_block(40);
+			assert!(leased(1));
+			assert!(!leased(2));
+
+			let slot = AssignedSlots::temporary_slots(ParaId::from(1)).unwrap();
+			assert_eq!(slot.lease_count, 2);
+			assert_eq!(slot.last_lease, Some(4));
+			let slot = AssignedSlots::temporary_slots(ParaId::from(2)).unwrap();
+			assert_eq!(slot.lease_count, 2);
+			assert_eq!(slot.last_lease, Some(3));
+		});
+	}
+
+	#[test]
+	fn lone_temp_slot_keeps_its_lease() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+
+			assert_ok!(AssignedSlots::assign_temp_parachain_slot(Origin::root(), 1.into(), 1));
+			run_to_block(10);
+			assert!(leased(1));
+
+			// Nobody else is waiting, so the turn is simply renewed.
+			run_to_block(20);
+			assert!(leased(1));
+			assert!(Paras::upcoming_downgrades().is_empty());
+			assert_eq!(AssignedSlots::temporary_slots(ParaId::from(1)).unwrap().lease_count, 2);
+		});
+	}
+
+	#[test]
+	fn unassign_slot_downgrades() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+
+			assert_noop!(
+				AssignedSlots::unassign_parachain_slot(Origin::root(), 1.into()),
+				Error::<Test>::SlotNotAssigned,
+			);
+
+			assert_ok!(AssignedSlots::assign_temp_parachain_slot(Origin::root(), 1.into(), 1));
+			assert_ok!(AssignedSlots::assign_temp_parachain_slot(Origin::root(), 2.into(), 1));
+			run_to_block(10);
+			assert!(leased(1));
+
+			// Unleased slots are just forgotten.
+			assert_ok!(AssignedSlots::unassign_parachain_slot(Origin::root(), 2.into()));
+			assert!(Paras::upcoming_downgrades().is_empty());
+			assert_eq!(AssignedSlots::temporary_slot_count(), 1);
+
+			// Para 1 was still a parathread, so the pending upgrade is cancelled instead.
+			assert_eq!(Paras::upcoming_upgrades(), vec![ParaId::from(1)]);
+			assert_ok!(AssignedSlots::unassign_parachain_slot(Origin::root(), 1.into()));
+			assert!(Paras::upcoming_upgrades().is_empty());
+			assert!(Paras::upcoming_downgrades().is_empty());
+			assert_eq!(AssignedSlots::temporary_slots(ParaId::from(1)), None);
+			assert_eq!(AssignedSlots::temporary_slot_count(), 0);
+		});
+	}
+}
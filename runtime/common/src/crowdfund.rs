@@ -61,20 +61,28 @@
 //! contributors. *Retirement* may be initiated by any account (using the `begin_retirement` call)
 //! once the parachain is removed from the its slot.
 //!
+//! Funds may be created with an unlock schedule, given as a number of lease periods. Refunds from
+//! such a fund, once retired from a slot it won, are then not spendable right away: they are
+//! locked under a lock specific to the fund, and an equal part of them unlocks with every lease
+//! period passed since the block the fund was retired at. Contributors apply the unlocked part
+//! with the `unlock` call. Refunds from funds which never won a slot are always unlocked
+//! immediately. The locks are independent of the fund, so they outlive its dissolution.
+//!
 //! @WARNING: For funds to be returned, it is imperative that this module's account is provided as
 //! the offboarding account for the slot. In the case that a parachain supplemented these funds in
 //! order to win a later auction, then it is the parachain's duty to ensure that the right amount of
 //! funds ultimately end up in module's fund sub-account.
 
 use frame_support::{
-	decl_module, decl_storage, decl_event, decl_error, storage::child, ensure,
+	decl_module, decl_storage, decl_event, decl_error, storage::child, ensure, weights::Weight,
 	traits::{
-		Currency, Get, OnUnbalanced, WithdrawReason, ExistenceRequirement::AllowDeath
+		Currency, Get, OnUnbalanced, WithdrawReason, WithdrawReasons, ExistenceRequirement::AllowDeath,
+		LockableCurrency, LockIdentifier,
 	},
 };
 use frame_system::ensure_signed;
 use sp_runtime::{ModuleId,
-	traits::{AccountIdConversion, Hash, Saturating, Zero, CheckedAdd}
+	traits::{AccountIdConversion, Hash, Saturating, Zero, CheckedAdd, One, UniqueSaturatedInto}
 };
 use crate::slots;
 use codec::{Encode, Decode};
@@ -161,6 +169,24 @@ pub struct FundInfo<AccountId, Balance, Hash, BlockNumber> {
 	/// The deployment data associated with this fund, if any. Once set it may not be reset. First
 	/// is the code hash, second is the code size, third is the initial head data.
 	deploy_data: Option<DeployData<Hash>>,
+	/// The number of lease periods over which refunds are unlocked linearly once the fund is
+	/// retired from a slot it won. If this is `None`, refunds are unlocked immediately.
+	unlock_periods: Option<BlockNumber>,
+	/// Whether the fund was retired from a slot it won, as opposed to having never won one.
+	retired: bool,
+}
+
+/// A refund from a fund retired from a slot it won, which unlocks per lease period.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct RefundLock<Balance, BlockNumber> {
+	/// The amount refunded.
+	amount: Balance,
+	/// The block from which on lease periods count towards the unlock, i.e. the retirement of the
+	/// fund.
+	start: BlockNumber,
+	/// The number of lease periods after which the refund is fully unlocked.
+	periods: BlockNumber,
 }
 
 decl_storage! {
@@ -179,6 +205,11 @@ decl_storage! {
 
 		/// The number of auctions that have entered into their ending period so far.
 		EndingsCount get(fn endings_count): slots::AuctionIndex;
+
+		/// The refunds which are still locked, by fund and contributor.
+		RefundLocks get(fn refund_locks):
+			double_map hasher(twox_64_concat) FundIndex, hasher(blake2_128_concat) T::AccountId
+			=> Option<RefundLock<BalanceOf<T>, T::BlockNumber>>;
 	}
 }
 
@@ -193,6 +224,8 @@ decl_event! {
 		Contributed(AccountId, FundIndex, Balance),
 		/// Withdrew full balance of a contributor. [who, fund_index, amount]
 		Withdrew(AccountId, FundIndex, Balance),
+		/// The lock on a refund was updated to the part still locked. [who, fund_index, locked]
+		Unlocked(AccountId, FundIndex, Balance),
 		/// Fund is placed into retirement. [fund_index]
 		Retiring(FundIndex),
 		/// Fund is dissolved. [fund_index]
@@ -247,6 +280,10 @@ decl_error! {
 		HasActiveParachain,
 		/// The retirement period has not ended.
 		InRetirementPeriod,
+		/// The unlock schedule of a fund must span at least one lease period.
+		NoUnlockPeriods,
+		/// There is no locked refund from this fund.
+		NoRefundLock,
 	}
 }
 
@@ -258,19 +295,28 @@ decl_module! {
 
 		fn deposit_event() = default;
 
+		fn on_runtime_upgrade() -> Weight {
+			migration::migrate_fund_info::<T>()
+		}
+
 		/// Create a new crowdfunding campaign for a parachain slot deposit for the current auction.
+		///
+		/// - `unlock_periods` is the number of lease periods over which refunds are unlocked once
+		///   the fund is retired from a slot it won, or `None` to unlock them immediately.
 		#[weight = 100_000_000]
 		fn create(origin,
 			#[compact] cap: BalanceOf<T>,
 			#[compact] first_slot: T::BlockNumber,
 			#[compact] last_slot: T::BlockNumber,
-			#[compact] end: T::BlockNumber
+			#[compact] end: T::BlockNumber,
+			unlock_periods: Option<T::BlockNumber>,
 		) {
 			let owner = ensure_signed(origin)?;
 
 			ensure!(first_slot < last_slot, Error::<T>::LastSlotBeforeFirstSlot);
 			ensure!(last_slot <= first_slot + 3.into(), Error::<T>::LastSlotTooFarInFuture);
 			ensure!(end > <frame_system::Module<T>>::block_number(), Error::<T>::CannotEndInPast);
+			ensure!(unlock_periods.map_or(true, |p| !p.is_zero()), Error::<T>::NoUnlockPeriods);
 
 			let deposit = T::SubmissionDeposit::get();
 			let transfer = WithdrawReason::Transfer.into();
@@ -295,6 +341,8 @@ decl_module! {
 				first_slot,
 				last_slot,
 				deploy_data: None,
+				unlock_periods,
+				retired: false,
 			});
 
 			Self::deposit_event(RawEvent::Created(index));
@@ -432,6 +480,7 @@ decl_module! {
 			// This fund just ended. Withdrawal period begins.
 			let now = <frame_system::Module<T>>::block_number();
 			fund.end = now;
+			fund.retired = true;
 
 			<Funds<T>>::insert(index, &fund);
 
@@ -439,6 +488,10 @@ decl_module! {
 		}
 
 		/// Withdraw full balance of a contributor to an unsuccessful or off-boarded fund.
+		///
+		/// If the fund was off-boarded and has an unlock schedule, the refund is locked and a part
+		/// of it unlocks with every lease period since the retirement, over the fund's
+		/// `unlock_periods`. See `unlock`.
 		#[weight = 0]
 		fn withdraw(origin, #[compact] index: FundIndex) {
 			let who = ensure_signed(origin)?;
@@ -459,6 +512,11 @@ decl_module! {
 			let imbalance = T::Currency::withdraw(fund_account, balance, transfer, AllowDeath)?;
 			let _ = T::Currency::resolve_into_existing(&who, imbalance);
 
+			if let Some(periods) = fund.unlock_periods.filter(|_| fund.retired) {
+				<RefundLocks<T>>::insert(index, &who, RefundLock { amount: balance, start: fund.end, periods });
+				Self::update_refund_lock(index, &who, now);
+			}
+
 			Self::contribution_kill(index, &who);
 			fund.raised = fund.raised.saturating_sub(balance);
 
@@ -467,6 +525,20 @@ decl_module! {
 			Self::deposit_event(RawEvent::Withdrew(who, index, balance));
 		}
 
+		/// Unlock the part of the caller's refund from a fund which became unlocked so far. The lock
+		/// is removed once the refund is fully unlocked.
+		// Reads and writes the refund lock, and the balance locks and account of the caller.
+		#[weight = 50_000_000 + T::DbWeight::get().reads_writes(3, 3)]
+		fn unlock(origin, #[compact] index: FundIndex) {
+			let who = ensure_signed(origin)?;
+
+			ensure!(<RefundLocks<T>>::contains_key(index, &who), Error::<T>::NoRefundLock);
+			let now = <frame_system::Module<T>>::block_number();
+			let locked = Self::update_refund_lock(index, &who, now);
+
+			Self::deposit_event(RawEvent::Unlocked(who, index, locked));
+		}
+
 		/// Remove a fund after either: it was unsuccessful and it timed out; or it was successful
 		/// but it has been retired from its parachain slot. This places any deposits that were not
 		/// withdrawn into the treasury.
@@ -562,6 +634,121 @@ impl<T: Trait> Module<T> {
 	pub fn crowdfund_kill(index: FundIndex) {
 		child::kill_storage(&Self::id_from_index(index));
 	}
+
+	/// The identifier of the lock on refunds from the fund.
+	pub fn lock_id(index: FundIndex) -> LockIdentifier {
+		let mut id = *b"cfnd\0\0\0\0";
+		id[4..].copy_from_slice(&index.to_le_bytes());
+		id
+	}
+
+	/// The part of a refund still locked at `now`: the refund unlocks in equal parts at the end of
+	/// each of its lease periods.
+	pub fn locked_at(lock: &RefundLock<BalanceOf<T>, T::BlockNumber>, now: T::BlockNumber) -> BalanceOf<T> {
+		let elapsed = now.saturating_sub(lock.start) / T::LeasePeriod::get().max(One::one());
+		if elapsed >= lock.periods {
+			return Zero::zero();
+		}
+
+		let to_balance = |n: T::BlockNumber| -> BalanceOf<T> {
+			let n: u128 = n.unique_saturated_into();
+			n.unique_saturated_into()
+		};
+		lock.amount.saturating_mul(to_balance(lock.periods - elapsed)) / to_balance(lock.periods)
+	}
+
+	/// Set the lock on the refund of `who` from the fund to the part still locked at `now`, which
+	/// is returned. The lock is removed once the refund is fully unlocked.
+	fn update_refund_lock(index: FundIndex, who: &T::AccountId, now: T::BlockNumber) -> BalanceOf<T> {
+		let lock = match Self::refund_locks(index, who) {
+			Some(lock) => lock,
+			None => return Zero::zero(),
+		};
+
+		let locked = Self::locked_at(&lock, now);
+		if locked.is_zero() {
+			T::Currency::remove_lock(Self::lock_id(index), who);
+			<RefundLocks<T>>::remove(index, who);
+		} else {
+			let reasons = WithdrawReasons::except(WithdrawReason::TransactionPayment);
+			T::Currency::set_lock(Self::lock_id(index), who, locked, reasons);
+		}
+
+		locked
+	}
+}
+
+mod migration {
+	use super::*;
+	use frame_support::storage::{unhashed, StorageMap};
+
+	/// The layout of [`FundInfo`] before refunds of retired funds were unlocked over lease periods.
+	#[derive(Encode, Decode)]
+	pub(super) struct OldFundInfo<AccountId, Balance, Hash, BlockNumber> {
+		pub parachain: Option<ParaId>,
+		pub owner: AccountId,
+		pub deposit: Balance,
+		pub raised: Balance,
+		pub end: BlockNumber,
+		pub cap: Balance,
+		pub last_contribution: LastContribution<BlockNumber>,
+		pub first_slot: BlockNumber,
+		pub last_slot: BlockNumber,
+		pub deploy_data: Option<DeployData<Hash>>,
+	}
+
+	impl<AccountId, Balance, Hash, BlockNumber> OldFundInfo<AccountId, Balance, Hash, BlockNumber> {
+		/// Convert to the current layout. Funds created before refunds could be locked unlock them
+		/// immediately, and none of them was retired from a slot under the new rules.
+		fn migrate(self) -> FundInfo<AccountId, Balance, Hash, BlockNumber> {
+			FundInfo {
+				parachain: self.parachain,
+				owner: self.owner,
+				deposit: self.deposit,
+				raised: self.raised,
+				end: self.end,
+				cap: self.cap,
+				last_contribution: self.last_contribution,
+				first_slot: self.first_slot,
+				last_slot: self.last_slot,
+				deploy_data: self.deploy_data,
+				unlock_periods: None,
+				retired: false,
+			}
+		}
+	}
+
+	/// Translate the funds which are still in the layout before `unlock_periods` and `retired`.
+	///
+	/// Both fields are last in [`FundInfo`], so funds in the old layout never decode in the current
+	/// one, and those in the current one are left untouched. This makes the migration a no-op once
+	/// done.
+	pub(super) fn migrate_fund_info<T: Trait>() -> Weight {
+		let count = FundCount::get();
+		let mut weight = T::DbWeight::get().reads(1);
+
+		for index in 0..count {
+			let key = <Funds<T>>::hashed_key_for(&index);
+			weight += T::DbWeight::get().reads(1);
+
+			let raw = match unhashed::get_raw(&key) {
+				Some(raw) => raw,
+				None => continue,
+			};
+			if FundInfo::<T::AccountId, BalanceOf<T>, T::Hash, T::BlockNumber>::decode(&mut &raw[..]).is_ok() {
+				continue;
+			}
+			let old = OldFundInfo::<T::AccountId, BalanceOf<T>, T::Hash, T::BlockNumber>::decode(
+				&mut &raw[..],
+			);
+			if let Ok(old) = old {
+				<Funds<T>>::insert(index, old.migrate());
+				weight += T::DbWeight::get().writes(1);
+			}
+		}
+
+		weight
+	}
 }
 
 #[cfg(test)]
@@ -571,9 +758,10 @@ mod tests {
 	use std::{collections::HashMap, cell::RefCell};
 	use frame_support::{
 		impl_outer_origin, assert_ok, assert_noop, parameter_types,
-		traits::{OnInitialize, OnFinalize},
+		traits::{OnInitialize, OnFinalize, OnRuntimeUpgrade},
 	};
 	use frame_support::traits::{Contains, ContainsLengthBound};
+	use frame_support::storage::StorageMap;
 	use sp_core::H256;
 	use primitives::v1::{Id as ParaId, ValidationCode};
 	// The testing primitives are very useful for avoiding having to work with signatures
@@ -730,6 +918,15 @@ mod tests {
 				Ok(())
 			})
 		}
+
+		fn make_parathread(id: ParaId) -> DispatchResult {
+			PARACHAINS.with(|p| {
+				if p.borrow_mut().remove(&id.into()).is_none() {
+					panic!("ID isn't a parachain")
+				}
+				Ok(())
+			})
+		}
 	}
 
 	parameter_types!{
@@ -811,7 +1008,7 @@ mod tests {
 	fn create_works() {
 		new_test_ext().execute_with(|| {
 			// Now try to create a crowdfund campaign
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Crowdfund::fund_count(), 1);
 			// This is what the initial `fund_info` should look like
 			let fund_info = FundInfo {
@@ -826,6 +1023,8 @@ mod tests {
 				first_slot: 1,
 				last_slot: 4,
 				deploy_data: None,
+				unlock_periods: None,
+				retired: false,
 			};
 			assert_eq!(Crowdfund::funds(0), Some(fund_info));
 			// User has deposit removed from their free balance
@@ -843,17 +1042,22 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Cannot create a crowdfund with bad slots
 			assert_noop!(
-				Crowdfund::create(Origin::signed(1), 1000, 4, 1, 9),
+				Crowdfund::create(Origin::signed(1), 1000, 4, 1, 9, None),
 				Error::<Test>::LastSlotBeforeFirstSlot
 			);
 			assert_noop!(
-				Crowdfund::create(Origin::signed(1), 1000, 1, 5, 9),
+				Crowdfund::create(Origin::signed(1), 1000, 1, 5, 9, None),
 				Error::<Test>::LastSlotTooFarInFuture
 			);
+			// Cannot create a crowdfund with an empty unlock schedule
+			assert_noop!(
+				Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, Some(0)),
+				Error::<Test>::NoUnlockPeriods
+			);
 
 			// Cannot create a crowdfund without some deposit funds
 			assert_noop!(
-				Crowdfund::create(Origin::signed(1337), 1000, 1, 3, 9),
+				Crowdfund::create(Origin::signed(1337), 1000, 1, 3, 9, None),
 				BalancesError::<Test, _>::InsufficientBalance
 			);
 		});
@@ -863,7 +1067,7 @@ mod tests {
 	fn contribute_works() {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Balances::free_balance(1), 999);
 			assert_eq!(Balances::free_balance(Crowdfund::fund_account_id(0)), 1);
 
@@ -898,7 +1102,7 @@ mod tests {
 			assert_noop!(Crowdfund::contribute(Origin::signed(1), 0, 9), Error::<Test>::ContributionTooSmall);
 
 			// Set up a crowdfund
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_ok!(Crowdfund::contribute(Origin::signed(1), 0, 101));
 
 			// Cannot contribute past the limit
@@ -916,7 +1120,7 @@ mod tests {
 	fn fix_deploy_data_works() {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Balances::free_balance(1), 999);
 
 			// Add deploy data
@@ -946,7 +1150,7 @@ mod tests {
 	fn fix_deploy_data_handles_basic_errors() {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Balances::free_balance(1), 999);
 
 			// Cannot set deploy data by non-owner
@@ -994,7 +1198,7 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Balances::free_balance(1), 999);
 
 			// Add deploy data
@@ -1030,7 +1234,7 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Balances::free_balance(1), 999);
 
 			// Fund crowdfund
@@ -1068,7 +1272,7 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Balances::free_balance(1), 999);
 
 			// Add deploy data
@@ -1111,7 +1315,7 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			assert_eq!(Balances::free_balance(1), 999);
 
 			// Add deploy data
@@ -1156,7 +1360,7 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			// Transfer fee is taken here
 			assert_ok!(Crowdfund::contribute(Origin::signed(1), 0, 100));
 			assert_ok!(Crowdfund::contribute(Origin::signed(2), 0, 200));
@@ -1182,7 +1386,7 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			// Transfer fee is taken here
 			assert_ok!(Crowdfund::contribute(Origin::signed(1), 0, 49));
 			assert_eq!(Balances::free_balance(1), 950);
@@ -1201,12 +1405,151 @@ mod tests {
 		});
 	}
 
+	// Sets up fund 0 with the given unlock schedule, has it win a slot with contributions from
+	// 2 and 3 and retires it at block 50.
+	fn retired_fund(unlock_periods: Option<u64>) {
+		assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
+		assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, unlock_periods));
+		assert_ok!(Crowdfund::fix_deploy_data(
+			Origin::signed(1),
+			0,
+			<Test as frame_system::Trait>::Hash::default(),
+			0,
+			vec![0].into(),
+		));
+		assert_ok!(Crowdfund::contribute(Origin::signed(2), 0, 600));
+		assert_ok!(Crowdfund::contribute(Origin::signed(3), 0, 400));
+
+		run_to_block(10);
+		assert_ok!(Crowdfund::onboard(Origin::signed(1), 0, 0.into()));
+
+		run_to_block(50);
+		assert_ok!(Crowdfund::begin_retirement(Origin::signed(1), 0));
+	}
+
+	// The amount locked on the account by the lock of any fund.
+	fn refund_locked(who: u64) -> u64 {
+		Balances::locks(&who).iter()
+			.filter(|lock| lock.id[..4] == b"cfnd"[..])
+			.map(|lock| lock.amount)
+			.sum()
+	}
+
+	#[test]
+	fn withdraw_from_retired_fund_unlocks_per_lease_period() {
+		new_test_ext().execute_with(|| {
+			// Refunds unlock over 2 lease periods, i.e. 20 blocks, from the retirement at 50.
+			retired_fund(Some(2));
+			assert!(Crowdfund::funds(0).unwrap().retired);
+
+			// 2 withdraws right away; the whole refund is locked.
+			assert_ok!(Crowdfund::withdraw(Origin::signed(2), 0));
+			assert_eq!(Balances::free_balance(2), 2000);
+			assert_eq!(refund_locked(2), 600);
+			assert_noop!(
+				Balances::transfer(Origin::signed(2), 5, 1401),
+				BalancesError::<Test, _>::LiquidityRestrictions
+			);
+			assert_ok!(Balances::transfer(Origin::signed(2), 5, 1400));
+
+			// Nothing unlocks within a lease period.
+			run_to_block(59);
+			assert_ok!(Crowdfund::unlock(Origin::signed(2), 0));
+			assert_eq!(refund_locked(2), 600);
+
+			// At the lease boundary, half of the refund is unlocked.
+			run_to_block(60);
+			assert_ok!(Crowdfund::unlock(Origin::signed(2), 0));
+			assert_eq!(refund_locked(2), 300);
+			assert_ok!(Balances::transfer(Origin::signed(2), 5, 300));
+			assert_noop!(
+				Balances::transfer(Origin::signed(2), 5, 1),
+				BalancesError::<Test, _>::LiquidityRestrictions
+			);
+
+			// 3 withdraws in the second lease period; the periods count from the retirement, so
+			// half of the refund is unlocked already.
+			run_to_block(65);
+			assert_ok!(Crowdfund::withdraw(Origin::signed(3), 0));
+			assert_eq!(Balances::free_balance(3), 3000);
+			assert_eq!(refund_locked(3), 200);
+			assert_noop!(
+				Balances::transfer(Origin::signed(3), 5, 2801),
+				BalancesError::<Test, _>::LiquidityRestrictions
+			);
+
+			// Everything is unlocked at the end of the second lease period.
+			run_to_block(70);
+			assert_ok!(Crowdfund::unlock(Origin::signed(2), 0));
+			assert_ok!(Crowdfund::unlock(Origin::signed(3), 0));
+			assert_eq!(refund_locked(2), 0);
+			assert_eq!(refund_locked(3), 0);
+			assert_eq!(Crowdfund::refund_locks(0, 2), None);
+			assert_eq!(Crowdfund::refund_locks(0, 3), None);
+			assert_ok!(Balances::transfer(Origin::signed(2), 5, 300));
+			assert_ok!(Balances::transfer(Origin::signed(3), 5, 3000));
+
+			// There is nothing left to unlock.
+			assert_noop!(Crowdfund::unlock(Origin::signed(2), 0), Error::<Test>::NoRefundLock);
+		});
+	}
+
+	#[test]
+	fn withdraw_from_retired_fund_without_unlock_schedule_is_unlocked() {
+		new_test_ext().execute_with(|| {
+			retired_fund(None);
+
+			assert_ok!(Crowdfund::withdraw(Origin::signed(2), 0));
+			assert_eq!(refund_locked(2), 0);
+			assert_eq!(Crowdfund::refund_locks(0, 2), None);
+			assert_ok!(Balances::transfer(Origin::signed(2), 5, 2000));
+		});
+	}
+
+	#[test]
+	fn withdraw_from_unsuccessful_fund_is_unlocked() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, Some(2)));
+			assert_ok!(Crowdfund::contribute(Origin::signed(2), 0, 200));
+
+			run_to_block(10);
+
+			// The fund never won a slot, so the unlock schedule doesn't apply.
+			assert_ok!(Crowdfund::withdraw(Origin::signed(2), 0));
+			assert_eq!(refund_locked(2), 0);
+			assert_ok!(Balances::transfer(Origin::signed(2), 5, 2000));
+		});
+	}
+
+	#[test]
+	fn refund_lock_is_independent_of_other_locks_and_the_fund() {
+		new_test_ext().execute_with(|| {
+			retired_fund(Some(2));
+
+			// 2 has a lock of its own, e.g. from vesting; it doesn't hinder the withdrawal.
+			Balances::set_lock(*b"vesting ", &2, 1000, WithdrawReasons::all());
+			assert_ok!(Crowdfund::withdraw(Origin::signed(2), 0));
+			assert_eq!(refund_locked(2), 600);
+
+			// The fund is dissolved after the retirement period; the refund lock stays.
+			run_to_block(55);
+			assert_ok!(Crowdfund::dissolve(Origin::signed(1), 0));
+			assert_eq!(Crowdfund::funds(0), None);
+			assert_eq!(refund_locked(2), 600);
+
+			run_to_block(70);
+			assert_ok!(Crowdfund::unlock(Origin::signed(2), 0));
+			assert_eq!(refund_locked(2), 0);
+			assert_eq!(Balances::locks(&2).len(), 1);
+		});
+	}
+
 	#[test]
 	fn dissolve_works() {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			// Transfer fee is taken here
 			assert_ok!(Crowdfund::contribute(Origin::signed(1), 0, 100));
 			assert_ok!(Crowdfund::contribute(Origin::signed(2), 0, 200));
@@ -1243,7 +1586,7 @@ mod tests {
 		new_test_ext().execute_with(|| {
 			// Set up a crowdfund
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			// Transfer fee is taken here
 			assert_ok!(Crowdfund::contribute(Origin::signed(1), 0, 100));
 			assert_ok!(Crowdfund::contribute(Origin::signed(2), 0, 200));
@@ -1275,7 +1618,7 @@ mod tests {
 	fn fund_before_auction_works() {
 		new_test_ext().execute_with(|| {
 			// Create a crowdfund before an auction is created
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
 			// Users can already contribute
 			assert_ok!(Crowdfund::contribute(Origin::signed(1), 0, 49));
 			// Fund added to NewRaise
@@ -1317,8 +1660,8 @@ mod tests {
 			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
 			// Create two competing crowdfunds, with end dates across multiple auctions
 			// Each crowdfund is competing for the same slots, so only one can win
-			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 30));
-			assert_ok!(Crowdfund::create(Origin::signed(2), 1000, 1, 4, 30));
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 30, None));
+			assert_ok!(Crowdfund::create(Origin::signed(2), 1000, 1, 4, 30, None));
 
 			// Contribute to all, but more money to 0, less to 1
 			assert_ok!(Crowdfund::contribute(Origin::signed(1), 0, 300));
@@ -1368,4 +1711,40 @@ mod tests {
 			assert_eq!(Slots::managed_ids(), vec![0.into(), 1.into()]);
 		});
 	}
+
+	#[test]
+	fn funds_are_migrated_to_unlock_periods() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Crowdfund::create(Origin::signed(1), 1000, 1, 4, 9, None));
+			assert_ok!(Crowdfund::create(Origin::signed(2), 1000, 1, 4, 9, Some(2)));
+			let current = Crowdfund::funds(1).unwrap();
+
+			// Put fund 0 back into the layout without `unlock_periods` and `retired`.
+			let old = migration::OldFundInfo {
+				parachain: None,
+				owner: 1u64,
+				deposit: 1u64,
+				raised: 0u64,
+				end: 9u64,
+				cap: 1000u64,
+				last_contribution: LastContribution::Never,
+				first_slot: 1u64,
+				last_slot: 4u64,
+				deploy_data: None::<DeployData<H256>>,
+			};
+			let key = <Funds<Test>>::hashed_key_for(&0);
+			frame_support::storage::unhashed::put_raw(&key, &old.encode());
+			assert!(Crowdfund::funds(0).is_none());
+
+			Crowdfund::on_runtime_upgrade();
+
+			let migrated = Crowdfund::funds(0).unwrap();
+			assert_eq!(migrated.owner, 1);
+			assert_eq!(migrated.cap, 1000);
+			assert_eq!(migrated.unlock_periods, None);
+			assert!(!migrated.retired);
+			// Funds already in the current layout are left untouched.
+			assert_eq!(Crowdfund::funds(1), Some(current));
+		});
+	}
 }
@@ -0,0 +1,75 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A module reporting how [`TreasuryAndBurn`](crate::impls::TreasuryAndBurn) split imbalances.
+//!
+//! The amounts split during a block are added up and reported with a single event when the block
+//! is finalized, if any of them was burned. It has no calls.
+
+use frame_support::{decl_event, decl_module, decl_storage, traits::Get, weights::Weight};
+use sp_runtime::traits::{Saturating, Zero};
+
+type BalanceOf<T> = <T as pallet_balances::Trait>::Balance;
+
+/// The module's configuration trait.
+pub trait Trait: pallet_balances::Trait {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ImbalanceSplit {
+		/// The amounts split so far in this block. [to_treasury, burned]
+		BlockSplit get(fn block_split): (BalanceOf<T>, BalanceOf<T>);
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where Balance = BalanceOf<T> {
+		/// Imbalances were split between the treasury and burning during the block.
+		/// [to_treasury, burned]
+		ImbalanceSplit(Balance, Balance),
+	}
+}
+
+decl_module! {
+	/// The imbalance split module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
+		fn deposit_event() = default;
+
+		fn on_initialize() -> Weight {
+			// Taking the split of the block and depositing the event in `on_finalize`.
+			T::DbWeight::get().reads_writes(1, 2)
+		}
+
+		fn on_finalize() {
+			let (to_treasury, burned) = <BlockSplit<T>>::take();
+			if !burned.is_zero() {
+				Self::deposit_event(RawEvent::ImbalanceSplit(to_treasury, burned));
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Note that an imbalance was split, to be reported at the end of the block.
+	pub fn note_split(to_treasury: BalanceOf<T>, burned: BalanceOf<T>) {
+		<BlockSplit<T>>::mutate(|(t, b)| {
+			*t = t.saturating_add(to_treasury);
+			*b = b.saturating_add(burned);
+		});
+	}
+}
@@ -16,9 +16,14 @@
 
 //! Auxillary struct/enums for polkadot runtime.
 
-use sp_runtime::traits::Convert;
-use frame_support::traits::{OnUnbalanced, Imbalance, Currency};
-use crate::NegativeImbalance;
+use sp_runtime::{Perbill, traits::Convert};
+use frame_support::{
+	traits::{OnUnbalanced, Imbalance, Currency, Get},
+	weights::{WeightToFeePolynomial, WeightToFeeCoefficient, WeightToFeeCoefficients},
+};
+use primitives::v1::Balance;
+use smallvec::smallvec;
+use crate::{imbalance_split, NegativeImbalance, ExtrinsicBaseWeight};
 
 /// Logic for the author to get a portion of fees.
 pub struct ToAuthor<R>(sp_std::marker::PhantomData<R>);
@@ -42,6 +47,31 @@ where
 	}
 }
 
+/// Logic for a portion of an imbalance to go to the treasury and the rest to be burned.
+///
+/// `TreasuryShare` is the portion going to `Treasury`, rounded down. The amounts are added up per
+/// block and reported by the `imbalance_split` module.
+pub struct TreasuryAndBurn<R, TreasuryShare, Treasury>(
+	sp_std::marker::PhantomData<(R, TreasuryShare, Treasury)>
+);
+
+impl<R, TreasuryShare, Treasury> OnUnbalanced<NegativeImbalance<R>>
+	for TreasuryAndBurn<R, TreasuryShare, Treasury>
+where
+	R: imbalance_split::Trait,
+	TreasuryShare: Get<Perbill>,
+	Treasury: OnUnbalanced<NegativeImbalance<R>>,
+{
+	fn on_nonzero_unbalanced(amount: NegativeImbalance<R>) {
+		let to_treasury = TreasuryShare::get() * amount.peek();
+		let (to_treasury, to_burn) = amount.split(to_treasury);
+		<imbalance_split::Module<R>>::note_split(to_treasury.peek(), to_burn.peek());
+		Treasury::on_unbalanced(to_treasury);
+		// dropping the imbalance burns it.
+		drop(to_burn);
+	}
+}
+
 /// Converter for currencies to votes.
 pub struct CurrencyToVoteHandler<R>(sp_std::marker::PhantomData<R>);
 
@@ -71,3 +101,195 @@ where
 {
 	fn convert(x: u128) -> u128 { x * Self::factor() }
 }
+
+/// Handles converting a weight scalar to a fee value, based on the scale and granularity of the
+/// node's balance type.
+///
+/// The extrinsic base weight (smallest non-zero weight) is mapped to 1/10 of `Cent`, growing
+/// linearly from there. This is shared by all runtimes, each one providing its own `Cent`.
+pub struct WeightToFee<Cent>(sp_std::marker::PhantomData<Cent>);
+
+impl<Cent: Get<Balance>> WeightToFeePolynomial for WeightToFee<Cent> {
+	type Balance = Balance;
+	fn polynomial() -> WeightToFeeCoefficients<Self::Balance> {
+		let p = Cent::get();
+		let q = 10 * Balance::from(ExtrinsicBaseWeight::get());
+		smallvec![WeightToFeeCoefficient {
+			degree: 1,
+			negative: false,
+			coeff_frac: Perbill::from_rational_approximation(p % q, q),
+			coeff_integer: p / q,
+		}]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::{
+		impl_outer_origin, impl_outer_event, parameter_types,
+		traits::{ExistenceRequirement, WithdrawReason, OnFinalize},
+	};
+	use sp_core::H256;
+	use sp_runtime::{testing::Header, traits::{BlakeTwo256, IdentityLookup}};
+	use crate::{
+		AvailableBlockRatio, BlockHashCount, MaximumBlockLength, MaximumBlockWeight,
+	};
+
+	const CENTS: Balance = 100_000_000;
+
+	parameter_types! {
+		pub const Cent: Balance = CENTS;
+	}
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for Test {
+			frame_system<T>,
+			imbalance_split<T>,
+		}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Call = ();
+		type Index = u64;
+		type BlockNumber = primitives::v1::BlockNumber;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<u64>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = pallet_balances::AccountData<u64>;
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+	}
+
+	parameter_types! {
+		pub const ExistentialDeposit: u64 = 1;
+		pub const TreasuryShare: Perbill = Perbill::from_percent(30);
+	}
+
+	impl pallet_balances::Trait for Test {
+		type Balance = u64;
+		type Event = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type AccountStore = System;
+		type WeightInfo = ();
+	}
+
+	impl imbalance_split::Trait for Test {
+		type Event = TestEvent;
+	}
+
+	type System = frame_system::Module<Test>;
+	type Balances = pallet_balances::Module<Test>;
+	type ImbalanceSplit = imbalance_split::Module<Test>;
+
+	const TREASURY: u64 = 0;
+
+	struct ToTreasuryAccount;
+
+	impl OnUnbalanced<NegativeImbalance<Test>> for ToTreasuryAccount {
+		fn on_nonzero_unbalanced(amount: NegativeImbalance<Test>) {
+			Balances::resolve_creating(&TREASURY, amount);
+		}
+	}
+
+	fn split_events() -> Vec<TestEvent> {
+		System::events().into_iter()
+			.map(|record| record.event)
+			.filter(|event| matches!(event, TestEvent::imbalance_split(_)))
+			.collect()
+	}
+
+	#[test]
+	fn treasury_and_burn_splits_imbalance() {
+		let t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		sp_io::TestExternalities::new(t).execute_with(|| {
+			System::set_block_number(1);
+			Balances::make_free_balance_be(&1, 1000);
+
+			for amount in &[101, 50] {
+				let fee = Balances::withdraw(
+					&1,
+					*amount,
+					WithdrawReason::TransactionPayment.into(),
+					ExistenceRequirement::KeepAlive,
+				).unwrap();
+				TreasuryAndBurn::<Test, TreasuryShare, ToTreasuryAccount>::on_unbalanced(fee);
+			}
+
+			// 30% of each fee, rounded down, goes to the treasury and the rest is burned.
+			assert_eq!(Balances::free_balance(&1), 849);
+			assert_eq!(Balances::free_balance(&TREASURY), 45);
+			assert_eq!(Balances::total_issuance(), 894);
+
+			// Both splits are reported with a single event at the end of the block.
+			assert!(split_events().is_empty());
+			ImbalanceSplit::on_finalize(1);
+			assert_eq!(
+				split_events(),
+				vec![TestEvent::imbalance_split(imbalance_split::RawEvent::ImbalanceSplit(45, 106))],
+			);
+			assert_eq!(ImbalanceSplit::block_split(), (0, 0));
+		});
+	}
+
+	#[test]
+	fn nothing_burned_is_not_reported() {
+		let t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		sp_io::TestExternalities::new(t).execute_with(|| {
+			System::set_block_number(1);
+
+			ImbalanceSplit::note_split(5, 0);
+			ImbalanceSplit::on_finalize(1);
+
+			assert!(split_events().is_empty());
+			assert_eq!(ImbalanceSplit::block_split(), (0, 0));
+		});
+	}
+
+	#[test]
+	fn empty_weight_is_free() {
+		assert_eq!(WeightToFee::<Cent>::calc(&0), 0);
+	}
+
+	#[test]
+	fn extrinsic_base_fee_is_a_tenth_of_a_cent() {
+		let x = WeightToFee::<Cent>::calc(&ExtrinsicBaseWeight::get());
+		let y = CENTS / 10;
+		assert!(x.max(y) - x.min(y) <= 1);
+	}
+
+	#[test]
+	fn fee_is_linear_up_to_full_block() {
+		let base = ExtrinsicBaseWeight::get();
+		let full = MaximumBlockWeight::get();
+		let x = WeightToFee::<Cent>::calc(&full);
+		let y = Balance::from(full / base) * WeightToFee::<Cent>::calc(&base);
+		// a full block costs the same as filling it with base extrinsics, modulo rounding.
+		assert!(x >= y);
+		assert!(x - y < WeightToFee::<Cent>::calc(&base));
+	}
+}
@@ -24,7 +24,10 @@ pub mod slots;
 pub mod crowdfund;
 pub mod purchase;
 pub mod impls;
+pub mod imbalance_split;
 pub mod paras_sudo_wrapper;
+pub mod assigned_slots;
+pub mod paras_registrar;
 
 pub mod dummy;
 
@@ -46,7 +49,7 @@ pub use pallet_timestamp::Call as TimestampCall;
 pub use pallet_balances::Call as BalancesCall;
 
 /// Implementations of some helper traits passed into runtime modules as associated types.
-pub use impls::{CurrencyToVoteHandler, ToAuthor};
+pub use impls::{CurrencyToVoteHandler, ToAuthor, TreasuryAndBurn, WeightToFee};
 
 pub type NegativeImbalance<T> = <pallet_balances::Module<T> as Currency<<T as frame_system::Trait>::AccountId>>::NegativeImbalance;
 
@@ -194,4 +197,29 @@ mod multiplier_tests {
 			assert!(next > minimum_multiplier, "{:?} !>= {:?}", next, minimum_multiplier);
 		})
 	}
+
+	#[test]
+	fn multiplier_decreases_on_empty_block() {
+		run_with_system_weight(0, || {
+			let next = SlowAdjustingFeeUpdate::<Runtime>::convert(Multiplier::one());
+			assert!(next < Multiplier::one(), "{:?} !< 1", next);
+		})
+	}
+
+	#[test]
+	fn multiplier_never_drops_below_minimum() {
+		let minimum_multiplier = MinimumMultiplier::get();
+		run_with_system_weight(0, || {
+			let next = SlowAdjustingFeeUpdate::<Runtime>::convert(minimum_multiplier);
+			assert_eq!(next, minimum_multiplier);
+		})
+	}
+
+	#[test]
+	fn multiplier_increases_on_full_block() {
+		run_with_system_weight(AvailableBlockRatio::get() * MaximumBlockWeight::get(), || {
+			let next = SlowAdjustingFeeUpdate::<Runtime>::convert(Multiplier::one());
+			assert!(next > Multiplier::one(), "{:?} !> 1", next);
+		})
+	}
 }
@@ -0,0 +1,413 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The parachain registrar used by the slots module, backed by the `paras` module.
+//!
+//! Registration, deregistration and downgrades are all scheduled with `paras` and take effect at
+//! the start of the next session.
+
+use frame_support::{
+	decl_error, decl_module, decl_storage, ensure,
+	dispatch::DispatchResult,
+};
+use primitives::v1::{Id as ParaId, ValidationCode, HeadData, LOWEST_USER_ID};
+use runtime_parachains::{
+	configuration,
+	paras::{self, ParaGenesisArgs},
+};
+use crate::slots::Registrar;
+
+/// The module's configuration trait.
+pub trait Trait: paras::Trait { }
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ParasRegistrar {
+		/// The next para ID to hand out with `new_id`.
+		NextFreeId: ParaId = LOWEST_USER_ID;
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The para is already registered.
+		ParaAlreadyExists,
+		/// The para is not registered.
+		ParaDoesntExist,
+		/// The para is not a parachain.
+		NotParachain,
+	}
+}
+
+decl_module! {
+	/// The paras registrar module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
+		type Error = Error<T>;
+	}
+}
+
+impl<T: Trait> Registrar<T::AccountId> for Module<T> {
+	fn new_id() -> ParaId {
+		NextFreeId::mutate(|id| {
+			let new = *id;
+			*id = ParaId::from(u32::from(new) + 1);
+			new
+		})
+	}
+
+	fn head_data_size_allowed(head_data_size: u32) -> bool {
+		head_data_size <= <configuration::Module<T>>::config().max_head_data_size
+	}
+
+	fn code_size_allowed(code_size: u32) -> bool {
+		code_size <= <configuration::Module<T>>::config().max_code_size
+	}
+
+	fn register_para(
+		id: ParaId,
+		parachain: bool,
+		code: ValidationCode,
+		initial_head_data: HeadData,
+	) -> DispatchResult {
+		ensure!(
+			!<paras::Module<T>>::is_parachain(id) && !<paras::Module<T>>::is_parathread(id),
+			Error::<T>::ParaAlreadyExists,
+		);
+
+		<paras::Module<T>>::schedule_para_initialize(id, ParaGenesisArgs {
+			genesis_head: initial_head_data,
+			validation_code: code,
+			parachain,
+		});
+
+		Ok(())
+	}
+
+	fn deregister_para(id: ParaId) -> DispatchResult {
+		ensure!(
+			<paras::Module<T>>::is_parachain(id) || <paras::Module<T>>::is_parathread(id),
+			Error::<T>::ParaDoesntExist,
+		);
+
+		<paras::Module<T>>::schedule_para_cleanup(id);
+
+		Ok(())
+	}
+
+	fn make_parathread(id: ParaId) -> DispatchResult {
+		ensure!(<paras::Module<T>>::is_parachain(id), Error::<T>::NotParachain);
+
+		<paras::Module<T>>::schedule_parachain_downgrade(id);
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use sp_runtime::{
+		Perbill,
+		testing::UintAuthorityId,
+		traits::{BlakeTwo256, Hash, IdentityLookup, ConvertInto},
+	};
+	use frame_support::{
+		impl_outer_origin, impl_outer_dispatch, parameter_types, assert_ok, assert_noop,
+		traits::{OnInitialize, OnFinalize},
+		weights::Weight,
+	};
+	use pallet_session::OneSessionHandler;
+	use primitives::v1::{BlockNumber, Header, ValidatorId};
+	use runtime_parachains::{
+		configuration::HostConfiguration,
+		initializer, inclusion, router, scheduler,
+		origin as parachains_origin,
+	};
+	use crate::slots;
+
+	impl_outer_origin! {
+		pub enum Origin for Test {
+			parachains_origin
+		}
+	}
+
+	impl_outer_dispatch! {
+		pub enum Call for Test where origin: Origin {
+			pallet_balances::Balances,
+		}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u32 = 250;
+		pub const MaximumBlockWeight: Weight = 4 * 1024 * 1024;
+		pub const MaximumBlockLength: u32 = 4 * 1024 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	}
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Index = u64;
+		type BlockNumber = BlockNumber;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<u64>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = pallet_balances::AccountData<u128>;
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+	}
+
+	parameter_types! {
+		pub const ExistentialDeposit: u128 = 1;
+		pub const HrmpSenderDeposit: u128 = 0;
+		pub const HrmpRecipientDeposit: u128 = 0;
+	}
+
+	impl pallet_balances::Trait for Test {
+		type Balance = u128;
+		type Event = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type AccountStore = System;
+		type WeightInfo = ();
+	}
+
+	parameter_types! {
+		pub const Period: BlockNumber = 1;
+		pub const Offset: BlockNumber = 0;
+		pub const DisabledValidatorsThreshold: Perbill = Perbill::from_percent(17);
+	}
+
+	impl pallet_session::Trait for Test {
+		type Event = ();
+		type ValidatorId = u64;
+		type ValidatorIdOf = ConvertInto;
+		type ShouldEndSession = pallet_session::PeriodicSessions<Period, Offset>;
+		type NextSessionRotation = pallet_session::PeriodicSessions<Period, Offset>;
+		type SessionManager = ();
+		type SessionHandler = pallet_session::TestSessionHandler;
+		type Keys = UintAuthorityId;
+		type DisabledValidatorsThreshold = DisabledValidatorsThreshold;
+		type WeightInfo = ();
+	}
+
+	impl configuration::Trait for Test { }
+
+	impl paras::Trait for Test { }
+
+	impl scheduler::Trait for Test { }
+
+	impl parachains_origin::Trait for Test { }
+
+	impl router::Trait for Test {
+		type Event = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Currency = Balances;
+		type HrmpSenderDeposit = HrmpSenderDeposit;
+		type HrmpRecipientDeposit = HrmpRecipientDeposit;
+	}
+
+	impl inclusion::Trait for Test {
+		type Event = ();
+	}
+
+	impl initializer::Trait for Test {
+		type Randomness = RandomnessCollectiveFlip;
+	}
+
+	parameter_types! {
+		pub const LeasePeriod: BlockNumber = 10;
+		pub const EndingPeriod: BlockNumber = 3;
+	}
+
+	impl slots::Trait for Test {
+		type Event = ();
+		type Currency = Balances;
+		type Parachains = ParasRegistrar;
+		type LeasePeriod = LeasePeriod;
+		type EndingPeriod = EndingPeriod;
+		type Randomness = RandomnessCollectiveFlip;
+	}
+
+	impl Trait for Test { }
+
+	type System = frame_system::Module<Test>;
+	type Balances = pallet_balances::Module<Test>;
+	type Initializer = initializer::Module<Test>;
+	type Paras = paras::Module<Test>;
+	type Slots = slots::Module<Test>;
+	type ParasRegistrar = Module<Test>;
+	type RandomnessCollectiveFlip = pallet_randomness_collective_flip::Module<Test>;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		pallet_balances::GenesisConfig::<Test> {
+			balances: vec![(1, 10), (2, 20)],
+		}.assimilate_storage(&mut t).unwrap();
+		configuration::GenesisConfig::<Test> {
+			config: HostConfiguration {
+				max_code_size: 100,
+				max_head_data_size: 10,
+				..Default::default()
+			},
+		}.assimilate_storage(&mut t).unwrap();
+		t.into()
+	}
+
+	fn run_to_block(n: BlockNumber) {
+		while System::block_number() < n {
+			Slots::on_finalize(System::block_number());
+			Initializer::on_finalize(System::block_number());
+			System::on_finalize(System::block_number());
+			System::set_block_number(System::block_number() + 1);
+			System::on_initialize(System::block_number());
+			Initializer::on_initialize(System::block_number());
+			Slots::on_initialize(System::block_number());
+		}
+	}
+
+	/// Start a new session, which is applied by the paras modules at the next block.
+	fn new_session() {
+		let validators = Vec::<(&u64, ValidatorId)>::new();
+		<Initializer as OneSessionHandler<u64>>::on_new_session(
+			true,
+			validators.clone().into_iter(),
+			validators.into_iter(),
+		);
+	}
+
+	fn args() -> (ValidationCode, HeadData) {
+		(vec![1].into(), vec![1].into())
+	}
+
+	#[test]
+	fn new_id_starts_at_lowest_user_id() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(ParasRegistrar::new_id(), LOWEST_USER_ID);
+			assert_eq!(ParasRegistrar::new_id(), ParaId::from(u32::from(LOWEST_USER_ID) + 1));
+		});
+	}
+
+	#[test]
+	fn size_limits_follow_the_configuration() {
+		new_test_ext().execute_with(|| {
+			assert!(ParasRegistrar::head_data_size_allowed(10));
+			assert!(!ParasRegistrar::head_data_size_allowed(11));
+			assert!(ParasRegistrar::code_size_allowed(100));
+			assert!(!ParasRegistrar::code_size_allowed(101));
+		});
+	}
+
+	#[test]
+	fn register_and_deregister_take_effect_at_the_next_session() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+			let (code, head) = args();
+			let id = ParaId::from(1000);
+
+			assert_noop!(ParasRegistrar::deregister_para(id), Error::<Test>::ParaDoesntExist);
+			assert_ok!(ParasRegistrar::register_para(id, true, code.clone(), head.clone()));
+
+			new_session();
+			run_to_block(2);
+			assert!(Paras::is_parachain(id));
+			assert_eq!(Paras::current_code(id), Some(code.clone()));
+			assert_eq!(Paras::para_head(id), Some(head.clone()));
+			assert_noop!(
+				ParasRegistrar::register_para(id, true, code, head),
+				Error::<Test>::ParaAlreadyExists,
+			);
+
+			assert_ok!(ParasRegistrar::deregister_para(id));
+			new_session();
+			run_to_block(3);
+			assert!(!Paras::is_parachain(id));
+			assert_eq!(Paras::current_code(id), None);
+		});
+	}
+
+	#[test]
+	fn make_parathread_requires_a_parachain() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+			let (code, head) = args();
+			let id = ParaId::from(1000);
+
+			assert_noop!(ParasRegistrar::make_parathread(id), Error::<Test>::NotParachain);
+			assert_ok!(ParasRegistrar::register_para(id, false, code, head));
+			new_session();
+			run_to_block(2);
+			assert!(Paras::is_parathread(id));
+			assert_noop!(ParasRegistrar::make_parathread(id), Error::<Test>::NotParachain);
+		});
+	}
+
+	#[test]
+	fn lease_expiry_downgrades_to_parathread() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
+			assert_ok!(Slots::bid(Origin::signed(1), 0, 1, 1, 1, 1));
+
+			run_to_block(9);
+			let id = LOWEST_USER_ID;
+			let code: ValidationCode = vec![1].into();
+			let h = BlakeTwo256::hash(&code.0[..]);
+			assert_ok!(Slots::fix_deploy_data(Origin::signed(1), 0, id, h, 1, vec![1].into()));
+			assert_ok!(Slots::elaborate_deploy_data(Origin::signed(0), id, code.clone()));
+
+			// The lease starts at block 10 and the parachain is onboarded at the following
+			// session change.
+			run_to_block(10);
+			assert_eq!(Slots::managed_ids(), vec![id]);
+			new_session();
+			run_to_block(11);
+			assert!(Paras::is_parachain(id));
+
+			// The lease ends at block 20 without a renewal: the downgrade is scheduled and the
+			// para stays a parachain until the next session change.
+			run_to_block(20);
+			assert!(Slots::managed_ids().is_empty());
+			assert_eq!(Paras::upcoming_downgrades(), vec![id]);
+			assert!(Paras::is_parachain(id));
+
+			new_session();
+			run_to_block(21);
+			assert!(!Paras::is_parachain(id));
+			assert!(Paras::is_parathread(id));
+			assert_eq!(Paras::current_code(id), Some(code));
+			assert_eq!(Paras::para_head(id), Some(vec![1].into()));
+		});
+	}
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
-//! A simple wrapper allowing `Sudo` to call into `paras` routines.
+//! A simple wrapper allowing `Sudo` to call into `paras` and `router` routines.
 
 use frame_support::{
 	decl_error, decl_module,
@@ -22,14 +22,14 @@ use frame_support::{
 	weights::DispatchClass,
 };
 use frame_system::ensure_root;
-use runtime_parachains::paras::{
-	self,
-	ParaGenesisArgs,
+use runtime_parachains::{
+	paras::{self, ParaGenesisArgs},
+	router,
 };
-use primitives::v1::Id as ParaId;
+use primitives::v1::{Id as ParaId, Balance};
 
 /// The module's configuration trait.
-pub trait Trait: paras::Trait { }
+pub trait Trait: paras::Trait + router::Trait { }
 
 decl_error! {
 	pub enum Error for Module<T: Trait> { }
@@ -59,5 +59,177 @@ decl_module! {
 			paras::Module::<T>::schedule_para_cleanup(id);
 			Ok(())
 		}
+
+		/// Open an HRMP channel from `sender` to `recipient` right away, reserving the channel
+		/// deposits from the sovereign accounts of both.
+		#[weight = (router::establish_hrmp_channel_weight::<T>(), DispatchClass::Operational)]
+		pub fn sudo_establish_hrmp_channel(
+			origin,
+			sender: ParaId,
+			recipient: ParaId,
+			limit_used_places: u32,
+			limit_used_bytes: u32,
+			fee_per_byte: Balance,
+		) -> DispatchResult {
+			ensure_root(origin)?;
+			router::Module::<T>::establish_hrmp_channel(
+				sender,
+				recipient,
+				limit_used_places,
+				limit_used_bytes,
+				fee_per_byte,
+			)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use sp_runtime::{
+		Perbill,
+		traits::{BlakeTwo256, IdentityLookup, BadOrigin},
+	};
+	use frame_support::{
+		impl_outer_origin, impl_outer_dispatch, parameter_types, assert_ok, assert_noop,
+		weights::Weight,
+	};
+	use primitives::v1::{AccountIdConversion, BlockNumber, Header, HrmpChannelId};
+	use runtime_parachains::{configuration, origin as parachains_origin};
+
+	impl_outer_origin! {
+		pub enum Origin for Test {
+			parachains_origin
+		}
+	}
+
+	impl_outer_dispatch! {
+		pub enum Call for Test where origin: Origin {
+			pallet_balances::Balances,
+		}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u32 = 250;
+		pub const MaximumBlockWeight: Weight = 4 * 1024 * 1024;
+		pub const MaximumBlockLength: u32 = 4 * 1024 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	}
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Index = u64;
+		type BlockNumber = BlockNumber;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<u64>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = pallet_balances::AccountData<u128>;
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+	}
+
+	parameter_types! {
+		pub const ExistentialDeposit: u128 = 1;
+		pub const HrmpSenderDeposit: u128 = 5;
+		pub const HrmpRecipientDeposit: u128 = 3;
+	}
+
+	impl pallet_balances::Trait for Test {
+		type Balance = u128;
+		type Event = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type AccountStore = System;
+		type WeightInfo = ();
+	}
+
+	impl configuration::Trait for Test { }
+
+	impl paras::Trait for Test { }
+
+	impl parachains_origin::Trait for Test { }
+
+	impl router::Trait for Test {
+		type Event = ();
+		type Origin = Origin;
+		type Call = Call;
+		type Currency = Balances;
+		type HrmpSenderDeposit = HrmpSenderDeposit;
+		type HrmpRecipientDeposit = HrmpRecipientDeposit;
+	}
+
+	impl Trait for Test { }
+
+	type System = frame_system::Module<Test>;
+	type Balances = pallet_balances::Module<Test>;
+	type Router = router::Module<Test>;
+	type ParasSudoWrapper = Module<Test>;
+
+	fn sovereign_account(para: u32) -> u64 {
+		ParaId::from(para).into_account()
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		pallet_balances::GenesisConfig::<Test> {
+			balances: vec![(sovereign_account(1), 100), (sovereign_account(2), 100)],
+		}.assimilate_storage(&mut t).unwrap();
+		t.into()
+	}
+
+	#[test]
+	fn sudo_establish_hrmp_channel_works() {
+		new_test_ext().execute_with(|| {
+			assert_noop!(
+				ParasSudoWrapper::sudo_establish_hrmp_channel(Origin::signed(1), 1.into(), 2.into(), 10, 100, 1),
+				BadOrigin,
+			);
+			assert_noop!(
+				ParasSudoWrapper::sudo_establish_hrmp_channel(Origin::root(), 1.into(), 1.into(), 10, 100, 1),
+				router::Error::<Test>::HrmpChannelToSelf,
+			);
+			// para 3 can't afford the recipient deposit.
+			assert_noop!(
+				ParasSudoWrapper::sudo_establish_hrmp_channel(Origin::root(), 1.into(), 3.into(), 10, 100, 1),
+				router::Error::<Test>::HrmpInsufficientDeposit,
+			);
+
+			assert_ok!(
+				ParasSudoWrapper::sudo_establish_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1),
+			);
+
+			let channel_id = HrmpChannelId { sender: 1.into(), recipient: 2.into() };
+			let channel = Router::hrmp_channels(&channel_id).unwrap();
+			assert_eq!(channel.limit_used_places, 10);
+			assert_eq!(channel.limit_used_bytes, 100);
+			assert_eq!(channel.fee_per_byte, 1);
+			assert_eq!(Router::hrmp_egress_channels_index(&ParaId::from(1)), vec![ParaId::from(2)]);
+			assert_eq!(Balances::reserved_balance(&sovereign_account(1)), 5);
+			assert_eq!(Balances::reserved_balance(&sovereign_account(2)), 3);
+
+			assert_noop!(
+				ParasSudoWrapper::sudo_establish_hrmp_channel(Origin::root(), 1.into(), 2.into(), 5, 50, 0),
+				router::Error::<Test>::HrmpChannelAlreadyExists,
+			);
+		});
 	}
 }
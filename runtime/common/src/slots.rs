@@ -25,7 +25,9 @@ use sp_runtime::traits::{
 use codec::{Encode, Decode, Codec};
 use frame_support::{
 	decl_module, decl_storage, decl_event, decl_error, ensure, dispatch::DispatchResult,
-	traits::{Currency, ReservableCurrency, WithdrawReason, ExistenceRequirement, Get, Randomness},
+	traits::{
+		Currency, ReservableCurrency, LockableCurrency, WithdrawReason, ExistenceRequirement, Get, Randomness,
+	},
 	weights::{DispatchClass, Weight},
 };
 use primitives::v1::{
@@ -41,8 +43,9 @@ pub trait Trait: frame_system::Trait {
 	/// The overarching event type.
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
 
-	/// The currency type used for bidding.
-	type Currency: ReservableCurrency<Self::AccountId>;
+	/// The currency type used for bidding. It is lockable for crowdfunds to lock refunds.
+	type Currency: ReservableCurrency<Self::AccountId>
+		+ LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
 
 	/// The parachain registrar type.
 	type Parachains: Registrar<Self::AccountId>;
@@ -84,6 +87,10 @@ pub trait Registrar<AccountId> {
 
 	/// Deregister a parachain with given `id`. If `id` is not currently registered, an error is returned.
 	fn deregister_para(id: ParaId) -> DispatchResult;
+
+	/// Downgrade the parachain with given `id` to a parathread, keeping its code and head data
+	/// registered. If `id` is not currently a parachain, an error is returned.
+	fn make_parathread(id: ParaId) -> DispatchResult;
 }
 
 /// Auxilliary for when there's an attempt to swap two parachains/parathreads.
@@ -288,6 +295,10 @@ decl_event!(
 		Reserved(AccountId, Balance, Balance),
 		/// Funds were unreserved since bidder is no longer active. [bidder, amount]
 		Unreserved(AccountId, Balance),
+		/// The lease of a parachain ended without being renewed. If it was deployed, it is
+		/// downgraded to a parathread. The deposit is returned to the off-boarding account.
+		/// [parachain_id, lease_period, returned_deposit]
+		LeaseEnded(ParaId, LeasePeriod, Balance),
 	}
 );
 
@@ -734,15 +745,17 @@ impl<T: Trait> Module<T> {
 						// Just one entry, which corresponds to the now-ended lease period. Time
 						// to decommission this chain.
 						if <Onboarding<T>>::take(id).is_none() {
-							// Only unregister it if it was actually registered in the first place.
+							// Only downgrade it if it was actually registered in the first place.
 							// If the on-boarding entry still existed, then it was never actually
-							// commissioned.
-							let _ = T::Parachains::deregister_para(id.clone());
+							// commissioned. The parathread stays registered so that it may keep
+							// producing blocks or win a slot again later on.
+							let _ = T::Parachains::make_parathread(id.clone());
 						}
 						// Return the full deposit to the off-boarding account.
 						T::Currency::deposit_creating(&<Offboarding<T>>::take(id), d[0]);
 						// Remove the now-empty deposits set and don't keep the ID around.
 						<Deposits<T>>::remove(id);
+						Self::deposit_event(RawEvent::LeaseEnded(*id, lease_period_index, d[0]));
 						false
 					} else {
 						// The parachain entry is continuing into the next lease period.
@@ -1013,6 +1026,8 @@ mod tests {
 		pub static PARACHAIN_COUNT: RefCell<u32> = RefCell::new(0);
 		pub static PARACHAINS:
 			RefCell<HashMap<u32, (ValidationCode, HeadData)>> = RefCell::new(HashMap::new());
+		pub static PARATHREADS:
+			RefCell<HashMap<u32, (ValidationCode, HeadData)>> = RefCell::new(HashMap::new());
 	}
 
 	const MAX_CODE_SIZE: u32 = 100;
@@ -1058,6 +1073,13 @@ mod tests {
 				Ok(())
 			})
 		}
+
+		fn make_parathread(id: ParaId) -> DispatchResult {
+			let para = PARACHAINS.with(|p| p.borrow_mut().remove(&id.into()))
+				.expect("ID isn't a parachain");
+			PARATHREADS.with(|p| p.borrow_mut().insert(id.into(), para));
+			Ok(())
+		}
 	}
 
 	fn reset_count() {
@@ -1068,6 +1090,10 @@ mod tests {
 		PARACHAINS.with(|p| f(&*p.borrow()))
 	}
 
+	fn with_parathreads<T>(f: impl FnOnce(&HashMap<u32, (ValidationCode, HeadData)>) -> T) -> T {
+		PARATHREADS.with(|p| f(&*p.borrow()))
+	}
+
 	parameter_types!{
 		pub const LeasePeriod: BlockNumber = 10;
 		pub const EndingPeriod: BlockNumber = 3;
@@ -1468,6 +1494,82 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn lease_expiry_downgrades_to_parathread() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
+			assert_ok!(Slots::bid(Origin::signed(1), 0, 1, 1, 1, 1));
+
+			run_to_block(9);
+			let h = BlakeTwo256::hash(&[1u8][..]);
+			assert_ok!(Slots::fix_deploy_data(Origin::signed(1), 0, 0.into(), h, 1, vec![1].into()));
+			assert_ok!(Slots::elaborate_deploy_data(Origin::signed(0), 0.into(), vec![1].into()));
+
+			run_to_block(10);
+			with_parachains(|p| assert_eq!(p.len(), 1));
+			assert_eq!(Balances::free_balance(1), 9);
+
+			// The lease ends: the chain keeps its code and head as a parathread and the deposit
+			// is returned exactly at the lease boundary.
+			run_to_block(19);
+			assert_eq!(Balances::free_balance(1), 9);
+			run_to_block(20);
+			with_parachains(|p| assert_eq!(p.len(), 0));
+			with_parathreads(|p| {
+				assert_eq!(p.len(), 1);
+				assert_eq!(p[&0], (vec![1].into(), vec![1].into()));
+			});
+			assert_eq!(Balances::free_balance(1), 10);
+			assert!(Slots::managed_ids().is_empty());
+		});
+	}
+
+	#[test]
+	fn renewal_avoids_downgrade() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
+			assert_ok!(Slots::bid(Origin::signed(1), 0, 1, 1, 1, 1));
+
+			run_to_block(10);
+			let h = BlakeTwo256::hash(&[1u8][..]);
+			assert_ok!(Slots::fix_deploy_data(Origin::signed(1), 0, 0.into(), h, 1, vec![1].into()));
+			assert_ok!(Slots::elaborate_deploy_data(Origin::signed(0), 0.into(), vec![1].into()));
+
+			assert_ok!(Slots::new_auction(Origin::root(), 5, 2));
+			assert_ok!(Slots::bid_renew(Origin::signed(ParaId::from(0).into_account()), 2, 2, 2, 1));
+
+			// Renewed for the next lease period, so it stays a parachain.
+			run_to_block(20);
+			with_parachains(|p| assert_eq!(p.len(), 1));
+			with_parathreads(|p| assert_eq!(p.len(), 0));
+			assert_eq!(Slots::deposits(ParaId::from(0)), vec![1]);
+
+			// Not renewed a second time.
+			run_to_block(30);
+			with_parachains(|p| assert_eq!(p.len(), 0));
+			with_parathreads(|p| assert_eq!(p.len(), 1));
+			assert_eq!(Balances::free_balance(1), 10);
+		});
+	}
+
+	#[test]
+	fn undeployed_lease_expiry_downgrades_nothing() {
+		new_test_ext().execute_with(|| {
+			run_to_block(1);
+			assert_ok!(Slots::new_auction(Origin::root(), 5, 1));
+			assert_ok!(Slots::bid(Origin::signed(1), 0, 1, 1, 1, 1));
+
+			// Deploy data is never provided.
+			run_to_block(20);
+			with_parachains(|p| assert_eq!(p.len(), 0));
+			with_parathreads(|p| assert_eq!(p.len(), 0));
+			assert_eq!(Slots::onboarding(ParaId::from(0)), None);
+			assert_eq!(Balances::free_balance(1), 10);
+		});
+	}
+
 	#[test]
 	fn renewal_with_lower_value_should_work() {
 		new_test_ext().execute_with(|| {
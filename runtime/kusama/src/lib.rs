@@ -28,8 +28,8 @@ use primitives::v1::{
 };
 use primitives::v0 as p_v0;
 use runtime_common::{
-	dummy, claims, SlowAdjustingFeeUpdate,
-	impls::{CurrencyToVoteHandler, ToAuthor},
+	dummy, claims, imbalance_split, SlowAdjustingFeeUpdate,
+	impls::{CurrencyToVoteHandler, ToAuthor, TreasuryAndBurn},
 	NegativeImbalance, BlockHashCount, MaximumBlockWeight, AvailableBlockRatio,
 	MaximumBlockLength, BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight,
 	MaximumExtrinsicWeight, ParachainSessionKeyPlaceholder,
@@ -87,7 +87,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("kusama"),
 	impl_name: create_runtime_str!("parity-kusama"),
 	authoring_version: 2,
-	spec_version: 2024,
+	spec_version: 2025,
 	impl_version: 0,
 	#[cfg(not(feature = "disable-runtime-api"))]
 	apis: RUNTIME_API_VERSIONS,
@@ -203,13 +203,16 @@ impl pallet_indices::Trait for Runtime {
 
 parameter_types! {
 	pub const ExistentialDeposit: Balance = 1 * CENTS;
+	// The portion of the treasury's part of transaction fees which isn't burned.
+	pub const TreasuryShareOfFees: Perbill = Perbill::from_percent(100);
 }
 
-/// Splits fees 80/20 between treasury and block author.
+/// Splits fees 80/20 between treasury and block author. Of the treasury's part, the
+/// `TreasuryShareOfFees` goes to the treasury and the rest is burned.
 pub type DealWithFees = SplitTwoWays<
 	Balance,
 	NegativeImbalance<Runtime>,
-	_4, Treasury,   // 4 parts (80%) goes to the treasury.
+	_4, TreasuryAndBurn<Runtime, TreasuryShareOfFees, Treasury>,   // 4 parts (80%) goes to the treasury.
 	_1, ToAuthor<Runtime>,   // 1 part (20%) goes to the block author.
 >;
 
@@ -316,6 +319,8 @@ parameter_types! {
 	pub const BondingDuration: pallet_staking::EraIndex = 28;
 	// 27 eras in which slashes can be cancelled (slightly less than 7 days).
 	pub const SlashDeferDuration: pallet_staking::EraIndex = 27;
+	// The portion of staking slashes going to the treasury, the rest is burned.
+	pub const TreasuryShareOfSlashes: Perbill = Perbill::from_percent(100);
 	pub const RewardCurve: &'static PiecewiseLinear<'static> = &REWARD_CURVE;
 	pub const MaxNominatorRewardedPerValidator: u32 = 64;
 	// quarter of the last session will be for election.
@@ -336,7 +341,7 @@ impl pallet_staking::Trait for Runtime {
 	type CurrencyToVote = CurrencyToVoteHandler<Self>;
 	type RewardRemainder = Treasury;
 	type Event = Event;
-	type Slash = Treasury;
+	type Slash = TreasuryAndBurn<Runtime, TreasuryShareOfSlashes, Treasury>;
 	type Reward = ();
 	type SessionsPerEra = SessionsPerEra;
 	type BondingDuration = BondingDuration;
@@ -518,6 +523,10 @@ impl pallet_treasury::Trait for Runtime {
 	type WeightInfo = ();
 }
 
+impl imbalance_split::Trait for Runtime {
+	type Event = Event;
+}
+
 parameter_types! {
 	pub OffencesWeightSoftLimit: Weight = Perbill::from_percent(60) * MaximumBlockWeight::get();
 }
@@ -941,6 +950,9 @@ construct_runtime! {
 
 		// Multisig module. Late addition.
 		Multisig: pallet_multisig::{Module, Call, Storage, Event<T>},
+
+		// Reports the split of fees and slashes between the treasury and burning. Late addition.
+		ImbalanceSplit: imbalance_split::{Module, Storage, Event<T>},
 	}
 }
 
@@ -19,14 +19,27 @@
 //! Configuration can change only at session boundaries and is buffered until then.
 
 use sp_std::prelude::*;
-use primitives::v1::ValidatorId;
+use primitives::v1::{ExecutorParams, ValidatorId};
 use frame_support::{
-	decl_storage, decl_module, decl_error,
+	decl_storage, decl_module, decl_error, ensure,
 	dispatch::DispatchResult,
 	weights::{DispatchClass, Weight},
 };
 use codec::{Encode, Decode};
 use frame_system::ensure_root;
+use sp_core::RuntimeDebug;
+
+/// The minimum number of heap pages which can be configured for validation functions.
+pub const MIN_HEAP_PAGES: u64 = 32;
+/// The maximum number of heap pages which can be configured for validation functions: 1 GiB,
+/// well within the default memory limit of validation workers.
+pub const MAX_HEAP_PAGES: u64 = 16_384;
+/// The minimum execution timeout, in milliseconds, which can be configured for validation
+/// functions.
+pub const MIN_EXECUTION_TIMEOUT_MS: u64 = 500;
+/// The maximum execution timeout, in milliseconds, which can be configured for validation
+/// functions.
+pub const MAX_EXECUTION_TIMEOUT_MS: u64 = 60_000;
 
 /// All configuration of the runtime with respect to parachains and parathreads.
 #[derive(Clone, Encode, Decode, PartialEq, Default)]
@@ -39,6 +52,10 @@ pub struct HostConfiguration<BlockNumber> {
 	/// The acceptance period, in blocks. This is the amount of blocks after availability that validators
 	/// and fishermen have to perform secondary checks or issue reports.
 	pub acceptance_period: BlockNumber,
+	/// The period, in blocks, for which past validation code is retained after being replaced.
+	/// Past code is never pruned before the acceptance period has passed, so this only has an
+	/// effect if longer than the acceptance period.
+	pub code_retention_period: BlockNumber,
 	/// The maximum validation code size, in bytes.
 	pub max_code_size: u32,
 	/// The maximum head-data size, in bytes.
@@ -58,6 +75,42 @@ pub struct HostConfiguration<BlockNumber> {
 	pub thread_availability_period: BlockNumber,
 	/// The amount of blocks ahead to schedule parachains and parathreads.
 	pub scheduling_lookahead: u32,
+	/// The number of ancestors of the parent block, within the current session, which backed
+	/// candidates may also use as their relay parent. Zero means that candidates must be built
+	/// on top of the parent block.
+	pub allowed_ancestry_len: u32,
+	/// The maximum number of validators to take part in parachain consensus. The first validators
+	/// of the session's validator set are used. `None` means that all validators take part.
+	pub max_validators: Option<u32>,
+	/// The maximum number of upward messages a candidate may send.
+	pub max_upward_message_num_per_candidate: u32,
+	/// The maximum total size, in bytes, of the data of the upward messages a candidate may send.
+	pub max_upward_message_size_per_candidate: u32,
+	/// The weight of the upward message calls to dispatch in a block, after which the remaining
+	/// messages are left for the next block. At least one message is dispatched per block.
+	pub preferred_dispatchable_upward_messages_step_weight: Weight,
+	/// The maximum weight of the call of a single upward message. Messages whose call takes more
+	/// are dropped without being dispatched.
+	pub dispatchable_upward_message_critical_weight: Weight,
+	/// The parameters of the environment validation functions are executed in.
+	pub executor_params: ExecutorParams,
+}
+
+/// The versions of the storage layout of the configuration module.
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub enum Releases {
+	/// The original layout of [`HostConfiguration`].
+	V0,
+	/// [`HostConfiguration`] gained the code retention period, the allowed ancestry length, the
+	/// maximum number of validators, the upward message limits and dispatch weights and the
+	/// executor parameters.
+	V1,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V0
+	}
 }
 
 pub trait Trait: frame_system::Trait { }
@@ -68,11 +121,20 @@ decl_storage! {
 		Config get(fn config) config(): HostConfiguration<T::BlockNumber>;
 		/// Pending configuration (if any) for the next session.
 		PendingConfig: Option<HostConfiguration<T::BlockNumber>>;
+		/// The version of the storage layout. Chains started before versioning are at `V0`.
+		StorageVersion build(|_: &GenesisConfig<T>| Releases::V1): Releases;
 	}
 }
 
 decl_error! {
-	pub enum Error for Module<T: Trait> { }
+	pub enum Error for Module<T: Trait> {
+		/// The number of heap pages is out of the bounds given by `MIN_HEAP_PAGES` and
+		/// `MAX_HEAP_PAGES`.
+		InvalidHeapPages,
+		/// The execution timeout is out of the bounds given by `MIN_EXECUTION_TIMEOUT_MS` and
+		/// `MAX_EXECUTION_TIMEOUT_MS`.
+		InvalidExecutionTimeout,
+	}
 }
 
 decl_module! {
@@ -80,6 +142,10 @@ decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
 		type Error = Error<T>;
 
+		fn on_runtime_upgrade() -> Weight {
+			migration::migrate_to_v1::<T>()
+		}
+
 		/// Set the validation upgrade frequency.
 		#[weight = (1_000, DispatchClass::Operational)]
 		pub fn set_validation_upgrade_frequency(origin, new: T::BlockNumber) -> DispatchResult {
@@ -110,6 +176,16 @@ decl_module! {
 			Ok(())
 		}
 
+		/// Set the period for which past validation code is retained.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_code_retention_period(origin, new: T::BlockNumber) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.code_retention_period, new) != new
+			});
+			Ok(())
+		}
+
 		/// Set the max validation code size for incoming upgrades.
 		#[weight = (1_000, DispatchClass::Operational)]
 		pub fn set_max_code_size(origin, new: u32) -> DispatchResult {
@@ -190,6 +266,85 @@ decl_module! {
 			});
 			Ok(())
 		}
+
+		/// Set the number of ancestors of the parent block which can be used as relay parents.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_allowed_ancestry_len(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.allowed_ancestry_len, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of validators to take part in parachain consensus.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_validators(origin, new: Option<u32>) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_validators, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of upward messages a candidate may send.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_message_num_per_candidate(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_message_num_per_candidate, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum total size of the upward messages a candidate may send.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_message_size_per_candidate(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_message_size_per_candidate, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the weight of the upward message calls to dispatch in a block.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_preferred_dispatchable_upward_messages_step_weight(origin, new: Weight) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.preferred_dispatchable_upward_messages_step_weight, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum weight of the call of a single upward message.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_dispatchable_upward_message_critical_weight(origin, new: Weight) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.dispatchable_upward_message_critical_weight, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the parameters of the environment validation functions are executed in.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_executor_params(origin, new: ExecutorParams) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(
+				new.heap_pages >= MIN_HEAP_PAGES && new.heap_pages <= MAX_HEAP_PAGES,
+				Error::<T>::InvalidHeapPages,
+			);
+			ensure!(
+				new.execution_timeout_ms >= MIN_EXECUTION_TIMEOUT_MS
+					&& new.execution_timeout_ms <= MAX_EXECUTION_TIMEOUT_MS,
+				Error::<T>::InvalidExecutionTimeout,
+			);
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.executor_params, new.clone()) != new
+			});
+			Ok(())
+		}
 	}
 }
 
@@ -221,12 +376,87 @@ impl<T: Trait> Module<T> {
 	}
 }
 
+mod migration {
+	use super::*;
+	use frame_support::{traits::Get, storage::StorageValue};
+
+	/// The layout of [`HostConfiguration`] at [`Releases::V0`].
+	#[derive(Encode, Decode)]
+	#[cfg_attr(test, derive(Default))]
+	pub(super) struct OldHostConfiguration<BlockNumber> {
+		pub validation_upgrade_frequency: BlockNumber,
+		pub validation_upgrade_delay: BlockNumber,
+		pub acceptance_period: BlockNumber,
+		pub max_code_size: u32,
+		pub max_head_data_size: u32,
+		pub parathread_cores: u32,
+		pub parathread_retries: u32,
+		pub group_rotation_frequency: BlockNumber,
+		pub chain_availability_period: BlockNumber,
+		pub thread_availability_period: BlockNumber,
+		pub scheduling_lookahead: u32,
+	}
+
+	impl<BlockNumber: Default> OldHostConfiguration<BlockNumber> {
+		/// Convert to the current layout. The fields added since are set to their defaults: no code
+		/// is retained beyond the acceptance period, candidates must be built on top of the parent
+		/// block, all validators take part in parachain consensus and validation functions run
+		/// with the default executor parameters. Upward messages are refused until their limits
+		/// are configured.
+		fn migrate(self) -> HostConfiguration<BlockNumber> {
+			HostConfiguration {
+				validation_upgrade_frequency: self.validation_upgrade_frequency,
+				validation_upgrade_delay: self.validation_upgrade_delay,
+				acceptance_period: self.acceptance_period,
+				code_retention_period: Default::default(),
+				max_code_size: self.max_code_size,
+				max_head_data_size: self.max_head_data_size,
+				parathread_cores: self.parathread_cores,
+				parathread_retries: self.parathread_retries,
+				group_rotation_frequency: self.group_rotation_frequency,
+				chain_availability_period: self.chain_availability_period,
+				thread_availability_period: self.thread_availability_period,
+				scheduling_lookahead: self.scheduling_lookahead,
+				allowed_ancestry_len: 0,
+				max_validators: None,
+				max_upward_message_num_per_candidate: 0,
+				max_upward_message_size_per_candidate: 0,
+				preferred_dispatchable_upward_messages_step_weight: 0,
+				dispatchable_upward_message_critical_weight: 0,
+				executor_params: ExecutorParams::default(),
+			}
+		}
+	}
+
+	/// Migrate the active and pending configuration from [`Releases::V0`] to [`Releases::V1`].
+	pub(super) fn migrate_to_v1<T: Trait>() -> Weight {
+		if <Module<T> as Store>::StorageVersion::get() != Releases::V0 {
+			return T::DbWeight::get().reads(1);
+		}
+
+		let _ = <Module<T> as Store>::Config::translate(
+			|old: Option<OldHostConfiguration<T::BlockNumber>>| old.map(OldHostConfiguration::migrate),
+		);
+		let _ = <Module<T> as Store>::PendingConfig::translate(
+			|old: Option<OldHostConfiguration<T::BlockNumber>>| old.map(OldHostConfiguration::migrate),
+		);
+		<Module<T> as Store>::StorageVersion::put(Releases::V1);
+
+		T::DbWeight::get().reads_writes(3, 3)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::mock::{new_test_ext, Initializer, Configuration, Origin};
+	use crate::mock::{new_test_ext, Initializer, Configuration, Origin, Test};
+	use primitives::v1::BlockNumber;
 
-	use frame_support::traits::{OnFinalize, OnInitialize};
+	use frame_support::{
+		assert_noop,
+		storage::{unhashed, StorageValue},
+		traits::{OnFinalize, OnInitialize, OnRuntimeUpgrade},
+	};
 
 	#[test]
 	fn config_changes_on_session_boundary() {
@@ -260,6 +490,7 @@ mod tests {
 				validation_upgrade_frequency: 100,
 				validation_upgrade_delay: 10,
 				acceptance_period: 5,
+				code_retention_period: 20,
 				max_code_size: 100_000,
 				max_head_data_size: 1_000,
 				parathread_cores: 2,
@@ -268,6 +499,16 @@ mod tests {
 				chain_availability_period: 10,
 				thread_availability_period: 8,
 				scheduling_lookahead: 3,
+				allowed_ancestry_len: 2,
+				max_validators: Some(100),
+				max_upward_message_num_per_candidate: 10,
+				max_upward_message_size_per_candidate: 1024,
+				preferred_dispatchable_upward_messages_step_weight: 20_000,
+				dispatchable_upward_message_critical_weight: 5_000,
+				executor_params: ExecutorParams {
+					heap_pages: 2048,
+					execution_timeout_ms: 2_000,
+				},
 			};
 
 			assert!(<Configuration as Store>::PendingConfig::get().is_none());
@@ -281,6 +522,9 @@ mod tests {
 			Configuration::set_acceptance_period(
 				Origin::root(), new_config.acceptance_period,
 			).unwrap();
+			Configuration::set_code_retention_period(
+				Origin::root(), new_config.code_retention_period,
+			).unwrap();
 			Configuration::set_max_code_size(
 				Origin::root(), new_config.max_code_size,
 			).unwrap();
@@ -305,6 +549,27 @@ mod tests {
 			Configuration::set_scheduling_lookahead(
 				Origin::root(), new_config.scheduling_lookahead,
 			).unwrap();
+			Configuration::set_allowed_ancestry_len(
+				Origin::root(), new_config.allowed_ancestry_len,
+			).unwrap();
+			Configuration::set_max_validators(
+				Origin::root(), new_config.max_validators,
+			).unwrap();
+			Configuration::set_max_upward_message_num_per_candidate(
+				Origin::root(), new_config.max_upward_message_num_per_candidate,
+			).unwrap();
+			Configuration::set_max_upward_message_size_per_candidate(
+				Origin::root(), new_config.max_upward_message_size_per_candidate,
+			).unwrap();
+			Configuration::set_preferred_dispatchable_upward_messages_step_weight(
+				Origin::root(), new_config.preferred_dispatchable_upward_messages_step_weight,
+			).unwrap();
+			Configuration::set_dispatchable_upward_message_critical_weight(
+				Origin::root(), new_config.dispatchable_upward_message_critical_weight,
+			).unwrap();
+			Configuration::set_executor_params(
+				Origin::root(), new_config.executor_params.clone(),
+			).unwrap();
 
 			assert_eq!(<Configuration as Store>::PendingConfig::get(), Some(new_config));
 		})
@@ -324,4 +589,87 @@ mod tests {
 			assert!(<Configuration as Store>::PendingConfig::get().is_none())
 		});
 	}
+
+	#[test]
+	fn executor_params_out_of_bounds_are_rejected() {
+		new_test_ext(Default::default()).execute_with(|| {
+			let params = |heap_pages, execution_timeout_ms| ExecutorParams { heap_pages, execution_timeout_ms };
+
+			assert_noop!(
+				Configuration::set_executor_params(Origin::root(), params(1024, 0)),
+				Error::<Test>::InvalidExecutionTimeout,
+			);
+			assert_noop!(
+				Configuration::set_executor_params(Origin::root(), params(1024, MAX_EXECUTION_TIMEOUT_MS + 1)),
+				Error::<Test>::InvalidExecutionTimeout,
+			);
+			assert_noop!(
+				Configuration::set_executor_params(Origin::root(), params(0, 5_000)),
+				Error::<Test>::InvalidHeapPages,
+			);
+			assert_noop!(
+				Configuration::set_executor_params(Origin::root(), params(MAX_HEAP_PAGES + 1, 5_000)),
+				Error::<Test>::InvalidHeapPages,
+			);
+
+			Configuration::set_executor_params(
+				Origin::root(),
+				params(MIN_HEAP_PAGES, MIN_EXECUTION_TIMEOUT_MS),
+			).unwrap();
+			Configuration::set_executor_params(
+				Origin::root(),
+				params(MAX_HEAP_PAGES, MAX_EXECUTION_TIMEOUT_MS),
+			).unwrap();
+			assert_eq!(
+				<Configuration as Store>::PendingConfig::get().unwrap().executor_params,
+				params(MAX_HEAP_PAGES, MAX_EXECUTION_TIMEOUT_MS),
+			);
+		});
+	}
+
+	#[test]
+	fn migration_to_v1_translates_configs() {
+		new_test_ext(Default::default()).execute_with(|| {
+			assert_eq!(<Configuration as Store>::StorageVersion::get(), Releases::V1);
+
+			let old_config = migration::OldHostConfiguration::<BlockNumber> {
+				validation_upgrade_frequency: 100,
+				acceptance_period: 5,
+				max_code_size: 100_000,
+				group_rotation_frequency: 20,
+				scheduling_lookahead: 3,
+				..Default::default()
+			};
+			let old_pending = migration::OldHostConfiguration::<BlockNumber> {
+				validation_upgrade_delay: 10,
+				..Default::default()
+			};
+
+			unhashed::put(&<Configuration as Store>::Config::hashed_key(), &old_config);
+			unhashed::put(&<Configuration as Store>::PendingConfig::hashed_key(), &old_pending);
+			<Configuration as Store>::StorageVersion::put(Releases::V0);
+
+			Configuration::on_runtime_upgrade();
+
+			let config = Configuration::config();
+			assert_eq!(config.validation_upgrade_frequency, 100);
+			assert_eq!(config.acceptance_period, 5);
+			assert_eq!(config.max_code_size, 100_000);
+			assert_eq!(config.group_rotation_frequency, 20);
+			assert_eq!(config.scheduling_lookahead, 3);
+			assert_eq!(config.code_retention_period, 0);
+			assert_eq!(config.max_validators, None);
+			assert_eq!(config.executor_params, ExecutorParams::default());
+
+			let pending = <Configuration as Store>::PendingConfig::get().unwrap();
+			assert_eq!(pending.validation_upgrade_delay, 10);
+			assert_eq!(pending.executor_params, ExecutorParams::default());
+
+			assert_eq!(<Configuration as Store>::StorageVersion::get(), Releases::V1);
+
+			// Running the migration again leaves the configuration untouched.
+			Configuration::on_runtime_upgrade();
+			assert_eq!(Configuration::config(), config);
+		});
+	}
 }
@@ -34,9 +34,9 @@ use frame_support::{
 use codec::{Encode, Decode};
 use bitvec::{order::Lsb0 as BitOrderLsb0, vec::BitVec};
 use sp_staking::SessionIndex;
-use sp_runtime::{DispatchError, traits::{One, Saturating}};
+use sp_runtime::{DispatchError, traits::One};
 
-use crate::{configuration, paras, scheduler::CoreAssignment};
+use crate::{configuration, paras, router, scheduler::CoreAssignment};
 
 /// A bitfield signed by a validator indicating that it is keeping its piece of the erasure-coding
 /// for any backed candidates referred to by a `1` bit available.
@@ -50,6 +50,26 @@ pub struct AvailabilityBitfieldRecord<N> {
 	submitted_at: N, // for accounting, as meaning of bits may change over time.
 }
 
+/// The result of processing backed candidates.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub(crate) struct ProcessedCandidates<H> {
+	/// The cores occupied by the candidates, in the order the candidates were given.
+	pub(crate) core_indices: Vec<CoreIndex>,
+	/// The receipts of the processed candidates, along with the indices of the validators
+	/// that backed them.
+	pub(crate) candidate_receipt_with_backing_validator_indices:
+		Vec<(CandidateReceipt<H>, Vec<ValidatorIndex>)>,
+}
+
+impl<H> Default for ProcessedCandidates<H> {
+	fn default() -> Self {
+		ProcessedCandidates {
+			core_indices: Vec::new(),
+			candidate_receipt_with_backing_validator_indices: Vec::new(),
+		}
+	}
+}
+
 /// A backed candidate pending availability.
 // TODO: split this type and change this to hold a plain `CandidateReceipt`.
 // https://github.com/paritytech/polkadot/issues/1357
@@ -86,7 +106,7 @@ impl<H, N> CandidatePendingAvailability<H, N> {
 }
 
 pub trait Trait:
-	frame_system::Trait + paras::Trait + configuration::Trait
+	frame_system::Trait + paras::Trait + router::Trait + configuration::Trait
 {
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
 }
@@ -110,6 +130,11 @@ decl_storage! {
 
 		/// The current session index.
 		CurrentSessionIndex get(fn session_index): SessionIndex;
+
+		/// Recent ancestors of the current block within this session, along with their numbers,
+		/// most recent first. Backed candidates may use any of these as their relay parent,
+		/// according to the `allowed_ancestry_len` of the configuration.
+		AllowedRelayParents get(fn allowed_relay_parents): Vec<(T::Hash, T::BlockNumber)>;
 	}
 }
 
@@ -133,7 +158,7 @@ decl_error! {
 		ScheduledOutOfOrder,
 		/// Code upgrade prematurely.
 		PrematureCodeUpgrade,
-		/// Candidate not in parent context.
+		/// Candidate not in the context of the parent block or one of the allowed ancestors.
 		CandidateNotInParentContext,
 		/// The bitfield contains a bit relating to an unassigned availability core.
 		UnoccupiedBitInBitfield,
@@ -147,6 +172,10 @@ decl_error! {
 		NotCollatorSigned,
 		/// The validation data hash does not match expected.
 		ValidationDataHashMismatch,
+		/// The candidate sends more upward messages than permitted.
+		UpwardMessagesCountExceeded,
+		/// The upward messages sent by the candidate are larger in total than permitted.
+		UpwardMessagesSizeExceeded,
 		/// Internal error only returned when compiled with debug assertions.
 		InternalError,
 	}
@@ -171,13 +200,27 @@ decl_module! {
 		type Error = Error<T>;
 
 		fn deposit_event() = default;
+
+		fn on_runtime_upgrade() -> Weight {
+			migration::migrate_pending_commitments::<T>()
+		}
 	}
 }
 
 impl<T: Trait> Module<T> {
 
 	/// Block initialization logic, called by initializer.
-	pub(crate) fn initializer_initialize(_now: T::BlockNumber) -> Weight { 0 }
+	pub(crate) fn initializer_initialize(now: T::BlockNumber) -> Weight {
+		let config = <configuration::Module<T>>::config();
+		let parent_hash = <frame_system::Module<T>>::parent_hash();
+
+		<AllowedRelayParents<T>>::mutate(|allowed| {
+			allowed.insert(0, (parent_hash, now - One::one()));
+			allowed.truncate(config.allowed_ancestry_len as usize + 1);
+		});
+
+		T::DbWeight::get().reads_writes(2, 1)
+	}
 
 	/// Block finalization logic, called by initializer.
 	pub(crate) fn initializer_finalize() { }
@@ -192,6 +235,10 @@ impl<T: Trait> Module<T> {
 		for _ in <PendingAvailability<T>>::drain() { }
 		for _ in <AvailabilityBitfields<T>>::drain() { }
 
+		// candidates are backed with respect to the session of the block after their relay
+		// parent, so relay parents from earlier sessions can't be used anymore.
+		<AllowedRelayParents<T>>::kill();
+
 		Validators::set(notification.validators.clone()); // substrate forces us to clone, stupidly.
 		CurrentSessionIndex::set(notification.session_index);
 	}
@@ -346,24 +393,27 @@ impl<T: Trait> Module<T> {
 		scheduled: Vec<CoreAssignment>,
 		group_validators: impl Fn(GroupIndex) -> Option<Vec<ValidatorIndex>>,
 	)
-		-> Result<Vec<CoreIndex>, DispatchError>
+		-> Result<ProcessedCandidates<T::Hash>, DispatchError>
 	{
 		ensure!(candidates.len() <= scheduled.len(), Error::<T>::UnscheduledCandidate);
 
 		if scheduled.is_empty() {
-			return Ok(Vec::new());
+			return Ok(ProcessedCandidates::default());
 		}
 
 		let validators = Validators::get();
 		let parent_hash = <frame_system::Module<T>>::parent_hash();
 		let config = <configuration::Module<T>>::config();
 		let now = <frame_system::Module<T>>::block_number();
-		let relay_parent_number = now - One::one();
+		let allowed_relay_parents = Self::allowed_relay_parents();
+		let session_index = CurrentSessionIndex::get();
 
 		// do all checks before writing storage.
-		let core_indices = {
+		let (core_indices, backers, relay_parent_numbers) = {
 			let mut skip = 0;
 			let mut core_indices = Vec::with_capacity(candidates.len());
+			let mut backers = Vec::with_capacity(candidates.len());
+			let mut relay_parent_numbers = Vec::with_capacity(candidates.len());
 			let mut last_core = None;
 
 			let mut check_assignment_in_order = |assignment: &CoreAssignment| -> DispatchResult {
@@ -376,9 +426,11 @@ impl<T: Trait> Module<T> {
 				Ok(())
 			};
 
+			// candidates are backed by the groups assigned at the parent block, so the backing
+			// is signed in its context even if a candidate is built on an ancestor.
 			let signing_context = SigningContext {
 				parent_hash,
-				session_index: CurrentSessionIndex::get(),
+				session_index,
 			};
 
 			// We combine an outer loop over candidates with an inner loop over the scheduled,
@@ -395,27 +447,48 @@ impl<T: Trait> Module<T> {
 			for candidate in &candidates {
 				let para_id = candidate.descriptor().para_id;
 
-				// we require that the candidate is in the context of the parent block.
-				ensure!(
-					candidate.descriptor().relay_parent == parent_hash,
-					Error::<T>::CandidateNotInParentContext,
-				);
+				// we require that the candidate is in the context of the parent block or one of
+				// the allowed ancestors.
+				let relay_parent = candidate.descriptor().relay_parent;
+				let relay_parent_number = if relay_parent == parent_hash {
+					now - One::one()
+				} else {
+					match allowed_relay_parents.iter().find(|(hash, _)| hash == &relay_parent) {
+						Some((_, number)) => *number,
+						None => Err(Error::<T>::CandidateNotInParentContext)?,
+					}
+				};
 
 				// if any, the code upgrade attempt is allowed.
 				let valid_upgrade_attempt =
 					candidate.candidate.commitments.new_validation_code.is_none() ||
-					<paras::Module<T>>::last_code_upgrade(para_id, true)
-						.map_or(
-							true,
-							|last| last <= relay_parent_number &&
-								relay_parent_number.saturating_sub(last)
-									>= config.validation_upgrade_frequency,
-						);
+					<paras::Module<T>>::can_upgrade_validation_code(para_id, relay_parent_number);
 
 				ensure!(
 					valid_upgrade_attempt,
 					Error::<T>::PrematureCodeUpgrade,
 				);
+
+				let upward_messages = &candidate.candidate.commitments.upward_messages;
+				ensure!(
+					upward_messages.len() <= config.max_upward_message_num_per_candidate as usize,
+					Error::<T>::UpwardMessagesCountExceeded,
+				);
+				ensure!(
+					upward_messages.iter().map(|m| m.data.len()).sum::<usize>()
+						<= config.max_upward_message_size_per_candidate as usize,
+					Error::<T>::UpwardMessagesSizeExceeded,
+				);
+
+				<router::Module<T>>::verify_outbound_hrmp(
+					para_id,
+					&candidate.candidate.commitments.horizontal_messages,
+				)?;
+				<router::Module<T>>::check_hrmp_watermark(
+					para_id,
+					relay_parent_number,
+					candidate.candidate.commitments.hrmp_watermark.into(),
+				)?;
 				ensure!(
 					candidate.descriptor().check_collator_signature().is_ok(),
 					Error::<T>::NotCollatorSigned,
@@ -435,13 +508,16 @@ impl<T: Trait> Module<T> {
 						{
 							// this should never fail because the para is registered
 							let persisted_validation_data =
-								match crate::util::make_persisted_validation_data::<T>(para_id) {
+								match crate::util::make_persisted_validation_data_at::<T>(
+									para_id,
+									relay_parent_number,
+								) {
 									Some(l) => l,
 									None => {
 										// We don't want to error out here because it will
 										// brick the relay-chain. So we return early without
 										// doing anything.
-										return Ok(Vec::new());
+										return Ok(ProcessedCandidates::default());
 									}
 								};
 
@@ -487,6 +563,14 @@ impl<T: Trait> Module<T> {
 						}
 
 						core_indices.push(assignment.core);
+						relay_parent_numbers.push(relay_parent_number);
+						backers.push(
+							group_vals.iter()
+								.zip(candidate.validator_indices.iter())
+								.filter(|(_, backed)| **backed)
+								.map(|(v, _)| *v)
+								.collect::<Vec<_>>()
+						);
 						continue 'a;
 					}
 				}
@@ -505,19 +589,29 @@ impl<T: Trait> Module<T> {
 				check_assignment_in_order(assignment)?;
 			}
 
-			core_indices
+			(core_indices, backers, relay_parent_numbers)
 		};
 
+		let mut candidate_receipt_with_backing_validator_indices =
+			Vec::with_capacity(candidates.len());
+
 		// one more sweep for actually writing to storage.
-		for (candidate, core) in candidates.into_iter().zip(core_indices.iter().cloned()) {
+		for (((candidate, core), backers), relay_parent_number) in candidates.into_iter()
+			.zip(core_indices.iter().cloned())
+			.zip(backers)
+			.zip(relay_parent_numbers)
+		{
 			let para_id = candidate.descriptor().para_id;
 
 			// initialize all availability votes to 0.
 			let availability_votes: BitVec<BitOrderLsb0, u8>
 				= bitvec::bitvec![BitOrderLsb0, u8; 0; validators.len()];
 
+			let receipt = candidate.candidate.to_plain();
+			candidate_receipt_with_backing_validator_indices.push((receipt.clone(), backers));
+
 			Self::deposit_event(Event::<T>::CandidateBacked(
-				candidate.candidate.to_plain(),
+				receipt,
 				candidate.candidate.commitments.head_data.clone(),
 			));
 
@@ -536,7 +630,10 @@ impl<T: Trait> Module<T> {
 			<PendingAvailabilityCommitments>::insert(&para_id, commitments);
 		}
 
-		Ok(core_indices)
+		Ok(ProcessedCandidates {
+			core_indices,
+			candidate_receipt_with_backing_validator_indices,
+		})
 	}
 
 	fn enact_candidate(
@@ -557,6 +654,19 @@ impl<T: Trait> Module<T> {
 			);
 		}
 
+		weight += <router::Module<T>>::queue_outbound_hrmp(
+			receipt.descriptor.para_id,
+			commitments.horizontal_messages,
+		);
+		weight += <router::Module<T>>::enact_upward_messages(
+			receipt.descriptor.para_id,
+			commitments.upward_messages,
+		);
+		weight += <router::Module<T>>::prune_hrmp(
+			receipt.descriptor.para_id,
+			commitments.hrmp_watermark.into(),
+		);
+
 		Self::deposit_event(
 			Event::<T>::CandidateIncluded(plain, commitments.head_data.clone())
 		);
@@ -654,6 +764,70 @@ const fn availability_threshold(n_validators: usize) -> usize {
 	threshold
 }
 
+mod migration {
+	use super::*;
+	use frame_support::storage::{unhashed, StorageMap};
+	use primitives::v1::{
+		Balance, Hash, UpwardMessage, OutboundHrmpMessage, ValidationCode,
+	};
+
+	/// The layout of [`CandidateCommitments`] before the HRMP watermark was added.
+	#[derive(Encode, Decode)]
+	pub(super) struct OldCandidateCommitments {
+		pub fees: Balance,
+		pub upward_messages: Vec<UpwardMessage>,
+		pub horizontal_messages: Vec<OutboundHrmpMessage<ParaId>>,
+		pub erasure_root: Hash,
+		pub new_validation_code: Option<ValidationCode>,
+		pub head_data: HeadData,
+	}
+
+	impl OldCandidateCommitments {
+		/// Convert to the current layout. The candidate didn't commit to processing any inbound
+		/// horizontal messages, so its watermark is zero and enacting it prunes nothing.
+		fn migrate(self) -> CandidateCommitments {
+			CandidateCommitments {
+				fees: self.fees,
+				upward_messages: self.upward_messages,
+				horizontal_messages: self.horizontal_messages,
+				erasure_root: self.erasure_root,
+				new_validation_code: self.new_validation_code,
+				head_data: self.head_data,
+				hrmp_watermark: 0,
+			}
+		}
+	}
+
+	/// Translate the commitments of the candidates pending availability which are still in the
+	/// layout before the HRMP watermark.
+	///
+	/// The watermark is the last field of [`CandidateCommitments`], so commitments in the old layout
+	/// never decode in the current one, and those in the current one are left untouched. This
+	/// makes the migration a no-op once done.
+	pub(super) fn migrate_pending_commitments<T: Trait>() -> Weight {
+		let mut weight = 0;
+
+		for (para_id, _) in <PendingAvailability<T>>::iter() {
+			let key = <PendingAvailabilityCommitments>::hashed_key_for(&para_id);
+			weight += T::DbWeight::get().reads(2);
+
+			let raw = match unhashed::get_raw(&key) {
+				Some(raw) => raw,
+				None => continue,
+			};
+			if CandidateCommitments::decode(&mut &raw[..]).is_ok() {
+				continue;
+			}
+			if let Ok(old) = OldCandidateCommitments::decode(&mut &raw[..]) {
+				<PendingAvailabilityCommitments>::insert(&para_id, old.migrate());
+				weight += T::DbWeight::get().writes(1);
+			}
+		}
+
+		weight
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -662,8 +836,9 @@ mod tests {
 	use primitives::v1::{
 		SignedAvailabilityBitfield, CompactStatement as Statement, ValidityAttestation, CollatorId,
 		CandidateCommitments, SignedStatement, CandidateDescriptor, ValidationCode,
+		UpwardMessage, ParachainDispatchOrigin,
 	};
-	use frame_support::traits::{OnFinalize, OnInitialize};
+	use frame_support::traits::{OnFinalize, OnInitialize, OnRuntimeUpgrade};
 	use keyring::Sr25519Keyring;
 
 	use crate::mock::{
@@ -678,6 +853,8 @@ mod tests {
 	fn default_config() -> HostConfiguration<BlockNumber> {
 		let mut config = HostConfiguration::default();
 		config.parathread_cores = 1;
+		config.max_upward_message_num_per_candidate = 1;
+		config.max_upward_message_size_per_candidate = 4;
 		config
 	}
 
@@ -839,6 +1016,9 @@ mod tests {
 		)
 	}
 
+	/// The number of the parent block the tests back candidates in, after `run_to_block(5, ..)`.
+	const RELAY_PARENT_NUM: BlockNumber = 4;
+
 	#[derive(Default)]
 	struct TestCandidateBuilder {
 		para_id: ParaId,
@@ -847,6 +1027,8 @@ mod tests {
 		relay_parent: Hash,
 		persisted_validation_data_hash: Hash,
 		new_validation_code: Option<ValidationCode>,
+		upward_messages: Vec<UpwardMessage>,
+		hrmp_watermark: BlockNumber,
 	}
 
 	impl TestCandidateBuilder {
@@ -862,6 +1044,8 @@ mod tests {
 				commitments: CandidateCommitments {
 					head_data: self.head_data,
 					new_validation_code: self.new_validation_code,
+					upward_messages: self.upward_messages,
+					hrmp_watermark: self.hrmp_watermark,
 					..Default::default()
 				},
 			}
@@ -1299,6 +1483,7 @@ mod tests {
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
 					..Default::default()
@@ -1331,6 +1516,7 @@ mod tests {
 				let mut candidate_a = TestCandidateBuilder {
 					para_id: chain_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
 					..Default::default()
@@ -1338,6 +1524,7 @@ mod tests {
 				let mut candidate_b = TestCandidateBuilder {
 					para_id: chain_b,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([2; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_b).unwrap(),
 					..Default::default()
@@ -1385,6 +1572,7 @@ mod tests {
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
 					..Default::default()
@@ -1452,6 +1640,7 @@ mod tests {
 				let mut candidate = TestCandidateBuilder {
 					para_id: thread_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(thread_a).unwrap(),
 					..Default::default()
@@ -1490,6 +1679,7 @@ mod tests {
 				let mut candidate = TestCandidateBuilder {
 					para_id: thread_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(thread_a).unwrap(),
 					..Default::default()
@@ -1527,6 +1717,7 @@ mod tests {
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
 					..Default::default()
@@ -1573,6 +1764,7 @@ mod tests {
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
 					..Default::default()
@@ -1611,6 +1803,7 @@ mod tests {
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					new_validation_code: Some(vec![5, 6, 7, 8].into()),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1648,11 +1841,87 @@ mod tests {
 				);
 			}
 
+			let upward_message = |data: Vec<u8>| UpwardMessage {
+				origin: ParachainDispatchOrigin::Parachain,
+				data,
+			};
+
+			// too many upward messages - reject
+			{
+				let mut candidate = TestCandidateBuilder {
+					para_id: chain_b,
+					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
+					pov_hash: Hash::from([1; 32]),
+					persisted_validation_data_hash: make_vdata_hash(chain_b).unwrap(),
+					upward_messages: vec![upward_message(vec![1]), upward_message(vec![2])],
+					..Default::default()
+				}.build();
+
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let backed = back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(1)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				);
+
+				assert_eq!(
+					Inclusion::process_candidates(
+						vec![backed],
+						vec![chain_b_assignment.clone()],
+						&group_validators,
+					),
+					Err(Error::<Test>::UpwardMessagesCountExceeded.into()),
+				);
+			}
+
+			// upward messages too large - reject
+			{
+				let mut candidate = TestCandidateBuilder {
+					para_id: chain_b,
+					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
+					pov_hash: Hash::from([1; 32]),
+					persisted_validation_data_hash: make_vdata_hash(chain_b).unwrap(),
+					upward_messages: vec![upward_message(vec![1, 2, 3, 4, 5])],
+					..Default::default()
+				}.build();
+
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let backed = back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(1)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				);
+
+				assert_eq!(
+					Inclusion::process_candidates(
+						vec![backed],
+						vec![chain_b_assignment.clone()],
+						&group_validators,
+					),
+					Err(Error::<Test>::UpwardMessagesSizeExceeded.into()),
+				);
+			}
+
 			// Bad validation data hash - reject
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
 					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: [42u8; 32].into(),
 					..Default::default()
@@ -1680,6 +1949,40 @@ mod tests {
 					Err(Error::<Test>::ValidationDataHashMismatch.into()),
 				);
 			}
+
+			// HRMP watermark ahead of the relay parent - reject
+			{
+				let mut candidate = TestCandidateBuilder {
+					para_id: chain_a,
+					relay_parent: System::parent_hash(),
+					hrmp_watermark: RELAY_PARENT_NUM + 1,
+					pov_hash: Hash::from([1; 32]),
+					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
+					..Default::default()
+				}.build();
+
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let backed = back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(0)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				);
+
+				assert_eq!(
+					Inclusion::process_candidates(
+						vec![backed],
+						vec![chain_a_assignment.clone()],
+						&group_validators,
+					),
+					Err(router::Error::<Test>::HrmpWatermarkAheadOfRelayParent.into()),
+				);
+			}
 		});
 	}
 
@@ -1743,6 +2046,7 @@ mod tests {
 			let mut candidate_a = TestCandidateBuilder {
 				para_id: chain_a,
 				relay_parent: System::parent_hash(),
+				hrmp_watermark: RELAY_PARENT_NUM,
 				pov_hash: Hash::from([1; 32]),
 				persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
 				..Default::default()
@@ -1755,6 +2059,7 @@ mod tests {
 			let mut candidate_b = TestCandidateBuilder {
 				para_id: chain_b,
 				relay_parent: System::parent_hash(),
+				hrmp_watermark: RELAY_PARENT_NUM,
 				pov_hash: Hash::from([2; 32]),
 				persisted_validation_data_hash: make_vdata_hash(chain_b).unwrap(),
 				..Default::default()
@@ -1767,6 +2072,7 @@ mod tests {
 			let mut candidate_c = TestCandidateBuilder {
 				para_id: thread_a,
 				relay_parent: System::parent_hash(),
+				hrmp_watermark: RELAY_PARENT_NUM,
 				pov_hash: Hash::from([3; 32]),
 				persisted_validation_data_hash: make_vdata_hash(thread_a).unwrap(),
 				..Default::default()
@@ -1800,7 +2106,10 @@ mod tests {
 				BackingKind::Threshold,
 			);
 
-			let occupied_cores = Inclusion::process_candidates(
+			let ProcessedCandidates {
+				core_indices: occupied_cores,
+				candidate_receipt_with_backing_validator_indices,
+			} = Inclusion::process_candidates(
 				vec![backed_a, backed_b, backed_c],
 				vec![
 					chain_a_assignment.clone(),
@@ -1811,6 +2120,14 @@ mod tests {
 			).expect("candidates scheduled, in order, and backed");
 
 			assert_eq!(occupied_cores, vec![CoreIndex::from(0), CoreIndex::from(1), CoreIndex::from(2)]);
+			assert_eq!(
+				candidate_receipt_with_backing_validator_indices,
+				vec![
+					(candidate_a.to_plain(), vec![0, 1]),
+					(candidate_b.to_plain(), vec![2, 3]),
+					(candidate_c.to_plain(), vec![4]),
+				],
+			);
 
 			assert_eq!(
 				<PendingAvailability<Test>>::get(&chain_a),
@@ -1899,6 +2216,7 @@ mod tests {
 			let mut candidate_a = TestCandidateBuilder {
 				para_id: chain_a,
 				relay_parent: System::parent_hash(),
+				hrmp_watermark: RELAY_PARENT_NUM,
 				pov_hash: Hash::from([1; 32]),
 				persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
 				new_validation_code: Some(vec![1, 2, 3].into()),
@@ -1923,7 +2241,7 @@ mod tests {
 					chain_a_assignment.clone(),
 				],
 				&group_validators,
-			).expect("candidates scheduled, in order, and backed");
+			).expect("candidates scheduled, in order, and backed").core_indices;
 
 			assert_eq!(occupied_cores, vec![CoreIndex::from(0)]);
 
@@ -1944,6 +2262,146 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn can_include_candidate_built_on_allowed_ancestor() {
+		let chain_a = ParaId::from(1);
+
+		let paras = vec![(chain_a, true)];
+		let validators = vec![
+			Sr25519Keyring::Alice,
+			Sr25519Keyring::Bob,
+			Sr25519Keyring::Charlie,
+			Sr25519Keyring::Dave,
+			Sr25519Keyring::Ferdie,
+		];
+		let validator_public = validator_pubkeys(&validators);
+
+		let mut genesis = genesis_config(paras);
+		genesis.configuration.config.allowed_ancestry_len = 1;
+
+		new_test_ext(genesis).execute_with(|| {
+			Validators::set(validator_public.clone());
+			CurrentSessionIndex::set(5);
+
+			for b in 2..=5 {
+				System::set_parent_hash([b as u8; 32].into());
+				run_to_block(b, |_| None);
+			}
+
+			assert_eq!(
+				Inclusion::allowed_relay_parents(),
+				vec![(Hash::from([5; 32]), 4), (Hash::from([4; 32]), 3)],
+			);
+
+			let group_validators = |group_index: GroupIndex| match group_index {
+				group_index if group_index == GroupIndex::from(0) => Some(vec![0, 1, 2, 3, 4]),
+				_ => panic!("Group index out of bounds for 1 parachain"),
+			};
+
+			let chain_a_assignment = CoreAssignment {
+				core: CoreIndex::from(0),
+				para_id: chain_a,
+				kind: AssignmentKind::Parachain,
+				group_idx: GroupIndex::from(0),
+			};
+
+			let make_backed_signed_at = |
+				relay_parent: Hash,
+				relay_parent_number: BlockNumber,
+				signing_parent: Hash,
+			| {
+				let persisted_validation_data = crate::util::make_persisted_validation_data_at::<Test>(
+					chain_a,
+					relay_parent_number,
+				).unwrap();
+
+				let mut candidate = TestCandidateBuilder {
+					para_id: chain_a,
+					relay_parent,
+					pov_hash: Hash::from([1; 32]),
+					hrmp_watermark: relay_parent_number,
+					persisted_validation_data_hash: persisted_validation_data.hash(),
+					..Default::default()
+				}.build();
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let signing_context = SigningContext {
+					parent_hash: signing_parent,
+					session_index: 5,
+				};
+
+				back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(0)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				)
+			};
+
+			// the backing is signed in the context of the parent block.
+			let make_backed = |relay_parent: Hash, relay_parent_number: BlockNumber| {
+				make_backed_signed_at(relay_parent, relay_parent_number, Hash::from([5; 32]))
+			};
+
+			assert_eq!(
+				Inclusion::process_candidates(
+					vec![make_backed_signed_at(Hash::from([4; 32]), 3, Hash::from([4; 32]))],
+					vec![chain_a_assignment.clone()],
+					&group_validators,
+				),
+				Err(Error::<Test>::InvalidBacking.into()),
+			);
+
+			// too old.
+			assert_eq!(
+				Inclusion::process_candidates(
+					vec![make_backed(Hash::from([3; 32]), 2)],
+					vec![chain_a_assignment.clone()],
+					&group_validators,
+				),
+				Err(Error::<Test>::CandidateNotInParentContext.into()),
+			);
+
+			let backed = make_backed(Hash::from([4; 32]), 3);
+			let descriptor = backed.candidate.descriptor.clone();
+
+			let occupied_cores = Inclusion::process_candidates(
+				vec![backed],
+				vec![chain_a_assignment.clone()],
+				&group_validators,
+			).expect("candidate built on allowed ancestor").core_indices;
+
+			assert_eq!(occupied_cores, vec![CoreIndex::from(0)]);
+			assert_eq!(
+				<PendingAvailability<Test>>::get(&chain_a),
+				Some(CandidatePendingAvailability {
+					core: CoreIndex::from(0),
+					descriptor,
+					availability_votes: default_availability_votes(),
+					relay_parent_number: 3,
+					backed_in_number: System::block_number(),
+				})
+			);
+
+			// ancestors from the previous session are forgotten.
+			System::set_parent_hash([6; 32].into());
+			run_to_block(6, |_| Some(SessionChangeNotification {
+				validators: validator_public.clone(),
+				queued: Vec::new(),
+				prev_config: default_config(),
+				new_config: default_config(),
+				random_seed: Default::default(),
+				session_index: 6,
+			}));
+
+			assert_eq!(Inclusion::allowed_relay_parents(), vec![(Hash::from([6; 32]), 5)]);
+		});
+	}
+
 	#[test]
 	fn session_change_wipes_and_updates_session_info() {
 		let chain_a = ParaId::from(1);
@@ -2060,4 +2518,58 @@ mod tests {
 			assert!(<PendingAvailabilityCommitments>::iter().collect::<Vec<_>>().is_empty());
 		});
 	}
+
+	#[test]
+	fn pending_commitments_are_migrated() {
+		let chain_a = ParaId::from(1);
+		let chain_b = ParaId::from(2);
+
+		let paras = vec![(chain_a, true), (chain_b, true)];
+		new_test_ext(genesis_config(paras)).execute_with(|| {
+			let candidate = TestCandidateBuilder {
+				head_data: vec![1, 2, 3].into(),
+				hrmp_watermark: 3,
+				..Default::default()
+			}.build();
+
+			for &para_id in &[chain_a, chain_b] {
+				<PendingAvailability<Test>>::insert(para_id, CandidatePendingAvailability {
+					core: CoreIndex::from(0),
+					descriptor: candidate.descriptor.clone(),
+					availability_votes: default_availability_votes(),
+					relay_parent_number: 0,
+					backed_in_number: 0,
+				});
+			}
+
+			let old = migration::OldCandidateCommitments {
+				fees: 0,
+				upward_messages: Vec::new(),
+				horizontal_messages: Vec::new(),
+				erasure_root: Default::default(),
+				new_validation_code: None,
+				head_data: vec![4, 5, 6].into(),
+			};
+			frame_support::storage::unhashed::put(
+				&<PendingAvailabilityCommitments>::hashed_key_for(&chain_a),
+				&old,
+			);
+			PendingAvailabilityCommitments::insert(chain_b, candidate.commitments.clone());
+
+			Inclusion::on_runtime_upgrade();
+
+			let migrated = PendingAvailabilityCommitments::get(&chain_a).unwrap();
+			assert_eq!(migrated.head_data, HeadData::from(vec![4, 5, 6]));
+			assert_eq!(migrated.hrmp_watermark, 0);
+			assert_eq!(
+				PendingAvailabilityCommitments::get(&chain_b),
+				Some(candidate.commitments.clone()),
+			);
+
+			// running the migration again leaves the commitments untouched.
+			Inclusion::on_runtime_upgrade();
+			assert_eq!(PendingAvailabilityCommitments::get(&chain_a), Some(migrated));
+			assert_eq!(PendingAvailabilityCommitments::get(&chain_b), Some(candidate.commitments));
+		});
+	}
 }
@@ -23,7 +23,7 @@
 
 use sp_std::prelude::*;
 use primitives::v1::{
-	BackedCandidate, SignedAvailabilityBitfields, INCLUSION_INHERENT_IDENTIFIER,
+	BackedCandidate, SignedAvailabilityBitfields, ScrapedOnChainVotes, INCLUSION_INHERENT_IDENTIFIER,
 };
 use frame_support::{
 	decl_error, decl_module, decl_storage, ensure,
@@ -49,6 +49,10 @@ decl_storage! {
 		///
 		/// If this is `None` at the end of the block, we panic and render the block invalid.
 		Included: Option<()>;
+
+		/// The backing votes processed by the inclusion inherent of this block, so that nodes can
+		/// import votes which made it on-chain.
+		OnChainVotes get(fn on_chain_votes): Option<ScrapedOnChainVotes<T::Hash>>;
 	}
 }
 
@@ -106,7 +110,10 @@ decl_module! {
 			<scheduler::Module<T>>::schedule(freed.collect());
 
 			// Process backed candidates according to scheduled cores.
-			let occupied = <inclusion::Module<T>>::process_candidates(
+			let inclusion::ProcessedCandidates {
+				core_indices: occupied,
+				candidate_receipt_with_backing_validator_indices,
+			} = <inclusion::Module<T>>::process_candidates(
 				backed_candidates,
 				<scheduler::Module<T>>::scheduled(),
 				<scheduler::Module<T>>::group_validators,
@@ -115,6 +122,12 @@ decl_module! {
 			// Note which of the scheduled cores were actually occupied by a backed candidate.
 			<scheduler::Module<T>>::occupied(&occupied);
 
+			// Record the backing votes for nodes to scrape.
+			OnChainVotes::<T>::put(ScrapedOnChainVotes {
+				session: <inclusion::Module<T>>::session_index(),
+				backing_validators_per_candidate: candidate_receipt_with_backing_validator_indices,
+			});
+
 			// And track that we've finished processing the inherent for this block.
 			Included::set(Some(()));
 
@@ -141,4 +154,99 @@ impl<T: Trait> ProvideInherent for Module<T> {
 				}
 			})
 	}
+
+	fn check_inherent(call: &Self::Call, _: &InherentData) -> Result<(), Self::Error> {
+		match call {
+			Call::inclusion(signed_bitfields, backed_candidates)
+				if !has_valid_structure(signed_bitfields, backed_candidates) => Err(().into()),
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Cheap structural checks of the inclusion inherent, which don't need any state and
+/// so can reject a malformed inherent before the block is executed.
+///
+/// Passing these checks doesn't mean the inherent is valid.
+fn has_valid_structure<H: PartialEq>(
+	signed_bitfields: &SignedAvailabilityBitfields,
+	backed_candidates: &[BackedCandidate<H>],
+) -> bool {
+	// bitfields are sorted by validator index, without duplicates, and all have the same size.
+	let bitfields_ordered = signed_bitfields.windows(2).all(|w|
+		w[0].validator_index() < w[1].validator_index()
+			&& w[0].payload().0.len() == w[1].payload().0.len()
+	);
+
+	// there is at most one candidate per para, and each has a vote for every
+	// validator it claims is backing it.
+	let candidates_unique = backed_candidates.iter().enumerate().all(|(i, c)|
+		backed_candidates[..i].iter().all(|prev| prev.descriptor().para_id != c.descriptor().para_id)
+	);
+	let votes_match = backed_candidates.iter().all(|c|
+		c.validator_indices.count_ones() == c.validity_votes.len()
+	);
+
+	bitfields_ordered && candidates_unique && votes_match
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use primitives::v1::{
+		AvailabilityBitfield, CommittedCandidateReceipt, Hash, Id as ParaId, SignedAvailabilityBitfield,
+		SigningContext, ValidityAttestation,
+	};
+	use keyring::Sr25519Keyring;
+	use bitvec::order::Lsb0;
+
+	fn bitfield(validator_index: u32, bits: usize) -> SignedAvailabilityBitfield {
+		SignedAvailabilityBitfield::sign(
+			AvailabilityBitfield(bitvec::bitvec![Lsb0, u8; 0; bits]),
+			&SigningContext { parent_hash: Hash::zero(), session_index: 0 },
+			validator_index,
+			&Sr25519Keyring::Alice.pair().into(),
+		)
+	}
+
+	fn candidate(para_id: u32, n_votes: usize) -> BackedCandidate<Hash> {
+		let mut candidate = CommittedCandidateReceipt::default();
+		candidate.descriptor.para_id = ParaId::from(para_id);
+
+		BackedCandidate {
+			candidate,
+			validity_votes: vec![ValidityAttestation::Implicit(Sr25519Keyring::Alice.sign(&[]).into()); n_votes],
+			validator_indices: bitvec::bitvec![Lsb0, u8; 1; n_votes],
+		}
+	}
+
+	#[test]
+	fn well_formed_inherent_passes() {
+		assert!(has_valid_structure::<Hash>(&Vec::new(), &[]));
+		assert!(has_valid_structure(
+			&vec![bitfield(0, 4), bitfield(2, 4)],
+			&[candidate(1, 2), candidate(2, 3)],
+		));
+	}
+
+	#[test]
+	fn unordered_or_duplicate_bitfields_are_rejected() {
+		assert!(!has_valid_structure::<Hash>(&vec![bitfield(2, 4), bitfield(0, 4)], &[]));
+		assert!(!has_valid_structure::<Hash>(&vec![bitfield(1, 4), bitfield(1, 4)], &[]));
+		assert!(!has_valid_structure::<Hash>(&vec![bitfield(0, 4), bitfield(1, 5)], &[]));
+	}
+
+	#[test]
+	fn duplicate_candidates_are_rejected() {
+		assert!(!has_valid_structure(&Vec::new(), &[candidate(1, 2), candidate(1, 2)]));
+	}
+
+	#[test]
+	fn candidates_with_missing_votes_are_rejected() {
+		let mut c = candidate(1, 2);
+		c.validity_votes.pop();
+
+		assert!(!has_valid_structure(&Vec::new(), &[c]));
+	}
 }
@@ -21,13 +21,13 @@
 
 use sp_std::prelude::*;
 use frame_support::weights::Weight;
-use primitives::v1::ValidatorId;
+use primitives::v1::{ValidatorId, ValidatorIndex};
 use frame_support::{
 	decl_storage, decl_module, decl_error, traits::Randomness,
 };
 use sp_runtime::traits::One;
 use codec::{Encode, Decode};
-use crate::{configuration::{self, HostConfiguration}, paras, scheduler, inclusion};
+use crate::{configuration::{self, HostConfiguration}, paras, router, scheduler, inclusion};
 
 /// Information about a session change that has just occurred.
 #[derive(Default, Clone)]
@@ -55,7 +55,8 @@ struct BufferedSessionChange<N> {
 }
 
 pub trait Trait:
-	frame_system::Trait + configuration::Trait + paras::Trait + scheduler::Trait + inclusion::Trait
+	frame_system::Trait + configuration::Trait + paras::Trait + router::Trait + scheduler::Trait +
+	inclusion::Trait
 {
 	/// A randomness beacon.
 	type Randomness: Randomness<Self::Hash>;
@@ -80,6 +81,10 @@ decl_storage! {
 		/// However this is a `Vec` regardless to handle various edge cases that may occur at runtime
 		/// upgrade boundaries or if governance intervenes.
 		BufferedSessionChanges: Vec<BufferedSessionChange<T::BlockNumber>>;
+		/// The indices, within the session's full validator set, of the validators taking part in
+		/// parachain consensus during the current session. Ordered the same as the validators
+		/// known to the other parachains modules.
+		ActiveValidatorIndices get(fn active_validator_indices): Vec<ValidatorIndex>;
 	}
 }
 
@@ -95,27 +100,28 @@ decl_module! {
 		fn on_initialize(now: T::BlockNumber) -> Weight {
 			// Apply buffered session changes before initializing modules, so they
 			// can be initialized with respect to the current validator set.
-			<BufferedSessionChanges<T>>::mutate(|v| {
+			let session_change_weight = <BufferedSessionChanges<T>>::mutate(|v| {
 				let drain_up_to = v.iter().take_while(|b| b.apply_at <= now).count();
 
 				// apply only the last session as all others lasted less than a block (weirdly).
-				if let Some(buffered) = v.drain(..drain_up_to).last() {
-					Self::apply_new_session(
-						buffered.session_index,
-						buffered.validators,
-						buffered.queued,
-					);
-				}
+				v.drain(..drain_up_to).last().map_or(0, |buffered| Self::apply_new_session(
+					buffered.session_index,
+					buffered.validators,
+					buffered.queued,
+				))
 			});
 
 			// The other modules are initialized in this order:
 			// - Configuration
 			// - Paras
+			// - Router
 			// - Scheduler
 			// - Inclusion
 			// - Validity
-			let total_weight = configuration::Module::<T>::initializer_initialize(now) +
+			let total_weight = session_change_weight +
+				configuration::Module::<T>::initializer_initialize(now) +
 				paras::Module::<T>::initializer_initialize(now) +
+				router::Module::<T>::initializer_initialize(now) +
 				scheduler::Module::<T>::initializer_initialize(now) +
 				inclusion::Module::<T>::initializer_initialize(now);
 
@@ -129,6 +135,7 @@ decl_module! {
 
 			inclusion::Module::<T>::initializer_finalize();
 			scheduler::Module::<T>::initializer_finalize();
+			router::Module::<T>::initializer_finalize();
 			paras::Module::<T>::initializer_finalize();
 			configuration::Module::<T>::initializer_finalize();
 			HasInitialized::take();
@@ -139,9 +146,9 @@ decl_module! {
 impl<T: Trait> Module<T> {
 	fn apply_new_session(
 		session_index: sp_staking::SessionIndex,
-		validators: Vec<ValidatorId>,
-		queued: Vec<ValidatorId>,
-	) {
+		mut validators: Vec<ValidatorId>,
+		mut queued: Vec<ValidatorId>,
+	) -> Weight {
 		let prev_config = <configuration::Module<T>>::config();
 
 		let random_seed = {
@@ -158,6 +165,13 @@ impl<T: Trait> Module<T> {
 
 		let new_config = <configuration::Module<T>>::config();
 
+		// Only a subset of the session's validators may take part in parachain consensus.
+		if let Some(max) = new_config.max_validators {
+			validators.truncate(max as usize);
+			queued.truncate(max as usize);
+		}
+		ActiveValidatorIndices::set((0..validators.len() as ValidatorIndex).collect());
+
 		let notification = SessionChangeNotification {
 			validators,
 			queued,
@@ -167,9 +181,13 @@ impl<T: Trait> Module<T> {
 			session_index,
 		};
 
-		paras::Module::<T>::initializer_on_new_session(&notification);
+		let outgoing_paras = paras::Module::<T>::initializer_on_new_session(&notification);
+		let router_weight =
+			router::Module::<T>::initializer_on_new_session(&notification, &outgoing_paras);
 		scheduler::Module::<T>::initializer_on_new_session(&notification);
 		inclusion::Module::<T>::initializer_on_new_session(&notification);
+
+		router_weight
 	}
 
 	/// Should be called when a new session occurs. Buffers the session notification to be applied
@@ -268,6 +286,35 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn session_change_limits_active_validators() {
+		use keyring::Sr25519Keyring;
+		use crate::mock::{Configuration, Inclusion};
+
+		let mut genesis = crate::mock::GenesisConfig::default();
+		genesis.configuration.config.max_validators = Some(2);
+
+		new_test_ext(genesis).execute_with(|| {
+			Initializer::on_initialize(1);
+
+			let validators: Vec<ValidatorId> = [
+				Sr25519Keyring::Alice,
+				Sr25519Keyring::Bob,
+				Sr25519Keyring::Charlie,
+				Sr25519Keyring::Dave,
+			].iter().map(|k| k.public().into()).collect();
+			let accounts = [0u64, 1, 2, 3];
+			let session = || accounts.iter().zip(validators.iter().cloned());
+
+			Initializer::on_new_session(false, 1, session(), Some(session()));
+			Initializer::on_initialize(2);
+
+			assert_eq!(Configuration::config().max_validators, Some(2));
+			assert_eq!(Initializer::active_validator_indices(), vec![0, 1]);
+			assert_eq!(Inclusion::validators(), validators[..2].to_vec());
+		});
+	}
+
 	#[test]
 	fn sets_flag_on_initialize() {
 		new_test_ext(Default::default()).execute_with(|| {
@@ -23,16 +23,13 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use sp_std::result;
-use sp_runtime::traits::BadOrigin;
-use primitives::v1::Id as ParaId;
-use codec::{Decode, Encode};
-
 pub mod configuration;
 pub mod inclusion;
 pub mod inclusion_inherent;
 pub mod initializer;
+pub mod origin;
 pub mod paras;
+pub mod router;
 pub mod scheduler;
 pub mod validity;
 
@@ -43,21 +40,4 @@ mod util;
 #[cfg(test)]
 mod mock;
 
-/// Origin for the parachains.
-#[derive(PartialEq, Eq, Clone, Encode, Decode)]
-#[cfg_attr(feature = "std", derive(Debug))]
-pub enum Origin {
-	/// It comes from a parachain.
-	Parachain(ParaId),
-}
-
-/// Ensure that the origin `o` represents a parachain.
-/// Returns `Ok` with the parachain ID that effected the extrinsic or an `Err` otherwise.
-pub fn ensure_parachain<OuterOrigin>(o: OuterOrigin) -> result::Result<ParaId, BadOrigin>
-	where OuterOrigin: Into<result::Result<Origin, OuterOrigin>>
-{
-	match o.into() {
-		Ok(Origin::Parachain(id)) => Ok(id),
-		_ => Err(BadOrigin),
-	}
-}
+pub use origin::{Origin, ensure_parachain};
@@ -30,18 +30,24 @@ use frame_support::{
 	weights::Weight, traits::Randomness as RandomnessT,
 };
 use crate::inclusion;
+use crate::router;
+use crate::origin as parachains_origin;
 
 /// A test runtime struct.
 #[derive(Clone, Eq, PartialEq)]
 pub struct Test;
 
 impl_outer_origin! {
-	pub enum Origin for Test { }
+	pub enum Origin for Test {
+		parachains_origin
+	}
 }
 
 impl_outer_dispatch! {
 	pub enum Call for Test where origin: Origin {
 		initializer::Initializer,
+		pallet_balances::Balances,
+		router::Router,
 	}
 }
 
@@ -49,6 +55,8 @@ impl_outer_event! {
 	pub enum TestEvent for Test {
 		frame_system<T>,
 		inclusion<T>,
+		router,
+		pallet_balances<T>,
 	}
 }
 
@@ -65,6 +73,9 @@ parameter_types! {
 	pub const MaximumBlockWeight: Weight = 4 * 1024 * 1024;
 	pub const MaximumBlockLength: u32 = 4 * 1024 * 1024;
 	pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	pub const ExistentialDeposit: u128 = 1;
+	pub const HrmpSenderDeposit: u128 = 5;
+	pub const HrmpRecipientDeposit: u128 = 3;
 }
 
 impl frame_system::Trait for Test {
@@ -95,6 +106,15 @@ impl frame_system::Trait for Test {
 	type SystemWeightInfo = ();
 }
 
+impl pallet_balances::Trait for Test {
+	type Balance = u128;
+	type DustRemoval = ();
+	type Event = TestEvent;
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+}
+
 impl crate::initializer::Trait for Test {
 	type Randomness = TestRandomness;
 }
@@ -105,12 +125,26 @@ impl crate::paras::Trait for Test { }
 
 impl crate::scheduler::Trait for Test { }
 
+impl crate::origin::Trait for Test { }
+
+impl crate::router::Trait for Test {
+	type Event = TestEvent;
+	type Origin = Origin;
+	type Call = Call;
+	type Currency = Balances;
+	type HrmpSenderDeposit = HrmpSenderDeposit;
+	type HrmpRecipientDeposit = HrmpRecipientDeposit;
+}
+
 impl crate::inclusion::Trait for Test {
 	type Event = TestEvent;
 }
 
 pub type System = frame_system::Module<Test>;
 
+/// Mocked balances.
+pub type Balances = pallet_balances::Module<Test>;
+
 /// Mocked initializer.
 pub type Initializer = crate::initializer::Module<Test>;
 
@@ -123,6 +157,9 @@ pub type Paras = crate::paras::Module<Test>;
 /// Mocked scheduler.
 pub type Scheduler = crate::scheduler::Module<Test>;
 
+/// Mocked router.
+pub type Router = crate::router::Module<Test>;
+
 /// Mocked inclusion module.
 pub type Inclusion = crate::inclusion::Module<Test>;
 
@@ -0,0 +1,78 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The origin of calls dispatched on behalf of parachains, and a module which hosts it.
+//!
+//! Calls can be dispatched with the `Parachain` origin, e.g. from the upward messages of a para.
+//! Dispatchables which are only meant to be called by paras use `ensure_parachain` to obtain the
+//! id of the calling para.
+
+use sp_std::result;
+use sp_runtime::traits::BadOrigin;
+use primitives::v1::Id as ParaId;
+use frame_support::decl_module;
+use codec::{Decode, Encode};
+
+/// Origin for the parachains.
+#[derive(PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Origin {
+	/// It comes from a parachain.
+	Parachain(ParaId),
+}
+
+impl From<ParaId> for Origin {
+	fn from(id: ParaId) -> Origin {
+		Origin::Parachain(id)
+	}
+}
+
+/// Ensure that the origin `o` represents a parachain.
+/// Returns `Ok` with the parachain ID that effected the extrinsic or an `Err` otherwise.
+pub fn ensure_parachain<OuterOrigin>(o: OuterOrigin) -> result::Result<ParaId, BadOrigin>
+	where OuterOrigin: Into<result::Result<Origin, OuterOrigin>>
+{
+	match o.into() {
+		Ok(Origin::Parachain(id)) => Ok(id),
+		_ => Err(BadOrigin),
+	}
+}
+
+pub trait Trait: frame_system::Trait { }
+
+decl_module! {
+	/// The parachains origin module.
+	///
+	/// It has no calls or storage: `construct_runtime` can only register an origin along with the
+	/// module it belongs to, so this module exists to house the `Origin`.
+	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin { }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mock::Origin as TestOrigin;
+
+	#[test]
+	fn ensure_parachain_only_accepts_parachain_origins() {
+		let para_id = ParaId::from(5);
+
+		assert_eq!(ensure_parachain(TestOrigin::from(Origin::Parachain(para_id))), Ok(para_id));
+		assert_eq!(ensure_parachain(TestOrigin::root()), Err(BadOrigin));
+		assert_eq!(ensure_parachain(TestOrigin::signed(1)), Err(BadOrigin));
+		assert_eq!(ensure_parachain(TestOrigin::none()), Err(BadOrigin));
+	}
+}
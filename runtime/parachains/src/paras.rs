@@ -27,9 +27,9 @@ use sp_std::prelude::*;
 #[cfg(feature = "std")]
 use sp_std::marker::PhantomData;
 use primitives::v1::{
-	Id as ParaId, ValidationCode, HeadData,
+	Id as ParaId, ValidationCode, HeadData, Hash, validation_code_hash,
 };
-use sp_runtime::traits::One;
+use sp_runtime::traits::{One, Saturating};
 use frame_support::{
 	decl_storage, decl_module, decl_error,
 	traits::Get,
@@ -156,6 +156,21 @@ impl<N: Ord + Copy> ParaPastCodeMeta<N> {
 	}
 }
 
+/// The versions of the storage layout of the paras module.
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub enum Releases {
+	/// The original layout, without code indexed by its hash.
+	V0,
+	/// All current, future and past code is indexed by its hash in `CodeByHash`.
+	V1,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V0
+	}
+}
+
 /// Arguments for initializing a para.
 #[derive(PartialEq, Eq, Clone, Encode, Decode, RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
@@ -199,6 +214,12 @@ decl_storage! {
 		FutureCodeUpgrades get(fn future_code_upgrade_at): map hasher(twox_64_concat) ParaId => Option<T::BlockNumber>;
 		/// The actual future code of a para.
 		FutureCode: map hasher(twox_64_concat) ParaId => Option<ValidationCode>;
+		/// All current, future and past code, by its hash. Past code is available here until
+		/// it is pruned.
+		CodeByHash get(fn code_by_hash): map hasher(identity) Hash => Option<ValidationCode>;
+		/// The number of places in `CurrentCode`, `FutureCode` and `PastCode` holding the code
+		/// with a given hash. The code is removed from `CodeByHash` once this drops to zero.
+		CodeByHashRefs: map hasher(identity) Hash => u32;
 
 		/// Upcoming paras (chains and threads). These are only updated on session change. Corresponds to an
 		/// entry in the upcoming-genesis map.
@@ -207,7 +228,14 @@ decl_storage! {
 		UpcomingParasGenesis: map hasher(twox_64_concat) ParaId => Option<ParaGenesisArgs>;
 		/// Paras that are to be cleaned up at the end of the session.
 		OutgoingParas: Vec<ParaId>;
-
+		/// Parathreads that are to be upgraded to parachains at the end of the session.
+		UpcomingUpgrades get(fn upcoming_upgrades): Vec<ParaId>;
+		/// Parachains that are to be downgraded to parathreads at the end of the session.
+		UpcomingDowngrades get(fn upcoming_downgrades): Vec<ParaId>;
+
+		/// The version of the storage layout. Chains started before versioning, or without a
+		/// genesis config for this module, are at `V0`.
+		StorageVersion build(|_: &GenesisConfig<T>| Releases::V1): Releases;
 	}
 	add_extra_genesis {
 		config(paras): Vec<(ParaId, ParaGenesisArgs)>;
@@ -231,7 +259,11 @@ fn build<T: Trait>(config: &GenesisConfig<T>) {
 	Parachains::put(&parachains);
 
 	for (id, genesis_args) in &config.paras {
+		if !genesis_args.parachain {
+			<Module<T> as Store>::Parathreads::insert(&id, ());
+		}
 		<Module<T> as Store>::CurrentCode::insert(&id, &genesis_args.validation_code);
+		<Module<T>>::increase_code_ref(&genesis_args.validation_code);
 		<Module<T> as Store>::Heads::insert(&id, &genesis_args.genesis_head);
 	}
 }
@@ -244,6 +276,10 @@ decl_module! {
 	/// The parachains configuration module.
 	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
 		type Error = Error<T>;
+
+		fn on_runtime_upgrade() -> Weight {
+			migration::migrate_to_v1::<T>()
+		}
 	}
 }
 
@@ -257,19 +293,26 @@ impl<T: Trait> Module<T> {
 	pub(crate) fn initializer_finalize() { }
 
 	/// Called by the initializer to note that a new session has started.
-	pub(crate) fn initializer_on_new_session(_notification: &SessionChangeNotification<T::BlockNumber>) {
+	///
+	/// Returns the paras which were cleaned up at the session change.
+	pub(crate) fn initializer_on_new_session(
+		_notification: &SessionChangeNotification<T::BlockNumber>,
+	) -> Vec<ParaId> {
 		let now = <frame_system::Module<T>>::block_number();
-		let mut parachains = Self::clean_up_outgoing(now);
+		let (mut parachains, outgoing) = Self::clean_up_outgoing(now);
+		Self::apply_lifecycle_changes(&mut parachains);
 		Self::apply_incoming(&mut parachains);
 		<Self as Store>::Parachains::set(parachains);
+
+		outgoing
 	}
 
-	/// Cleans up all outgoing paras. Returns the new set of parachains
-	fn clean_up_outgoing(now: T::BlockNumber) -> Vec<ParaId> {
+	/// Cleans up all outgoing paras. Returns the new set of parachains and the outgoing paras.
+	fn clean_up_outgoing(now: T::BlockNumber) -> (Vec<ParaId>, Vec<ParaId>) {
 		let mut parachains = <Self as Store>::Parachains::get();
 		let outgoing = <Self as Store>::OutgoingParas::take();
 
-		for outgoing_para in outgoing {
+		for &outgoing_para in &outgoing {
 			if let Ok(i) = parachains.binary_search(&outgoing_para) {
 				parachains.remove(i);
 			} else {
@@ -278,7 +321,9 @@ impl<T: Trait> Module<T> {
 
 			<Self as Store>::Heads::remove(&outgoing_para);
 			<Self as Store>::FutureCodeUpgrades::remove(&outgoing_para);
-			<Self as Store>::FutureCode::remove(&outgoing_para);
+			if let Some(future_code) = <Self as Store>::FutureCode::take(&outgoing_para) {
+				Self::decrease_code_ref(&future_code);
+			}
 
 			let removed_code = <Self as Store>::CurrentCode::take(&outgoing_para);
 			if let Some(removed_code) = removed_code {
@@ -286,7 +331,26 @@ impl<T: Trait> Module<T> {
 			}
 		}
 
-		parachains
+		(parachains, outgoing)
+	}
+
+	/// Applies all scheduled upgrades and downgrades between parathreads and parachains. Paras
+	/// which are no longer a parathread (resp. parachain) by now are skipped.
+	fn apply_lifecycle_changes(parachains: &mut Vec<ParaId>) {
+		for upgraded in <Self as Store>::UpcomingUpgrades::take() {
+			if <Self as Store>::Parathreads::take(&upgraded).is_some() {
+				if let Err(i) = parachains.binary_search(&upgraded) {
+					parachains.insert(i, upgraded);
+				}
+			}
+		}
+
+		for downgraded in <Self as Store>::UpcomingDowngrades::take() {
+			if let Ok(i) = parachains.binary_search(&downgraded) {
+				parachains.remove(i);
+				<Self as Store>::Parathreads::insert(&downgraded, ());
+			}
+		}
 	}
 
 	/// Applies all incoming paras, updating the parachains list for those that are parachains.
@@ -307,6 +371,7 @@ impl<T: Trait> Module<T> {
 			}
 
 			<Self as Store>::Heads::insert(&upcoming_para, genesis_data.genesis_head);
+			Self::increase_code_ref(&genesis_data.validation_code);
 			<Self as Store>::CurrentCode::insert(&upcoming_para, genesis_data.validation_code);
 		}
 	}
@@ -331,9 +396,9 @@ impl<T: Trait> Module<T> {
 		<Self as Store>::PastCode::insert(&(id, at), old_code);
 
 		// Schedule pruning for this past-code to be removed as soon as it
-		// exits the slashing window.
+		// exits the retention window.
 		<Self as Store>::PastCodePruning::mutate(|pruning| {
-			let insert_idx = pruning.binary_search_by_key(&at, |&(_, b)| b)
+			let insert_idx = pruning.binary_search_by_key(&now, |&(_, b)| b)
 				.unwrap_or_else(|idx| idx);
 			pruning.insert(insert_idx, (id, now));
 		});
@@ -341,18 +406,19 @@ impl<T: Trait> Module<T> {
 		T::DbWeight::get().reads_writes(2, 3)
 	}
 
-	// looks at old code metadata, compares them to the current acceptance window, and prunes those
-	// that are too old.
+	// looks at old code metadata, compares them to the current retention window, and prunes those
+	// that are too old. Code is retained for the code retention period, but at least for the
+	// acceptance period, so that candidates executed with it can still be checked.
 	fn prune_old_code(now: T::BlockNumber) -> Weight {
 		let config = configuration::Module::<T>::config();
-		let acceptance_period = config.acceptance_period;
-		if now <= acceptance_period {
+		let retention_period = sp_std::cmp::max(config.acceptance_period, config.code_retention_period);
+		if now <= retention_period {
 			let weight = T::DbWeight::get().reads_writes(1, 0);
 			return weight;
 		}
 
 		// The height of any changes we no longer should keep around.
-		let pruning_height = now - (acceptance_period + One::one());
+		let pruning_height = now - (retention_period + One::one());
 
 		let pruning_tasks_done =
 			<Self as Store>::PastCodePruning::mutate(|pruning_tasks: &mut Vec<(_, T::BlockNumber)>| {
@@ -367,7 +433,9 @@ impl<T: Trait> Module<T> {
 				for (para_id, _) in pruning_tasks_to_do {
 					let full_deactivate = <Self as Store>::PastCodeMeta::mutate(&para_id, |meta| {
 						for pruned_repl_at in meta.prune_up_to(pruning_height) {
-							<Self as Store>::PastCode::remove(&(para_id, pruned_repl_at));
+							if let Some(code) = <Self as Store>::PastCode::take(&(para_id, pruned_repl_at)) {
+								Self::decrease_code_ref(&code);
+							}
 						}
 
 						meta.most_recent_change().is_none() && Self::para_head(&para_id).is_none()
@@ -383,9 +451,40 @@ impl<T: Trait> Module<T> {
 				pruning_tasks_done as u64
 			});
 
-		// 1 read for the meta for each pruning task, 1 read for the config
-		// 2 writes: updating the meta and pruning the code
-		T::DbWeight::get().reads_writes(1 + pruning_tasks_done, 2 * pruning_tasks_done)
+		// 1 read for the meta and 1 for the code refs for each pruning task, 1 read for the config
+		// 4 writes: updating the meta, pruning the code and updating the code by hash
+		T::DbWeight::get().reads_writes(1 + 2 * pruning_tasks_done, 4 * pruning_tasks_done)
+	}
+
+	// note that the given code is now held in one more place.
+	fn increase_code_ref(code: &ValidationCode) -> Weight {
+		let hash = validation_code_hash(code);
+		let refs = <Self as Store>::CodeByHashRefs::mutate(&hash, |refs| {
+			*refs += 1;
+			*refs
+		});
+
+		if refs == 1 {
+			<Self as Store>::CodeByHash::insert(&hash, code);
+		}
+
+		T::DbWeight::get().reads_writes(1, 2)
+	}
+
+	// note that the given code is held in one place less, removing it
+	// if it isn't held anywhere anymore.
+	fn decrease_code_ref(code: &ValidationCode) -> Weight {
+		let hash = validation_code_hash(code);
+		let refs = <Self as Store>::CodeByHashRefs::get(&hash);
+
+		if refs <= 1 {
+			<Self as Store>::CodeByHashRefs::remove(&hash);
+			<Self as Store>::CodeByHash::remove(&hash);
+		} else {
+			<Self as Store>::CodeByHashRefs::insert(&hash, refs - 1);
+		}
+
+		T::DbWeight::get().reads_writes(1, 2)
 	}
 
 	/// Schedule a para to be initialized at the start of the next session.
@@ -437,6 +536,46 @@ impl<T: Trait> Module<T> {
 		outgoing_weight + upcoming_weight
 	}
 
+	/// Schedule a parathread to be upgraded to a parachain at the start of the next session.
+	///
+	/// Any pending downgrade of the same para is cancelled. Returns `false` if the para is not
+	/// currently a parathread, in which case no upgrade is scheduled.
+	pub fn schedule_parathread_upgrade(id: ParaId) -> bool {
+		UpcomingDowngrades::mutate(|v| if let Ok(i) = v.binary_search(&id) {
+			v.remove(i);
+		});
+
+		if !Self::is_parathread(id) {
+			return false;
+		}
+
+		UpcomingUpgrades::mutate(|v| if let Err(i) = v.binary_search(&id) {
+			v.insert(i, id);
+		});
+
+		true
+	}
+
+	/// Schedule a parachain to be downgraded to a parathread at the start of the next session.
+	///
+	/// Any pending upgrade of the same para is cancelled. Returns `false` if the para is not
+	/// currently a parachain, in which case no downgrade is scheduled.
+	pub fn schedule_parachain_downgrade(id: ParaId) -> bool {
+		UpcomingUpgrades::mutate(|v| if let Ok(i) = v.binary_search(&id) {
+			v.remove(i);
+		});
+
+		if !Self::is_parachain(id) {
+			return false;
+		}
+
+		UpcomingDowngrades::mutate(|v| if let Err(i) = v.binary_search(&id) {
+			v.insert(i, id);
+		});
+
+		true
+	}
+
 	/// Schedule a future code upgrade of the given parachain, to be applied after inclusion
 	/// of a block of the same parachain executed in the context of a relay-chain block
 	/// with number >= `expected_at`
@@ -452,8 +591,9 @@ impl<T: Trait> Module<T> {
 				T::DbWeight::get().reads_writes(1, 0)
 			} else {
 				*up = Some(expected_at);
+				let weight = Self::increase_code_ref(&new_code);
 				FutureCode::insert(&id, new_code);
-				T::DbWeight::get().reads_writes(1, 2)
+				weight + T::DbWeight::get().reads_writes(1, 2)
 			}
 		})
 	}
@@ -504,7 +644,6 @@ impl<T: Trait> Module<T> {
 	///
 	/// `assume_intermediate`, if provided, must be before `at`. This will return `None` if the validation
 	/// code has been pruned.
-	#[allow(unused)]
 	pub(crate) fn validation_code_at(
 		id: ParaId,
 		at: T::BlockNumber,
@@ -535,10 +674,15 @@ impl<T: Trait> Module<T> {
 	}
 
 	/// Whether a para ID corresponds to any live parathread.
-	pub(crate) fn is_parathread(id: ParaId) -> bool {
+	pub fn is_parathread(id: ParaId) -> bool {
 		Parathreads::get(&id).is_some()
 	}
 
+	/// Whether a para ID corresponds to any live parachain.
+	pub fn is_parachain(id: ParaId) -> bool {
+		Parachains::get().binary_search(&id).is_ok()
+	}
+
 	/// The block number of the last scheduled upgrade of the requested para. Includes future upgrades
 	/// if the flag is set. This is the `expected_at` number, not the `activated_at` number.
 	pub(crate) fn last_code_upgrade(id: ParaId, include_future: bool) -> Option<T::BlockNumber> {
@@ -550,13 +694,73 @@ impl<T: Trait> Module<T> {
 
 		Self::past_code_meta(&id).most_recent_change()
 	}
+
+	/// Whether a candidate of the para with the given relay-parent number may signal a code
+	/// upgrade, given the para's last upgrade and the configured minimum frequency of upgrades.
+	pub(crate) fn can_upgrade_validation_code(id: ParaId, relay_parent_number: T::BlockNumber) -> bool {
+		let freq = <configuration::Module<T>>::config().validation_upgrade_frequency;
+
+		Self::last_code_upgrade(id, true).map_or(
+			true,
+			|last| last <= relay_parent_number && relay_parent_number.saturating_sub(last) >= freq,
+		)
+	}
+}
+
+mod migration {
+	use super::*;
+	use sp_std::collections::btree_map::BTreeMap;
+	use frame_support::storage::{IterableStorageMap, StorageMap, StorageValue};
+
+	/// Index all current, future and past code by its hash, moving from [`Releases::V0`] to
+	/// [`Releases::V1`].
+	///
+	/// The references are counted from scratch and overwrite any already in `CodeByHashRefs`, so
+	/// this is also correct on chains at `V0` which have been indexing code since they started.
+	pub(super) fn migrate_to_v1<T: Trait>() -> Weight {
+		if <Module<T> as Store>::StorageVersion::get() != Releases::V0 {
+			return T::DbWeight::get().reads(1);
+		}
+
+		let mut refs: BTreeMap<Hash, u32> = BTreeMap::new();
+		let mut note_code = |code: ValidationCode| {
+			let hash = validation_code_hash(&code);
+			let count = refs.entry(hash).or_insert(0);
+			if *count == 0 {
+				<Module<T> as Store>::CodeByHash::insert(&hash, code);
+			}
+			*count += 1;
+		};
+
+		let mut reads: Weight = 1;
+		for (_, code) in <Module<T> as Store>::CurrentCode::iter() {
+			note_code(code);
+			reads += 1;
+		}
+		for (_, code) in <Module<T> as Store>::FutureCode::iter() {
+			note_code(code);
+			reads += 1;
+		}
+		for (_, code) in <Module<T> as Store>::PastCode::iter() {
+			note_code(code);
+			reads += 1;
+		}
+
+		let writes = 2 * refs.len() as Weight + 1;
+		for (hash, count) in refs {
+			<Module<T> as Store>::CodeByHashRefs::insert(&hash, count);
+		}
+		<Module<T> as Store>::StorageVersion::put(Releases::V1);
+
+		T::DbWeight::get().reads_writes(reads, writes)
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
 	use primitives::v1::BlockNumber;
-	use frame_support::traits::{OnFinalize, OnInitialize};
+	use frame_support::traits::{OnFinalize, OnInitialize, OnRuntimeUpgrade};
 
 	use crate::mock::{new_test_ext, Paras, System, GenesisConfig as MockGenesisConfig};
 	use crate::configuration::HostConfiguration;
@@ -707,6 +911,145 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn past_code_retained_for_code_retention_period() {
+		let acceptance_period = 10;
+		let code_retention_period = 20;
+		let paras = vec![
+			(0u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: Default::default(),
+			}),
+		];
+
+		let genesis_config = MockGenesisConfig {
+			paras: GenesisConfig { paras, ..Default::default() },
+			configuration: crate::configuration::GenesisConfig {
+				config: HostConfiguration {
+					acceptance_period,
+					code_retention_period,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let id = ParaId::from(0u32);
+			let at_block: BlockNumber = 10;
+			let included_block: BlockNumber = 12;
+
+			Paras::note_past_code(id, at_block, included_block, vec![1, 2, 3].into());
+
+			let pruned_at: BlockNumber = included_block + code_retention_period + 1;
+
+			// still retained after the acceptance period.
+			run_to_block(included_block + acceptance_period + 1, None);
+			assert_eq!(<Paras as Store>::PastCode::get(&(id, at_block)), Some(vec![1, 2, 3].into()));
+
+			run_to_block(pruned_at - 1, None);
+			assert_eq!(<Paras as Store>::PastCode::get(&(id, at_block)), Some(vec![1, 2, 3].into()));
+			assert_eq!(Paras::past_code_meta(&id).most_recent_change(), Some(at_block));
+
+			run_to_block(pruned_at, None);
+			assert!(<Paras as Store>::PastCode::get(&(id, at_block)).is_none());
+			assert!(Paras::past_code_meta(&id).most_recent_change().is_none());
+		});
+	}
+
+	#[test]
+	fn past_code_not_pruned_within_acceptance_period() {
+		let acceptance_period = 10;
+		let code_retention_period = 5;
+		let paras = vec![
+			(0u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: Default::default(),
+			}),
+		];
+
+		let genesis_config = MockGenesisConfig {
+			paras: GenesisConfig { paras, ..Default::default() },
+			configuration: crate::configuration::GenesisConfig {
+				config: HostConfiguration {
+					acceptance_period,
+					code_retention_period,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let id = ParaId::from(0u32);
+			let at_block: BlockNumber = 10;
+			let included_block: BlockNumber = 12;
+
+			Paras::note_past_code(id, at_block, included_block, vec![1, 2, 3].into());
+
+			// the shorter retention period has no effect.
+			let pruned_at: BlockNumber = included_block + acceptance_period + 1;
+
+			run_to_block(pruned_at - 1, None);
+			assert_eq!(<Paras as Store>::PastCode::get(&(id, at_block)), Some(vec![1, 2, 3].into()));
+
+			run_to_block(pruned_at, None);
+			assert!(<Paras as Store>::PastCode::get(&(id, at_block)).is_none());
+		});
+	}
+
+	#[test]
+	fn past_code_pruned_in_order_of_replacement() {
+		let acceptance_period = 10;
+		let paras = vec![
+			(0u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: Default::default(),
+			}),
+			(1u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: Default::default(),
+			}),
+		];
+
+		let genesis_config = MockGenesisConfig {
+			paras: GenesisConfig { paras, ..Default::default() },
+			configuration: crate::configuration::GenesisConfig {
+				config: HostConfiguration {
+					acceptance_period,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let id_a = ParaId::from(0u32);
+			let id_b = ParaId::from(1u32);
+
+			// the code of `id_b` was expected to be replaced earlier, but was replaced later.
+			Paras::note_past_code(id_a, 5, 20, vec![1].into());
+			Paras::note_past_code(id_b, 3, 21, vec![2].into());
+
+			assert_eq!(<Paras as Store>::PastCodePruning::get(), vec![(id_a, 20), (id_b, 21)]);
+
+			run_to_block(20 + acceptance_period + 1, None);
+			assert!(<Paras as Store>::PastCode::get(&(id_a, 5)).is_none());
+			assert_eq!(<Paras as Store>::PastCode::get(&(id_b, 3)), Some(vec![2].into()));
+
+			run_to_block(21 + acceptance_period + 1, None);
+			assert!(<Paras as Store>::PastCode::get(&(id_b, 3)).is_none());
+			assert!(<Paras as Store>::PastCodePruning::get().is_empty());
+		});
+	}
+
 	#[test]
 	fn note_new_head_sets_head() {
 		let acceptance_period = 10;
@@ -945,6 +1288,128 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn code_by_hash_is_kept_until_past_code_is_pruned() {
+		let acceptance_period = 10;
+		let validation_upgrade_delay = 5;
+
+		let paras = vec![
+			(0u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: vec![1, 2, 3].into(),
+			}),
+		];
+
+		let genesis_config = MockGenesisConfig {
+			paras: GenesisConfig { paras, ..Default::default() },
+			configuration: crate::configuration::GenesisConfig {
+				config: HostConfiguration {
+					acceptance_period,
+					validation_upgrade_delay,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let para_id = ParaId::from(0);
+			let old_code = ValidationCode(vec![1, 2, 3]);
+			let new_code = ValidationCode(vec![4, 5, 6]);
+			let old_hash = validation_code_hash(&old_code);
+			let new_hash = validation_code_hash(&new_code);
+
+			run_to_block(2, None);
+			assert_eq!(Paras::code_by_hash(&old_hash), Some(old_code.clone()));
+			assert!(Paras::code_by_hash(&new_hash).is_none());
+
+			let expected_at = 1 + validation_upgrade_delay;
+			Paras::schedule_code_upgrade(para_id, new_code.clone(), expected_at);
+			assert_eq!(Paras::code_by_hash(&new_hash), Some(new_code.clone()));
+
+			run_to_block(expected_at + 1, None);
+			Paras::note_new_head(para_id, Default::default(), expected_at);
+
+			// the replaced code is still available.
+			assert_eq!(Paras::current_code(&para_id), Some(new_code.clone()));
+			assert_eq!(Paras::code_by_hash(&old_hash), Some(old_code.clone()));
+			assert_eq!(Paras::code_by_hash(&new_hash), Some(new_code.clone()));
+
+			let pruned_at = expected_at + 1 + acceptance_period + 1;
+			run_to_block(pruned_at - 1, None);
+			assert_eq!(Paras::code_by_hash(&old_hash), Some(old_code));
+
+			run_to_block(pruned_at, None);
+			assert!(Paras::code_by_hash(&old_hash).is_none());
+			assert_eq!(<Paras as Store>::CodeByHashRefs::get(&old_hash), 0);
+			assert_eq!(Paras::code_by_hash(&new_hash), Some(new_code));
+		});
+	}
+
+	#[test]
+	fn migration_to_v1_indexes_code_by_hash() {
+		let paras = vec![
+			(0u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: vec![1, 2, 3].into(),
+			}),
+			(1u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: vec![1, 2, 3].into(),
+			}),
+		];
+
+		let genesis_config = MockGenesisConfig {
+			paras: GenesisConfig { paras, ..Default::default() },
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			assert_eq!(<Paras as Store>::StorageVersion::get(), Releases::V1);
+
+			let shared_code = ValidationCode(vec![1, 2, 3]);
+			let future_code = ValidationCode(vec![4, 5, 6]);
+			let past_code = ValidationCode(vec![7, 8, 9]);
+			let shared_hash = validation_code_hash(&shared_code);
+			let future_hash = validation_code_hash(&future_code);
+			let past_hash = validation_code_hash(&past_code);
+
+			<Paras as Store>::FutureCode::insert(&ParaId::from(0), &future_code);
+			<Paras as Store>::PastCode::insert(&(ParaId::from(1), 1), &past_code);
+			<Paras as Store>::PastCode::insert(&(ParaId::from(1), 2), &shared_code);
+
+			// the layout before code was indexed by its hash.
+			<Paras as Store>::CodeByHash::remove(&shared_hash);
+			<Paras as Store>::CodeByHashRefs::remove(&shared_hash);
+			<Paras as Store>::StorageVersion::put(Releases::V0);
+
+			Paras::on_runtime_upgrade();
+
+			assert_eq!(Paras::code_by_hash(&shared_hash), Some(shared_code.clone()));
+			assert_eq!(<Paras as Store>::CodeByHashRefs::get(&shared_hash), 3);
+			assert_eq!(Paras::code_by_hash(&future_hash), Some(future_code));
+			assert_eq!(<Paras as Store>::CodeByHashRefs::get(&future_hash), 1);
+			assert_eq!(Paras::code_by_hash(&past_hash), Some(past_code));
+			assert_eq!(<Paras as Store>::CodeByHashRefs::get(&past_hash), 1);
+			assert_eq!(<Paras as Store>::StorageVersion::get(), Releases::V1);
+
+			// running the migration again leaves the index untouched.
+			Paras::on_runtime_upgrade();
+			assert_eq!(<Paras as Store>::CodeByHashRefs::get(&shared_hash), 3);
+
+			// the migrated references are released like any others.
+			Paras::decrease_code_ref(&shared_code);
+			Paras::decrease_code_ref(&shared_code);
+			assert_eq!(Paras::code_by_hash(&shared_hash), Some(shared_code.clone()));
+			Paras::decrease_code_ref(&shared_code);
+			assert!(Paras::code_by_hash(&shared_hash).is_none());
+		});
+	}
+
 	#[test]
 	fn submit_code_change_when_not_allowed_is_err() {
 		let acceptance_period = 10;
@@ -1133,6 +1598,67 @@ mod tests {
 		})
 	}
 
+	#[test]
+	fn parathread_upgrade_and_parachain_downgrade_at_session() {
+		let a = ParaId::from(111);
+		let b = ParaId::from(222);
+
+		let paras = vec![
+			(a, ParaGenesisArgs {
+				parachain: false,
+				genesis_head: vec![1].into(),
+				validation_code: vec![1].into(),
+			}),
+			(b, ParaGenesisArgs {
+				parachain: true,
+				genesis_head: vec![2].into(),
+				validation_code: vec![2].into(),
+			}),
+		];
+
+		let genesis_config = MockGenesisConfig {
+			paras: GenesisConfig { paras, ..Default::default() },
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			run_to_block(1, None);
+
+			// Only parathreads can be upgraded and only parachains downgraded.
+			assert!(!Paras::schedule_parathread_upgrade(b));
+			assert!(!Paras::schedule_parachain_downgrade(a));
+
+			assert!(Paras::schedule_parathread_upgrade(a));
+			assert!(Paras::schedule_parachain_downgrade(b));
+
+			run_to_block(2, None);
+			assert_eq!(Paras::parachains(), vec![b]);
+			assert!(Paras::is_parathread(a));
+
+			run_to_block(3, Some(vec![3]));
+			assert_eq!(Paras::parachains(), vec![a]);
+			assert!(Paras::is_parachain(a));
+			assert!(!Paras::is_parathread(a));
+			assert!(Paras::is_parathread(b));
+			assert!(<Paras as Store>::UpcomingUpgrades::get().is_empty());
+			assert!(<Paras as Store>::UpcomingDowngrades::get().is_empty());
+
+			assert!(Paras::schedule_parachain_downgrade(a));
+			assert!(Paras::schedule_parathread_upgrade(b));
+
+			// Scheduling the opposite change cancels a pending one, even if it schedules nothing.
+			assert!(!Paras::schedule_parathread_upgrade(a));
+			assert!(<Paras as Store>::UpcomingDowngrades::get().is_empty());
+			assert!(Paras::schedule_parachain_downgrade(a));
+			assert_eq!(<Paras as Store>::UpcomingDowngrades::get(), vec![a]);
+			assert_eq!(<Paras as Store>::UpcomingUpgrades::get(), vec![b]);
+
+			run_to_block(4, Some(vec![4]));
+			assert_eq!(Paras::parachains(), vec![b]);
+			assert!(Paras::is_parathread(a));
+		});
+	}
+
 	#[test]
 	fn para_cleanup_removes_upcoming() {
 		new_test_ext(Default::default()).execute_with(|| {
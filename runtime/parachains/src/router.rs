@@ -0,0 +1,1811 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The router module is responsible for the messaging mechanisms between paras and the relay chain.
+//!
+//! For now, horizontal messages (HRMP) and upward messages (UMP) are handled. The horizontal
+//! messages a candidate sends are checked against the channels they are sent over, put into those
+//! channels once the candidate is enacted, and charged to the sender per byte. They leave their
+//! channels once an enacted candidate of the recipient declares a watermark at or after the block
+//! they were sent in. The upward messages of an enacted candidate are queued in pages and dispatched
+//! as calls, with an origin chosen by the message, at the initialization of later blocks. The weight
+//! of the calls dispatched, the messages decoded and the pages loaded in a block is bounded by the
+//! configuration, and messages which don't fit are left in the queue for the next block.
+//!
+//! HRMP channels are opened by governance, and closed by governance or by either of the paras they
+//! connect, with the `Parachain` origin. The channels of paras which are offboarded are closed
+//! starting with the session change the para is cleaned up at, a bounded number of channels per
+//! block. Opening a channel reserves a deposit from the sovereign accounts of both its sender and
+//! its recipient, which is unreserved whenever the channel is closed.
+
+use sp_std::prelude::*;
+use primitives::v1::{
+	Id as ParaId, Balance, Hash, HrmpChannelId, OutboundHrmpMessage, InboundHrmpMessage,
+	AccountIdConversion, UpwardMessage, ParachainDispatchOrigin,
+};
+use sp_std::collections::btree_map::BTreeMap;
+use sp_runtime::{
+	DispatchError,
+	traits::{BlakeTwo256, Dispatchable, Hash as HashT, SaturatedConversion},
+};
+use frame_support::{
+	decl_storage, decl_module, decl_error, decl_event, ensure, Parameter,
+	dispatch::DispatchResult,
+	storage::IterableStorageMap,
+	traits::{Currency, ReservableCurrency, Get},
+	weights::{DispatchClass, GetDispatchInfo, Weight},
+};
+use frame_system::ensure_root;
+use codec::{Encode, Decode, DecodeLimit};
+use crate::{configuration, origin::{self, ensure_parachain}, initializer::SessionChangeNotification};
+
+type BalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+/// The maximum nesting depth of the call an upward message is decoded into.
+const UPWARD_MESSAGE_MAX_CALL_DEPTH: u32 = 256;
+
+/// The maximum number of HRMP channels of offboarded paras closed in a block.
+const MAX_OUTGOING_HRMP_CHANNELS_CLOSED_PER_BLOCK: u32 = 64;
+
+/// The maximum number of upward messages in a page of the dispatch queue of a para.
+const UPWARD_QUEUE_PAGE_SIZE: usize = 64;
+
+/// The weight of decoding an upward message into its call, besides the weight per byte.
+const UPWARD_MESSAGE_DECODE_BASE_WEIGHT: Weight = 1_000_000;
+
+/// The weight of decoding each byte of an upward message into its call.
+const UPWARD_MESSAGE_DECODE_WEIGHT_PER_BYTE: Weight = 1_000;
+
+pub trait Trait: frame_system::Trait + origin::Trait + configuration::Trait {
+	/// The overarching event type.
+	type Event: From<Event> + Into<<Self as frame_system::Trait>::Event>;
+	/// The origin upward messages are dispatched with, which is also the origin the calls meant
+	/// for paras are checked against.
+	type Origin: From<origin::Origin>
+		+ From<frame_system::RawOrigin<Self::AccountId>>
+		+ From<<Self as frame_system::Trait>::Origin>
+		+ Into<Result<origin::Origin, <Self as Trait>::Origin>>;
+	/// The calls upward messages are decoded into.
+	type Call: Parameter + Dispatchable<Origin = <Self as Trait>::Origin> + GetDispatchInfo;
+	/// The currency the fees of horizontal messages are paid and the deposits of HRMP channels are
+	/// reserved in.
+	type Currency: ReservableCurrency<Self::AccountId>;
+	/// The deposit reserved from the sovereign account of the sender of an HRMP channel while it
+	/// is open.
+	type HrmpSenderDeposit: Get<Balance>;
+	/// The deposit reserved from the sovereign account of the recipient of an HRMP channel while
+	/// it is open.
+	type HrmpRecipientDeposit: Get<Balance>;
+}
+
+/// The metadata of an HRMP channel.
+#[derive(Default, Encode, Decode)]
+#[cfg_attr(test, derive(Debug, Clone, PartialEq))]
+pub struct HrmpChannel {
+	/// The maximum number of messages that can be pending in the channel at once.
+	pub limit_used_places: u32,
+	/// The maximum total size of the messages that can be pending in the channel at once.
+	pub limit_used_bytes: u32,
+	/// The fee charged to the sender for each byte of message payload sent over the channel.
+	pub fee_per_byte: Balance,
+	/// The current number of messages pending in the channel.
+	/// Invariant: should be less or equal to `limit_used_places`.
+	pub used_places: u32,
+	/// The total size in bytes of all message payloads in the channel.
+	/// Invariant: should be less or equal to `limit_used_bytes`.
+	pub used_bytes: u32,
+	/// The head of the Message Queue Chain for this channel. Each link in this chain has the form
+	/// `(prev_head, B, H(M))`, where `B` is the relay-chain block number in which the message `M`
+	/// was appended. All zeroes indicate that no messages were added yet.
+	pub mqc_head: Hash,
+	/// The deposit reserved from the sovereign account of the sender while the channel is open.
+	pub sender_deposit: Balance,
+	/// The deposit reserved from the sovereign account of the recipient while the channel is open.
+	pub recipient_deposit: Balance,
+}
+
+/// The pages of the upward message dispatch queue of a para.
+///
+/// The pages with indices `head..tail` hold the queued messages, oldest first. Only the last page
+/// may hold less than `UPWARD_QUEUE_PAGE_SIZE` messages, and only the first one may have had
+/// messages dispatched from it.
+#[derive(Default, Clone, Copy, Encode, Decode)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct QueuePageIndices {
+	/// The index of the page holding the oldest messages.
+	pub head: u32,
+	/// The index after the page holding the newest messages. The queue is empty if it equals
+	/// `head`.
+	pub tail: u32,
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Router {
+		/// HRMP channel data associated with each para.
+		HrmpChannels get(fn hrmp_channels): map hasher(twox_64_concat) HrmpChannelId => Option<HrmpChannel>;
+		/// Storage for the messages for each channel.
+		/// Invariant: cannot be non-empty if the corresponding channel in `HrmpChannels` is `None`.
+		HrmpChannelContents get(fn hrmp_channel_contents): map hasher(twox_64_concat) HrmpChannelId
+			=> Vec<InboundHrmpMessage<T::BlockNumber>>;
+		/// The paras that sent messages to the given recipient, by the block number they were
+		/// sent at, ascending.
+		/// Invariant: The para ids vector is never empty.
+		HrmpChannelDigests get(fn hrmp_channel_digests): map hasher(twox_64_concat) ParaId
+			=> Vec<(T::BlockNumber, Vec<ParaId>)>;
+		/// The block number up to which, inclusive, each para processed its inbound horizontal
+		/// messages, as committed to by its last enacted candidate.
+		/// Invariant: `HrmpChannelDigests` of the para has no entries at or below the watermark.
+		HrmpWatermarks get(fn hrmp_watermarks): map hasher(twox_64_concat) ParaId
+			=> Option<T::BlockNumber>;
+		/// The recipients of the open HRMP channels of each sender, sorted ascending.
+		/// Invariant: contains exactly the recipients of the channels of the sender in `HrmpChannels`.
+		HrmpEgressChannelsIndex get(fn hrmp_egress_channels_index): map hasher(twox_64_concat) ParaId
+			=> Vec<ParaId>;
+		/// The senders of the open HRMP channels of each recipient, sorted ascending.
+		/// Invariant: contains exactly the senders of the channels of the recipient in `HrmpChannels`.
+		HrmpIngressChannelsIndex get(fn hrmp_ingress_channels_index): map hasher(twox_64_concat) ParaId
+			=> Vec<ParaId>;
+		/// The offboarded paras whose HRMP channels are yet to be closed, in the order they were
+		/// offboarded.
+		OutgoingHrmpParas get(fn outgoing_hrmp_paras): Vec<ParaId>;
+
+		/// The pages of the upward message queue of each para.
+		/// Invariant: `head < tail`; the para is in `NeedsDispatch` if and only if it has an entry.
+		RelayDispatchQueuePageIndices get(fn relay_dispatch_queue_page_indices):
+			map hasher(twox_64_concat) ParaId => Option<QueuePageIndices>;
+		/// The upward messages of each para waiting to be dispatched, in pages of at most
+		/// `UPWARD_QUEUE_PAGE_SIZE` messages.
+		/// Invariant: only the pages within the `RelayDispatchQueuePageIndices` of the para exist,
+		/// and none of them is empty.
+		RelayDispatchQueuePages get(fn relay_dispatch_queue_page):
+			double_map hasher(twox_64_concat) ParaId, hasher(twox_64_concat) u32 => Vec<UpwardMessage>;
+		/// The upward message queues before they were paged. Moved into `RelayDispatchQueuePages`
+		/// on runtime upgrade and empty since.
+		RelayDispatchQueues: map hasher(twox_64_concat) ParaId => Vec<UpwardMessage>;
+		/// The paras with upward messages waiting to be dispatched, sorted ascending.
+		NeedsDispatch get(fn needs_dispatch): Vec<ParaId>;
+		/// The para the next round of dispatching upward messages starts with, if the last round
+		/// ran out of weight. `None` starts with the first para of `NeedsDispatch`.
+		NextDispatchRoundStartWith get(fn next_dispatch_round_start_with): Option<ParaId>;
+	}
+}
+
+decl_event! {
+	pub enum Event {
+		/// An upward message was dropped because it doesn't decode into a call or asks for an
+		/// origin no para may use. [sender]
+		UpwardMessageInvalid(ParaId),
+		/// An upward message was dropped because its call takes more weight than any upward
+		/// message may. [sender, weight]
+		UpwardMessageOverweight(ParaId, Weight),
+		/// The call of an upward message was dispatched and failed. [sender, error]
+		UpwardMessageDispatchFailed(ParaId, DispatchError),
+		/// An HRMP channel was opened by governance. [sender, recipient]
+		HrmpChannelForceOpened(ParaId, ParaId),
+		/// All HRMP channels of a para were closed by governance. [para]
+		HrmpChannelsForceCleaned(ParaId),
+		/// An HRMP channel was closed by its sender or its recipient. [by_para, sender, recipient]
+		HrmpChannelClosed(ParaId, ParaId, ParaId),
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The horizontal messages aren't sorted ascending by recipient, or two of them have the
+		/// same recipient.
+		HrmpMessagesNotSorted,
+		/// There is no open channel from the sender to the recipient of a horizontal message, or
+		/// of the channel to close.
+		HrmpNoSuchChannel,
+		/// A horizontal message doesn't fit into its channel.
+		HrmpChannelCapacityExceeded,
+		/// The sender can't afford the fees of its horizontal messages.
+		HrmpInsufficientFunds,
+		/// The channel to open already exists.
+		HrmpChannelAlreadyExists,
+		/// The sender or the recipient of the channel to open can't afford its deposit.
+		HrmpInsufficientDeposit,
+		/// The sender and the recipient of the channel to open are the same para.
+		HrmpChannelToSelf,
+		/// The para closing a channel is neither its sender nor its recipient.
+		HrmpNotChannelParticipant,
+		/// The para has more channels than the number given to bound the weight of the call.
+		HrmpTooManyChannels,
+		/// The HRMP watermark isn't greater than the watermark of the last enacted candidate.
+		HrmpWatermarkNotAdvanced,
+		/// The HRMP watermark is greater than the number of the relay parent.
+		HrmpWatermarkAheadOfRelayParent,
+		/// The HRMP watermark is neither the number of the relay parent nor of a block in which
+		/// the para received horizontal messages.
+		HrmpWatermarkWithoutMessages,
+	}
+}
+
+decl_module! {
+	/// The router module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		fn on_runtime_upgrade() -> Weight {
+			Self::migrate_relay_dispatch_queues()
+				.saturating_add(migration::migrate_hrmp_channels::<T>())
+		}
+
+		/// Open an HRMP channel from `sender` to `recipient` right away, reserving the channel
+		/// deposits from the sovereign accounts of both.
+		#[weight = (establish_hrmp_channel_weight::<T>(), DispatchClass::Operational)]
+		pub fn force_open_hrmp_channel(
+			origin,
+			sender: ParaId,
+			recipient: ParaId,
+			limit_used_places: u32,
+			limit_used_bytes: u32,
+			fee_per_byte: Balance,
+		) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::establish_hrmp_channel(sender, recipient, limit_used_places, limit_used_bytes, fee_per_byte)
+		}
+
+		/// Close all HRMP channels from and to `para` right away, dropping the messages pending
+		/// in them.
+		///
+		/// `num_inbound` and `num_outbound` bound the weight of the call and must be at least the
+		/// number of channels to and from `para` respectively.
+		#[weight = (
+			T::DbWeight::get().reads(2)
+				.saturating_add(close_hrmp_channels_weight::<T>(num_inbound.saturating_add(*num_outbound))),
+			DispatchClass::Operational,
+		)]
+		pub fn force_clean_hrmp(origin, para: ParaId, num_inbound: u32, num_outbound: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(
+				<HrmpIngressChannelsIndex>::get(&para).len() <= num_inbound as usize &&
+					<HrmpEgressChannelsIndex>::get(&para).len() <= num_outbound as usize,
+				Error::<T>::HrmpTooManyChannels,
+			);
+
+			Self::clean_hrmp(para);
+			Self::deposit_event(Event::HrmpChannelsForceCleaned(para));
+			Ok(())
+		}
+
+		/// Close the HRMP channel `channel_id` right away, dropping the messages pending in it and
+		/// unreserving its deposits.
+		///
+		/// Must be called with the `Parachain` origin of the sender or the recipient of the
+		/// channel, e.g. from an upward message.
+		#[weight = T::DbWeight::get().reads(1).saturating_add(close_hrmp_channels_weight::<T>(1))]
+		pub fn hrmp_close_channel(origin, channel_id: HrmpChannelId) -> DispatchResult {
+			let para = ensure_parachain(<T as Trait>::Origin::from(origin))?;
+			ensure!(
+				para == channel_id.sender || para == channel_id.recipient,
+				Error::<T>::HrmpNotChannelParticipant,
+			);
+			ensure!(<HrmpChannels>::contains_key(&channel_id), Error::<T>::HrmpNoSuchChannel);
+
+			Self::close_hrmp_channel(channel_id);
+			Self::deposit_event(Event::HrmpChannelClosed(para, channel_id.sender, channel_id.recipient));
+			Ok(())
+		}
+	}
+}
+
+/// The weight of `Module::establish_hrmp_channel`.
+pub fn establish_hrmp_channel_weight<T: Trait>() -> Weight {
+	T::DbWeight::get().reads_writes(5, 5)
+}
+
+/// The weight of closing the given number of HRMP channels.
+fn close_hrmp_channels_weight<T: Trait>(channels: u32) -> Weight {
+	T::DbWeight::get().reads_writes(5, 7).saturating_mul(channels as Weight)
+}
+
+/// The weight of loading a page of an upward message queue and writing it back along with the
+/// page indices of the queue.
+fn upward_queue_page_weight<T: Trait>() -> Weight {
+	T::DbWeight::get().reads_writes(2, 2)
+}
+
+/// The weight of decoding `message` into its call, whether it decodes or not.
+fn upward_message_decode_weight(message: &UpwardMessage) -> Weight {
+	UPWARD_MESSAGE_DECODE_WEIGHT_PER_BYTE
+		.saturating_mul(message.data.len() as Weight)
+		.saturating_add(UPWARD_MESSAGE_DECODE_BASE_WEIGHT)
+}
+
+impl<T: Trait> Module<T> {
+	/// Open an HRMP channel from `sender` to `recipient` right away, reserving the channel
+	/// deposits from the sovereign accounts of both, and deposit `HrmpChannelForceOpened`.
+	///
+	/// This is the body of `force_open_hrmp_channel`, for other modules whose governance origins
+	/// open channels. The caller is responsible for checking the origin.
+	pub fn establish_hrmp_channel(
+		sender: ParaId,
+		recipient: ParaId,
+		limit_used_places: u32,
+		limit_used_bytes: u32,
+		fee_per_byte: Balance,
+	) -> DispatchResult {
+		ensure!(sender != recipient, Error::<T>::HrmpChannelToSelf);
+
+		let channel_id = HrmpChannelId { sender, recipient };
+		ensure!(!<HrmpChannels>::contains_key(&channel_id), Error::<T>::HrmpChannelAlreadyExists);
+
+		let sender_deposit = T::HrmpSenderDeposit::get();
+		let recipient_deposit = T::HrmpRecipientDeposit::get();
+		ensure!(
+			T::Currency::can_reserve(&sender.into_account(), sender_deposit.saturated_into()) &&
+				T::Currency::can_reserve(&recipient.into_account(), recipient_deposit.saturated_into()),
+			Error::<T>::HrmpInsufficientDeposit,
+		);
+		T::Currency::reserve(&sender.into_account(), sender_deposit.saturated_into())?;
+		T::Currency::reserve(&recipient.into_account(), recipient_deposit.saturated_into())?;
+
+		Self::open_hrmp_channel(channel_id, HrmpChannel {
+			limit_used_places,
+			limit_used_bytes,
+			fee_per_byte,
+			sender_deposit,
+			recipient_deposit,
+			..Default::default()
+		});
+		Self::deposit_event(Event::HrmpChannelForceOpened(sender, recipient));
+		Ok(())
+	}
+
+	/// Called by the initializer to initialize the router module.
+	///
+	/// Closes channels of offboarded paras and dispatches the queued upward messages which fit
+	/// into the weight of the block.
+	pub(crate) fn initializer_initialize(_now: T::BlockNumber) -> Weight {
+		Self::process_outgoing_hrmp_paras()
+			.saturating_add(Self::process_upward_dispatchables())
+	}
+
+	/// Called by the initializer to finalize the router module.
+	pub(crate) fn initializer_finalize() {}
+
+	/// Called by the initializer to note that a new session has started.
+	///
+	/// Drops the queued upward messages of the paras which were offboarded at the session change
+	/// and queues their channels for closing, which starts with the initialization of this block.
+	pub(crate) fn initializer_on_new_session(
+		_notification: &SessionChangeNotification<T::BlockNumber>,
+		outgoing_paras: &[ParaId],
+	) -> Weight {
+		if outgoing_paras.is_empty() {
+			return 0;
+		}
+
+		for &para in outgoing_paras {
+			Self::clean_upward_dispatch_queue(para);
+			<HrmpWatermarks<T>>::remove(&para);
+		}
+		<OutgoingHrmpParas>::mutate(|paras| paras.extend_from_slice(outgoing_paras));
+
+		T::DbWeight::get().reads_writes(3, 5).saturating_mul(outgoing_paras.len() as Weight)
+	}
+
+	/// Close the channels of the offboarded paras in `OutgoingHrmpParas`, oldest first, up to
+	/// `MAX_OUTGOING_HRMP_CHANNELS_CLOSED_PER_BLOCK` channels. The remaining channels are closed
+	/// in the next blocks.
+	fn process_outgoing_hrmp_paras() -> Weight {
+		let mut outgoing = <OutgoingHrmpParas>::get();
+		let mut weight = T::DbWeight::get().reads(1);
+
+		if outgoing.is_empty() {
+			return weight;
+		}
+
+		let mut budget = MAX_OUTGOING_HRMP_CHANNELS_CLOSED_PER_BLOCK;
+		while let Some(&para) = outgoing.first() {
+			let (closed, done) = Self::close_hrmp_channels_of(para, budget);
+			budget -= closed;
+			weight = weight
+				.saturating_add(T::DbWeight::get().reads(2))
+				.saturating_add(close_hrmp_channels_weight::<T>(closed));
+
+			if !done {
+				break;
+			}
+			outgoing.remove(0);
+		}
+
+		<OutgoingHrmpParas>::put(outgoing);
+		weight.saturating_add(T::DbWeight::get().writes(1))
+	}
+
+	/// Close all HRMP channels from and to `para`.
+	///
+	/// Only the channels of `para` are visited, through the channel indices.
+	pub(crate) fn clean_hrmp(para: ParaId) -> Weight {
+		let (closed, _) = Self::close_hrmp_channels_of(para, u32::max_value());
+
+		T::DbWeight::get().reads(2).saturating_add(close_hrmp_channels_weight::<T>(closed))
+	}
+
+	/// Close up to `max` HRMP channels from and to `para`, outbound channels first. Returns the
+	/// number of channels closed and whether `para` has no channels left.
+	fn close_hrmp_channels_of(para: ParaId, max: u32) -> (u32, bool) {
+		let recipients = <HrmpEgressChannelsIndex>::get(&para);
+		let senders = <HrmpIngressChannelsIndex>::get(&para);
+
+		let channels: Vec<_> = recipients.into_iter()
+			.map(|recipient| HrmpChannelId { sender: para, recipient })
+			.chain(senders.into_iter().map(|sender| HrmpChannelId { sender, recipient: para }))
+			.collect();
+
+		let to_close = sp_std::cmp::min(channels.len(), max as usize);
+		for &channel_id in &channels[..to_close] {
+			Self::close_hrmp_channel(channel_id);
+		}
+
+		(to_close.saturated_into(), to_close == channels.len())
+	}
+
+	fn open_hrmp_channel(channel_id: HrmpChannelId, channel: HrmpChannel) {
+		let HrmpChannelId { sender, recipient } = channel_id;
+
+		<HrmpChannels>::insert(&channel_id, channel);
+		<HrmpEgressChannelsIndex>::mutate(&sender, |recipients| {
+			if let Err(i) = recipients.binary_search(&recipient) {
+				recipients.insert(i, recipient);
+			}
+		});
+		<HrmpIngressChannelsIndex>::mutate(&recipient, |senders| {
+			if let Err(i) = senders.binary_search(&sender) {
+				senders.insert(i, sender);
+			}
+		});
+	}
+
+	/// Remove the channel along with its messages, and the sender from the digests of the
+	/// recipient. The deposits of the channel are unreserved.
+	fn close_hrmp_channel(channel_id: HrmpChannelId) {
+		let HrmpChannelId { sender, recipient } = channel_id;
+
+		if let Some(channel) = <HrmpChannels>::take(&channel_id) {
+			T::Currency::unreserve(&sender.into_account(), channel.sender_deposit.saturated_into());
+			T::Currency::unreserve(&recipient.into_account(), channel.recipient_deposit.saturated_into());
+		}
+		<HrmpChannelContents<T>>::remove(&channel_id);
+
+		let mut recipients = <HrmpEgressChannelsIndex>::get(&sender);
+		recipients.retain(|r| *r != recipient);
+		if recipients.is_empty() {
+			<HrmpEgressChannelsIndex>::remove(&sender);
+		} else {
+			<HrmpEgressChannelsIndex>::insert(&sender, recipients);
+		}
+
+		let mut senders = <HrmpIngressChannelsIndex>::get(&recipient);
+		senders.retain(|s| *s != sender);
+		if senders.is_empty() {
+			<HrmpIngressChannelsIndex>::remove(&recipient);
+		} else {
+			<HrmpIngressChannelsIndex>::insert(&recipient, senders);
+		}
+
+		let mut digests = <HrmpChannelDigests<T>>::get(&recipient);
+		for (_, senders) in digests.iter_mut() {
+			senders.retain(|s| *s != sender);
+		}
+		digests.retain(|(_, senders)| !senders.is_empty());
+		if digests.is_empty() {
+			<HrmpChannelDigests<T>>::remove(&recipient);
+		} else {
+			<HrmpChannelDigests<T>>::insert(&recipient, digests);
+		}
+	}
+
+	/// Check that the horizontal messages declared by a candidate of `sender` can be sent.
+	///
+	/// This doesn't modify any storage.
+	pub(crate) fn verify_outbound_hrmp(
+		sender: ParaId,
+		messages: &[OutboundHrmpMessage<ParaId>],
+	) -> DispatchResult {
+		let mut last_recipient = None;
+		let mut fees: Balance = 0;
+
+		for message in messages {
+			ensure!(
+				last_recipient.map_or(true, |last| message.recipient > last),
+				Error::<T>::HrmpMessagesNotSorted,
+			);
+			last_recipient = Some(message.recipient);
+
+			let channel_id = HrmpChannelId { sender, recipient: message.recipient };
+			let channel = Self::hrmp_channels(&channel_id).ok_or(Error::<T>::HrmpNoSuchChannel)?;
+
+			let size = message.data.len().saturated_into::<u32>();
+			ensure!(
+				channel.used_places < channel.limit_used_places &&
+					channel.used_bytes.saturating_add(size) <= channel.limit_used_bytes,
+				Error::<T>::HrmpChannelCapacityExceeded,
+			);
+
+			fees = fees.saturating_add(channel.fee_per_byte.saturating_mul(size.into()));
+		}
+
+		let fees: BalanceOf<T> = fees.saturated_into();
+		ensure!(
+			T::Currency::free_balance(&sender.into_account()) >= fees,
+			Error::<T>::HrmpInsufficientFunds,
+		);
+
+		Ok(())
+	}
+
+	/// Check that `new_hrmp_watermark`, declared by a candidate of `recipient` built on the relay
+	/// parent with the given number, may be enacted.
+	///
+	/// The watermark must advance past the one of the last enacted candidate of `recipient`, and
+	/// land either on the relay parent or on a block in which `recipient` received messages.
+	///
+	/// This doesn't modify any storage.
+	pub(crate) fn check_hrmp_watermark(
+		recipient: ParaId,
+		relay_parent_number: T::BlockNumber,
+		new_hrmp_watermark: T::BlockNumber,
+	) -> DispatchResult {
+		if let Some(last_watermark) = Self::hrmp_watermarks(&recipient) {
+			ensure!(new_hrmp_watermark > last_watermark, Error::<T>::HrmpWatermarkNotAdvanced);
+		}
+		ensure!(
+			new_hrmp_watermark <= relay_parent_number,
+			Error::<T>::HrmpWatermarkAheadOfRelayParent,
+		);
+		ensure!(
+			new_hrmp_watermark == relay_parent_number ||
+				Self::hrmp_channel_digests(&recipient)
+					.binary_search_by_key(&new_hrmp_watermark, |(block, _)| *block)
+					.is_ok(),
+			Error::<T>::HrmpWatermarkWithoutMessages,
+		);
+
+		Ok(())
+	}
+
+	/// Remove the messages `recipient` processed according to the `new_hrmp_watermark` of its
+	/// enacted candidate from their channels, freeing up their capacity, and note the watermark.
+	///
+	/// The watermark must have passed `check_hrmp_watermark` when the candidate was backed.
+	pub(crate) fn prune_hrmp(recipient: ParaId, new_hrmp_watermark: T::BlockNumber) -> Weight {
+		let mut digests = <HrmpChannelDigests<T>>::get(&recipient);
+		let mut weight = T::DbWeight::get().reads_writes(1, 2);
+
+		let processed = digests.iter()
+			.position(|(block, _)| *block > new_hrmp_watermark)
+			.unwrap_or(digests.len());
+		let mut senders: Vec<ParaId> = digests.drain(..processed)
+			.flat_map(|(_, senders)| senders)
+			.collect();
+		senders.sort();
+		senders.dedup();
+
+		for sender in senders {
+			let channel_id = HrmpChannelId { sender, recipient };
+			let mut contents = <HrmpChannelContents<T>>::get(&channel_id);
+			weight = weight.saturating_add(T::DbWeight::get().reads_writes(2, 2));
+
+			let pruned = contents.iter()
+				.position(|message| message.sent_at > new_hrmp_watermark)
+				.unwrap_or(contents.len());
+			let pruned_bytes = contents.drain(..pruned)
+				.map(|message| message.data.len())
+				.sum::<usize>()
+				.saturated_into::<u32>();
+
+			if let Some(mut channel) = Self::hrmp_channels(&channel_id) {
+				channel.used_places = channel.used_places.saturating_sub(pruned.saturated_into());
+				channel.used_bytes = channel.used_bytes.saturating_sub(pruned_bytes);
+				<HrmpChannels>::insert(&channel_id, channel);
+			}
+
+			if contents.is_empty() {
+				<HrmpChannelContents<T>>::remove(&channel_id);
+			} else {
+				<HrmpChannelContents<T>>::insert(&channel_id, contents);
+			}
+		}
+
+		if digests.is_empty() {
+			<HrmpChannelDigests<T>>::remove(&recipient);
+		} else {
+			<HrmpChannelDigests<T>>::insert(&recipient, digests);
+		}
+		<HrmpWatermarks<T>>::insert(&recipient, new_hrmp_watermark);
+
+		weight
+	}
+
+	/// Put the horizontal messages of an enacted candidate of `sender` into their channels and
+	/// charge the sender for them.
+	///
+	/// The messages must have passed `verify_outbound_hrmp` when the candidate was backed. The fees
+	/// are burned; should the sender no longer be able to afford them, as much as possible is taken.
+	pub(crate) fn queue_outbound_hrmp(
+		sender: ParaId,
+		messages: Vec<OutboundHrmpMessage<ParaId>>,
+	) -> Weight {
+		let now = <frame_system::Module<T>>::block_number();
+		let mut fees: Balance = 0;
+		let mut weight = 0;
+
+		for message in messages {
+			let channel_id = HrmpChannelId { sender, recipient: message.recipient };
+			weight += T::DbWeight::get().reads(1);
+
+			let mut channel = match Self::hrmp_channels(&channel_id) {
+				Some(channel) => channel,
+				// the channel was checked at backing, but governance may have closed it since.
+				None => continue,
+			};
+
+			let size = message.data.len().saturated_into::<u32>();
+			channel.used_places += 1;
+			channel.used_bytes = channel.used_bytes.saturating_add(size);
+			channel.mqc_head = mqc_link(channel.mqc_head, now, &message.data);
+			fees = fees.saturating_add(channel.fee_per_byte.saturating_mul(size.into()));
+
+			<HrmpChannels>::insert(&channel_id, channel);
+			<HrmpChannelContents<T>>::append(
+				&channel_id,
+				InboundHrmpMessage { sent_at: now, data: message.data },
+			);
+			<HrmpChannelDigests<T>>::mutate(&message.recipient, |digests| {
+				match digests.last_mut() {
+					Some((block, senders)) if *block == now => if !senders.contains(&sender) {
+						senders.push(sender);
+					},
+					_ => digests.push((now, vec![sender])),
+				}
+			});
+			weight += T::DbWeight::get().reads_writes(1, 3);
+		}
+
+		if fees != 0 {
+			// dropping the imbalance burns the fees.
+			let _ = T::Currency::slash(&sender.into_account(), fees.saturated_into());
+			weight += T::DbWeight::get().reads_writes(1, 1);
+		}
+
+		weight
+	}
+
+	/// Queue the upward messages of an enacted candidate of `sender` for dispatch.
+	///
+	/// The messages fill up the last page of the queue of `sender` first, then new pages.
+	pub(crate) fn enact_upward_messages(sender: ParaId, messages: Vec<UpwardMessage>) -> Weight {
+		if messages.is_empty() {
+			return 0;
+		}
+
+		let mut weight = T::DbWeight::get().reads_writes(2, 2);
+		let mut indices = <RelayDispatchQueuePageIndices>::get(&sender).unwrap_or_default();
+		let mut messages = messages.into_iter().peekable();
+
+		if indices.head < indices.tail {
+			let last = indices.tail - 1;
+			let mut page = <RelayDispatchQueuePages>::get(&sender, last);
+			if page.len() < UPWARD_QUEUE_PAGE_SIZE {
+				page.extend(messages.by_ref().take(UPWARD_QUEUE_PAGE_SIZE - page.len()));
+				<RelayDispatchQueuePages>::insert(&sender, last, page);
+			}
+			weight = weight.saturating_add(T::DbWeight::get().reads_writes(1, 1));
+		}
+
+		while messages.peek().is_some() {
+			let page: Vec<_> = messages.by_ref().take(UPWARD_QUEUE_PAGE_SIZE).collect();
+			<RelayDispatchQueuePages>::insert(&sender, indices.tail, page);
+			indices.tail += 1;
+			weight = weight.saturating_add(T::DbWeight::get().writes(1));
+		}
+
+		<RelayDispatchQueuePageIndices>::insert(&sender, indices);
+		<NeedsDispatch>::mutate(|paras| if let Err(i) = paras.binary_search(&sender) {
+			paras.insert(i, sender);
+		});
+
+		weight
+	}
+
+	/// Dispatch queued upward messages, taking one message of each para with messages queued in
+	/// turn, starting with `NextDispatchRoundStartWith`.
+	///
+	/// Only the first page of the queue of a para is loaded at a time. The weight of loading and
+	/// writing back a page and the weight of decoding each message count towards the weight of
+	/// the dispatched calls, and dispatching stops before the page or the message which would
+	/// take that weight above
+	/// `preferred_dispatchable_upward_messages_step_weight`, though the first message is always
+	/// dispatched. The round continues from the para of that message in the next block.
+	///
+	/// `Signed` messages are dispatched from the sovereign account of the sender and `Parachain`
+	/// messages from the `Parachain` origin. Messages which don't decode into a call, which ask for
+	/// the `Root` origin no para may use yet, or whose call takes more than
+	/// `dispatchable_upward_message_critical_weight` are dropped with an event. Whether the call
+	/// succeeds is up to the sender, but failures are reported with an event.
+	pub(crate) fn process_upward_dispatchables() -> Weight {
+		let config = <configuration::Module<T>>::config();
+		let step_weight = config.preferred_dispatchable_upward_messages_step_weight;
+		let mut needs_dispatch = <NeedsDispatch>::get();
+		let mut weight = T::DbWeight::get().reads(2);
+
+		if needs_dispatch.is_empty() {
+			return weight;
+		}
+
+		let mut index = <NextDispatchRoundStartWith>::get()
+			.map_or(0, |para| needs_dispatch.binary_search(&para).unwrap_or_else(|i| i));
+		let mut next_round_start_with = None;
+		// the first page of the queue of each para loaded in this block, with the indices of the
+		// queue.
+		let mut pages: BTreeMap<ParaId, (QueuePageIndices, Vec<UpwardMessage>)> = BTreeMap::new();
+		let mut dispatched_weight: Weight = 0;
+		let mut dispatched_any = false;
+
+		while !needs_dispatch.is_empty() {
+			index %= needs_dispatch.len();
+			let para = needs_dispatch[index];
+
+			if !pages.contains_key(&para) {
+				let page_weight = upward_queue_page_weight::<T>();
+				if dispatched_any && dispatched_weight.saturating_add(page_weight) > step_weight {
+					next_round_start_with = Some(para);
+					break;
+				}
+				dispatched_weight = dispatched_weight.saturating_add(page_weight);
+
+				let indices = match <RelayDispatchQueuePageIndices>::get(&para) {
+					Some(indices) => indices,
+					None => {
+						needs_dispatch.remove(index);
+						continue;
+					}
+				};
+				let page = <RelayDispatchQueuePages>::get(&para, indices.head);
+				pages.insert(para, (indices, page));
+			}
+
+			let (indices, page) = pages.get_mut(&para)
+				.expect("the page of `para` was inserted above if missing; qed");
+
+			if let Some(message) = page.first() {
+				let decode_weight = upward_message_decode_weight(message);
+				if dispatched_any && dispatched_weight.saturating_add(decode_weight) > step_weight {
+					next_round_start_with = Some(para);
+					break;
+				}
+				// the message is decoded even if its call doesn't fit, so the decoding is paid
+				// for either way.
+				dispatched_weight = dispatched_weight.saturating_add(decode_weight);
+
+				match Self::decode_upward_message(para, message) {
+					None => Self::deposit_event(Event::UpwardMessageInvalid(para)),
+					Some((call, origin)) => {
+						let call_weight = call.get_dispatch_info().weight;
+						if call_weight > config.dispatchable_upward_message_critical_weight {
+							Self::deposit_event(Event::UpwardMessageOverweight(para, call_weight));
+						} else if dispatched_any
+							&& dispatched_weight.saturating_add(call_weight) > step_weight
+						{
+							next_round_start_with = Some(para);
+							break;
+						} else {
+							dispatched_weight = dispatched_weight.saturating_add(call_weight);
+							if let Err(e) = call.dispatch(origin) {
+								Self::deposit_event(Event::UpwardMessageDispatchFailed(para, e.error));
+							}
+						}
+					}
+				}
+
+				dispatched_any = true;
+				page.remove(0);
+			}
+
+			if !page.is_empty() {
+				index += 1;
+				continue;
+			}
+
+			// the first page is used up, so the next one is loaded the next time the para is
+			// visited.
+			<RelayDispatchQueuePages>::remove(&para, indices.head);
+			indices.head += 1;
+			if indices.head < indices.tail {
+				<RelayDispatchQueuePageIndices>::insert(&para, *indices);
+				index += 1;
+			} else {
+				<RelayDispatchQueuePageIndices>::remove(&para);
+				needs_dispatch.remove(index);
+			}
+			pages.remove(&para);
+		}
+
+		for (para, (indices, page)) in pages {
+			<RelayDispatchQueuePages>::insert(&para, indices.head, page);
+		}
+		<NeedsDispatch>::put(needs_dispatch);
+		<NextDispatchRoundStartWith>::set(next_round_start_with);
+
+		weight = weight.saturating_add(T::DbWeight::get().writes(2));
+		weight.saturating_add(dispatched_weight)
+	}
+
+	/// Move the upward message queues of the layout before paging into pages.
+	fn migrate_relay_dispatch_queues() -> Weight {
+		let mut weight = T::DbWeight::get().reads(1);
+
+		for (para, queue) in <RelayDispatchQueues>::drain() {
+			weight = weight
+				.saturating_add(T::DbWeight::get().reads_writes(1, 1))
+				.saturating_add(Self::enact_upward_messages(para, queue));
+		}
+
+		weight
+	}
+
+	/// Decode an upward message of `sender` into its call and the origin to dispatch it with, or
+	/// `None` if the message is invalid.
+	fn decode_upward_message(
+		sender: ParaId,
+		message: &UpwardMessage,
+	) -> Option<(<T as Trait>::Call, <T as Trait>::Origin)> {
+		let call = <T as Trait>::Call::decode_with_depth_limit(
+			UPWARD_MESSAGE_MAX_CALL_DEPTH,
+			&message.data[..],
+		).ok()?;
+
+		let origin: <T as Trait>::Origin = match message.origin {
+			ParachainDispatchOrigin::Signed =>
+				frame_system::RawOrigin::<T::AccountId>::Signed(sender.into_account()).into(),
+			ParachainDispatchOrigin::Parachain => origin::Origin::Parachain(sender).into(),
+			ParachainDispatchOrigin::Root => return None,
+		};
+
+		Some((call, origin))
+	}
+
+	/// Drop the queued upward messages of `para`.
+	fn clean_upward_dispatch_queue(para: ParaId) {
+		<RelayDispatchQueuePageIndices>::remove(&para);
+		<RelayDispatchQueuePages>::remove_prefix(&para);
+		<NeedsDispatch>::mutate(|paras| if let Ok(i) = paras.binary_search(&para) {
+			paras.remove(i);
+		});
+		if <NextDispatchRoundStartWith>::get() == Some(para) {
+			<NextDispatchRoundStartWith>::kill();
+		}
+	}
+}
+
+mod migration {
+	use super::*;
+	use frame_support::storage::{unhashed, StorageMap};
+
+	/// The layout of [`HrmpChannel`] before deposits were reserved for channels.
+	#[derive(Encode, Decode)]
+	pub(super) struct OldHrmpChannel {
+		pub limit_used_places: u32,
+		pub limit_used_bytes: u32,
+		pub fee_per_byte: Balance,
+		pub used_places: u32,
+		pub used_bytes: u32,
+		pub mqc_head: Hash,
+	}
+
+	impl OldHrmpChannel {
+		/// Convert to the current layout. Nothing was reserved when the channel was opened, so
+		/// nothing is unreserved when it is closed.
+		fn migrate(self) -> HrmpChannel {
+			HrmpChannel {
+				limit_used_places: self.limit_used_places,
+				limit_used_bytes: self.limit_used_bytes,
+				fee_per_byte: self.fee_per_byte,
+				used_places: self.used_places,
+				used_bytes: self.used_bytes,
+				mqc_head: self.mqc_head,
+				sender_deposit: 0,
+				recipient_deposit: 0,
+			}
+		}
+	}
+
+	/// Translate the open channels which are still in the layout before deposits.
+	///
+	/// The deposits are the last fields of [`HrmpChannel`], so channels in the old layout never
+	/// decode in the current one, and those in the current one are left untouched. This makes the
+	/// migration a no-op once done.
+	pub(super) fn migrate_hrmp_channels<T: Trait>() -> Weight {
+		let mut weight = 0;
+
+		for (sender, recipients) in <HrmpEgressChannelsIndex>::iter() {
+			weight += T::DbWeight::get().reads(1);
+
+			for recipient in recipients {
+				let channel_id = HrmpChannelId { sender, recipient };
+				let key = <HrmpChannels>::hashed_key_for(&channel_id);
+				weight += T::DbWeight::get().reads(1);
+
+				let raw = match unhashed::get_raw(&key) {
+					Some(raw) => raw,
+					None => continue,
+				};
+				if HrmpChannel::decode(&mut &raw[..]).is_ok() {
+					continue;
+				}
+				if let Ok(old) = OldHrmpChannel::decode(&mut &raw[..]) {
+					<HrmpChannels>::insert(&channel_id, old.migrate());
+					weight += T::DbWeight::get().writes(1);
+				}
+			}
+		}
+
+		weight
+	}
+}
+
+/// Compute the next head of a Message Queue Chain, after appending a message sent at `sent_at`.
+fn mqc_link<N: Encode>(prev_head: Hash, sent_at: N, data: &[u8]) -> Hash {
+	BlakeTwo256::hash_of(&(prev_head, sent_at, BlakeTwo256::hash(data)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mock::{
+		new_test_ext, Balances, Origin, Paras, Router, System, GenesisConfig as MockGenesisConfig,
+	};
+	use frame_support::{assert_noop, assert_ok, traits::OnRuntimeUpgrade};
+	use sp_runtime::traits::BadOrigin;
+
+	fn channel(limit_used_places: u32, limit_used_bytes: u32, fee_per_byte: Balance) -> HrmpChannel {
+		HrmpChannel {
+			limit_used_places,
+			limit_used_bytes,
+			fee_per_byte,
+			..Default::default()
+		}
+	}
+
+	fn message(recipient: u32, data: Vec<u8>) -> OutboundHrmpMessage<ParaId> {
+		OutboundHrmpMessage { recipient: recipient.into(), data }
+	}
+
+	fn open_channel(sender: u32, recipient: u32, channel: HrmpChannel) {
+		Router::open_hrmp_channel(
+			HrmpChannelId { sender: sender.into(), recipient: recipient.into() },
+			channel,
+		);
+	}
+
+	fn channel_id(sender: u32, recipient: u32) -> HrmpChannelId {
+		HrmpChannelId { sender: sender.into(), recipient: recipient.into() }
+	}
+
+	fn paras(ids: &[u32]) -> Vec<ParaId> {
+		ids.iter().map(|&id| id.into()).collect()
+	}
+
+	fn sovereign_account(para: u32) -> u64 {
+		ParaId::from(para).into_account()
+	}
+
+	#[test]
+	fn verify_outbound_hrmp_checks_order() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			open_channel(1, 2, channel(10, 100, 0));
+			open_channel(1, 3, channel(10, 100, 0));
+
+			assert_ok!(Router::verify_outbound_hrmp(
+				1.into(),
+				&[message(2, vec![1]), message(3, vec![2])],
+			));
+			assert_noop!(
+				Router::verify_outbound_hrmp(1.into(), &[message(3, vec![1]), message(2, vec![2])]),
+				Error::<crate::mock::Test>::HrmpMessagesNotSorted,
+			);
+			assert_noop!(
+				Router::verify_outbound_hrmp(1.into(), &[message(2, vec![1]), message(2, vec![2])]),
+				Error::<crate::mock::Test>::HrmpMessagesNotSorted,
+			);
+		});
+	}
+
+	#[test]
+	fn verify_outbound_hrmp_checks_channels() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			open_channel(1, 2, channel(1, 4, 0));
+
+			assert_noop!(
+				Router::verify_outbound_hrmp(1.into(), &[message(3, vec![1])]),
+				Error::<crate::mock::Test>::HrmpNoSuchChannel,
+			);
+			// channels are unidirectional.
+			assert_noop!(
+				Router::verify_outbound_hrmp(2.into(), &[message(1, vec![1])]),
+				Error::<crate::mock::Test>::HrmpNoSuchChannel,
+			);
+			assert_noop!(
+				Router::verify_outbound_hrmp(1.into(), &[message(2, vec![1; 5])]),
+				Error::<crate::mock::Test>::HrmpChannelCapacityExceeded,
+			);
+			assert_ok!(Router::verify_outbound_hrmp(1.into(), &[message(2, vec![1; 4])]));
+
+			Router::queue_outbound_hrmp(1.into(), vec![message(2, vec![1])]);
+
+			// the single place of the channel is used now.
+			assert_noop!(
+				Router::verify_outbound_hrmp(1.into(), &[message(2, vec![1])]),
+				Error::<crate::mock::Test>::HrmpChannelCapacityExceeded,
+			);
+		});
+	}
+
+	#[test]
+	fn verify_outbound_hrmp_checks_fees() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			open_channel(1, 2, channel(10, 100, 3));
+			open_channel(1, 3, channel(10, 100, 5));
+
+			let messages = [message(2, vec![1; 10]), message(3, vec![1; 10])];
+
+			Balances::make_free_balance_be(&sovereign_account(1), 79);
+			assert_noop!(
+				Router::verify_outbound_hrmp(1.into(), &messages),
+				Error::<crate::mock::Test>::HrmpInsufficientFunds,
+			);
+
+			Balances::make_free_balance_be(&sovereign_account(1), 80);
+			assert_ok!(Router::verify_outbound_hrmp(1.into(), &messages));
+		});
+	}
+
+	#[test]
+	fn queue_outbound_hrmp_works() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			System::set_block_number(5);
+			open_channel(1, 3, channel(10, 100, 2));
+			open_channel(2, 3, channel(10, 100, 0));
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+
+			Router::queue_outbound_hrmp(1.into(), vec![message(3, vec![1, 2, 3])]);
+			Router::queue_outbound_hrmp(2.into(), vec![message(3, vec![4])]);
+
+			let channel_id = HrmpChannelId { sender: 1.into(), recipient: 3.into() };
+			let channel = Router::hrmp_channels(&channel_id).unwrap();
+			assert_eq!(channel.used_places, 1);
+			assert_eq!(channel.used_bytes, 3);
+			assert_eq!(channel.mqc_head, mqc_link(Hash::zero(), 5u32, &[1, 2, 3]));
+			assert_eq!(
+				Router::hrmp_channel_contents(&channel_id),
+				vec![InboundHrmpMessage { sent_at: 5, data: vec![1, 2, 3] }],
+			);
+			assert_eq!(
+				Router::hrmp_channel_digests(&ParaId::from(3)),
+				vec![(5, vec![1.into(), 2.into()])],
+			);
+
+			// 3 bytes at 2 per byte.
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 94);
+
+			System::set_block_number(6);
+			Router::queue_outbound_hrmp(1.into(), vec![message(3, vec![4])]);
+
+			let channel = Router::hrmp_channels(&channel_id).unwrap();
+			assert_eq!(channel.used_places, 2);
+			assert_eq!(
+				channel.mqc_head,
+				mqc_link(mqc_link(Hash::zero(), 5u32, &[1, 2, 3]), 6u32, &[4]),
+			);
+			assert_eq!(
+				Router::hrmp_channel_digests(&ParaId::from(3)),
+				vec![(5, vec![1.into(), 2.into()]), (6, vec![1.into()])],
+			);
+		});
+	}
+
+	#[test]
+	fn check_hrmp_watermark_works() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			System::set_block_number(3);
+			open_channel(1, 2, channel(10, 100, 0));
+			Router::queue_outbound_hrmp(1.into(), vec![message(2, vec![1])]);
+
+			// the relay parent is always a valid watermark.
+			assert_ok!(Router::check_hrmp_watermark(2.into(), 5, 5));
+			// so is a block in which messages were received.
+			assert_ok!(Router::check_hrmp_watermark(2.into(), 5, 3));
+			assert_noop!(
+				Router::check_hrmp_watermark(2.into(), 5, 4),
+				Error::<crate::mock::Test>::HrmpWatermarkWithoutMessages,
+			);
+			assert_noop!(
+				Router::check_hrmp_watermark(2.into(), 5, 6),
+				Error::<crate::mock::Test>::HrmpWatermarkAheadOfRelayParent,
+			);
+
+			Router::prune_hrmp(2.into(), 5);
+			assert_eq!(Router::hrmp_watermarks(&ParaId::from(2)), Some(5));
+
+			assert_noop!(
+				Router::check_hrmp_watermark(2.into(), 6, 5),
+				Error::<crate::mock::Test>::HrmpWatermarkNotAdvanced,
+			);
+			assert_ok!(Router::check_hrmp_watermark(2.into(), 6, 6));
+		});
+	}
+
+	#[test]
+	fn advancing_hrmp_watermark_frees_channel_capacity() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			open_channel(1, 3, channel(2, 100, 0));
+			open_channel(2, 3, channel(10, 100, 0));
+
+			System::set_block_number(1);
+			Router::queue_outbound_hrmp(1.into(), vec![message(3, vec![1, 2])]);
+			System::set_block_number(2);
+			Router::queue_outbound_hrmp(1.into(), vec![message(3, vec![3])]);
+			Router::queue_outbound_hrmp(2.into(), vec![message(3, vec![4])]);
+
+			// the channel is full.
+			assert_noop!(
+				Router::verify_outbound_hrmp(1.into(), &[message(3, vec![5])]),
+				Error::<crate::mock::Test>::HrmpChannelCapacityExceeded,
+			);
+
+			assert_ok!(Router::check_hrmp_watermark(3.into(), 2, 1));
+			Router::prune_hrmp(3.into(), 1);
+
+			// only the message sent at the watermark was pruned.
+			let id = channel_id(1, 3);
+			let channel = Router::hrmp_channels(&id).unwrap();
+			assert_eq!(channel.used_places, 1);
+			assert_eq!(channel.used_bytes, 1);
+			assert_eq!(
+				Router::hrmp_channel_contents(&id),
+				vec![InboundHrmpMessage { sent_at: 2, data: vec![3] }],
+			);
+			assert_eq!(Router::hrmp_channels(&channel_id(2, 3)).unwrap().used_places, 1);
+			assert_eq!(
+				Router::hrmp_channel_digests(&ParaId::from(3)),
+				vec![(2, vec![1.into(), 2.into()])],
+			);
+			assert_eq!(Router::hrmp_watermarks(&ParaId::from(3)), Some(1));
+
+			// the channel has room again.
+			assert_ok!(Router::verify_outbound_hrmp(1.into(), &[message(3, vec![5])]));
+			System::set_block_number(3);
+			Router::queue_outbound_hrmp(1.into(), vec![message(3, vec![5])]);
+			assert_eq!(Router::hrmp_channels(&id).unwrap().used_places, 2);
+
+			assert_ok!(Router::check_hrmp_watermark(3.into(), 3, 3));
+			Router::prune_hrmp(3.into(), 3);
+
+			assert_eq!(Router::hrmp_channels(&id).unwrap().used_places, 0);
+			assert_eq!(Router::hrmp_channels(&id).unwrap().used_bytes, 0);
+			assert_eq!(Router::hrmp_channels(&channel_id(2, 3)).unwrap().used_places, 0);
+			assert!(Router::hrmp_channel_contents(&id).is_empty());
+			assert!(Router::hrmp_channel_digests(&ParaId::from(3)).is_empty());
+		});
+	}
+
+	#[test]
+	fn force_open_hrmp_channel_works() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			assert_noop!(
+				Router::force_open_hrmp_channel(Origin::signed(1), 1.into(), 2.into(), 10, 100, 1),
+				BadOrigin,
+			);
+			assert_noop!(
+				Router::force_open_hrmp_channel(Origin::root(), 1.into(), 1.into(), 10, 100, 1),
+				Error::<crate::mock::Test>::HrmpChannelToSelf,
+			);
+
+			for &para in &[1, 2, 3] {
+				Balances::make_free_balance_be(&sovereign_account(para), 100);
+			}
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 3.into(), 10, 100, 1));
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1));
+			assert_eq!(
+				Router::hrmp_channels(&channel_id(1, 2)),
+				Some(HrmpChannel { sender_deposit: 5, recipient_deposit: 3, ..channel(10, 100, 1) }),
+			);
+			assert_eq!(Router::hrmp_egress_channels_index(&ParaId::from(1)), paras(&[2, 3]));
+			assert_eq!(Router::hrmp_ingress_channels_index(&ParaId::from(2)), paras(&[1]));
+
+			assert_noop!(
+				Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 5, 50, 0),
+				Error::<crate::mock::Test>::HrmpChannelAlreadyExists,
+			);
+		});
+	}
+
+	#[test]
+	fn force_clean_hrmp_works() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			System::set_block_number(5);
+			open_channel(1, 2, channel(10, 100, 0));
+			open_channel(2, 1, channel(10, 100, 0));
+			open_channel(3, 1, channel(10, 100, 0));
+			open_channel(3, 2, channel(10, 100, 0));
+
+			Router::queue_outbound_hrmp(1.into(), vec![message(2, vec![1])]);
+			Router::queue_outbound_hrmp(3.into(), vec![message(1, vec![2]), message(2, vec![3])]);
+
+			// para 1 has two inbound channels and one outbound channel.
+			assert_noop!(
+				Router::force_clean_hrmp(Origin::root(), 1.into(), 1, 1),
+				Error::<crate::mock::Test>::HrmpTooManyChannels,
+			);
+			assert_noop!(
+				Router::force_clean_hrmp(Origin::root(), 1.into(), 2, 0),
+				Error::<crate::mock::Test>::HrmpTooManyChannels,
+			);
+			assert_noop!(Router::force_clean_hrmp(Origin::signed(1), 1.into(), 2, 1), BadOrigin);
+
+			assert_ok!(Router::force_clean_hrmp(Origin::root(), 1.into(), 2, 1));
+
+			for &(sender, recipient) in &[(1, 2), (2, 1), (3, 1)] {
+				assert!(Router::hrmp_channels(&channel_id(sender, recipient)).is_none());
+				assert!(Router::hrmp_channel_contents(&channel_id(sender, recipient)).is_empty());
+			}
+			assert!(Router::hrmp_egress_channels_index(&ParaId::from(1)).is_empty());
+			assert!(Router::hrmp_ingress_channels_index(&ParaId::from(1)).is_empty());
+			assert!(Router::hrmp_channel_digests(&ParaId::from(1)).is_empty());
+
+			// the channel between the other paras is untouched.
+			assert!(Router::hrmp_channels(&channel_id(3, 2)).is_some());
+			assert_eq!(Router::hrmp_channel_contents(&channel_id(3, 2)).len(), 1);
+			assert_eq!(Router::hrmp_egress_channels_index(&ParaId::from(3)), paras(&[2]));
+			assert_eq!(Router::hrmp_ingress_channels_index(&ParaId::from(2)), paras(&[3]));
+			assert_eq!(Router::hrmp_channel_digests(&ParaId::from(2)), vec![(5, paras(&[3]))]);
+		});
+	}
+
+	#[test]
+	fn channels_of_outgoing_paras_are_closed_at_session_change() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			open_channel(1, 2, channel(10, 100, 0));
+			open_channel(2, 3, channel(10, 100, 0));
+
+			Paras::schedule_para_cleanup(1.into());
+			let outgoing = Paras::initializer_on_new_session(&Default::default());
+			assert_eq!(outgoing, paras(&[1]));
+
+			Router::initializer_on_new_session(&Default::default(), &outgoing);
+			assert_eq!(Router::outgoing_hrmp_paras(), paras(&[1]));
+
+			Router::initializer_initialize(1);
+
+			assert!(Router::hrmp_channels(&channel_id(1, 2)).is_none());
+			assert!(Router::hrmp_channels(&channel_id(2, 3)).is_some());
+			assert_eq!(Router::hrmp_egress_channels_index(&ParaId::from(2)), paras(&[3]));
+			assert!(Router::hrmp_ingress_channels_index(&ParaId::from(2)).is_empty());
+			assert!(Router::outgoing_hrmp_paras().is_empty());
+		});
+	}
+
+	#[test]
+	fn channels_of_outgoing_paras_are_closed_over_several_blocks() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			let max = MAX_OUTGOING_HRMP_CHANNELS_CLOSED_PER_BLOCK;
+			for recipient in 0..max {
+				open_channel(1, 100 + recipient, channel(10, 100, 0));
+			}
+			open_channel(2, 3, channel(10, 100, 0));
+			open_channel(3, 2, channel(10, 100, 0));
+
+			Router::initializer_on_new_session(&Default::default(), &paras(&[1, 2]));
+
+			// the channels of para 1 use up the first block.
+			Router::initializer_initialize(1);
+			assert!(Router::hrmp_egress_channels_index(&ParaId::from(1)).is_empty());
+			assert_eq!(Router::hrmp_egress_channels_index(&ParaId::from(2)), paras(&[3]));
+			assert_eq!(Router::outgoing_hrmp_paras(), paras(&[2]));
+
+			Router::initializer_initialize(2);
+			assert!(Router::hrmp_channels(&channel_id(2, 3)).is_none());
+			assert!(Router::hrmp_channels(&channel_id(3, 2)).is_none());
+			assert!(Router::outgoing_hrmp_paras().is_empty());
+		});
+	}
+
+	#[test]
+	fn force_open_hrmp_channel_reserves_deposits() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			// the recipient can't afford its deposit of 3.
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+			Balances::make_free_balance_be(&sovereign_account(2), 3);
+			assert_noop!(
+				Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1),
+				Error::<crate::mock::Test>::HrmpInsufficientDeposit,
+			);
+
+			// nor can the sender afford its deposit of 5.
+			Balances::make_free_balance_be(&sovereign_account(2), 100);
+			Balances::make_free_balance_be(&sovereign_account(1), 5);
+			assert_noop!(
+				Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1),
+				Error::<crate::mock::Test>::HrmpInsufficientDeposit,
+			);
+
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1));
+			assert_eq!(Balances::reserved_balance(&sovereign_account(1)), 5);
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 95);
+			assert_eq!(Balances::reserved_balance(&sovereign_account(2)), 3);
+			assert_eq!(Balances::free_balance(&sovereign_account(2)), 97);
+		});
+	}
+
+	#[test]
+	fn force_clean_hrmp_unreserves_deposits() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			for &para in &[1, 2, 3] {
+				Balances::make_free_balance_be(&sovereign_account(para), 100);
+			}
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1));
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 3.into(), 1.into(), 10, 100, 1));
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 2.into(), 3.into(), 10, 100, 1));
+
+			assert_ok!(Router::force_clean_hrmp(Origin::root(), 1.into(), 1, 1));
+
+			// only the deposits of the channel between 2 and 3 are left.
+			assert_eq!(Balances::reserved_balance(&sovereign_account(1)), 0);
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 100);
+			assert_eq!(Balances::reserved_balance(&sovereign_account(2)), 5);
+			assert_eq!(Balances::reserved_balance(&sovereign_account(3)), 3);
+		});
+	}
+
+	#[test]
+	fn deposits_of_outgoing_paras_are_unreserved() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+			Balances::make_free_balance_be(&sovereign_account(2), 100);
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1));
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 2.into(), 1.into(), 10, 100, 1));
+			assert_eq!(Balances::reserved_balance(&sovereign_account(2)), 8);
+
+			Router::initializer_on_new_session(&Default::default(), &paras(&[1]));
+			Router::initializer_initialize(1);
+
+			assert_eq!(Balances::reserved_balance(&sovereign_account(1)), 0);
+			assert_eq!(Balances::reserved_balance(&sovereign_account(2)), 0);
+			assert_eq!(Balances::free_balance(&sovereign_account(2)), 100);
+		});
+	}
+
+	#[test]
+	fn migration_translates_hrmp_channels() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			open_channel(1, 2, channel(10, 100, 1));
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+			Balances::make_free_balance_be(&sovereign_account(3), 100);
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 3.into(), 10, 100, 1));
+			let current = Router::hrmp_channels(&channel_id(1, 3));
+
+			let old = migration::OldHrmpChannel {
+				limit_used_places: 10,
+				limit_used_bytes: 100,
+				fee_per_byte: 1,
+				used_places: 1,
+				used_bytes: 4,
+				mqc_head: Hash::repeat_byte(1),
+			};
+			frame_support::storage::unhashed::put(
+				&<HrmpChannels>::hashed_key_for(&channel_id(1, 2)),
+				&old,
+			);
+
+			Router::on_runtime_upgrade();
+
+			let migrated = HrmpChannel {
+				used_places: 1,
+				used_bytes: 4,
+				mqc_head: Hash::repeat_byte(1),
+				..channel(10, 100, 1)
+			};
+			assert_eq!(Router::hrmp_channels(&channel_id(1, 2)), Some(migrated.clone()));
+			assert_eq!(Router::hrmp_channels(&channel_id(1, 3)), current);
+
+			// running the migration again leaves the channels untouched.
+			Router::on_runtime_upgrade();
+			assert_eq!(Router::hrmp_channels(&channel_id(1, 2)), Some(migrated));
+			assert_eq!(Router::hrmp_channels(&channel_id(1, 3)), current);
+		});
+	}
+
+	#[test]
+	fn hrmp_close_channel_works() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			System::set_block_number(1);
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+			Balances::make_free_balance_be(&sovereign_account(2), 100);
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1));
+			open_channel(2, 3, channel(10, 100, 0));
+
+			let parachain = |para: u32| Origin::from(crate::origin::Origin::Parachain(para.into()));
+			assert_noop!(Router::hrmp_close_channel(Origin::root(), channel_id(1, 2)), BadOrigin);
+			assert_noop!(Router::hrmp_close_channel(Origin::signed(1), channel_id(1, 2)), BadOrigin);
+			assert_noop!(
+				Router::hrmp_close_channel(parachain(3), channel_id(1, 2)),
+				Error::<crate::mock::Test>::HrmpNotChannelParticipant,
+			);
+			assert_noop!(
+				Router::hrmp_close_channel(parachain(2), channel_id(2, 1)),
+				Error::<crate::mock::Test>::HrmpNoSuchChannel,
+			);
+
+			// the recipient may close the channel too.
+			assert_ok!(Router::hrmp_close_channel(parachain(2), channel_id(1, 2)));
+			assert!(Router::hrmp_channels(&channel_id(1, 2)).is_none());
+			assert!(Router::hrmp_egress_channels_index(&ParaId::from(1)).is_empty());
+			assert_eq!(Balances::reserved_balance(&sovereign_account(1)), 0);
+			assert_eq!(Balances::reserved_balance(&sovereign_account(2)), 0);
+			assert!(Router::hrmp_channels(&channel_id(2, 3)).is_some());
+
+			assert_eq!(router_events(), vec![
+				Event::HrmpChannelForceOpened(1.into(), 2.into()),
+				Event::HrmpChannelClosed(2.into(), 1.into(), 2.into()),
+			]);
+		});
+	}
+
+	#[test]
+	fn paras_close_channels_with_upward_messages() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			open_channel(1, 2, channel(10, 100, 0));
+			open_channel(1, 3, channel(10, 100, 0));
+
+			let close = |sender, recipient| crate::mock::Call::Router(
+				Call::hrmp_close_channel(channel_id(sender, recipient)),
+			).encode();
+			Router::enact_upward_messages(1.into(), vec![
+				UpwardMessage { origin: ParachainDispatchOrigin::Parachain, data: close(1, 2) },
+				// the sovereign account of the para doesn't pass for the para.
+				UpwardMessage { origin: ParachainDispatchOrigin::Signed, data: close(1, 3) },
+			]);
+			Router::process_upward_dispatchables();
+
+			assert!(Router::hrmp_channels(&channel_id(1, 2)).is_none());
+			assert!(Router::hrmp_channels(&channel_id(1, 3)).is_some());
+		});
+	}
+
+	#[test]
+	fn force_hrmp_calls_deposit_events() {
+		new_test_ext(MockGenesisConfig::default()).execute_with(|| {
+			System::set_block_number(1);
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+			Balances::make_free_balance_be(&sovereign_account(2), 100);
+
+			assert_ok!(Router::force_open_hrmp_channel(Origin::root(), 1.into(), 2.into(), 10, 100, 1));
+			assert_ok!(Router::force_clean_hrmp(Origin::root(), 1.into(), 0, 1));
+
+			assert_eq!(router_events(), vec![
+				Event::HrmpChannelForceOpened(1.into(), 2.into()),
+				Event::HrmpChannelsForceCleaned(1.into()),
+			]);
+		});
+	}
+
+	fn transfer_call(value: u128) -> crate::mock::Call {
+		crate::mock::Call::Balances(pallet_balances::Call::transfer(5, value))
+	}
+
+	fn signed_transfer(value: u128) -> UpwardMessage {
+		UpwardMessage { origin: ParachainDispatchOrigin::Signed, data: transfer_call(value).encode() }
+	}
+
+	fn router_events() -> Vec<Event> {
+		System::events().into_iter().filter_map(|record| match record.event {
+			crate::mock::TestEvent::router(event) => Some(event),
+			_ => None,
+		}).collect()
+	}
+
+	/// The queued upward messages of `para`, oldest first.
+	fn dispatch_queue(para: u32) -> Vec<UpwardMessage> {
+		let para = ParaId::from(para);
+		Router::relay_dispatch_queue_page_indices(&para).map_or(Vec::new(), |indices| {
+			(indices.head..indices.tail)
+				.flat_map(|page| Router::relay_dispatch_queue_page(&para, page))
+				.collect()
+		})
+	}
+
+	fn ump_genesis(step_weight: Weight, critical_weight: Weight) -> MockGenesisConfig {
+		MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: crate::configuration::HostConfiguration {
+					preferred_dispatchable_upward_messages_step_weight: step_weight,
+					dispatchable_upward_message_critical_weight: critical_weight,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn upward_messages_are_dispatched() {
+		let transfer_weight = transfer_call(10).get_dispatch_info().weight;
+
+		new_test_ext(ump_genesis(Weight::max_value(), transfer_weight)).execute_with(|| {
+			System::set_block_number(1);
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+
+			Router::enact_upward_messages(1.into(), vec![
+				signed_transfer(10),
+				// a transfer requires a signed origin.
+				UpwardMessage {
+					origin: ParachainDispatchOrigin::Parachain,
+					data: transfer_call(20).encode(),
+				},
+				// no para may use the root origin.
+				UpwardMessage { origin: ParachainDispatchOrigin::Root, data: transfer_call(30).encode() },
+				UpwardMessage { origin: ParachainDispatchOrigin::Signed, data: vec![0xff, 0xff] },
+			]);
+
+			// nothing is dispatched until the messages are processed.
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 100);
+			assert_eq!(Router::needs_dispatch(), paras(&[1]));
+			assert_eq!(dispatch_queue(1).len(), 4);
+
+			let weight = Router::process_upward_dispatchables();
+
+			// only the calls which were dispatched are accounted for, but all messages were decoded.
+			let decode_weight: Weight = [transfer_call(10), transfer_call(20), transfer_call(30)].iter()
+				.map(|call| UpwardMessage { origin: ParachainDispatchOrigin::Signed, data: call.encode() })
+				.chain(Some(UpwardMessage { origin: ParachainDispatchOrigin::Signed, data: vec![0xff, 0xff] }))
+				.map(|message| upward_message_decode_weight(&message))
+				.sum();
+			assert!(weight >= 2 * transfer_weight + decode_weight);
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 90);
+			assert_eq!(Balances::free_balance(&5), 10);
+			assert_eq!(router_events(), vec![
+				Event::UpwardMessageDispatchFailed(1.into(), DispatchError::BadOrigin),
+				Event::UpwardMessageInvalid(1.into()),
+				Event::UpwardMessageInvalid(1.into()),
+			]);
+
+			assert!(Router::needs_dispatch().is_empty());
+			assert!(dispatch_queue(1).is_empty());
+			assert!(Router::next_dispatch_round_start_with().is_none());
+		});
+	}
+
+	#[test]
+	fn overweight_upward_messages_are_dropped() {
+		let transfer_weight = transfer_call(10).get_dispatch_info().weight;
+
+		new_test_ext(ump_genesis(Weight::max_value(), transfer_weight - 1)).execute_with(|| {
+			System::set_block_number(1);
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+
+			Router::enact_upward_messages(1.into(), vec![signed_transfer(10)]);
+			Router::process_upward_dispatchables();
+
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 100);
+			assert_eq!(
+				router_events(),
+				vec![Event::UpwardMessageOverweight(1.into(), transfer_weight)],
+			);
+			assert!(Router::needs_dispatch().is_empty());
+		});
+	}
+
+	#[test]
+	fn upward_dispatch_is_bounded_by_step_weight() {
+		let transfer_weight = transfer_call(10).get_dispatch_info().weight;
+		let message_weight = transfer_weight + upward_message_decode_weight(&signed_transfer(1));
+
+		new_test_ext(ump_genesis(2 * message_weight, transfer_weight)).execute_with(|| {
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+			Balances::make_free_balance_be(&sovereign_account(2), 100);
+
+			Router::enact_upward_messages(2.into(), vec![signed_transfer(1), signed_transfer(2)]);
+			Router::enact_upward_messages(
+				1.into(),
+				vec![signed_transfer(1), signed_transfer(2), signed_transfer(3)],
+			);
+			assert_eq!(Router::needs_dispatch(), paras(&[1, 2]));
+
+			// the first message of each para fits.
+			Router::process_upward_dispatchables();
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 99);
+			assert_eq!(Balances::free_balance(&sovereign_account(2)), 99);
+			assert_eq!(Router::next_dispatch_round_start_with(), Some(1.into()));
+
+			Router::process_upward_dispatchables();
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 97);
+			assert_eq!(Balances::free_balance(&sovereign_account(2)), 97);
+			assert_eq!(Router::needs_dispatch(), paras(&[1]));
+			assert_eq!(Router::next_dispatch_round_start_with(), Some(1.into()));
+
+			Router::process_upward_dispatchables();
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 94);
+			assert!(Router::needs_dispatch().is_empty());
+			assert!(dispatch_queue(1).is_empty());
+			assert!(Router::next_dispatch_round_start_with().is_none());
+		});
+	}
+
+	#[test]
+	fn upward_message_decoding_counts_towards_step_weight() {
+		let invalid = UpwardMessage { origin: ParachainDispatchOrigin::Signed, data: vec![0xff; 100] };
+		let decode_weight = upward_message_decode_weight(&invalid);
+
+		new_test_ext(ump_genesis(2 * decode_weight, Weight::max_value())).execute_with(|| {
+			System::set_block_number(1);
+			Router::enact_upward_messages(1.into(), vec![invalid.clone(); 3]);
+
+			// the messages don't decode, but only two of them fit into the step weight.
+			let weight = Router::process_upward_dispatchables();
+			assert!(weight >= 2 * decode_weight);
+			assert_eq!(router_events(), vec![Event::UpwardMessageInvalid(1.into()); 2]);
+			assert_eq!(dispatch_queue(1), vec![invalid]);
+			assert_eq!(Router::next_dispatch_round_start_with(), Some(1.into()));
+		});
+	}
+
+	#[test]
+	fn upward_dispatch_dispatches_at_least_one_message() {
+		let transfer_weight = transfer_call(10).get_dispatch_info().weight;
+
+		new_test_ext(ump_genesis(0, transfer_weight)).execute_with(|| {
+			Balances::make_free_balance_be(&sovereign_account(1), 100);
+
+			Router::enact_upward_messages(1.into(), vec![signed_transfer(1), signed_transfer(2)]);
+
+			Router::process_upward_dispatchables();
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 99);
+			assert_eq!(dispatch_queue(1), vec![signed_transfer(2)]);
+		});
+	}
+
+	#[test]
+	fn upward_messages_of_outgoing_paras_are_dropped_at_session_change() {
+		new_test_ext(ump_genesis(0, Weight::max_value())).execute_with(|| {
+			Router::enact_upward_messages(1.into(), vec![signed_transfer(1), signed_transfer(2)]);
+			Router::enact_upward_messages(2.into(), vec![signed_transfer(1), signed_transfer(2)]);
+
+			Router::process_upward_dispatchables();
+			assert_eq!(Router::next_dispatch_round_start_with(), Some(2.into()));
+
+			Paras::schedule_para_cleanup(2.into());
+			let outgoing = Paras::initializer_on_new_session(&Default::default());
+			Router::initializer_on_new_session(&Default::default(), &outgoing);
+
+			assert_eq!(Router::needs_dispatch(), paras(&[1]));
+			assert!(dispatch_queue(2).is_empty());
+			assert!(Router::next_dispatch_round_start_with().is_none());
+			assert_eq!(dispatch_queue(1), vec![signed_transfer(2)]);
+		});
+	}
+
+	#[test]
+	fn upward_messages_are_queued_in_pages() {
+		let transfer_weight = transfer_call(10).get_dispatch_info().weight;
+		let page_size = UPWARD_QUEUE_PAGE_SIZE as u128;
+
+		new_test_ext(ump_genesis(0, transfer_weight)).execute_with(|| {
+			Balances::make_free_balance_be(&sovereign_account(1), 1_000);
+
+			let messages = |values: std::ops::Range<u128>| -> Vec<_> {
+				values.map(signed_transfer).collect()
+			};
+
+			Router::enact_upward_messages(1.into(), messages(0..page_size - 1));
+			assert_eq!(
+				Router::relay_dispatch_queue_page_indices(&ParaId::from(1)),
+				Some(QueuePageIndices { head: 0, tail: 1 }),
+			);
+
+			// the last page is filled up before new pages are added.
+			Router::enact_upward_messages(1.into(), messages(page_size - 1..2 * page_size + 1));
+			assert_eq!(
+				Router::relay_dispatch_queue_page_indices(&ParaId::from(1)),
+				Some(QueuePageIndices { head: 0, tail: 3 }),
+			);
+			for page in 0..2 {
+				assert_eq!(
+					Router::relay_dispatch_queue_page(&ParaId::from(1), page).len(),
+					UPWARD_QUEUE_PAGE_SIZE,
+				);
+			}
+			assert_eq!(
+				Router::relay_dispatch_queue_page(&ParaId::from(1), 2),
+				messages(2 * page_size..2 * page_size + 1),
+			);
+			assert_eq!(dispatch_queue(1), messages(0..2 * page_size + 1));
+
+			// a used up page is removed.
+			for _ in 0..page_size {
+				Router::process_upward_dispatchables();
+			}
+			assert_eq!(
+				Router::relay_dispatch_queue_page_indices(&ParaId::from(1)),
+				Some(QueuePageIndices { head: 1, tail: 3 }),
+			);
+			assert!(Router::relay_dispatch_queue_page(&ParaId::from(1), 0).is_empty());
+			assert_eq!(dispatch_queue(1), messages(page_size..2 * page_size + 1));
+
+			for _ in 0..page_size + 1 {
+				Router::process_upward_dispatchables();
+			}
+			assert!(Router::relay_dispatch_queue_page_indices(&ParaId::from(1)).is_none());
+			assert!(Router::needs_dispatch().is_empty());
+
+			// the page indices start over once the queue is used up.
+			Router::enact_upward_messages(1.into(), messages(0..1));
+			assert_eq!(
+				Router::relay_dispatch_queue_page_indices(&ParaId::from(1)),
+				Some(QueuePageIndices { head: 0, tail: 1 }),
+			);
+		});
+	}
+
+	#[test]
+	fn upward_dispatch_may_use_up_many_pages_in_a_block() {
+		let transfer_weight = transfer_call(10).get_dispatch_info().weight;
+		let count = 3 * UPWARD_QUEUE_PAGE_SIZE as u128;
+
+		new_test_ext(ump_genesis(Weight::max_value(), transfer_weight)).execute_with(|| {
+			Balances::make_free_balance_be(&sovereign_account(1), 1_000);
+
+			Router::enact_upward_messages(1.into(), vec![signed_transfer(1); count as usize]);
+			Router::process_upward_dispatchables();
+
+			assert_eq!(Balances::free_balance(&sovereign_account(1)), 1_000 - count);
+			assert!(dispatch_queue(1).is_empty());
+			assert!(Router::needs_dispatch().is_empty());
+		});
+	}
+
+	#[test]
+	fn unpaged_upward_message_queues_are_migrated() {
+		new_test_ext(ump_genesis(0, Weight::max_value())).execute_with(|| {
+			let long_queue = vec![signed_transfer(1); UPWARD_QUEUE_PAGE_SIZE + 1];
+			<RelayDispatchQueues>::insert(&ParaId::from(1), long_queue.clone());
+			<RelayDispatchQueues>::insert(&ParaId::from(2), vec![signed_transfer(2)]);
+			<NeedsDispatch>::put(paras(&[1, 2]));
+
+			Router::on_runtime_upgrade();
+
+			assert_eq!(dispatch_queue(1), long_queue);
+			assert_eq!(dispatch_queue(2), vec![signed_transfer(2)]);
+			assert_eq!(
+				Router::relay_dispatch_queue_page_indices(&ParaId::from(1)),
+				Some(QueuePageIndices { head: 0, tail: 2 }),
+			);
+			assert_eq!(Router::needs_dispatch(), paras(&[1, 2]));
+			assert_eq!(<RelayDispatchQueues>::iter().count(), 0);
+
+			// the migration is a no-op once the old queues are gone.
+			Router::on_runtime_upgrade();
+			assert_eq!(dispatch_queue(1), long_queue);
+		});
+	}
+}
@@ -18,21 +18,28 @@
 //! functions.
 
 use sp_std::prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use primitives::v1::{
 	ValidatorId, ValidatorIndex, GroupRotationInfo, CoreState, ValidationData,
 	Id as ParaId, OccupiedCoreAssumption, SessionIndex, ValidationCode,
 	CommittedCandidateReceipt, ScheduledCore, OccupiedCore, CoreOccupied, CoreIndex,
-	GroupIndex, CandidateEvent, PersistedValidationData,
+	GroupIndex, CandidateEvent, PersistedValidationData, ScrapedOnChainVotes, Hash,
+	ExecutorParams,
 };
 use sp_runtime::traits::Zero;
 use frame_support::debug;
-use crate::{initializer, inclusion, scheduler, configuration, paras};
+use crate::{initializer, inclusion, inclusion_inherent, scheduler, configuration, paras};
 
 /// Implementation for the `validators` function of the runtime API.
 pub fn validators<T: initializer::Trait>() -> Vec<ValidatorId> {
 	<inclusion::Module<T>>::validators()
 }
 
+/// Implementation for the `active_validator_indices` function of the runtime API.
+pub fn active_validator_indices<T: initializer::Trait>() -> Vec<ValidatorIndex> {
+	<initializer::Module<T>>::active_validator_indices()
+}
+
 /// Implementation for the `validator_groups` function of the runtime API.
 pub fn validator_groups<T: initializer::Trait>() -> (
 	Vec<Vec<ValidatorIndex>>,
@@ -204,6 +211,37 @@ pub fn full_validation_data<T: initializer::Trait>(
 	)
 }
 
+/// Implementation for the `full_validation_data_at` function of the runtime API.
+pub fn full_validation_data_at<T: initializer::Trait>(
+	para_id: ParaId,
+	relay_parent_number: T::BlockNumber,
+	assumption: OccupiedCoreAssumption,
+)
+	-> Option<ValidationData<T::BlockNumber>>
+{
+	with_assumption::<T, _, _>(
+		para_id,
+		assumption,
+		|| Some(ValidationData {
+			persisted: crate::util::make_persisted_validation_data_at::<T>(
+				para_id,
+				relay_parent_number,
+			)?,
+			transient: crate::util::make_transient_validation_data_at::<T>(
+				para_id,
+				relay_parent_number,
+			)?,
+		}),
+	)
+}
+
+/// Implementation for the `allowed_relay_parents` function of the runtime API.
+pub fn allowed_relay_parents<T: initializer::Trait>() -> Vec<(T::Hash, T::BlockNumber)> {
+	// Like `session_index_for_child`, this relies on runtime APIs following initialization, so
+	// the parent of the child is already the first entry.
+	<inclusion::Module<T>>::allowed_relay_parents()
+}
+
 /// Implementation for the `persisted_validation_data` function of the runtime API.
 pub fn persisted_validation_data<T: initializer::Trait>(
 	para_id: ParaId,
@@ -228,6 +266,12 @@ pub fn session_index_for_child<T: initializer::Trait>() -> SessionIndex {
 	<inclusion::Module<T>>::session_index()
 }
 
+/// Implementation for the `availability_chunk_shuffle_seed` function of the runtime API.
+pub fn availability_chunk_shuffle_seed<T: initializer::Trait>() -> [u8; 32] {
+	// Like `session_index_for_child`, any pending session change has already been applied.
+	<scheduler::Module<T>>::availability_chunk_shuffle_seed()
+}
+
 /// Implementation for the `validation_code` function of the runtime API.
 pub fn validation_code<T: initializer::Trait>(
 	para_id: ParaId,
@@ -240,6 +284,37 @@ pub fn validation_code<T: initializer::Trait>(
 	)
 }
 
+/// Implementation for the `validation_code_by_hash` function of the runtime API.
+pub fn validation_code_by_hash<T: paras::Trait>(hash: Hash) -> Option<ValidationCode> {
+	<paras::Module<T>>::code_by_hash(&hash)
+}
+
+/// Implementation for the `historical_validation_code` function of the runtime API.
+pub fn historical_validation_code<T: initializer::Trait>(
+	para_id: ParaId,
+	context_height: T::BlockNumber,
+) -> Option<ValidationCode> {
+	<paras::Module<T>>::validation_code_at(para_id, context_height, None)
+}
+
+/// Implementation for the `session_executor_params` function of the runtime API.
+pub fn session_executor_params<T: initializer::Trait>(
+	session_index: SessionIndex,
+) -> Option<ExecutorParams> {
+	// Like `session_index_for_child`, this relies on runtime APIs following initialization,
+	// so the configuration is the one of the session expected at the child of the block.
+	if session_index == <inclusion::Module<T>>::session_index() {
+		Some(<configuration::Module<T>>::config().executor_params)
+	} else {
+		None
+	}
+}
+
+/// Implementation for the `claim_queue` function of the runtime API.
+pub fn claim_queue<T: initializer::Trait>() -> BTreeMap<CoreIndex, Vec<ParaId>> {
+	<scheduler::Module<T>>::claim_queue()
+}
+
 /// Implementation for the `candidate_pending_availability` function of the runtime API.
 pub fn candidate_pending_availability<T: initializer::Trait>(para_id: ParaId)
 	-> Option<CommittedCandidateReceipt<T::Hash>>
@@ -266,3 +341,11 @@ where
 		})
 		.collect()
 }
+
+/// Implementation for the `on_chain_votes` function of the runtime API.
+pub fn on_chain_votes<T>() -> Option<ScrapedOnChainVotes<T::Hash>>
+where
+	T: initializer::Trait + inclusion_inherent::Trait,
+{
+	<inclusion_inherent::Module<T>>::on_chain_votes()
+}
@@ -36,6 +36,7 @@
 //! over time.
 
 use sp_std::prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use sp_std::convert::TryInto;
 use primitives::v1::{
 	Id as ParaId, ValidatorIndex, CoreOccupied, CoreIndex, CollatorId,
@@ -53,6 +54,10 @@ use rand_chacha::ChaCha20Rng;
 
 use crate::{configuration, paras, initializer::SessionChangeNotification};
 
+/// The subject the session's random seed is hashed with to obtain the availability chunk
+/// shuffle seed, keeping it independent from the seed used to shuffle validator groups.
+const AVAILABILITY_CHUNK_SHUFFLE_SUBJECT: &[u8] = b"availability-chunks";
+
 /// A queued parathread entry, pre-assigned to a core.
 #[derive(Encode, Decode, Default)]
 #[cfg_attr(test, derive(PartialEq, Debug))]
@@ -182,6 +187,9 @@ decl_storage! {
 		ParathreadClaimIndex: Vec<ParaId>;
 		/// The block number where the session start occurred. Used to track how many group rotations have occurred.
 		SessionStartBlock get(fn session_start_block): T::BlockNumber;
+		/// The seed used by validators to shuffle the assignment of erasure chunks to validators
+		/// for the current session. Derived from the session's random seed.
+		AvailabilityChunkShuffleSeed get(fn availability_chunk_shuffle_seed): [u8; 32];
 		/// Currently scheduled cores - free but up to be occupied. Ephemeral storage item that's wiped on finalization.
 		///
 		/// Bounded by the number of cores: one for each parachain and parathread multiplexer.
@@ -244,6 +252,9 @@ impl<T: Trait> Module<T> {
 		let n_cores = n_parachains + config.parathread_cores;
 
 		<SessionStartBlock<T>>::set(<frame_system::Module<T>>::block_number());
+		AvailabilityChunkShuffleSeed::set(
+			(AVAILABILITY_CHUNK_SHUFFLE_SUBJECT, random_seed).using_encoded(sp_io::hashing::blake2_256)
+		);
 		AvailabilityCores::mutate(|cores| {
 			// clear all occupied cores.
 			for maybe_occupied in cores.iter_mut() {
@@ -655,6 +666,44 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Return the paras assigned to each core for the next `scheduling_lookahead` blocks, in
+	/// order. Cores without any upcoming assignment are left out.
+	///
+	/// For parachains, this is always the ID of the parachain. For parathreads, this is the
+	/// claim scheduled on the core, if any, followed by the items in the ParathreadQueue assigned
+	/// to that core.
+	pub(crate) fn claim_queue() -> BTreeMap<CoreIndex, Vec<ParaId>> {
+		let config = <configuration::Module<T>>::config();
+		let lookahead = config.scheduling_lookahead.max(1) as usize;
+		let parachains = <paras::Module<T>>::parachains();
+		let scheduled = Scheduled::get();
+		let queue = ParathreadQueue::get();
+
+		let n_cores = AvailabilityCores::get().len();
+		(0..n_cores).filter_map(|i| {
+			let core = CoreIndex(i as u32);
+			let claims: Vec<ParaId> = if i < parachains.len() {
+				vec![parachains[i]; lookahead]
+			} else {
+				let core_offset = (i - parachains.len()) as u32;
+				let scheduled = scheduled.iter()
+					.find(|assignment| assignment.core == core)
+					.map(|assignment| assignment.para_id);
+				let queued = queue.queue.iter()
+					.filter(|queued| queued.core_offset == core_offset)
+					.map(|queued| queued.claim.claim.0);
+
+				scheduled.into_iter().chain(queued).take(lookahead).collect()
+			};
+
+			if claims.is_empty() {
+				None
+			} else {
+				Some((core, claims))
+			}
+		}).collect()
+	}
+
 	/// Return the next thing that will be scheduled on this core assuming it is currently
 	/// occupied and the candidate occupying it became available.
 	///
@@ -1054,6 +1103,44 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn session_change_sets_availability_chunk_shuffle_seed() {
+		let genesis_config = MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: default_config(),
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let new_session = |number: BlockNumber| match number {
+				1 => Some(SessionChangeNotification {
+					new_config: default_config(),
+					random_seed: [99; 32],
+					..Default::default()
+				}),
+				2 => Some(SessionChangeNotification {
+					new_config: default_config(),
+					random_seed: [100; 32],
+					..Default::default()
+				}),
+				_ => None,
+			};
+
+			run_to_block(1, new_session);
+			let first_seed = Scheduler::availability_chunk_shuffle_seed();
+			assert_eq!(
+				first_seed,
+				(AVAILABILITY_CHUNK_SHUFFLE_SUBJECT, &[99u8; 32]).using_encoded(sp_io::hashing::blake2_256),
+			);
+			assert_ne!(first_seed, [99; 32]);
+
+			run_to_block(2, new_session);
+			assert_ne!(Scheduler::availability_chunk_shuffle_seed(), first_seed);
+		});
+	}
+
 	#[test]
 	fn schedule_schedules() {
 		let genesis_config = MockGenesisConfig {
@@ -1328,6 +1415,92 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn claim_queue_works() {
+		let genesis_config = MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: default_config(),
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		let chain_a = ParaId::from(1);
+		let chain_b = ParaId::from(2);
+
+		let thread_a = ParaId::from(3);
+		let thread_b = ParaId::from(4);
+		let thread_c = ParaId::from(5);
+		let thread_d = ParaId::from(6);
+
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		let schedule_blank_para = |id, is_chain| Paras::schedule_para_initialize(id, ParaGenesisArgs {
+			genesis_head: Vec::new().into(),
+			validation_code: Vec::new().into(),
+			parachain: is_chain,
+		});
+
+		new_test_ext(genesis_config).execute_with(|| {
+			assert_eq!(default_config().parathread_cores, 3);
+			assert_eq!(default_config().scheduling_lookahead, 2);
+
+			schedule_blank_para(chain_a, true);
+			schedule_blank_para(chain_b, true);
+
+			schedule_blank_para(thread_a, false);
+			schedule_blank_para(thread_b, false);
+			schedule_blank_para(thread_c, false);
+			schedule_blank_para(thread_d, false);
+
+			// start a new session to activate, 5 validators for 5 cores.
+			run_to_block(1, |number| match number {
+				1 => Some(SessionChangeNotification {
+					new_config: default_config(),
+					validators: vec![
+						ValidatorId::from(Sr25519Keyring::Alice.public()),
+						ValidatorId::from(Sr25519Keyring::Bob.public()),
+						ValidatorId::from(Sr25519Keyring::Charlie.public()),
+						ValidatorId::from(Sr25519Keyring::Dave.public()),
+						ValidatorId::from(Sr25519Keyring::Eve.public()),
+					],
+					..Default::default()
+				}),
+				_ => None,
+			});
+
+			// the parathread cores have no upcoming assignments yet.
+			assert_eq!(
+				Scheduler::claim_queue().into_iter().collect::<Vec<_>>(),
+				vec![
+					(CoreIndex(0), vec![chain_a, chain_a]),
+					(CoreIndex(1), vec![chain_b, chain_b]),
+				],
+			);
+
+			// thread_d is queued behind thread_a on the first parathread core.
+			Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone()));
+			Scheduler::add_parathread_claim(ParathreadClaim(thread_b, collator.clone()));
+			Scheduler::add_parathread_claim(ParathreadClaim(thread_c, collator.clone()));
+			Scheduler::add_parathread_claim(ParathreadClaim(thread_d, collator.clone()));
+
+			let expected = vec![
+				(CoreIndex(0), vec![chain_a, chain_a]),
+				(CoreIndex(1), vec![chain_b, chain_b]),
+				(CoreIndex(2), vec![thread_a, thread_d]),
+				(CoreIndex(3), vec![thread_b]),
+				(CoreIndex(4), vec![thread_c]),
+			];
+			assert_eq!(Scheduler::claim_queue().into_iter().collect::<Vec<_>>(), expected);
+
+			// once the first claims are scheduled, they still lead the claim queue.
+			run_to_block(2, |_| None);
+
+			assert_eq!(Scheduler::scheduled().len(), 5);
+			assert_eq!(Scheduler::claim_queue().into_iter().collect::<Vec<_>>(), expected);
+		});
+	}
+
 	#[test]
 	fn schedule_rotates_groups() {
 		let config = {
@@ -17,7 +17,7 @@
 //! Utilities that don't belong to any particular module but may draw
 //! on all modules.
 
-use sp_runtime::traits::{One, Saturating};
+use sp_runtime::traits::One;
 use primitives::v1::{Id as ParaId, PersistedValidationData, TransientValidationData};
 use sp_std::prelude::*;
 
@@ -31,6 +31,15 @@ pub fn make_persisted_validation_data<T: paras::Trait>(
 ) -> Option<PersistedValidationData<T::BlockNumber>> {
 	let relay_parent_number = <frame_system::Module<T>>::block_number() - One::one();
 
+	make_persisted_validation_data_at::<T>(para_id, relay_parent_number)
+}
+
+/// Make the persisted validation data for a particular parachain, for a candidate built on
+/// the relay-chain block with the given number.
+pub fn make_persisted_validation_data_at<T: paras::Trait>(
+	para_id: ParaId,
+	relay_parent_number: T::BlockNumber,
+) -> Option<PersistedValidationData<T::BlockNumber>> {
 	Some(PersistedValidationData {
 		parent_head: <paras::Module<T>>::para_head(&para_id)?,
 		block_number: relay_parent_number,
@@ -44,17 +53,22 @@ pub fn make_persisted_validation_data<T: paras::Trait>(
 pub fn make_transient_validation_data<T: paras::Trait>(
 	para_id: ParaId,
 ) -> Option<TransientValidationData<T::BlockNumber>> {
-	let config = <configuration::Module<T>>::config();
 	let relay_parent_number = <frame_system::Module<T>>::block_number() - One::one();
 
-	let freq = config.validation_upgrade_frequency;
+	make_transient_validation_data_at::<T>(para_id, relay_parent_number)
+}
+
+/// Make the transient validation data for a particular parachain, for a candidate built on
+/// the relay-chain block with the given number.
+pub fn make_transient_validation_data_at<T: paras::Trait>(
+	para_id: ParaId,
+	relay_parent_number: T::BlockNumber,
+) -> Option<TransientValidationData<T::BlockNumber>> {
+	let config = <configuration::Module<T>>::config();
+
 	let delay = config.validation_upgrade_delay;
 
-	let last_code_upgrade = <paras::Module<T>>::last_code_upgrade(para_id, true);
-	let can_upgrade_code = last_code_upgrade.map_or(
-		true,
-		|l| { l <= relay_parent_number && relay_parent_number.saturating_sub(l) >= freq },
-	);
+	let can_upgrade_code = <paras::Module<T>>::can_upgrade_validation_code(para_id, relay_parent_number);
 
 	let code_upgrade_allowed = if can_upgrade_code {
 		Some(relay_parent_number + delay)
@@ -65,6 +79,8 @@ pub fn make_transient_validation_data<T: paras::Trait>(
 	Some(TransientValidationData {
 		max_code_size: config.max_code_size,
 		max_head_data_size: config.max_head_data_size,
+		max_upward_message_num: config.max_upward_message_num_per_candidate,
+		max_upward_message_size: config.max_upward_message_size_per_candidate,
 		balance: 0,
 		code_upgrade_allowed,
 	})
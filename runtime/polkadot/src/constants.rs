@@ -48,40 +48,19 @@ pub mod time {
 pub mod fee {
 	pub use sp_runtime::Perbill;
 	use primitives::v0::Balance;
-	use runtime_common::ExtrinsicBaseWeight;
-	use frame_support::weights::{
-		WeightToFeePolynomial, WeightToFeeCoefficient, WeightToFeeCoefficients,
-	};
-	use smallvec::smallvec;
+	use frame_support::parameter_types;
 
 	/// The block saturation level. Fees will be updates based on this value.
 	pub const TARGET_BLOCK_FULLNESS: Perbill = Perbill::from_percent(25);
 
-	/// Handles converting a weight scalar to a fee value, based on the scale and granularity of the
-	/// node's balance type.
-	///
-	/// This should typically create a mapping between the following ranges:
-	///   - [0, frame_system::MaximumBlockWeight]
-	///   - [Balance::min, Balance::max]
-	///
-	/// Yet, it can be used for any other sort of change to weight-fee. Some examples being:
-	///   - Setting it to `0` will essentially disable the weight fee.
-	///   - Setting it to `1` will cause the literal `#[weight = x]` values to be charged.
-	pub struct WeightToFee;
-	impl WeightToFeePolynomial for WeightToFee {
-		type Balance = Balance;
-		fn polynomial() -> WeightToFeeCoefficients<Self::Balance> {
-			// in Polkadot, extrinsic base weight (smallest non-zero weight) is mapped to 1/10 CENT:
-			let p = super::currency::CENTS;
-			let q = 10 * Balance::from(ExtrinsicBaseWeight::get());
-			smallvec![WeightToFeeCoefficient {
-				degree: 1,
-				negative: false,
-				coeff_frac: Perbill::from_rational_approximation(p % q, q),
-				coeff_integer: p / q,
-			}]
-		}
+	parameter_types! {
+		/// The unit the extrinsic base weight fee is expressed in.
+		pub const Cent: Balance = super::currency::CENTS;
 	}
+
+	/// Handles converting a weight scalar to a fee value. The extrinsic base weight (smallest
+	/// non-zero weight) is mapped to 1/10 CENT.
+	pub type WeightToFee = runtime_common::WeightToFee<Cent>;
 }
 
 #[cfg(test)]
@@ -21,8 +21,8 @@
 #![recursion_limit="256"]
 
 use runtime_common::{
-	dummy, claims, SlowAdjustingFeeUpdate,
-	impls::{CurrencyToVoteHandler, ToAuthor},
+	dummy, claims, imbalance_split, SlowAdjustingFeeUpdate,
+	impls::{CurrencyToVoteHandler, ToAuthor, TreasuryAndBurn},
 	NegativeImbalance, BlockHashCount, MaximumBlockWeight, AvailableBlockRatio,
 	MaximumBlockLength, BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight,
 	MaximumExtrinsicWeight, purchase, ParachainSessionKeyPlaceholder,
@@ -86,7 +86,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("polkadot"),
 	impl_name: create_runtime_str!("parity-polkadot"),
 	authoring_version: 0,
-	spec_version: 24,
+	spec_version: 25,
 	impl_version: 0,
 	#[cfg(not(feature = "disable-runtime-api"))]
 	apis: RUNTIME_API_VERSIONS,
@@ -218,13 +218,16 @@ impl pallet_indices::Trait for Runtime {
 
 parameter_types! {
 	pub const ExistentialDeposit: Balance = 100 * CENTS;
+	// The portion of the treasury's part of transaction fees which isn't burned.
+	pub const TreasuryShareOfFees: Perbill = Perbill::from_percent(100);
 }
 
-/// Splits fees 80/20 between treasury and block author.
+/// Splits fees 80/20 between treasury and block author. Of the treasury's part, the
+/// `TreasuryShareOfFees` goes to the treasury and the rest is burned.
 pub type DealWithFees = SplitTwoWays<
 	Balance,
 	NegativeImbalance<Runtime>,
-	_4, Treasury,   		// 4 parts (80%) goes to the treasury.
+	_4, TreasuryAndBurn<Runtime, TreasuryShareOfFees, Treasury>,   		// 4 parts (80%) goes to the treasury.
 	_1, ToAuthor<Runtime>,   	// 1 part (20%) goes to the block author.
 >;
 
@@ -325,6 +328,8 @@ parameter_types! {
 	// 28 eras for unbonding (28 days).
 	pub const BondingDuration: pallet_staking::EraIndex = 28;
 	pub const SlashDeferDuration: pallet_staking::EraIndex = 27;
+	// The portion of staking slashes going to the treasury, the rest is burned.
+	pub const TreasuryShareOfSlashes: Perbill = Perbill::from_percent(100);
 	pub const RewardCurve: &'static PiecewiseLinear<'static> = &REWARD_CURVE;
 	pub const MaxNominatorRewardedPerValidator: u32 = 64;
 	// last 15 minutes of the last session will be for election.
@@ -345,7 +350,7 @@ impl pallet_staking::Trait for Runtime {
 	type CurrencyToVote = CurrencyToVoteHandler<Self>;
 	type RewardRemainder = Treasury;
 	type Event = Event;
-	type Slash = Treasury;
+	type Slash = TreasuryAndBurn<Runtime, TreasuryShareOfSlashes, Treasury>;
 	type Reward = ();
 	type SessionsPerEra = SessionsPerEra;
 	type BondingDuration = BondingDuration;
@@ -571,6 +576,10 @@ impl pallet_treasury::Trait for Runtime {
 	type WeightInfo = ();
 }
 
+impl imbalance_split::Trait for Runtime {
+	type Event = Event;
+}
+
 parameter_types! {
 	pub OffencesWeightSoftLimit: Weight = Perbill::from_percent(60) * MaximumBlockWeight::get();
 }
@@ -1082,6 +1091,9 @@ construct_runtime! {
 
 		// Multisig dispatch. Late addition.
 		Multisig: pallet_multisig::{Module, Call, Storage, Event<T>},
+
+		// Reports the split of fees and slashes between the treasury and burning. Late addition.
+		ImbalanceSplit: imbalance_split::{Module, Storage, Event<T>},
 	}
 }
 
@@ -21,12 +21,13 @@
 #![recursion_limit="256"]
 
 use sp_std::prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use codec::Encode;
 use primitives::v1::{
 	AccountId, AccountIndex, Balance, BlockNumber, Hash, Nonce, Signature, Moment,
 	GroupRotationInfo, CoreState, Id, ValidationData, ValidationCode, CandidateEvent,
 	ValidatorId, ValidatorIndex, CommittedCandidateReceipt, OccupiedCoreAssumption,
-	PersistedValidationData,
+	PersistedValidationData, ScrapedOnChainVotes, ExecutorParams, CoreIndex,
 };
 use runtime_common::{
 	SlowAdjustingFeeUpdate,
@@ -62,12 +63,15 @@ use sp_staking::SessionIndex;
 use pallet_session::historical as session_historical;
 use frame_system::EnsureRoot;
 use runtime_common::paras_sudo_wrapper as paras_sudo_wrapper;
+use runtime_common::assigned_slots;
 
 use runtime_parachains::configuration as parachains_configuration;
 use runtime_parachains::inclusion as parachains_inclusion;
 use runtime_parachains::inclusion_inherent as parachains_inclusion_inherent;
 use runtime_parachains::initializer as parachains_initializer;
+use runtime_parachains::origin as parachains_origin;
 use runtime_parachains::paras as parachains_paras;
+use runtime_parachains::router as parachains_router;
 use runtime_parachains::scheduler as parachains_scheduler;
 
 pub use pallet_balances::Call as BalancesCall;
@@ -168,6 +172,10 @@ sp_api::impl_runtime_apis! {
 			runtime_api_impl::validators::<Runtime>()
 		}
 
+		fn active_validator_indices() -> Vec<ValidatorIndex> {
+			runtime_api_impl::active_validator_indices::<Runtime>()
+		}
+
 		fn validator_groups() -> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo<BlockNumber>) {
 			runtime_api_impl::validator_groups::<Runtime>()
 		}
@@ -181,6 +189,22 @@ sp_api::impl_runtime_apis! {
 			runtime_api_impl::full_validation_data::<Runtime>(para_id, assumption)
 		}
 
+		fn full_validation_data_at(
+			para_id: Id,
+			relay_parent_number: BlockNumber,
+			assumption: OccupiedCoreAssumption,
+		) -> Option<ValidationData<BlockNumber>> {
+			runtime_api_impl::full_validation_data_at::<Runtime>(
+				para_id,
+				relay_parent_number,
+				assumption,
+			)
+		}
+
+		fn allowed_relay_parents() -> Vec<(Hash, BlockNumber)> {
+			runtime_api_impl::allowed_relay_parents::<Runtime>()
+		}
+
 		fn persisted_validation_data(para_id: Id, assumption: OccupiedCoreAssumption)
 			-> Option<PersistedValidationData<BlockNumber>> {
 			runtime_api_impl::persisted_validation_data::<Runtime>(para_id, assumption)
@@ -190,11 +214,33 @@ sp_api::impl_runtime_apis! {
 			runtime_api_impl::session_index_for_child::<Runtime>()
 		}
 
+		fn availability_chunk_shuffle_seed() -> [u8; 32] {
+			runtime_api_impl::availability_chunk_shuffle_seed::<Runtime>()
+		}
+
 		fn validation_code(para_id: Id, assumption: OccupiedCoreAssumption)
 			-> Option<ValidationCode> {
 			runtime_api_impl::validation_code::<Runtime>(para_id, assumption)
 		}
 
+		fn validation_code_by_hash(hash: Hash) -> Option<ValidationCode> {
+			runtime_api_impl::validation_code_by_hash::<Runtime>(hash)
+		}
+
+		fn historical_validation_code(para_id: Id, context_height: BlockNumber)
+			-> Option<ValidationCode>
+		{
+			runtime_api_impl::historical_validation_code::<Runtime>(para_id, context_height)
+		}
+
+		fn session_executor_params(session_index: SessionIndex) -> Option<ExecutorParams> {
+			runtime_api_impl::session_executor_params::<Runtime>(session_index)
+		}
+
+		fn claim_queue() -> BTreeMap<CoreIndex, Vec<Id>> {
+			runtime_api_impl::claim_queue::<Runtime>()
+		}
+
 		fn candidate_pending_availability(para_id: Id) -> Option<CommittedCandidateReceipt<Hash>> {
 			runtime_api_impl::candidate_pending_availability::<Runtime>(para_id)
 		}
@@ -209,6 +255,10 @@ sp_api::impl_runtime_apis! {
 				}
 			})
 		}
+
+		fn on_chain_votes() -> Option<ScrapedOnChainVotes<Hash>> {
+			runtime_api_impl::on_chain_votes::<Runtime>()
+		}
 	}
 
 	impl fg_primitives::GrandpaApi<Block> for Runtime {
@@ -365,6 +415,7 @@ construct_runtime! {
 		AuthorityDiscovery: pallet_authority_discovery::{Module, Call, Config},
 
 		// Parachains modules.
+		ParachainsOrigin: parachains_origin::{Module, Origin},
 		Config: parachains_configuration::{Module, Call, Storage},
 		Inclusion: parachains_inclusion::{Module, Call, Storage, Event<T>},
 		InclusionInherent: parachains_inclusion_inherent::{Module, Call, Storage},
@@ -373,6 +424,10 @@ construct_runtime! {
 		Initializer: parachains_initializer::{Module, Call, Storage},
 
 		ParasSudoWrapper: paras_sudo_wrapper::{Module, Call},
+		AssignedSlots: assigned_slots::{Module, Call, Storage, Event<T>},
+
+		// Appended after the existing modules to keep their call indices.
+		Router: parachains_router::{Module, Call, Storage, Event},
 	}
 }
 
@@ -388,7 +443,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("rococo-v1"),
 	impl_name: create_runtime_str!("parity-rococo-v1"),
 	authoring_version: 0,
-	spec_version: 1,
+	spec_version: 2,
 	impl_version: 0,
 	#[cfg(not(feature = "disable-runtime-api"))]
 	apis: RUNTIME_API_VERSIONS,
@@ -719,6 +774,22 @@ impl parachains_inclusion::Trait for Runtime {
 
 impl parachains_paras::Trait for Runtime { }
 
+impl parachains_origin::Trait for Runtime { }
+
+parameter_types! {
+	pub const HrmpSenderDeposit: Balance = 5 * DOLLARS;
+	pub const HrmpRecipientDeposit: Balance = 5 * DOLLARS;
+}
+
+impl parachains_router::Trait for Runtime {
+	type Event = Event;
+	type Origin = Origin;
+	type Call = Call;
+	type Currency = Balances;
+	type HrmpSenderDeposit = HrmpSenderDeposit;
+	type HrmpRecipientDeposit = HrmpRecipientDeposit;
+}
+
 impl parachains_inclusion_inherent::Trait for Runtime { }
 
 impl parachains_scheduler::Trait for Runtime { }
@@ -728,3 +799,19 @@ impl parachains_initializer::Trait for Runtime {
 }
 
 impl paras_sudo_wrapper::Trait for Runtime { }
+
+parameter_types! {
+	pub const LeasePeriod: BlockNumber = 1 * DAYS;
+	pub const MaxPermanentSlots: u32 = 25;
+	pub const MaxTemporarySlots: u32 = 20;
+	pub const MaxTemporarySlotPerLeasePeriod: u32 = 5;
+}
+
+impl assigned_slots::Trait for Runtime {
+	type Event = Event;
+	type AssignSlotOrigin = EnsureRoot<AccountId>;
+	type LeasePeriod = LeasePeriod;
+	type MaxPermanentSlots = MaxPermanentSlots;
+	type MaxTemporarySlots = MaxTemporarySlots;
+	type MaxTemporarySlotPerLeasePeriod = MaxTemporarySlotPerLeasePeriod;
+}